@@ -0,0 +1,37 @@
+//! Timing helpers shared by the benchmarks
+//!
+//! Each benchmark runs its closure repeatedly for about a second after a
+//! warm-up pass and prints the mean time per run, plus throughput when the
+//! input size is known.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const WARM_UP: Duration = Duration::from_millis(200);
+const MEASURE: Duration = Duration::from_secs(1);
+
+/// Time `run` and print its mean duration under `name`
+///
+/// `bytes` is the size of the input each run processes, reported as MB/s
+pub fn bench<T>(name: &str, bytes: Option<usize>, mut run: impl FnMut() -> T) {
+    let start = Instant::now();
+    while start.elapsed() < WARM_UP {
+        black_box(run());
+    }
+
+    let mut iterations = 0u32;
+    let start = Instant::now();
+    while start.elapsed() < MEASURE {
+        black_box(run());
+        iterations += 1;
+    }
+    let mean = start.elapsed() / iterations;
+
+    match bytes {
+        Some(bytes) => {
+            let megabytes_per_second = bytes as f64 / mean.as_secs_f64() / 1_000_000.0;
+            println!("{name:<40} {mean:>12.2?}/iter {megabytes_per_second:>10.1} MB/s");
+        }
+        None => println!("{name:<40} {mean:>12.2?}/iter"),
+    }
+}
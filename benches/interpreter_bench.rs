@@ -0,0 +1,48 @@
+//! Interpreter benchmarks for scripts that never leave the shell
+//!
+//! Run with: cargo bench --bench interpreter_bench
+
+mod common;
+
+use shex_interpreter::Interpreter;
+use shex_parser::Parser;
+
+fn main() {
+    let numbers: Vec<String> = (1..=1000).map(|n| n.to_string()).collect();
+    let arithmetic_loop = format!(
+        r#"total=0; for n in {}; do total="$((total + n * 2))"; done"#,
+        numbers.join(" ")
+    );
+    bench_script("interpreter/loop 1000 iterations", &arithmetic_loop);
+
+    let assignments: String = (0..1000)
+        .map(|n| format!("var_{n}=value_{n}; copy_{n}=\"$var_{n}\"\n"))
+        .collect();
+    bench_script("interpreter/set 1000 variables", &assignments);
+
+    // Every call copies the function's definition, so a large body
+    // measures the cost of cloning the AST
+    let body: String = (0..50).map(|n| format!("x={n}; ")).collect();
+    let calls = format!(
+        "work() {{ {body}if false; then echo never; fi; }}\nfor n in {}; do work; done",
+        numbers.join(" ")
+    );
+    bench_script("interpreter/call function 1000 times", &calls);
+
+    // Each subshell copies every variable, including the imported
+    // environment, whose values are shared rather than copied
+    let subshells = format!("for n in {}; do (true); done", numbers.join(" "));
+    let program = Parser::new(&subshells).unwrap().parse().unwrap();
+    let mut interpreter = Interpreter::with_env();
+    common::bench("interpreter/1000 subshells with environment", None, || {
+        interpreter.execute(program.clone()).unwrap()
+    });
+}
+
+/// Time parsing once and executing repeatedly in a fresh interpreter
+fn bench_script(name: &str, script: &str) {
+    let program = Parser::new(script).unwrap().parse().unwrap();
+    common::bench(name, None, || {
+        Interpreter::new().execute(program.clone()).unwrap()
+    });
+}
@@ -0,0 +1,34 @@
+//! Lexer and parser benchmarks
+//!
+//! Run with: cargo bench --bench lexer_bench
+
+mod common;
+
+use shex_lexer::Lexer;
+use shex_parser::Parser;
+
+/// Ten lines mixing variables, pipelines and control flow
+const BLOCK: &str = r#"name="world"; count="$((count + 1))"
+greet() { echo "hello $1" | tr a-z A-Z; }
+if [ -n "$name" ]; then greet "$name"; else echo none >&2; fi
+for item in alpha beta gamma; do echo "$item" >> /dev/null; done
+while false; do break; done
+case $name in world) echo match;; *) echo other;; esac
+ls -l /tmp | grep shex | wc -l
+total=${total:-0} && echo "total: ${total}" || echo failed
+echo $(( (count * 3) % 7 )) 'single quoted' "double $name"
+# a comment between commands
+"#;
+
+fn main() {
+    // 100 lines of representative script
+    let script = BLOCK.repeat(10);
+    common::bench("lexer/tokenize 100 lines", Some(script.len()), || {
+        Lexer::new(&script).tokenize()
+    });
+
+    let commands: String = (0..1000).map(|n| format!("echo line {n} $var\n")).collect();
+    common::bench("parser/parse 1000 commands", Some(commands.len()), || {
+        Parser::new(&commands).unwrap().parse().unwrap()
+    });
+}
@@ -1,6 +1,15 @@
 //! Abstract Syntax Tree definitions for Shex
 //!
 //! Every AST node preserves location information for error reporting.
+//!
+//! `Command`, `RedirectionKind` and `ShexError` are `#[non_exhaustive]`:
+//! new variants may be added in minor releases, so matches on them from
+//! other crates need a wildcard arm.
+
+pub mod pretty;
+pub mod walk;
+
+use std::sync::Arc;
 
 /// Source location information for error reporting
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -19,6 +28,39 @@ impl Span {
     pub const fn dummy() -> Self {
         Self { start: 0, end: 0 }
     }
+
+    /// Whether this is the placeholder from `dummy()` rather than a real location
+    #[must_use]
+    pub const fn is_dummy(self) -> bool {
+        self.start == 0 && self.end == 0
+    }
+
+    /// Whether `other` lies entirely within this span
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Whether the two spans share at least one byte
+    ///
+    /// Spans that only touch, such as `0..5` and `5..10`, do not overlap
+    #[must_use]
+    pub const fn overlaps(self, other: Self) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    /// The bytes covered by both spans, or `None` if they do not overlap
+    #[must_use]
+    pub fn intersect(self, other: Self) -> Option<Self> {
+        self.overlaps(other)
+            .then(|| Self::new(self.start.max(other.start), self.end.min(other.end)))
+    }
+
+    /// The smallest span covering both spans and anything between them
+    #[must_use]
+    pub fn union(self, other: Self) -> Self {
+        Self::new(self.start.min(other.start), self.end.max(other.end))
+    }
 }
 
 /// Line and column position in source text
@@ -38,6 +80,8 @@ impl Position {
 /// Convert byte span to line/column positions
 pub struct SourceMap {
     line_starts: Vec<usize>,
+    /// Length of the source in bytes
+    len: usize,
 }
 
 impl SourceMap {
@@ -49,7 +93,10 @@ impl SourceMap {
                 line_starts.push(pos + 1);
             }
         }
-        Self { line_starts }
+        Self {
+            line_starts,
+            len: source.len(),
+        }
     }
 
     #[must_use]
@@ -67,6 +114,67 @@ impl SourceMap {
     pub fn span_to_positions(&self, span: Span) -> (Position, Position) {
         (self.position(span.start), self.position(span.end))
     }
+
+    /// Byte offset of a 1-based line/column position, the reverse of `position`
+    ///
+    /// Columns past the end of their line are clamped to it. Returns None
+    /// for line or column 0 and for lines the source does not have.
+    #[must_use]
+    pub fn byte_offset(&self, position: Position) -> Option<usize> {
+        if position.column == 0 {
+            return None;
+        }
+        let line_start = *self.line_starts.get(position.line.checked_sub(1)?)?;
+        // A line ends at its newline, or at the end of the last line
+        let line_end = self
+            .line_starts
+            .get(position.line)
+            .map_or(self.len, |next| next - 1);
+        Some((line_start + position.column - 1).min(line_end))
+    }
+
+    /// Span between two line/column positions
+    ///
+    /// Returns None if either position is invalid or `end` comes before `start`
+    #[must_use]
+    pub fn span_from_positions(&self, start: Position, end: Position) -> Option<Span> {
+        let start = self.byte_offset(start)?;
+        let end = self.byte_offset(end)?;
+        (start <= end).then(|| Span::new(start, end))
+    }
+
+    /// Source text covered by `span`
+    ///
+    /// Out-of-range offsets are clamped to the source, and offsets inside a
+    /// multi-byte character move back to its start, so this never panics
+    #[must_use]
+    pub fn range_text<'a>(&'a self, source: &'a str, span: Span) -> &'a str {
+        let clamp = |offset: usize| {
+            let mut offset = offset.min(source.len());
+            while !source.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            offset
+        };
+        let end = clamp(span.end);
+        let start = clamp(span.start).min(end);
+        &source[start..end]
+    }
+
+    /// 1-based line numbers that `span` touches, as a half-open range
+    ///
+    /// An empty span covers the line it sits on
+    #[must_use]
+    pub fn lines_for_span(&self, span: Span) -> std::ops::Range<usize> {
+        let first = self.position(span.start).line;
+        let last_offset = if span.end > span.start {
+            span.end - 1
+        } else {
+            span.start
+        };
+        let last = self.position(last_offset).line.max(first);
+        first..last + 1
+    }
 }
 
 /// AST node with location information
@@ -83,14 +191,66 @@ impl<T> Spanned<T> {
     }
 }
 
+/// Spans are not compared, so the same code parsed from differently
+/// formatted sources compares equal
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
 /// Top-level program
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Program {
     pub commands: Vec<Spanned<Command>>,
 }
 
+impl Program {
+    /// Whether the program has no commands at all
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Number of top-level commands
+    #[must_use]
+    pub fn command_count(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Top-level commands in source order
+    #[must_use]
+    pub fn commands(&self) -> &[Spanned<Command>] {
+        &self.commands
+    }
+
+    /// Iterate over the top-level commands
+    pub fn iter(&self) -> impl Iterator<Item = &Spanned<Command>> {
+        self.commands.iter()
+    }
+}
+
+impl IntoIterator for Program {
+    type Item = Spanned<Command>;
+    type IntoIter = std::vec::IntoIter<Spanned<Command>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.commands.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Program {
+    type Item = &'a Spanned<Command>;
+    type IntoIter = std::slice::Iter<'a, Spanned<Command>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.commands.iter()
+    }
+}
+
 /// Type of I/O redirection
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum RedirectionKind {
     /// < file (stdin from file)
     Input,
@@ -102,6 +262,8 @@ pub enum RedirectionKind {
     HereDoc { delimiter: String, text: String },
     /// <<- delimiter (here-document with tab stripping)
     HereDocDash { delimiter: String, text: String },
+    /// <<< word (here-string: the expanded word and a newline as stdin)
+    HereString,
     /// <& fd (duplicate input fd)
     InputDup,
     /// >& fd (duplicate output fd)
@@ -113,7 +275,7 @@ pub enum RedirectionKind {
 }
 
 /// I/O redirection
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Redirection {
     /// File descriptor number (None means default: 0 for input, 1 for output)
     pub fd: Option<i32>,
@@ -123,8 +285,34 @@ pub struct Redirection {
     pub target: String,
 }
 
+impl Redirection {
+    /// File descriptor being redirected, defaulting to 0 for input and 1 for output
+    #[must_use]
+    pub const fn target_fd(&self) -> i32 {
+        match self.fd {
+            Some(fd) => fd,
+            None => match self.kind {
+                RedirectionKind::Input
+                | RedirectionKind::InputDup
+                | RedirectionKind::InputOutput
+                | RedirectionKind::HereDoc { .. }
+                | RedirectionKind::HereDocDash { .. }
+                | RedirectionKind::HereString => 0,
+                RedirectionKind::Output
+                | RedirectionKind::Append
+                | RedirectionKind::OutputDup
+                | RedirectionKind::Clobber => 1,
+            },
+        }
+    }
+}
+
 /// A shell command - follows POSIX command hierarchy
-#[derive(Debug, Clone)]
+///
+/// Nested commands are shared through `Arc`, so cloning a command, as
+/// defining a function does, copies no subtrees
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum Command {
     /// Simple command: echo hello (with optional prefix assignments and redirections)
     Simple {
@@ -142,35 +330,35 @@ pub enum Command {
     Assignment { assignments: Vec<(String, String)> },
     /// Logical AND: cmd1 && cmd2
     AndIf {
-        left: Box<Spanned<Command>>,
-        right: Box<Spanned<Command>>,
+        left: Arc<Spanned<Command>>,
+        right: Arc<Spanned<Command>>,
     },
     /// Logical OR: cmd1 || cmd2  
     OrIf {
-        left: Box<Spanned<Command>>,
-        right: Box<Spanned<Command>>,
+        left: Arc<Spanned<Command>>,
+        right: Arc<Spanned<Command>>,
     },
     /// Sequential execution: cmd1; cmd2
     Sequence { commands: Vec<Spanned<Command>> },
     /// Background execution: cmd &
-    Background { command: Box<Spanned<Command>> },
+    Background { command: Arc<Spanned<Command>> },
     
     // Compound Commands (POSIX)
     /// if condition; then commands; [elif condition; then commands;]... [else commands;] fi
     If {
-        condition: Box<Spanned<Command>>,
+        condition: Arc<Spanned<Command>>,
         then_body: Vec<Spanned<Command>>,
         elif_clauses: Vec<(Spanned<Command>, Vec<Spanned<Command>>)>, // (condition, body) pairs
         else_body: Option<Vec<Spanned<Command>>>,
     },
     /// while condition; do commands; done
     While {
-        condition: Box<Spanned<Command>>,
+        condition: Arc<Spanned<Command>>,
         body: Vec<Spanned<Command>>,
     },
     /// until condition; do commands; done  
     Until {
-        condition: Box<Spanned<Command>>,
+        condition: Arc<Spanned<Command>>,
         body: Vec<Spanned<Command>>,
     },
     /// for name [in words]; do commands; done
@@ -187,7 +375,7 @@ pub enum Command {
     /// function name() { commands; }
     Function {
         name: String,
-        body: Box<Spanned<Command>>,
+        body: Arc<Spanned<Command>>,
         redirections: Vec<Redirection>,
     },
     /// ( commands ) - subshell
@@ -198,10 +386,19 @@ pub enum Command {
     BraceGroup {
         commands: Vec<Spanned<Command>>,
     },
+    /// coproc [NAME] command - asynchronous command with bidirectional pipes
+    Coproc {
+        name: Option<String>,
+        command: Arc<Spanned<Command>>,
+    },
+    /// # comment - kept only when the parser is asked to preserve comments
+    Comment { text: String },
+    /// { } - an empty statement that does nothing and succeeds
+    Noop,
 }
 
 /// Case pattern arm: pattern) commands ;;
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CaseArm {
     /// Patterns to match (e.g., "*.txt", "foo|bar") 
     pub patterns: Vec<String>,
@@ -209,8 +406,21 @@ pub struct CaseArm {
     pub commands: Vec<Spanned<Command>>,
 }
 
+impl CaseArm {
+    /// Whether any pattern uses `*`, `?`, `[` or a backslash escape
+    ///
+    /// Arms without one can only match a word equal to one of their patterns
+    #[must_use]
+    pub fn has_glob_pattern(&self) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.contains(['*', '?', '[', '\\']))
+    }
+}
+
 /// Error types with location information
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, Clone)]
+#[non_exhaustive]
 pub enum ShexError {
     #[error("Shex:{filename}:{line}:{column}: ERR_SYNTAX: {message}")]
     Syntax {
@@ -238,9 +448,25 @@ pub enum ShexError {
         line: usize,
         column: usize,
     },
+
+    /// A failed read or write, which has no location in the script
+    #[error("Shex: ERR_IO: {message}")]
+    IoError {
+        message: String,
+        kind: std::io::ErrorKind,
+    },
 }
 
 impl ShexError {
+    /// Exit code for syntax errors
+    pub const ERR_SYNTAX: u32 = 1;
+    /// Exit code for expanding an unset variable
+    pub const ERR_UNDEF_VAR: u32 = 2;
+    /// Exit code for a command that could not be found, as in other shells
+    pub const ERR_COMMAND_NOT_FOUND: u32 = 127;
+    /// Exit code for a failed read or write
+    pub const ERR_IO: u32 = 1;
+
     #[must_use]
     pub fn syntax(message: String, span: Span, source_map: &SourceMap, filename: &str) -> Self {
         let pos = source_map.position(span.start);
@@ -293,10 +519,171 @@ impl ShexError {
             Self::Syntax { span, .. }
             | Self::UndefinedVariable { span, .. }
             | Self::CommandNotFound { span, .. } => *span,
+            Self::IoError { .. } => Span::dummy(),
+        }
+    }
+
+    /// Conventional shell exit code for this error
+    #[must_use]
+    pub const fn error_code(&self) -> u32 {
+        match self {
+            Self::Syntax { .. } => Self::ERR_SYNTAX,
+            Self::UndefinedVariable { .. } => Self::ERR_UNDEF_VAR,
+            Self::CommandNotFound { .. } => Self::ERR_COMMAND_NOT_FOUND,
+            Self::IoError { .. } => Self::ERR_IO,
+        }
+    }
+
+    /// Whether the shell can carry on with the next command after this error
+    ///
+    /// A failed lookup leaves the interpreter state intact; a syntax error
+    /// means the rest of the program cannot be trusted
+    #[must_use]
+    pub const fn is_recoverable(&self) -> bool {
+        match self {
+            Self::UndefinedVariable { .. } | Self::CommandNotFound { .. } | Self::IoError { .. } => {
+                true
+            }
+            Self::Syntax { .. } => false,
+        }
+    }
+
+    /// Machine-readable form of this error, for editors and CI systems
+    ///
+    /// The object has the error `code` name, the `message` without its
+    /// location, the `file`, `line` and `column`, and the byte `span`.
+    /// I/O errors have no location and only carry the `code` and `message`.
+    #[must_use]
+    pub fn to_json(&self) -> serde_json::Value {
+        let (code, message) = match self {
+            Self::IoError { message, .. } => {
+                return serde_json::json!({ "code": "ERR_IO", "message": message });
+            }
+            Self::Syntax { message, .. } => ("ERR_SYNTAX", message.clone()),
+            Self::UndefinedVariable { var, .. } => ("ERR_UNDEF_VAR", format!("{var} is not set")),
+            Self::CommandNotFound { command, .. } => {
+                ("ERR_COMMAND_NOT_FOUND", format!("{command} not found"))
+            }
+        };
+        let (filename, line, column) = self.location();
+        let span = self.span();
+        serde_json::json!({
+            "code": code,
+            "message": message,
+            "file": filename,
+            "line": line,
+            "column": column,
+            "span": { "start": span.start, "end": span.end },
+        })
+    }
+
+    /// File name, line and column the error points at
+    ///
+    /// I/O errors have no location, so their file name is empty
+    fn location(&self) -> (&str, usize, usize) {
+        match self {
+            Self::Syntax {
+                filename,
+                line,
+                column,
+                ..
+            }
+            | Self::UndefinedVariable {
+                filename,
+                line,
+                column,
+                ..
+            }
+            | Self::CommandNotFound {
+                filename,
+                line,
+                column,
+                ..
+            } => (filename, *line, *column),
+            Self::IoError { .. } => ("", 0, 0),
+        }
+    }
+
+    /// The error message followed by the offending source line, with a
+    /// caret under the column where the error was found
+    ///
+    /// With `use_color` the message is red, variable names yellow and file
+    /// names cyan, for display on a terminal. `source` is the text the
+    /// error's line and column refer to.
+    #[must_use]
+    pub fn display_with_source(&self, source: &str, use_color: bool) -> String {
+        let paint = |text: &str, color: &str| {
+            if use_color {
+                format!("{color}{text}{ANSI_RESET}")
+            } else {
+                text.to_string()
+            }
+        };
+        let (filename, line, column) = self.location();
+        let description = match self {
+            Self::Syntax { message, .. } => paint(&format!("ERR_SYNTAX: {message}"), ANSI_RED),
+            Self::UndefinedVariable { var, .. } => format!(
+                "{} {}{}",
+                paint("ERR_UNDEF_VAR:", ANSI_RED),
+                paint(var, ANSI_YELLOW),
+                paint(" is not set", ANSI_RED)
+            ),
+            Self::CommandNotFound { command, .. } => paint(
+                &format!("ERR_COMMAND_NOT_FOUND: {command} not found"),
+                ANSI_RED,
+            ),
+            // There is no source line to point at
+            Self::IoError { .. } => return paint(&self.to_string(), ANSI_RED),
+        };
+        let mut output = format!(
+            "Shex:{}:{line}:{column}: {description}",
+            paint(filename, ANSI_CYAN)
+        );
+        if let Some(text) = line
+            .checked_sub(1)
+            .and_then(|index| source.lines().nth(index))
+        {
+            let indent = text
+                .get(..column.saturating_sub(1))
+                .map_or(0, |prefix| prefix.chars().count());
+            output.push_str(&format!(
+                "\n{text}\n{}{}",
+                " ".repeat(indent),
+                paint("^", ANSI_RED)
+            ));
+        }
+        output
+    }
+}
+
+impl From<std::io::Error> for ShexError {
+    fn from(error: std::io::Error) -> Self {
+        Self::IoError {
+            message: error.to_string(),
+            kind: error.kind(),
         }
     }
 }
 
+/// Lets `?` pass a `ShexError` up from functions returning `io::Result`
+///
+/// An I/O error keeps its kind and message; any other error becomes
+/// `ErrorKind::Other` with the full error text
+impl From<ShexError> for std::io::Error {
+    fn from(error: ShexError) -> Self {
+        match error {
+            ShexError::IoError { message, kind } => Self::new(kind, message),
+            error => Self::other(error.to_string()),
+        }
+    }
+}
+
+/// Terminal escape sequences used by `ShexError::display_with_source`
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_YELLOW: &str = "\x1b[33m";
+const ANSI_CYAN: &str = "\x1b[36m";
+const ANSI_RESET: &str = "\x1b[0m";
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -308,6 +695,94 @@ mod tests {
         assert_eq!(span.end, 20);
     }
 
+    #[test]
+    fn test_span_contains() {
+        let span = Span::new(10, 20);
+        assert!(span.contains(span));
+        assert!(span.contains(Span::new(12, 18)));
+        assert!(span.contains(Span::new(10, 10)));
+        assert!(span.contains(Span::new(20, 20)));
+        assert!(!span.contains(Span::new(9, 15)));
+        assert!(!span.contains(Span::new(15, 21)));
+        assert!(!Span::new(12, 18).contains(span));
+    }
+
+    #[test]
+    fn test_span_overlaps() {
+        let span = Span::new(10, 20);
+        assert!(span.overlaps(Span::new(5, 11)));
+        assert!(span.overlaps(Span::new(19, 30)));
+        assert!(span.overlaps(Span::new(0, 30)));
+        assert!(!span.overlaps(Span::new(0, 10)));
+        assert!(!span.overlaps(Span::new(20, 30)));
+        assert!(!span.overlaps(Span::new(10, 10)));
+    }
+
+    #[test]
+    fn test_span_intersect() {
+        let span = Span::new(10, 20);
+        assert_eq!(span.intersect(Span::new(15, 25)), Some(Span::new(15, 20)));
+        assert_eq!(span.intersect(Span::new(12, 18)), Some(Span::new(12, 18)));
+        assert_eq!(Span::new(15, 25).intersect(span), Some(Span::new(15, 20)));
+        assert_eq!(span.intersect(Span::new(20, 25)), None);
+        assert_eq!(span.intersect(Span::new(30, 40)), None);
+    }
+
+    #[test]
+    fn test_span_union() {
+        let span = Span::new(10, 20);
+        assert_eq!(span.union(Span::new(15, 25)), Span::new(10, 25));
+        assert_eq!(span.union(Span::new(30, 40)), Span::new(10, 40));
+        assert_eq!(Span::new(30, 40).union(span), Span::new(10, 40));
+        assert_eq!(span.union(Span::new(12, 18)), span);
+    }
+
+    #[test]
+    fn test_program_accessors() {
+        let comment = |text: &str| {
+            Spanned::new(
+                Command::Comment {
+                    text: text.to_string(),
+                },
+                Span::dummy(),
+            )
+        };
+        assert!(Program { commands: vec![] }.is_empty());
+
+        let program = Program {
+            commands: vec![comment("a"), comment("b")],
+        };
+        assert!(!program.is_empty());
+        assert_eq!(program.command_count(), 2);
+        assert_eq!(program.commands().len(), program.iter().count());
+        let texts: Vec<String> = program
+            .into_iter()
+            .filter_map(|command| match command.node {
+                Command::Comment { text } => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_case_arm_glob_detection() {
+        let arm = |patterns: &[&str]| CaseArm {
+            patterns: patterns.iter().map(|p| (*p).to_string()).collect(),
+            commands: vec![],
+        };
+        assert!(!arm(&["x", "yes"]).has_glob_pattern());
+        for pattern in ["*.txt", "?", "[ab]", "a\\*"] {
+            assert!(arm(&["x", pattern]).has_glob_pattern(), "{pattern}");
+        }
+    }
+
+    #[test]
+    fn test_dummy_span() {
+        assert!(Span::dummy().is_dummy());
+        assert!(!Span::new(0, 4).is_dummy());
+    }
+
     #[test]
     fn test_spanned_node() {
         let cmd = Command::Simple {
@@ -342,6 +817,83 @@ mod tests {
         assert_eq!(pos.column, 1);
     }
 
+    #[test]
+    fn test_range_text() {
+        let source = "echo hi\nls -l\n";
+        let source_map = SourceMap::new(source);
+
+        assert_eq!(source_map.range_text(source, Span::new(0, 4)), "echo");
+        assert_eq!(source_map.range_text(source, Span::new(8, 13)), "ls -l");
+        assert_eq!(source_map.range_text(source, Span::new(11, 40)), "-l\n");
+        assert_eq!(source_map.range_text(source, Span::new(3, 3)), "");
+        assert_eq!(source_map.range_text(source, Span::new(50, 60)), "");
+        assert_eq!(source_map.range_text(source, Span::new(5, 11)), "hi\nls ");
+
+        let source = "echo é";
+        let source_map = SourceMap::new(source);
+        assert_eq!(source_map.range_text(source, Span::new(5, 6)), "");
+    }
+
+    #[test]
+    fn test_lines_for_span() {
+        let source_map = SourceMap::new("echo hi\nls -l\nexit\n");
+
+        assert_eq!(source_map.lines_for_span(Span::new(0, 4)), 1..2);
+        assert_eq!(source_map.lines_for_span(Span::new(3, 3)), 1..2);
+        assert_eq!(source_map.lines_for_span(Span::new(5, 17)), 1..4);
+        // A span ending just after a newline stays on that line
+        assert_eq!(source_map.lines_for_span(Span::new(8, 14)), 2..3);
+        assert_eq!(source_map.lines_for_span(Span::new(14, 18)), 3..4);
+    }
+
+    #[test]
+    fn test_byte_offset_round_trips() {
+        let source = "echo hi\nls -l\nexit";
+        let source_map = SourceMap::new(source);
+
+        for offset in 0..=source.len() {
+            let position = source_map.position(offset);
+            assert_eq!(source_map.byte_offset(position), Some(offset));
+        }
+        for (line, column) in [(1, 1), (1, 8), (2, 3), (3, 5)] {
+            let position = Position::new(line, column);
+            let offset = source_map.byte_offset(position).unwrap();
+            assert_eq!(source_map.position(offset), position);
+        }
+
+        assert_eq!(source_map.byte_offset(Position::new(1, 40)), Some(7));
+        assert_eq!(source_map.byte_offset(Position::new(3, 40)), Some(18));
+        assert_eq!(source_map.byte_offset(Position::new(0, 1)), None);
+        assert_eq!(source_map.byte_offset(Position::new(2, 0)), None);
+        assert_eq!(source_map.byte_offset(Position::new(4, 1)), None);
+
+        assert_eq!(
+            source_map.span_from_positions(Position::new(2, 1), Position::new(2, 6)),
+            Some(Span::new(8, 13))
+        );
+        assert_eq!(
+            source_map.span_from_positions(Position::new(2, 1), Position::new(1, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let source_map = SourceMap::new("cmd");
+        let span = Span::new(0, 3);
+
+        let not_found = ShexError::command_not_found("cmd".to_string(), span, &source_map, "t");
+        assert_eq!(not_found.error_code(), 127);
+        assert!(not_found.is_recoverable());
+
+        let syntax = ShexError::syntax("bad".to_string(), span, &source_map, "t");
+        assert_eq!(syntax.error_code(), 1);
+        assert!(!syntax.is_recoverable());
+
+        let unset = ShexError::undefined_variable("x".to_string(), span, &source_map, "t");
+        assert_eq!(unset.error_code(), ShexError::ERR_UNDEF_VAR);
+    }
+
     #[test]
     fn test_error_with_proper_format() {
         let source = "echo hello\nnonexistent";
@@ -355,4 +907,88 @@ mod tests {
         assert!(error_str.contains("Shex:test.sh:2:1"));
         assert!(error_str.contains("ERR_COMMAND_NOT_FOUND"));
     }
+
+    #[test]
+    fn test_to_json() {
+        let source = "echo ok\necho $missing";
+        let source_map = SourceMap::new(source);
+        let error = ShexError::undefined_variable(
+            "missing".to_string(),
+            Span::new(13, 21),
+            &source_map,
+            "t.sh",
+        );
+
+        assert_eq!(
+            error.to_json(),
+            serde_json::json!({
+                "code": "ERR_UNDEF_VAR",
+                "message": "missing is not set",
+                "file": "t.sh",
+                "line": 2,
+                "column": 6,
+                "span": { "start": 13, "end": 21 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_display_with_source() {
+        let source = "echo ok\necho $missing";
+        let source_map = SourceMap::new(source);
+        let error = ShexError::undefined_variable(
+            "missing".to_string(),
+            Span::new(13, 21),
+            &source_map,
+            "t.sh",
+        );
+
+        assert_eq!(
+            error.display_with_source(source, false),
+            format!("{error}\necho $missing\n     ^")
+        );
+        assert_eq!(
+            error.display_with_source(source, true),
+            "Shex:\x1b[36mt.sh\x1b[0m:2:6: \x1b[31mERR_UNDEF_VAR:\x1b[0m \x1b[33mmissing\x1b[0m\x1b[31m is not set\x1b[0m\necho $missing\n     \x1b[31m^\x1b[0m"
+        );
+        assert_eq!(error.display_with_source("", false), error.to_string());
+    }
+
+    #[test]
+    fn test_io_error_conversions() {
+        use std::io::{Error, ErrorKind};
+
+        let error = ShexError::from(Error::new(ErrorKind::NotFound, "no such file"));
+        assert_eq!(error.to_string(), "Shex: ERR_IO: no such file");
+        assert_eq!(error.error_code(), ShexError::ERR_IO);
+        assert_eq!(
+            error.to_json(),
+            serde_json::json!({ "code": "ERR_IO", "message": "no such file" })
+        );
+
+        let io_error = Error::from(error);
+        assert_eq!(io_error.kind(), ErrorKind::NotFound);
+        assert_eq!(io_error.to_string(), "no such file");
+
+        let syntax = ShexError::syntax("bad".to_string(), Span::new(0, 1), &SourceMap::new("x"), "t.sh");
+        let message = syntax.to_string();
+        let io_error = Error::from(syntax);
+        assert_eq!(io_error.kind(), ErrorKind::Other);
+        assert_eq!(io_error.to_string(), message);
+    }
+
+    #[test]
+    fn test_question_mark_conversions() {
+        fn write_script() -> std::io::Result<()> {
+            Err(ShexError::from(std::io::Error::other("disk full")))?
+        }
+        fn boxed() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            write_script()?;
+            Ok(())
+        }
+        assert_eq!(boxed().unwrap_err().to_string(), "disk full");
+        // The standard library's blanket conversion covers boxed errors
+        let error: Box<dyn std::error::Error + Send + Sync> = ShexError::from(std::io::Error::other("gone")).into();
+        assert_eq!(error.to_string(), "Shex: ERR_IO: gone");
+    }
 }
@@ -0,0 +1,419 @@
+//! Pretty-printer that turns an AST back into shell source
+//!
+//! Output is canonical rather than a copy of the original text: bodies of
+//! compound commands are indented by two spaces, `then` and `do` share a
+//! line with their condition, every `;;` gets its own line and commands
+//! joined by `;` or newlines are printed one per line.
+
+use crate::{Command, Program, Redirection, RedirectionKind, Spanned};
+
+const INDENT: &str = "  ";
+
+/// Print a whole program, one command per line
+#[must_use]
+pub fn pretty_print(program: &Program) -> String {
+    let mut printer = Printer::default();
+    printer.block(&program.commands);
+    printer.out
+}
+
+/// Print a single command without a trailing newline
+///
+/// Compound commands still span several lines
+#[must_use]
+pub fn pretty_print_command(command: &Spanned<Command>) -> String {
+    let mut printer = Printer::default();
+    printer.command(command);
+    printer.out
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+    /// Here-document bodies and delimiters to print after the current line
+    here_docs: Vec<(String, String)>,
+}
+
+impl Printer {
+    /// Print commands on their own lines at the current depth
+    fn block(&mut self, commands: &[Spanned<Command>]) {
+        for command in commands {
+            if let Command::Sequence { commands } = &command.node {
+                self.block(commands);
+                continue;
+            }
+            self.indent();
+            self.command(command);
+            self.out.push('\n');
+            self.here_doc_bodies();
+        }
+    }
+
+    /// Bodies of the here-documents opened on the line just printed
+    fn here_doc_bodies(&mut self) {
+        for (text, delimiter) in std::mem::take(&mut self.here_docs) {
+            self.out.push_str(&text);
+            self.out.push_str(&delimiter);
+            self.out.push('\n');
+        }
+    }
+
+    /// Print a nested body one level deeper
+    fn body(&mut self, commands: &[Spanned<Command>]) {
+        self.depth += 1;
+        self.block(commands);
+        self.depth -= 1;
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    /// Print a command starting at the current position
+    ///
+    /// Compound commands continue over several lines, ending on the line of
+    /// their closing keyword
+    fn command(&mut self, command: &Spanned<Command>) {
+        match &command.node {
+            Command::Simple {
+                name,
+                args,
+                assignments,
+                redirections,
+            } => {
+                let words = assignments
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .chain(std::iter::once(name.clone()).filter(|name| !name.is_empty()))
+                    .chain(args.iter().cloned());
+                self.out.push_str(&words.collect::<Vec<_>>().join(" "));
+                self.redirections(redirections);
+            }
+            Command::Assignment { assignments } => {
+                let words: Vec<String> = assignments
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .collect();
+                self.out.push_str(&words.join(" "));
+            }
+            Command::Pipeline {
+                commands,
+                redirections,
+            } => {
+                self.joined(commands, " | ");
+                self.redirections(redirections);
+            }
+            Command::AndIf { left, right } => self.binary(left, " && ", right),
+            Command::OrIf { left, right } => self.binary(left, " || ", right),
+            Command::Sequence { commands } => self.sequence(commands),
+            Command::Background { command } => {
+                self.command(command);
+                self.out.push_str(" &");
+            }
+            Command::If {
+                condition,
+                then_body,
+                elif_clauses,
+                else_body,
+            } => {
+                self.clause("if ", condition, "; then\n");
+                self.body(then_body);
+                for (condition, body) in elif_clauses {
+                    self.indent();
+                    self.clause("elif ", condition, "; then\n");
+                    self.body(body);
+                }
+                if let Some(body) = else_body {
+                    self.indent();
+                    self.out.push_str("else\n");
+                    self.body(body);
+                }
+                self.close("fi");
+            }
+            Command::While { condition, body } => self.loop_clause("while ", condition, body),
+            Command::Until { condition, body } => self.loop_clause("until ", condition, body),
+            Command::For {
+                variable,
+                words,
+                body,
+            } => {
+                self.out.push_str("for ");
+                self.out.push_str(variable);
+                if let Some(words) = words {
+                    self.out.push_str(" in");
+                    for word in words {
+                        self.out.push(' ');
+                        self.out.push_str(word);
+                    }
+                }
+                self.out.push_str("; do\n");
+                self.body(body);
+                self.close("done");
+            }
+            Command::Case { word, arms } => {
+                self.out.push_str(&format!("case {word} in\n"));
+                self.depth += 1;
+                for arm in arms {
+                    self.indent();
+                    self.out.push_str(&arm.patterns.join(" | "));
+                    self.out.push_str(")\n");
+                    self.body(&arm.commands);
+                    self.depth += 1;
+                    self.indent();
+                    self.depth -= 1;
+                    self.out.push_str(";;\n");
+                }
+                self.depth -= 1;
+                self.close("esac");
+            }
+            Command::Function {
+                name,
+                body,
+                redirections,
+            } => {
+                self.out.push_str(&format!("{name}() "));
+                self.command(body);
+                self.redirections(redirections);
+            }
+            Command::Subshell { commands } => {
+                self.out.push('(');
+                self.sequence(commands);
+                self.out.push(')');
+            }
+            Command::BraceGroup { commands } => {
+                self.out.push_str("{\n");
+                self.body(commands);
+                self.close("}");
+            }
+            Command::Coproc { name, command } => {
+                self.out.push_str("coproc ");
+                if let Some(name) = name {
+                    self.out.push_str(name);
+                    self.out.push(' ');
+                }
+                self.command(command);
+            }
+            Command::Comment { text } => {
+                self.out.push('#');
+                self.out.push_str(text);
+            }
+            Command::Noop => self.out.push_str("{ }"),
+        }
+    }
+
+    /// Commands joined with `;` on one line, as inside `( )` or a condition
+    fn sequence(&mut self, commands: &[Spanned<Command>]) {
+        for (index, command) in commands.iter().enumerate() {
+            if index > 0 {
+                // `cmd &` is already terminated
+                let after_background =
+                    matches!(commands[index - 1].node, Command::Background { .. });
+                self.out.push_str(if after_background { " " } else { "; " });
+            }
+            self.command(command);
+        }
+    }
+
+    fn joined(&mut self, commands: &[Spanned<Command>], separator: &str) {
+        for (index, command) in commands.iter().enumerate() {
+            if index > 0 {
+                self.out.push_str(separator);
+            }
+            self.command(command);
+        }
+    }
+
+    fn binary(&mut self, left: &Spanned<Command>, operator: &str, right: &Spanned<Command>) {
+        self.command(left);
+        self.out.push_str(operator);
+        self.command(right);
+    }
+
+    fn clause(&mut self, keyword: &str, condition: &Spanned<Command>, terminator: &str) {
+        self.out.push_str(keyword);
+        self.command(condition);
+        self.out.push_str(terminator);
+    }
+
+    fn loop_clause(
+        &mut self,
+        keyword: &str,
+        condition: &Spanned<Command>,
+        body: &[Spanned<Command>],
+    ) {
+        self.clause(keyword, condition, "; do\n");
+        self.body(body);
+        self.close("done");
+    }
+
+    /// Closing keyword of a compound command, on its own line
+    fn close(&mut self, keyword: &str) {
+        self.indent();
+        self.out.push_str(keyword);
+    }
+
+    fn redirections(&mut self, redirections: &[Redirection]) {
+        for redirection in redirections {
+            self.out.push(' ');
+            if let Some(fd) = redirection.fd {
+                self.out.push_str(&fd.to_string());
+            }
+            if let RedirectionKind::HereDoc { delimiter, text }
+            | RedirectionKind::HereDocDash { delimiter, text } = &redirection.kind
+            {
+                self.here_docs.push((text.clone(), delimiter.clone()));
+            }
+            let operator = match &redirection.kind {
+                RedirectionKind::Input => "<",
+                RedirectionKind::Output => ">",
+                RedirectionKind::Append => ">>",
+                RedirectionKind::HereDoc { .. } => "<<",
+                RedirectionKind::HereDocDash { .. } => "<<-",
+                RedirectionKind::HereString => "<<<",
+                RedirectionKind::InputDup => "<&",
+                RedirectionKind::OutputDup => ">&",
+                RedirectionKind::InputOutput => "<>",
+                RedirectionKind::Clobber => ">|",
+            };
+            self.out.push_str(operator);
+            self.out.push_str(&redirection.target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CaseArm, Span};
+    use std::sync::Arc;
+
+    fn spanned(node: Command) -> Spanned<Command> {
+        Spanned::new(node, Span::dummy())
+    }
+
+    fn simple(name: &str, args: &[&str]) -> Spanned<Command> {
+        spanned(Command::Simple {
+            name: name.to_string(),
+            args: args.iter().map(|arg| (*arg).to_string()).collect(),
+            assignments: vec![],
+            redirections: vec![],
+        })
+    }
+
+    fn print(commands: Vec<Spanned<Command>>) -> String {
+        pretty_print(&Program { commands })
+    }
+
+    #[test]
+    fn test_simple_commands() {
+        let redirected = spanned(Command::Simple {
+            name: "ls".to_string(),
+            args: vec!["-l".to_string()],
+            assignments: vec![("LC_ALL".to_string(), "C".to_string())],
+            redirections: vec![
+                Redirection {
+                    fd: None,
+                    kind: RedirectionKind::Output,
+                    target: "out".to_string(),
+                },
+                Redirection {
+                    fd: Some(2),
+                    kind: RedirectionKind::OutputDup,
+                    target: "1".to_string(),
+                },
+            ],
+        });
+        let sequence = spanned(Command::Sequence {
+            commands: vec![
+                spanned(Command::Background {
+                    command: Arc::new(simple("sleep", &["1"])),
+                }),
+                simple("echo", &["\"a b\""]),
+            ],
+        });
+        assert_eq!(
+            print(vec![redirected, sequence]),
+            "LC_ALL=C ls -l >out 2>&1\nsleep 1 &\necho \"a b\"\n"
+        );
+    }
+
+    #[test]
+    fn test_compound_commands_are_indented() {
+        let program = vec![spanned(Command::If {
+            condition: Arc::new(simple("test", &["-f", "x"])),
+            then_body: vec![spanned(Command::While {
+                condition: Arc::new(simple("true", &[])),
+                body: vec![simple("echo", &["loop"])],
+            })],
+            elif_clauses: vec![],
+            else_body: Some(vec![spanned(Command::Case {
+                word: "$x".to_string(),
+                arms: vec![CaseArm {
+                    patterns: vec!["a".to_string(), "b".to_string()],
+                    commands: vec![simple("echo", &["ab"])],
+                }],
+            })]),
+        })];
+        assert_eq!(
+            print(program),
+            "if test -f x; then\n  while true; do\n    echo loop\n  done\nelse\n  case $x in\n    a | b)\n      echo ab\n      ;;\n  esac\nfi\n"
+        );
+    }
+
+    #[test]
+    fn test_functions_and_groups() {
+        let function = spanned(Command::Function {
+            name: "greet".to_string(),
+            body: Arc::new(spanned(Command::BraceGroup {
+                commands: vec![simple("echo", &["hi"])],
+            })),
+            redirections: vec![],
+        });
+        let subshell = spanned(Command::Subshell {
+            commands: vec![simple("cd", &["/tmp"]), simple("ls", &[])],
+        });
+        let comment = spanned(Command::Comment {
+            text: " done".to_string(),
+        });
+        assert_eq!(
+            print(vec![function, subshell, comment]),
+            "greet() {\n  echo hi\n}\n(cd /tmp; ls)\n# done\n"
+        );
+    }
+
+    #[test]
+    fn test_here_doc_body_follows_its_line() {
+        let here_doc = spanned(Command::Simple {
+            name: "cat".to_string(),
+            args: vec![],
+            assignments: vec![],
+            redirections: vec![Redirection {
+                fd: None,
+                kind: RedirectionKind::HereDoc {
+                    delimiter: "EOF".to_string(),
+                    text: "hello $x\n".to_string(),
+                },
+                target: "'EOF'".to_string(),
+            }],
+        });
+        let sequence = spanned(Command::Sequence {
+            commands: vec![here_doc, simple("echo", &["done"])],
+        });
+        assert_eq!(
+            print(vec![sequence]),
+            "cat <<'EOF'\nhello $x\nEOF\necho done\n"
+        );
+    }
+
+    #[test]
+    fn test_single_command_has_no_trailing_newline() {
+        let pipeline = spanned(Command::Pipeline {
+            commands: vec![simple("ls", &["-l"]), simple("wc", &["-l"])],
+            redirections: vec![],
+        });
+        assert_eq!(pretty_print_command(&pipeline), "ls -l | wc -l");
+    }
+}
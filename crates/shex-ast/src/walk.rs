@@ -0,0 +1,56 @@
+//! Depth-first traversal of command trees
+
+use crate::{Command, Spanned};
+
+/// Call `visit` on every command in `commands` and all commands nested in them
+///
+/// Parents are visited before their children, in source order
+pub fn walk<'a>(commands: &'a [Spanned<Command>], visit: &mut dyn FnMut(&'a Spanned<Command>)) {
+    for command in commands {
+        walk_command(command, visit);
+    }
+}
+
+fn walk_command<'a>(command: &'a Spanned<Command>, visit: &mut dyn FnMut(&'a Spanned<Command>)) {
+    visit(command);
+    match &command.node {
+        Command::Pipeline { commands, .. }
+        | Command::Sequence { commands }
+        | Command::Subshell { commands }
+        | Command::BraceGroup { commands } => walk(commands, visit),
+        Command::AndIf { left, right } | Command::OrIf { left, right } => {
+            walk_command(left, visit);
+            walk_command(right, visit);
+        }
+        Command::Background { command }
+        | Command::Coproc { command, .. }
+        | Command::Function { body: command, .. } => walk_command(command, visit),
+        Command::If {
+            condition,
+            then_body,
+            elif_clauses,
+            else_body,
+        } => {
+            walk_command(condition, visit);
+            walk(then_body, visit);
+            for (condition, body) in elif_clauses {
+                walk_command(condition, visit);
+                walk(body, visit);
+            }
+            if let Some(body) = else_body {
+                walk(body, visit);
+            }
+        }
+        Command::While { condition, body } | Command::Until { condition, body } => {
+            walk_command(condition, visit);
+            walk(body, visit);
+        }
+        Command::For { body, .. } => walk(body, visit),
+        Command::Case { arms, .. } => {
+            for arm in arms {
+                walk(&arm.commands, visit);
+            }
+        }
+        _ => {}
+    }
+}
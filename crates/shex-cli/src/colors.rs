@@ -0,0 +1,113 @@
+//! Terminal colors for the interactive shell
+//!
+//! Colors are only used when stderr is a terminal, and never when
+//! `$NO_COLOR` is set or `$TERM` is `dumb`.
+
+use std::io::IsTerminal;
+
+/// A foreground color, or the sequence that ends one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnsiColor {
+    Red,
+    Yellow,
+    Cyan,
+    Reset,
+}
+
+impl AnsiColor {
+    /// Escape sequence that switches to this color
+    #[must_use]
+    pub const fn code(self) -> &'static str {
+        match self {
+            Self::Red => "\x1b[31m",
+            Self::Yellow => "\x1b[33m",
+            Self::Cyan => "\x1b[36m",
+            Self::Reset => "\x1b[0m",
+        }
+    }
+}
+
+/// `text` in `color` when `enabled`, otherwise unchanged
+#[must_use]
+pub fn colorize(text: &str, color: AnsiColor, enabled: bool) -> String {
+    if enabled {
+        format!("{}{text}{}", color.code(), AnsiColor::Reset.code())
+    } else {
+        text.to_string()
+    }
+}
+
+/// Whether messages written to stderr should be colored
+#[must_use]
+pub fn stderr_colors_enabled() -> bool {
+    colors_enabled(
+        std::io::stderr().is_terminal(),
+        std::env::var_os("NO_COLOR").is_some(),
+        std::env::var("TERM").ok().as_deref(),
+    )
+}
+
+fn colors_enabled(is_terminal: bool, no_color: bool, term: Option<&str>) -> bool {
+    is_terminal && !no_color && term != Some("dumb")
+}
+
+/// Turn a `$PS1`-style prompt into the text to print
+///
+/// `\033` and `\e` stand for the escape character, so `\033[32m` starts a
+/// color. Without colors, escape sequences are dropped from the prompt.
+#[must_use]
+pub fn render_prompt(prompt: &str, enabled: bool) -> String {
+    let prompt = prompt.replace("\\033", "\x1b").replace("\\e", "\x1b");
+    if enabled {
+        return prompt;
+    }
+    let mut plain = String::with_capacity(prompt.len());
+    let mut chars = prompt.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            // Skip `[`, parameters and the final letter of the sequence
+            for ch in chars.by_ref() {
+                if ch.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(ch);
+        }
+    }
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colorize() {
+        assert_eq!(
+            colorize("oops", AnsiColor::Red, true),
+            "\x1b[31moops\x1b[0m"
+        );
+        assert_eq!(colorize("oops", AnsiColor::Red, false), "oops");
+    }
+
+    #[test]
+    fn test_colors_disabled_by_environment() {
+        assert!(colors_enabled(true, false, Some("xterm-256color")));
+        assert!(colors_enabled(true, false, None));
+        assert!(!colors_enabled(false, false, Some("xterm")));
+        assert!(!colors_enabled(true, true, Some("xterm")));
+        assert!(!colors_enabled(true, false, Some("dumb")));
+    }
+
+    #[test]
+    fn test_render_prompt() {
+        let prompt = r"\033[32mshex\033[0m\e[1m$\e[0m ";
+        assert_eq!(
+            render_prompt(prompt, true),
+            "\x1b[32mshex\x1b[0m\x1b[1m$\x1b[0m "
+        );
+        assert_eq!(render_prompt(prompt, false), "shex$ ");
+        assert_eq!(render_prompt("$ ", false), "$ ");
+    }
+}
@@ -2,10 +2,21 @@
 //!
 //! Command-line interface for the Shex shell interpreter.
 
-use clap::{Arg, Command};
-use shex_interpreter::Interpreter;
-use shex_parser::Parser;
+mod colors;
+mod startup;
+
+use clap::{Arg, ArgAction, Command};
+use colors::{AnsiColor, colorize, render_prompt, stderr_colors_enabled};
+use shex_ast::{Program, ShexError, SourceMap};
+use shex_interpreter::{
+    CaptureMode, ExitStatus, Interpreter, InterpreterStats, Severity, ShellOptions, Validator,
+};
+use shex_parser::{ParseState, Parser};
+use startup::Startup;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
 use std::process;
+use std::sync::Arc;
 
 fn main() {
     let matches = Command::new("shex")
@@ -19,60 +30,320 @@ fn main() {
                 .help("Execute command string")
                 .num_args(1),
         )
+        .arg(
+            Arg::new("posix")
+                .long("posix")
+                .help("Disable features beyond POSIX sh")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("login")
+                .short('l')
+                .long("login")
+                .help("Act as a login shell and read the profile files")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("lint")
+                .long("lint")
+                .help("Report lint warnings before executing")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("validate")
+                .long("validate")
+                .help("Check the script for semantic errors before executing")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .help("Print execution counters after the script finishes")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("explain")
+                .long("explain")
+                .help("Describe what the script would do instead of running it")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("json-errors")
+                .long("json-errors")
+                .help("Report errors on stderr as JSON")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("file")
                 .value_name("FILE")
                 .help("Script file to execute")
                 .index(1),
         )
+        .arg(
+            Arg::new("args")
+                .value_name("ARG")
+                .help("Arguments passed to the script as $1, $2, ...")
+                .index(2)
+                .num_args(0..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true),
+        )
         .get_matches();
 
+    // login(1) starts shells with a leading dash in argv[0]
+    let login = matches.get_flag("login")
+        || std::env::args()
+            .next()
+            .is_some_and(|arg0| arg0.starts_with('-'));
+    let settings = Settings {
+        options: ShellOptions {
+            posix_mode: matches.get_flag("posix"),
+            login,
+            ..ShellOptions::default()
+        },
+        startup: Startup::from_env(login),
+        lint: matches.get_flag("lint"),
+        validate: matches.get_flag("validate"),
+        profile: matches.get_flag("profile"),
+        explain: matches.get_flag("explain"),
+        args: matches
+            .get_many::<String>("args")
+            .map(|args| args.cloned().collect())
+            .unwrap_or_default(),
+    };
+
     let result = matches.get_one::<String>("command").map_or_else(
         || {
             matches.get_one::<String>("file").map_or_else(
-                || {
-                    // TODO: Interactive mode for Phase 1
-                    eprintln!("Interactive mode not implemented yet");
-                    process::exit(1);
-                },
+                || run_interactive(&settings),
                 // Execute script file
-                |file_path| execute_file(file_path),
+                |file_path| execute_file(file_path, &settings),
             )
         },
         // Execute command string
-        |command_str| execute_string(command_str),
+        |command_str| execute_string(command_str, &settings),
     );
 
+    let json_errors = matches.get_flag("json-errors");
+    if json_errors {
+        let errors: Vec<_> = result.as_ref().err().map(error_json).into_iter().collect();
+        eprintln!("{}", serde_json::json!({ "errors": errors }));
+    }
     match result {
         Ok(exit_code) => process::exit(exit_code),
         Err(e) => {
-            eprintln!("{e}");
-            process::exit(1);
+            let shex_error = e.downcast_ref::<ShexError>();
+            if !json_errors {
+                let color = shex_error.is_some() && stderr_colors_enabled();
+                eprintln!("{}", colorize(&e.to_string(), AnsiColor::Red, color));
+            }
+            let code = shex_error.map_or(1, ShexError::error_code);
+            process::exit(i32::try_from(code).unwrap_or(1));
         }
     }
 }
 
-fn execute_string(command_str: &str) -> Result<i32, anyhow::Error> {
-    let parser = Parser::new(command_str)?;
+/// JSON object describing an error, for `--json-errors`
+///
+/// Errors other than `ShexError`, such as an unreadable script, only have a
+/// code and a message
+fn error_json(error: &anyhow::Error) -> serde_json::Value {
+    error.downcast_ref::<ShexError>().map_or_else(
+        || serde_json::json!({ "code": "ERR_IO", "message": error.to_string() }),
+        ShexError::to_json,
+    )
+}
+
+/// How the command line asked the shell to run
+#[derive(Debug, Default)]
+struct Settings {
+    options: ShellOptions,
+    startup: Startup,
+    /// Print lint diagnostics before executing
+    lint: bool,
+    /// Print validation problems, and refuse to run if any is an error
+    validate: bool,
+    /// Print execution counters to stderr after running
+    profile: bool,
+    /// Print what the program would do instead of executing it
+    explain: bool,
+    /// Arguments after the script file, the script's positional parameters
+    args: Vec<String>,
+}
+
+fn execute_string(command_str: &str, settings: &Settings) -> Result<i32, anyhow::Error> {
+    execute_source(command_str, None, settings)
+}
+
+fn execute_file(file_path: &str, settings: &Settings) -> Result<i32, anyhow::Error> {
+    let content = std::fs::read_to_string(file_path).map_err(|e| {
+        let path = colorize(file_path, AnsiColor::Cyan, stderr_colors_enabled());
+        anyhow::anyhow!("shex: {path}: {e}")
+    })?;
+    execute_source(&content, Some(file_path), settings)
+}
+
+/// Parse and run a program, naming the script file if there is one
+fn execute_source(
+    command_str: &str,
+    file_path: Option<&str>,
+    settings: &Settings,
+) -> Result<i32, anyhow::Error> {
+    let parser = match file_path {
+        Some(path) => Parser::new_with_filename(command_str, path)?,
+        None => Parser::new(command_str)?,
+    };
     let program = parser.parse()?;
+    if settings.lint {
+        let color = stderr_colors_enabled();
+        for diagnostic in shex_linter::lint(&program) {
+            let warning = diagnostic.render(parser.source_map(), parser.filename());
+            eprintln!("{}", colorize(&warning, AnsiColor::Yellow, color));
+        }
+    }
+    // Only a script file is given the remaining arguments as $1, $2, ...
+    let validator = file_path.map_or_else(Validator::default, |_| {
+        Validator::with_positional_count(settings.args.len())
+    });
+    if settings.validate && !validate(&validator, &program, &parser) {
+        return Ok(2);
+    }
+    if settings.explain {
+        for explanation in Interpreter::new().explain(&program) {
+            let position = parser.source_map().position(explanation.span.start);
+            println!("{}:{}: {}", position.line, position.column, explanation.description);
+        }
+        return Ok(0);
+    }
+
+    let mut interpreter = Interpreter::with_source(
+        Arc::new(SourceMap::new(command_str)),
+        parser.filename().to_string(),
+    );
+    interpreter.import_env();
+    // Output streams as each command finishes, so it is not lost when a
+    // later command fails and `set -x` traces come before what they trace
+    interpreter.set_capture_mode(CaptureMode::Passthrough);
+    *interpreter.options_mut() = settings.options.clone();
+    if let Some(path) = file_path {
+        interpreter.set_script_name(path);
+        interpreter.set_positional_params(settings.args.clone());
+    }
+    source_all(&mut interpreter, &settings.startup.startup_files(false));
+    let status = interpreter.execute(program);
+    source_all(&mut interpreter, &settings.startup.logout_files());
+    interpreter.hangup_jobs();
+    if settings.profile {
+        print_statistics(&interpreter.statistics());
+    }
+    let status = status?;
+
+    print_status(&status);
+    Ok(status.code)
+}
 
-    let mut interpreter = Interpreter::new();
-    let status = interpreter.execute(program)?;
+/// Print validation problems, returning whether the program may run
+fn validate(validator: &Validator, program: &Program, parser: &Parser) -> bool {
+    let color = stderr_colors_enabled();
+    let errors = validator.check(program);
+    for error in &errors {
+        let message = error.render(parser.source_map(), parser.filename());
+        let ansi = match error.severity {
+            Severity::Warning => AnsiColor::Yellow,
+            Severity::Error => AnsiColor::Red,
+        };
+        eprintln!("{}", colorize(&message, ansi, color));
+    }
+    errors.iter().all(|error| error.severity == Severity::Warning)
+}
+
+/// Print the `--profile` summary to stderr
+///
+/// The counters are only collected when built with the `statistics` feature
+fn print_statistics(stats: &InterpreterStats) {
+    if !cfg!(feature = "statistics") {
+        eprintln!("--profile: statistics are not available; rebuild with --features statistics");
+        return;
+    }
+    eprintln!("commands executed: {}", stats.commands_executed);
+    eprintln!("builtin commands:  {}", stats.builtin_commands);
+    eprintln!("external commands: {}", stats.external_commands);
+    eprintln!("function calls:    {}", stats.function_calls);
+    eprintln!("pipeline stages:   {}", stats.pipeline_stages);
+    eprintln!("variable lookups:  {}", stats.variable_lookups);
+    eprintln!("variable sets:     {}", stats.variable_sets);
+}
+
+/// Source startup or logout files, reporting failures without stopping
+fn source_all(interpreter: &mut Interpreter, files: &[PathBuf]) {
+    for file in files {
+        match interpreter.source_file(&file.to_string_lossy()) {
+            Ok(status) => print_status(&status),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
 
-    // Print output
+fn print_status(status: &ExitStatus) {
     if !status.stdout.is_empty() {
         print!("{}", status.stdout);
     }
     if !status.stderr.is_empty() {
         eprint!("{}", status.stderr);
     }
-
-    Ok(status.code)
 }
 
-fn execute_file(file_path: &str) -> Result<i32, anyhow::Error> {
-    let content = std::fs::read_to_string(file_path)?;
-    execute_string(&content)
+/// Read commands from stdin, running each as soon as it is complete
+///
+/// Continuation lines are requested while a command is still open. The
+/// prompts come from `$PS1` and `$PS2`; prompts and error messages are
+/// colored when stderr is a terminal that allows it.
+fn run_interactive(settings: &Settings) -> Result<i32, anyhow::Error> {
+    let stdin = std::io::stdin();
+    let mut interpreter = Interpreter::with_env();
+    *interpreter.options_mut() = settings.options.clone();
+    interpreter.set_interactive(stdin.is_terminal());
+    source_all(&mut interpreter, &settings.startup.startup_files(true));
+
+    let color = stderr_colors_enabled();
+    let mut parser = Parser::new_interactive("");
+    let mut lines = stdin.lock().lines();
+    loop {
+        let (variable, default) = if parser.input().is_empty() {
+            ("PS1", "$ ")
+        } else {
+            ("PS2", "> ")
+        };
+        let prompt = interpreter
+            .variable(variable)
+            .unwrap_or_else(|| default.to_string());
+        eprint!("{}", render_prompt(&prompt, color));
+        std::io::stderr().flush()?;
+
+        let Some(line) = lines.next().transpose()? else {
+            break;
+        };
+        match parser.add_line(&line) {
+            ParseState::Incomplete(_) => continue,
+            ParseState::Complete => {
+                let program = parser.parse()?;
+                interpreter.set_source_map(Arc::new(SourceMap::new(parser.input())));
+                match interpreter.execute(program) {
+                    Ok(status) => {
+                        print_status(&status);
+                        std::io::stdout().flush()?;
+                    }
+                    Err(e) => eprintln!("{}", e.display_with_source(parser.input(), color)),
+                }
+            }
+            ParseState::Error(e) => eprintln!("{}", e.display_with_source(parser.input(), color)),
+        }
+        parser = Parser::new_interactive("");
+    }
+
+    source_all(&mut interpreter, &settings.startup.logout_files());
+    interpreter.hangup_jobs();
+    Ok(interpreter.last_status())
 }
 
 #[cfg(test)]
@@ -83,27 +354,27 @@ mod tests {
 
     #[test]
     fn test_execute_string_success() {
-        let result = execute_string("echo hello");
+        let result = execute_string("echo hello", &Settings::default());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), 0);
     }
 
     #[test]
     fn test_execute_string_command_failure() {
-        let result = execute_string("false");
+        let result = execute_string("false", &Settings::default());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), 1);
     }
 
     #[test]
     fn test_execute_string_syntax_error() {
-        let result = execute_string("$invalid_expansion");
+        let result = execute_string("$invalid_expansion", &Settings::default());
         assert!(result.is_err());
     }
 
     #[test]
     fn test_execute_string_complex_command() {
-        let result = execute_string("echo hello && echo world");
+        let result = execute_string("echo hello && echo world", &Settings::default());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), 0);
     }
@@ -113,23 +384,57 @@ mod tests {
         let temp_file = NamedTempFile::new().unwrap();
         fs::write(&temp_file, "echo test").unwrap();
 
-        let result = execute_file(temp_file.path().to_str().unwrap());
+        let result = execute_file(temp_file.path().to_str().unwrap(), &Settings::default());
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), 0);
     }
 
     #[test]
     fn test_execute_file_not_found() {
-        let result = execute_file("nonexistent_file.sh");
+        let result = execute_file("nonexistent_file.sh", &Settings::default());
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_login_shell_sources_profiles_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("home");
+        fs::create_dir(&home).unwrap();
+        fs::write(dir.path().join("profile"), "ORDER=system").unwrap();
+        fs::write(home.join(".shex_profile"), "ORDER=\"$ORDER-user\"").unwrap();
+        fs::write(home.join(".shexrc"), "ORDER=rc").unwrap();
+
+        let settings = Settings {
+            startup: Startup {
+                system_profile: dir.path().join("profile"),
+                home: Some(home),
+                login: true,
+            },
+            ..Settings::default()
+        };
+        let result = execute_string("test \"$ORDER\" \"=\" system-user", &settings);
+        assert_eq!(result.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_explain_does_not_execute() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("marker");
+        let settings = Settings {
+            explain: true,
+            ..Settings::default()
+        };
+        let script = format!("echo ran > {}", marker.display());
+        assert_eq!(execute_string(&script, &settings).unwrap(), 0);
+        assert!(!marker.exists());
+    }
+
     #[test]
     fn test_execute_file_with_syntax_error() {
         let temp_file = NamedTempFile::new().unwrap();
         fs::write(&temp_file, "$undefined_var").unwrap();
 
-        let result = execute_file(temp_file.path().to_str().unwrap());
+        let result = execute_file(temp_file.path().to_str().unwrap(), &Settings::default());
         assert!(result.is_err());
     }
 }
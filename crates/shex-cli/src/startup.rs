@@ -0,0 +1,135 @@
+//! Startup and logout files
+//!
+//! A login shell reads the system profile and then the user's profile; an
+//! interactive shell that is not a login shell reads `~/.shexrc` instead.
+//! When a login shell exits it reads `~/.shex_logout`.
+
+use std::path::PathBuf;
+
+/// Where the startup files live and whether this is a login shell
+#[derive(Debug, Clone, Default)]
+pub struct Startup {
+    /// System-wide profile, normally `/etc/profile`
+    pub system_profile: PathBuf,
+    /// Directory holding the user's dot files, normally `$HOME`
+    pub home: Option<PathBuf>,
+    /// Started with `--login`, `-l`, or a `$0` beginning with `-`
+    pub login: bool,
+}
+
+impl Startup {
+    /// Use `/etc/profile` and the home directory from `$HOME`
+    #[must_use]
+    pub fn from_env(login: bool) -> Self {
+        Self {
+            system_profile: PathBuf::from("/etc/profile"),
+            home: std::env::var_os("HOME").map(PathBuf::from),
+            login,
+        }
+    }
+
+    /// Existing files to source before running commands, in order
+    #[must_use]
+    pub fn startup_files(&self, interactive: bool) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        if self.login {
+            files.push(self.system_profile.clone());
+            // Only the first user profile that exists is read
+            if let Some(profile) = self
+                .home_files(&[".shex_profile", ".profile"])
+                .into_iter()
+                .find(|path| path.is_file())
+            {
+                files.push(profile);
+            }
+        } else if interactive {
+            files.extend(self.home_files(&[".shexrc"]));
+        }
+        files.retain(|path| path.is_file());
+        files
+    }
+
+    /// Existing files to source when the shell exits
+    #[must_use]
+    pub fn logout_files(&self) -> Vec<PathBuf> {
+        if !self.login {
+            return Vec::new();
+        }
+        let mut files = self.home_files(&[".shex_logout"]);
+        files.retain(|path| path.is_file());
+        files
+    }
+
+    fn home_files(&self, names: &[&str]) -> Vec<PathBuf> {
+        self.home
+            .iter()
+            .flat_map(|home| names.iter().map(|name| home.join(name)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn startup_in(dir: &std::path::Path, login: bool) -> Startup {
+        Startup {
+            system_profile: dir.join("etc_profile"),
+            home: Some(dir.join("home")),
+            login,
+        }
+    }
+
+    #[test]
+    fn test_login_reads_profiles_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("home")).unwrap();
+        for name in [
+            "etc_profile",
+            "home/.shex_profile",
+            "home/.profile",
+            "home/.shexrc",
+        ] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let login = startup_in(dir.path(), true);
+        assert_eq!(
+            login.startup_files(true),
+            vec![
+                dir.path().join("etc_profile"),
+                dir.path().join("home/.shex_profile")
+            ]
+        );
+
+        fs::remove_file(dir.path().join("home/.shex_profile")).unwrap();
+        assert_eq!(
+            login.startup_files(false),
+            vec![
+                dir.path().join("etc_profile"),
+                dir.path().join("home/.profile")
+            ]
+        );
+
+        let shell = startup_in(dir.path(), false);
+        assert_eq!(
+            shell.startup_files(true),
+            vec![dir.path().join("home/.shexrc")]
+        );
+        assert!(shell.startup_files(false).is_empty());
+    }
+
+    #[test]
+    fn test_logout_file_only_for_login_shells() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("home")).unwrap();
+        fs::write(dir.path().join("home/.shex_logout"), "").unwrap();
+
+        assert_eq!(
+            startup_in(dir.path(), true).logout_files(),
+            vec![dir.path().join("home/.shex_logout")]
+        );
+        assert!(startup_in(dir.path(), false).logout_files().is_empty());
+    }
+}
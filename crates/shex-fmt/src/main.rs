@@ -0,0 +1,123 @@
+//! Shex formatter
+//!
+//! Rewrites shell scripts in canonical form. Like `rustfmt`, files given on
+//! the command line are formatted in place, `--check` only reports files
+//! that would change, and with no files the script is read from stdin and
+//! written to stdout.
+
+use clap::{Arg, ArgAction, Command};
+use shex_ast::ShexError;
+use shex_ast::pretty::pretty_print;
+use shex_parser::Parser;
+use std::io::Read;
+use std::process;
+
+fn main() {
+    let matches = Command::new("shex-fmt")
+        .version("0.1.0")
+        .about("Format Shex shell scripts")
+        .arg(
+            Arg::new("check")
+                .long("check")
+                .help("Exit with status 1 if any file is not formatted, without changing it")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("files")
+                .value_name("FILE")
+                .help("Scripts to format in place; stdin is used when none are given")
+                .num_args(0..),
+        )
+        .get_matches();
+
+    let check = matches.get_flag("check");
+    let files: Vec<&String> = matches
+        .get_many::<String>("files")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+
+    let result = if files.is_empty() {
+        format_stdin(check)
+    } else {
+        format_files(&files, check)
+    };
+
+    match result {
+        Ok(formatted) => process::exit(if formatted { 0 } else { 1 }),
+        Err(e) => {
+            eprintln!("{e}");
+            process::exit(2);
+        }
+    }
+}
+
+/// Parse a script, keeping comments, and print it in canonical form
+fn format_source(source: &str, filename: &str) -> Result<String, ShexError> {
+    let program = Parser::new_with_filename(source, filename)?.parse_with_comments()?;
+    Ok(pretty_print(&program))
+}
+
+/// Format or check each file; returns false if a checked file would change
+fn format_files(files: &[&String], check: bool) -> Result<bool, anyhow::Error> {
+    let mut all_formatted = true;
+    for file in files {
+        let source = std::fs::read_to_string(file)?;
+        let formatted = format_source(&source, file)?;
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{file}: not formatted");
+            all_formatted = false;
+        } else {
+            std::fs::write(file, formatted)?;
+        }
+    }
+    Ok(all_formatted)
+}
+
+fn format_stdin(check: bool) -> Result<bool, anyhow::Error> {
+    let mut source = String::new();
+    std::io::stdin().read_to_string(&mut source)?;
+    let formatted = format_source(&source, "<stdin>")?;
+    if check {
+        return Ok(formatted == source);
+    }
+    print!("{formatted}");
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const MESSY: &str = "# greet everyone\nif true;then echo hi;fi\nfor x in a b\ndo\necho $x;done\ncase $x in a) echo a;; b|c) echo other;;esac\nf() { echo inside f; }\n";
+
+    const FORMATTED: &str = "# greet everyone\nif true; then\n  echo hi\nfi\nfor x in a b; do\n  echo $x\ndone\ncase $x in\n  a)\n    echo a\n    ;;\n  b | c)\n    echo other\n    ;;\nesac\nf() {\n  echo inside f\n}\n";
+
+    #[test]
+    fn test_format_source() {
+        assert_eq!(format_source(MESSY, "t.sh").unwrap(), FORMATTED);
+    }
+
+    #[test]
+    fn test_formatting_is_stable() {
+        let once = format_source(MESSY, "t.sh").unwrap();
+        assert_eq!(format_source(&once, "t.sh").unwrap(), once);
+    }
+
+    #[test]
+    fn test_format_files_in_place_and_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("script.sh").to_string_lossy().to_string();
+        fs::write(&path, MESSY).unwrap();
+
+        assert!(!format_files(&[&path], true).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), MESSY);
+
+        assert!(format_files(&[&path], false).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), FORMATTED);
+        assert!(format_files(&[&path], true).unwrap());
+    }
+}
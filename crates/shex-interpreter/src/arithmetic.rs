@@ -0,0 +1,347 @@
+//! Arithmetic expansion for Shex interpreter
+//!
+//! Evaluates POSIX `$(( ))` expressions using signed 64-bit integers.
+//! Parameter expansions inside the expression are resolved by the caller;
+//! bare variable names are looked up in the variable context here.
+
+use shex_parser::variable_resolver::VariableContext;
+
+/// Evaluate an arithmetic expression
+///
+/// # Errors
+///
+/// Returns an error message for malformed expressions, division by zero,
+/// or variables whose value is not an integer
+pub fn evaluate(expression: &str, context: &mut VariableContext) -> Result<i64, String> {
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+
+    let mut parser = ExprParser { tokens, pos: 0 };
+    let expr = parser.parse_assignment()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!("syntax error in expression: {expression}"));
+    }
+    eval(&expr, context)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ArithToken {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+/// Operators ordered longest first so that `**` wins over `*`
+const OPERATORS: [&str; 34] = [
+    "<<=", ">>=", "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=", "/=",
+    "%=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "?", ":",
+    "=",
+];
+
+fn tokenize(expression: &str) -> Result<Vec<ArithToken>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expression;
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            tokens.push(ArithToken::Number(parse_number(&rest[..end])?));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(ArithToken::Name(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if c == '(' || c == ')' {
+            tokens.push(ArithToken::Op(if c == '(' { "(" } else { ")" }));
+            rest = &rest[1..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(ArithToken::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(format!("invalid arithmetic operator: {c}"));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Parse an integer constant: decimal, octal (leading 0) or hex (0x)
+fn parse_number(text: &str) -> Result<i64, String> {
+    let result = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if text.len() > 1 && text.starts_with('0') {
+        i64::from_str_radix(&text[1..], 8)
+    } else {
+        text.parse()
+    };
+    result.map_err(|_| format!("invalid number: {text}"))
+}
+
+#[derive(Debug)]
+enum Expr {
+    Number(i64),
+    Variable(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    Assign(String, &'static str, Box<Expr>),
+}
+
+struct ExprParser {
+    tokens: Vec<ArithToken>,
+    pos: usize,
+}
+
+/// Binary operator precedence levels, lowest first
+const BINARY_LEVELS: [&[&str]; 10] = [
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+impl ExprParser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(ArithToken::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{op}' in expression"))
+        }
+    }
+
+    fn parse_assignment(&mut self) -> Result<Expr, String> {
+        if let (Some(ArithToken::Name(name)), Some(ArithToken::Op(op))) =
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+            && op.ends_with('=')
+            && !matches!(*op, "==" | "!=" | "<=" | ">=")
+        {
+            let name = name.clone();
+            let op: &'static str = op;
+            self.pos += 2;
+            let value = self.parse_assignment()?;
+            return Ok(Expr::Assign(name, op, Box::new(value)));
+        }
+        self.parse_ternary()
+    }
+
+    fn parse_ternary(&mut self) -> Result<Expr, String> {
+        let condition = self.parse_binary(0)?;
+        if self.peek_op() == Some("?") {
+            self.pos += 1;
+            let then_expr = self.parse_assignment()?;
+            self.expect(":")?;
+            let else_expr = self.parse_ternary()?;
+            return Ok(Expr::Ternary(
+                Box::new(condition),
+                Box::new(then_expr),
+                Box::new(else_expr),
+            ));
+        }
+        Ok(condition)
+    }
+
+    fn parse_binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level >= BINARY_LEVELS.len() {
+            return self.parse_power();
+        }
+        let mut left = self.parse_binary(level + 1)?;
+        while let Some(op) = self.peek_op() {
+            if !BINARY_LEVELS[level].contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            let right = self.parse_binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_power(&mut self) -> Result<Expr, String> {
+        let base = self.parse_unary()?;
+        if self.peek_op() == Some("**") {
+            self.pos += 1;
+            // Exponentiation is right-associative
+            let exponent = self.parse_power()?;
+            return Ok(Expr::Binary("**", Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.peek_op() {
+            Some(op @ ("-" | "+" | "!" | "~")) => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(ArithToken::Number(n)) => {
+                self.pos += 1;
+                Ok(Expr::Number(n))
+            }
+            Some(ArithToken::Name(name)) => {
+                self.pos += 1;
+                Ok(Expr::Variable(name))
+            }
+            Some(ArithToken::Op("(")) => {
+                self.pos += 1;
+                let expr = self.parse_assignment()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            _ => Err("syntax error: operand expected".to_string()),
+        }
+    }
+}
+
+fn lookup(name: &str, context: &VariableContext) -> Result<i64, String> {
+    match context.get(name).map(|value| value.trim()) {
+        None | Some("") => Ok(0),
+        Some(value) => value
+            .parse()
+            .or_else(|_| parse_number(value))
+            .map_err(|_| format!("{name}: value is not an integer: {value}")),
+    }
+}
+
+fn eval(expr: &Expr, context: &mut VariableContext) -> Result<i64, String> {
+    match expr {
+        Expr::Number(n) => Ok(*n),
+        Expr::Variable(name) => lookup(name, context),
+        Expr::Unary(op, operand) => {
+            let value = eval(operand, context)?;
+            Ok(match *op {
+                "-" => value.wrapping_neg(),
+                "!" => i64::from(value == 0),
+                "~" => !value,
+                _ => value,
+            })
+        }
+        Expr::Binary("&&", left, right) => Ok(i64::from(
+            eval(left, context)? != 0 && eval(right, context)? != 0,
+        )),
+        Expr::Binary("||", left, right) => Ok(i64::from(
+            eval(left, context)? != 0 || eval(right, context)? != 0,
+        )),
+        Expr::Binary(op, left, right) => {
+            let left = eval(left, context)?;
+            let right = eval(right, context)?;
+            apply_binary(op, left, right)
+        }
+        Expr::Ternary(condition, then_expr, else_expr) => {
+            if eval(condition, context)? != 0 {
+                eval(then_expr, context)
+            } else {
+                eval(else_expr, context)
+            }
+        }
+        Expr::Assign(name, op, value) => {
+            let value = eval(value, context)?;
+            let result = if *op == "=" {
+                value
+            } else {
+                apply_binary(&op[..op.len() - 1], lookup(name, context)?, value)?
+            };
+            context.set(name.clone(), result.to_string());
+            Ok(result)
+        }
+    }
+}
+
+fn apply_binary(op: &str, left: i64, right: i64) -> Result<i64, String> {
+    Ok(match op {
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err("division by zero".to_string()),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        "**" => {
+            let exponent = u32::try_from(right).map_err(|_| "exponent less than 0".to_string())?;
+            left.wrapping_pow(exponent)
+        }
+        "<<" => left.wrapping_shl(u32::try_from(right).unwrap_or(0)),
+        ">>" => left.wrapping_shr(u32::try_from(right).unwrap_or(0)),
+        "<" => i64::from(left < right),
+        "<=" => i64::from(left <= right),
+        ">" => i64::from(left > right),
+        ">=" => i64::from(left >= right),
+        "==" => i64::from(left == right),
+        "!=" => i64::from(left != right),
+        "&" => left & right,
+        "^" => left ^ right,
+        "|" => left | right,
+        _ => return Err(format!("invalid arithmetic operator: {op}")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_str(expression: &str) -> Result<i64, String> {
+        evaluate(expression, &mut VariableContext::new())
+    }
+
+    #[test]
+    fn test_basic_operators() {
+        assert_eq!(eval_str("2*3"), Ok(6));
+        assert_eq!(eval_str("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval_str("(2+3) * (4-1)"), Ok(15));
+        assert_eq!(eval_str("7 / 2"), Ok(3));
+        assert_eq!(eval_str("7 % 3"), Ok(1));
+        assert_eq!(eval_str("2 ** 3 ** 2"), Ok(512));
+        assert_eq!(eval_str("-5 + 2"), Ok(-3));
+    }
+
+    #[test]
+    fn test_comparisons_and_logic() {
+        assert_eq!(eval_str("3 > 2 && 1 == 1"), Ok(1));
+        assert_eq!(eval_str("!0 || 0"), Ok(1));
+        assert_eq!(eval_str("1 ? 10 : 20"), Ok(10));
+        assert_eq!(eval_str("0x10 + 010"), Ok(24));
+    }
+
+    #[test]
+    fn test_variables_and_assignment() {
+        let mut context = VariableContext::new();
+        context.set("x".to_string(), "4".to_string());
+        assert_eq!(evaluate("x * 2", &mut context), Ok(8));
+        assert_eq!(evaluate("unset_var + 1", &mut context), Ok(1));
+        assert_eq!(evaluate("x += 3", &mut context), Ok(7));
+        assert_eq!(context.get("x"), Some("7"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(eval_str("1 / 0").is_err());
+        assert!(eval_str("1 +").is_err());
+        assert!(eval_str("(1").is_err());
+    }
+}
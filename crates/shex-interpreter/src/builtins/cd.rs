@@ -0,0 +1,96 @@
+//! `cd [DIR | -]` - change the working directory
+//!
+//! Without an argument `cd` goes to `$HOME`, and `cd -` returns to
+//! `$OLDPWD`. A relative `DIR` that does not start with `.` or `..` is
+//! looked up in each colon-separated directory of `$CDPATH` first, an empty
+//! entry meaning the current directory. When it is found through a
+//! non-empty `CDPATH` entry or `-`, the new directory is printed, as POSIX
+//! requires. `PWD` and `OLDPWD` are updated on success.
+//!
+//! Only the interpreter's working directory changes, never the process's;
+//! commands the script runs start in it.
+
+use crate::{ExitStatus, Interpreter};
+use std::path::{Path, PathBuf};
+
+impl Interpreter {
+    /// Run the `cd` builtin
+    pub(crate) fn builtin_cd(&mut self, args: &[String]) -> ExitStatus {
+        let (target, print) = match args {
+            [] => match self.shell_or_env_var("HOME") {
+                Some(home) => (PathBuf::from(home), false),
+                None => return cd_error("cd: HOME not set"),
+            },
+            [dir] if dir == "-" => match self.variable_context.value("OLDPWD") {
+                Some(previous) => (PathBuf::from(previous), true),
+                None => return cd_error("cd: OLDPWD not set"),
+            },
+            [dir] => match self.search_cdpath(dir) {
+                Some(found) => (found, true),
+                None => (PathBuf::from(dir), false),
+            },
+            _ => return cd_error("cd: too many arguments"),
+        };
+
+        if let Err(error) = self.change_dir(&target) {
+            return cd_error(&format!("cd: {}: {}", target.display(), error_text(&error)));
+        }
+
+        ExitStatus {
+            code: 0,
+            stdout: if print {
+                format!("{}\n", self.cwd.display())
+            } else {
+                String::new()
+            },
+            stderr: String::new(),
+        }
+    }
+
+    /// Find `dir` under a non-empty `CDPATH` entry
+    ///
+    /// Returns None when `CDPATH` does not apply to `dir`, or `dir` was only
+    /// found relative to the current directory, so nothing is printed
+    fn search_cdpath(&self, dir: &str) -> Option<PathBuf> {
+        let first = Path::new(dir).components().next()?;
+        if !matches!(first, std::path::Component::Normal(_)) {
+            return None;
+        }
+        let cdpath = self.shell_or_env_var("CDPATH")?;
+        for entry in cdpath.split(':') {
+            if entry.is_empty() {
+                if self.resolve_path(dir).is_dir() {
+                    return None;
+                }
+                continue;
+            }
+            let candidate = self.resolve_path(entry).join(dir);
+            if candidate.is_dir() {
+                return Some(candidate);
+            }
+        }
+        None
+    }
+}
+
+impl Interpreter {
+    /// A shell variable, falling back to the inherited environment
+    fn shell_or_env_var(&self, name: &str) -> Option<String> {
+        self.variable_context
+            .value(name)
+            .or_else(|| std::env::var(name).ok())
+    }
+}
+
+/// An I/O error's message without the "(os error N)" suffix
+fn error_text(error: &std::io::Error) -> String {
+    let text = error.to_string();
+    match text.find(" (os error") {
+        Some(end) => text[..end].to_string(),
+        None => text,
+    }
+}
+
+fn cd_error(message: &str) -> ExitStatus {
+    ExitStatus::with(1, format!("{message}\n"))
+}
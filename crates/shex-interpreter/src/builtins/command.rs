@@ -0,0 +1,151 @@
+//! `command [-vV] name [args ...]` and `type name ...` - look up commands
+//!
+//! `command name args` runs a builtin or external program even when a
+//! function of the same name exists. With `-v` it prints how `name` would
+//! be run instead, and with `-V` it describes it as `type` does: as a shell
+//! builtin, a function, or the file found on `$PATH`.
+
+use super::is_builtin;
+use crate::{ExitStatus, Interpreter};
+use std::path::Path;
+
+/// What a command name resolves to
+enum Lookup {
+    Function,
+    Builtin,
+    File(String),
+}
+
+impl Interpreter {
+    /// Split `command name args` into the command to run and its arguments
+    ///
+    /// Returns None when the first argument is an option, so the builtin
+    /// itself handles `-v` and `-V`
+    pub(crate) fn command_bypass(args: &[String]) -> Option<(&str, &[String])> {
+        let args = match args.first().map(String::as_str) {
+            Some("--") => &args[1..],
+            Some(arg) if arg.starts_with('-') => return None,
+            _ => args,
+        };
+        let (name, rest) = args.split_first()?;
+        Some((name.as_str(), rest))
+    }
+
+    /// Run the `command` builtin with its lookup options
+    pub(crate) fn builtin_command(&mut self, args: &[String]) -> ExitStatus {
+        let (verbose, names) = match args.first().map(String::as_str) {
+            None => return ExitStatus::empty(),
+            Some("-v") => (false, &args[1..]),
+            Some("-V") => (true, &args[1..]),
+            Some(flag) => return lookup_error(2, &format!("command: {flag}: invalid option")),
+        };
+        if verbose {
+            return self.describe_commands("command", names);
+        }
+
+        let mut status = ExitStatus::empty();
+        for name in names {
+            match self.lookup_command(name) {
+                Some(Lookup::File(path)) => status.stdout.push_str(&format!("{path}\n")),
+                Some(_) => status.stdout.push_str(&format!("{name}\n")),
+                None => status.code = 1,
+            }
+        }
+        status
+    }
+
+    /// Run the `type` builtin
+    pub(crate) fn builtin_type(&mut self, args: &[String]) -> ExitStatus {
+        self.describe_commands("type", args)
+    }
+
+    fn describe_commands(&self, builtin: &str, names: &[String]) -> ExitStatus {
+        let mut status = ExitStatus::empty();
+        for name in names {
+            match self.lookup_command(name) {
+                Some(Lookup::Function) => status.stdout.push_str(&format!("{name} is a function\n")),
+                Some(Lookup::Builtin) => {
+                    status.stdout.push_str(&format!("{name} is a shell builtin\n"));
+                }
+                Some(Lookup::File(path)) => status.stdout.push_str(&format!("{name} is {path}\n")),
+                None => {
+                    status.stderr.push_str(&format!("{builtin}: {name}: not found\n"));
+                    status.code = 1;
+                }
+            }
+        }
+        status
+    }
+
+    /// Resolve a name the way running it would: functions, builtins, `$PATH`
+    fn lookup_command(&self, name: &str) -> Option<Lookup> {
+        if self.functions.contains_key(name) {
+            return Some(Lookup::Function);
+        }
+        if is_builtin(name) {
+            return Some(Lookup::Builtin);
+        }
+        if name.contains('/') {
+            return is_executable(&self.resolve_path(name)).then(|| Lookup::File(name.to_string()));
+        }
+        let path = self.variable_context.get("PATH")?;
+        path.split(':')
+            .map(|dir| self.resolve_path(dir).join(name))
+            .find(|candidate| is_executable(candidate))
+            .map(|candidate| Lookup::File(candidate.to_string_lossy().into_owned()))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+fn lookup_error(code: i32, message: &str) -> ExitStatus {
+    ExitStatus::with(code, format!("{message}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Interpreter;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| (*value).to_string()).collect()
+    }
+
+    #[test]
+    fn test_true_and_false_are_builtins() {
+        let mut interpreter = Interpreter::new();
+        let status = interpreter.builtin_type(&args(&["true", "false"]));
+        assert_eq!(status.code, 0);
+        assert_eq!(status.stdout, "true is a shell builtin\nfalse is a shell builtin\n");
+
+        let status = interpreter.builtin_command(&args(&["-v", "true"]));
+        assert_eq!(status.stdout, "true\n");
+    }
+
+    #[test]
+    fn test_unknown_name() {
+        let mut interpreter = Interpreter::new();
+        let status = interpreter.builtin_type(&args(&["shex-no-such-command"]));
+        assert_eq!(status.code, 1);
+        assert_eq!(status.stderr, "type: shex-no-such-command: not found\n");
+        assert_eq!(interpreter.builtin_command(&args(&["-v", "shex-no-such-command"])).code, 1);
+    }
+
+    #[test]
+    fn test_bypass_arguments() {
+        let words = args(&["--", "true", "--bogus-flag"]);
+        let (name, rest) = Interpreter::command_bypass(&words).unwrap();
+        assert_eq!(name, "true");
+        assert_eq!(rest, &words[2..]);
+        assert!(Interpreter::command_bypass(&args(&["-v", "true"])).is_none());
+    }
+}
@@ -0,0 +1,167 @@
+//! `coproc [NAME] command` - run a command asynchronously with pipes
+//!
+//! The coprocess' stdout read end is stored in `NAME[0]` and its stdin
+//! write end in `NAME[1]`; `NAME_PID` holds its process id.
+
+use crate::{ExitStatus, Interpreter};
+use shex_ast::{Command, ShexError, Span, Spanned};
+
+/// Name used when `coproc` is given no NAME
+pub(crate) const DEFAULT_COPROC_NAME: &str = "COPROC";
+
+impl Interpreter {
+    /// Start a coprocess and return immediately
+    pub(crate) fn execute_coproc(
+        &mut self,
+        name: Option<&str>,
+        command: &Spanned<Command>,
+        span: Span,
+    ) -> Result<ExitStatus, ShexError> {
+        let name = name.unwrap_or(DEFAULT_COPROC_NAME);
+
+        let Command::Simple {
+            name: program,
+            args,
+            assignments,
+            ..
+        } = &command.node
+        else {
+            return Err(self.syntax_error(
+                "coproc: only simple commands are supported".to_string(),
+                span,
+            ));
+        };
+
+        self.with_prefix_assignments(assignments, span, |interpreter| {
+            let program = interpreter.expand_command_name(program, span)?;
+            let args = interpreter.expand_arguments(args, span)?;
+            interpreter.spawn_coproc(name, &program, &args, span)
+        })
+    }
+
+    #[cfg(unix)]
+    fn spawn_coproc(
+        &mut self,
+        name: &str,
+        program: &str,
+        args: &[String],
+        span: Span,
+    ) -> Result<ExitStatus, ShexError> {
+        use std::os::unix::io::AsRawFd;
+        use std::process::{Command as StdCommand, Stdio};
+
+        let mut cmd = StdCommand::new(program);
+        self.apply_environment(&mut cmd);
+        let child = cmd
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|_| self.command_not_found_error(program.to_string(), span))?;
+
+        let read_fd = child.stdout.as_ref().map_or(-1, AsRawFd::as_raw_fd);
+        let write_fd = child.stdin.as_ref().map_or(-1, AsRawFd::as_raw_fd);
+
+        self.variable_context.set_array(
+            name.to_string(),
+            vec![read_fd.to_string(), write_fd.to_string()],
+        );
+        self.variable_context
+            .set(format!("{name}_PID"), child.id().to_string());
+
+        // Keep the child (and its pipe ends) alive until the shell exits
+        // or another coproc reuses the name
+        self.coprocs.insert(name.to_string(), child);
+
+        Ok(ExitStatus::empty())
+    }
+
+    #[cfg(not(unix))]
+    fn spawn_coproc(
+        &mut self,
+        _name: &str,
+        _program: &str,
+        _args: &[String],
+        span: Span,
+    ) -> Result<ExitStatus, ShexError> {
+        Err(self.syntax_error("coproc: not supported on this platform".to_string(), span))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::sync::Arc;
+
+    fn coproc_of(program: &str, name: Option<&str>) -> Spanned<Command> {
+        Spanned::new(
+            Command::Coproc {
+                name: name.map(str::to_string),
+                command: Arc::new(Spanned::new(
+                    Command::Simple {
+                        name: program.to_string(),
+                        args: vec![],
+                        assignments: vec![],
+                        redirections: vec![],
+                    },
+                    Span::dummy(),
+                )),
+            },
+            Span::dummy(),
+        )
+    }
+
+    #[test]
+    fn test_coproc_sets_fd_array_and_pid() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter
+            .execute_command(&coproc_of("cat", None))
+            .unwrap();
+        assert_eq!(result.code, 0);
+
+        let fds = interpreter
+            .variable_context
+            .get_array("COPROC")
+            .unwrap()
+            .clone();
+        assert_eq!(fds.len(), 2);
+        assert!(fds.iter().all(|fd| fd.parse::<i32>().unwrap() > 2));
+        assert!(interpreter.variable_context.get("COPROC_PID").is_some());
+    }
+
+    #[test]
+    fn test_coproc_round_trip() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .execute_command(&coproc_of("cat", Some("CAT")))
+            .unwrap();
+
+        let child = interpreter.coprocs.get_mut("CAT").unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        writeln!(stdin, "ping").unwrap();
+        drop(stdin);
+
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        assert_eq!(line, "ping\n");
+    }
+
+    #[test]
+    fn test_coproc_rejects_compound_command() {
+        let mut interpreter = Interpreter::new();
+        let command = Spanned::new(
+            Command::Coproc {
+                name: None,
+                command: Arc::new(Spanned::new(
+                    Command::BraceGroup { commands: vec![] },
+                    Span::dummy(),
+                )),
+            },
+            Span::dummy(),
+        );
+        assert!(interpreter.execute_command(&command).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! `disown [-ah] [%jobspec | PID ...]` - remove jobs from the job table
+//!
+//! Disowned jobs are not sent SIGHUP when the shell exits. With `-h` the
+//! job stays in the table but is still spared the signal. Without job
+//! arguments the current (most recent) job is used; `-a` selects every job.
+
+use crate::{ExitStatus, Interpreter};
+
+impl Interpreter {
+    /// Run the `disown` builtin
+    pub(crate) fn builtin_disown(&mut self, args: &[String]) -> ExitStatus {
+        let mut all = false;
+        let mut keep = false;
+        let mut specs = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() && specs.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'a' => all = true,
+                            'h' => keep = true,
+                            _ => {
+                                return disown_error(2, &format!("disown: -{flag}: invalid option"));
+                            }
+                        }
+                    }
+                }
+                _ => specs.push(arg.as_str()),
+            }
+        }
+
+        let mut selected = Vec::new();
+        if all {
+            selected.extend(self.jobs.iter().map(|job| job.id));
+        } else if specs.is_empty() {
+            match self.jobs.last() {
+                Some(job) => selected.push(job.id),
+                None => return disown_error(1, "disown: current: no such job"),
+            }
+        }
+        for spec in specs {
+            match self.find_job(spec) {
+                Some(id) => selected.push(id),
+                None => return disown_error(1, &format!("disown: {spec}: no such job")),
+            }
+        }
+
+        if keep {
+            for job in self
+                .jobs
+                .iter_mut()
+                .filter(|job| selected.contains(&job.id))
+            {
+                job.no_hup = true;
+            }
+        } else {
+            self.jobs.retain(|job| !selected.contains(&job.id));
+        }
+
+        ExitStatus::empty()
+    }
+
+    /// Resolve `%N`, `%%`, `%+`, `%-` or a PID to a job id
+    fn find_job(&self, spec: &str) -> Option<usize> {
+        let Some(job_spec) = spec.strip_prefix('%') else {
+            let pid: u32 = spec.parse().ok()?;
+            return self
+                .jobs
+                .iter()
+                .find(|job| job.child.id() == pid)
+                .map(|job| job.id);
+        };
+        let job = match job_spec {
+            "" | "%" | "+" => self.jobs.last(),
+            "-" => self.jobs.iter().rev().nth(1),
+            number => {
+                let id: usize = number.parse().ok()?;
+                self.jobs.iter().find(|job| job.id == id)
+            }
+        };
+        job.map(|job| job.id)
+    }
+}
+
+fn disown_error(code: i32, message: &str) -> ExitStatus {
+    ExitStatus::with(code, format!("{message}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Interpreter;
+    use crate::jobs::Job;
+    use std::process::Command;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| (*value).to_string()).collect()
+    }
+
+    /// Interpreter whose job table holds `count` sleeping processes
+    fn with_jobs(count: usize) -> (Interpreter, Vec<u32>) {
+        let mut interpreter = Interpreter::new();
+        for id in 1..=count {
+            let child = Command::new("sleep").arg("30").spawn().unwrap();
+            interpreter.jobs.push(Job {
+                id,
+                child,
+                no_hup: false,
+            });
+        }
+        let pids = interpreter.jobs.iter().map(|job| job.child.id()).collect();
+        (interpreter, pids)
+    }
+
+    fn job_ids(interpreter: &Interpreter) -> Vec<usize> {
+        interpreter.jobs.iter().map(|job| job.id).collect()
+    }
+
+    /// Stop the sleeping processes, including ones no longer in the table
+    fn kill_all(pids: &[u32]) {
+        let pids: Vec<String> = pids.iter().map(ToString::to_string).collect();
+        let _ = Command::new("kill").args(&pids).status();
+    }
+
+    #[test]
+    fn test_disown_current_and_by_spec() {
+        let (mut interpreter, pids) = with_jobs(3);
+
+        assert_eq!(interpreter.builtin_disown(&[]).code, 0);
+        assert_eq!(job_ids(&interpreter), vec![1, 2]);
+
+        assert_eq!(
+            interpreter
+                .builtin_disown(&args(&[&pids[0].to_string()]))
+                .code,
+            0
+        );
+        assert_eq!(job_ids(&interpreter), vec![2]);
+
+        assert_eq!(interpreter.builtin_disown(&args(&["%7"])).code, 1);
+        assert_eq!(interpreter.builtin_disown(&args(&["%2"])).code, 0);
+        assert!(interpreter.jobs.is_empty());
+        assert_eq!(interpreter.builtin_disown(&[]).code, 1);
+        kill_all(&pids);
+    }
+
+    #[test]
+    fn test_disown_h_keeps_job_and_a_takes_all() {
+        let (mut interpreter, pids) = with_jobs(2);
+
+        assert_eq!(interpreter.builtin_disown(&args(&["-h", "%1"])).code, 0);
+        assert_eq!(job_ids(&interpreter), vec![1, 2]);
+        assert!(interpreter.jobs[0].no_hup);
+        assert!(!interpreter.jobs[1].no_hup);
+
+        assert_eq!(interpreter.builtin_disown(&args(&["-a"])).code, 0);
+        assert!(interpreter.jobs.is_empty());
+        kill_all(&pids);
+    }
+}
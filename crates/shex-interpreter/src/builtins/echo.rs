@@ -0,0 +1,69 @@
+//! `echo [-neE] [ARGUMENT...]` - write arguments to standard output
+//!
+//! `-n` drops the trailing newline and `-e` interprets backslash escapes
+//! the same way as `printf %b`, including `\c` to stop output.
+
+use super::printf::expand_b_escape;
+use crate::{ExitStatus, Interpreter};
+
+impl Interpreter {
+    /// Run the `echo` builtin
+    pub(crate) fn builtin_echo(&self, args: &[String]) -> ExitStatus {
+        let mut newline = true;
+        let mut escapes = false;
+        let mut words = args;
+        // Leading arguments made only of n, e and E are options
+        while let Some((first, rest)) = words.split_first() {
+            let Some(flags) = first.strip_prefix('-') else {
+                break;
+            };
+            if flags.is_empty() || !flags.chars().all(|flag| matches!(flag, 'n' | 'e' | 'E')) {
+                break;
+            }
+            for flag in flags.chars() {
+                match flag {
+                    'n' => newline = false,
+                    'e' => escapes = true,
+                    _ => escapes = false,
+                }
+            }
+            words = rest;
+        }
+
+        let mut output = words.join(" ");
+        if escapes {
+            let (expanded, stop) = expand_b_escape(&output);
+            output = expanded;
+            newline &= !stop;
+        }
+        if newline {
+            output.push('\n');
+        }
+
+        ExitStatus {
+            code: 0,
+            stdout: output,
+            stderr: String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn echo(values: &[&str]) -> String {
+        let args: Vec<String> = values.iter().map(|value| (*value).to_string()).collect();
+        Interpreter::new().builtin_echo(&args).stdout
+    }
+
+    #[test]
+    fn test_echo_flags() {
+        assert_eq!(echo(&["hello", "world"]), "hello world\n");
+        assert_eq!(echo(&["-n", "hi"]), "hi");
+        assert_eq!(echo(&["-e", r"a\tb"]), "a\tb\n");
+        assert_eq!(echo(&["-eE", r"a\tb"]), "a\\tb\n");
+        assert_eq!(echo(&["-e", r"one\ctwo"]), "one");
+        assert_eq!(echo(&["-x", "-n"]), "-x -n\n");
+    }
+}
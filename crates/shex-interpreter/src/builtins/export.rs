@@ -0,0 +1,74 @@
+//! `export [-p] [NAME[=VALUE] ...]` - pass variables to spawned commands
+//!
+//! A name may be exported before it has a value. Without names, or with
+//! `-p`, every exported variable is listed as an `export` command that
+//! recreates it when the output is run by the shell.
+
+use super::readonly::declaration_options;
+use crate::{ExitStatus, Interpreter};
+use shex_parser::string_utils::is_valid_variable_name;
+
+impl Interpreter {
+    /// Run the `export` builtin
+    pub(crate) fn builtin_export(&mut self, args: &[String]) -> ExitStatus {
+        let (print, names) = match declaration_options("export", args) {
+            Ok(options) => options,
+            Err(status) => return status,
+        };
+        if print || names.is_empty() {
+            return ExitStatus {
+                stdout: self.declarations("export", &self.variable_context.exported_names()),
+                ..ExitStatus::empty()
+            };
+        }
+
+        let mut stderr = String::new();
+        for arg in names {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            if !is_valid_variable_name(name) {
+                stderr.push_str(&format!("export: `{arg}': not a valid identifier\n"));
+                continue;
+            }
+            if let Some(value) = value {
+                if self.is_readonly(name) {
+                    stderr.push_str(&format!("export: {name}: readonly variable\n"));
+                    continue;
+                }
+                self.variable_context
+                    .set(name.to_string(), value.to_string());
+            }
+            self.variable_context.export(name);
+        }
+        ExitStatus::with(i32::from(!stderr.is_empty()), stderr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Interpreter;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| (*value).to_string()).collect()
+    }
+
+    #[test]
+    fn test_export_sets_and_lists() {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(
+            interpreter
+                .builtin_export(&args(&["B=two words", "A"]))
+                .code,
+            0
+        );
+        assert_eq!(interpreter.variable("B").as_deref(), Some("two words"));
+
+        let result = interpreter.builtin_export(&args(&["-p"]));
+        assert_eq!(result.stdout, "export A\nexport B='two words'\n");
+        let result = interpreter.builtin_export(&args(&["-q"]));
+        assert_eq!((result.code, result.stderr.as_str()), (2, "export: -q: invalid option\n"));
+    }
+}
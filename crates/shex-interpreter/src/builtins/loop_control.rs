@@ -0,0 +1,171 @@
+//! `break [n]` and `continue [n]` - leave or restart enclosing loops, and
+//! `return [n]` - leave a function or sourced file
+//!
+//! The builtin records the request and the commands around it stop running
+//! until it reaches the `n`th enclosing `for`, `while` or `until` loop,
+//! which then ends or starts its next iteration. A count larger than the
+//! number of enclosing loops means the outermost one. A `return` passes
+//! through every loop on its way out to the function call or `source`.
+
+use crate::{ExitStatus, Interpreter};
+
+/// A `break`, `continue` or `return` on its way out to its loop or function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LoopControl {
+    /// Leave this many enclosing loops
+    Break(usize),
+    /// Leave one fewer than this many loops and restart the next one out
+    Continue(usize),
+    /// Leave the function or sourced file with this status
+    Return(i32),
+}
+
+impl Interpreter {
+    /// Run the `break` builtin
+    pub(crate) fn builtin_break(&mut self, args: &[String]) -> ExitStatus {
+        self.loop_control_builtin("break", args, LoopControl::Break)
+    }
+
+    /// Run the `continue` builtin
+    pub(crate) fn builtin_continue(&mut self, args: &[String]) -> ExitStatus {
+        self.loop_control_builtin("continue", args, LoopControl::Continue)
+    }
+
+    /// Run the `return` builtin
+    ///
+    /// Without a count the status is that of the last command run
+    pub(crate) fn builtin_return(&mut self, args: &[String]) -> ExitStatus {
+        let code = match args {
+            [] => self.last_status,
+            [code] => match code.parse::<i32>() {
+                Ok(code) => code & 0xff,
+                Err(_) => return loop_control_error(2, &format!("return: {code}: numeric argument required")),
+            },
+            _ => return loop_control_error(2, "return: too many arguments"),
+        };
+        // Function calls and `source` both push the file they run
+        if self.source_files.len() <= 1 {
+            return loop_control_error(1, "return: can only `return' from a function or sourced script");
+        }
+        self.loop_control = Some(LoopControl::Return(code));
+        ExitStatus {
+            code,
+            stdout: String::new(),
+            stderr: String::new(),
+        }
+    }
+
+    /// Take a pending `return` once its function or sourced file has
+    /// finished, giving the status it asked for
+    pub(crate) fn take_return(&mut self) -> Option<i32> {
+        match self.loop_control {
+            Some(LoopControl::Return(code)) => {
+                self.loop_control = None;
+                Some(code)
+            }
+            _ => None,
+        }
+    }
+
+    fn loop_control_builtin(
+        &mut self,
+        name: &str,
+        args: &[String],
+        control: fn(usize) -> LoopControl,
+    ) -> ExitStatus {
+        let count = match args {
+            [] => 1,
+            [count] => match count.parse::<usize>() {
+                Ok(0) => return loop_control_error(1, &format!("{name}: 0: loop count out of range")),
+                Ok(count) => count,
+                Err(_) => {
+                    return loop_control_error(
+                        2,
+                        &format!("{name}: {count}: numeric argument required"),
+                    );
+                }
+            },
+            _ => return loop_control_error(2, &format!("{name}: too many arguments")),
+        };
+        if self.loop_depth == 0 {
+            return loop_control_error(
+                0,
+                &format!("{name}: only meaningful in a `for', `while', or `until' loop"),
+            );
+        }
+        self.loop_control = Some(control(count.min(self.loop_depth)));
+        ExitStatus::empty()
+    }
+
+    /// Run a loop so `break` and `continue` inside it can reach it
+    pub(crate) fn run_loop<T>(&mut self, run: impl FnOnce(&mut Self) -> T) -> T {
+        self.loop_depth += 1;
+        let result = run(self);
+        self.loop_depth -= 1;
+        result
+    }
+
+    /// Take a pending `break` or `continue` at the end of an iteration
+    ///
+    /// Returns whether the loop must stop; requests for loops further out
+    /// stay pending for them
+    pub(crate) fn leave_loop(&mut self) -> bool {
+        match self.loop_control.take() {
+            None | Some(LoopControl::Continue(1)) => false,
+            Some(LoopControl::Return(code)) => {
+                self.loop_control = Some(LoopControl::Return(code));
+                true
+            }
+            Some(LoopControl::Break(1)) => true,
+            Some(LoopControl::Break(count)) => {
+                self.loop_control = Some(LoopControl::Break(count - 1));
+                true
+            }
+            Some(LoopControl::Continue(count)) => {
+                self.loop_control = Some(LoopControl::Continue(count - 1));
+                true
+            }
+        }
+    }
+}
+
+fn loop_control_error(code: i32, message: &str) -> ExitStatus {
+    ExitStatus::with(code, format!("{message}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LoopControl;
+    use crate::Interpreter;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| (*value).to_string()).collect()
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        let mut interpreter = Interpreter::new();
+        let status = interpreter.builtin_break(&[]);
+        assert_eq!(status.code, 0);
+        assert!(status.stderr.contains("only meaningful"));
+        assert_eq!(interpreter.loop_control, None);
+    }
+
+    #[test]
+    fn test_count_is_checked_and_clamped() {
+        let mut interpreter = Interpreter::new();
+        interpreter.loop_depth = 2;
+
+        assert_eq!(interpreter.builtin_continue(&args(&["0"])).code, 1);
+        assert_eq!(interpreter.builtin_break(&args(&["x"])).code, 2);
+        assert_eq!(interpreter.builtin_break(&args(&["1", "2"])).code, 2);
+        assert_eq!(interpreter.loop_control, None);
+
+        assert_eq!(interpreter.builtin_break(&args(&["5"])).code, 0);
+        assert_eq!(interpreter.loop_control, Some(LoopControl::Break(2)));
+        assert!(interpreter.leave_loop());
+        assert_eq!(interpreter.loop_control, Some(LoopControl::Break(1)));
+        assert!(interpreter.leave_loop());
+        assert_eq!(interpreter.loop_control, None);
+    }
+}
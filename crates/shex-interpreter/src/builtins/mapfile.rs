@@ -0,0 +1,106 @@
+//! `mapfile [-t] [-n COUNT] [-s SKIP] [-C CALLBACK [-c QUANTUM]] [ARRAY]` -
+//! read lines into an indexed array
+//!
+//! Lines come from the previous pipeline stage or stdin, as for `read`, and
+//! go to `MAPFILE` unless an array name is given. `-t` strips each line's
+//! newline, `-s` discards the first `SKIP` lines and `-n` stops after
+//! `COUNT`. With `-C`, the callback runs as `CALLBACK INDEX LINE` before
+//! every `QUANTUM`th line is stored (5000 by default), starting with the
+//! first. The line is passed without its newline, and the callback's words
+//! are split on whitespace, so it can carry fixed leading arguments.
+
+use crate::{ExitStatus, Interpreter};
+use shex_ast::{ShexError, Span};
+
+const DEFAULT_QUANTUM: usize = 5000;
+
+impl Interpreter {
+    /// Run the `mapfile` builtin
+    pub(crate) fn builtin_mapfile(&mut self, args: &[String]) -> Result<ExitStatus, ShexError> {
+        let mut strip = false;
+        let mut count = None;
+        let mut skip = 0;
+        let mut quantum = DEFAULT_QUANTUM;
+        let mut callback = None;
+        let mut args = args.iter();
+        let mut name = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-t" => strip = true,
+                "-n" | "-s" | "-c" => {
+                    let Some(value) = args.next().and_then(|value| value.parse().ok()) else {
+                        return Ok(mapfile_error(&format!("mapfile: {arg}: invalid number")));
+                    };
+                    match arg.as_str() {
+                        "-n" => count = Some(value).filter(|count| *count > 0),
+                        "-s" => skip = value,
+                        _ if value == 0 => {
+                            return Ok(mapfile_error("mapfile: 0: invalid callback quantum"));
+                        }
+                        _ => quantum = value,
+                    }
+                }
+                "-C" => match args.next() {
+                    Some(value) => callback = Some(value.clone()),
+                    None => return Ok(mapfile_error("mapfile: -C: option requires an argument")),
+                },
+                "--" => {
+                    name = args.next();
+                    break;
+                }
+                option if option.starts_with('-') && option.len() > 1 => {
+                    return Ok(mapfile_error(&format!("mapfile: {option}: invalid option")));
+                }
+                _ => {
+                    name = Some(arg);
+                    break;
+                }
+            }
+        }
+        let name = name.map_or("MAPFILE", String::as_str);
+
+        let mut status = ExitStatus::empty();
+        let mut lines = Vec::new();
+        let mut skipped = 0;
+        while count.is_none_or(|count| lines.len() < count) {
+            let Some((mut line, complete)) = self.read_physical_line(b'\n') else {
+                break;
+            };
+            if skipped < skip {
+                skipped += 1;
+                continue;
+            }
+            // The callback sees the line without its delimiter, as with -t
+            if let Some(callback) = callback.as_deref().filter(|_| lines.len() % quantum == 0) {
+                let result = self.run_mapfile_callback(callback, lines.len(), &line)?;
+                status.append_output(&result);
+            }
+            if complete && !strip {
+                line.push('\n');
+            }
+            lines.push(line);
+        }
+
+        self.variable_context.set_array(name.to_string(), lines);
+        Ok(status)
+    }
+
+    /// Call `callback` with the index the line is about to be stored at
+    fn run_mapfile_callback(
+        &mut self,
+        callback: &str,
+        index: usize,
+        line: &str,
+    ) -> Result<ExitStatus, ShexError> {
+        let mut words = callback.split_whitespace().map(str::to_string);
+        let Some(command) = words.next() else {
+            return Ok(mapfile_error("mapfile: -C: empty callback"));
+        };
+        let args = words.chain([index.to_string(), line.to_string()]).collect();
+        self.dispatch_simple_command(&command, args, &[], Span::dummy())
+    }
+}
+
+fn mapfile_error(message: &str) -> ExitStatus {
+    ExitStatus::with(2, format!("{message}\n"))
+}
@@ -0,0 +1,117 @@
+//! Builtin command implementations for the Shex interpreter
+//!
+//! Each builtin lives in its own module and extends `Interpreter`.
+
+mod cd;
+mod command;
+mod coproc;
+mod disown;
+mod echo;
+mod export;
+mod loop_control;
+mod mapfile;
+mod printf;
+mod pwd;
+mod read;
+mod readonly;
+mod set;
+mod shift;
+mod source;
+mod trap;
+
+pub(crate) use loop_control::LoopControl;
+
+use crate::{ExitStatus, Interpreter};
+use shex_ast::{ShexError, Span};
+
+/// Runs a builtin with the name it was invoked as and its arguments
+type Builtin = fn(&mut Interpreter, &str, &[String]) -> Result<ExitStatus, ShexError>;
+
+/// Commands handled inside the interpreter rather than spawned
+const BUILTINS: &[(&str, Builtin)] = &[
+    ("echo", |shell, _, args| Ok(shell.builtin_echo(args))),
+    ("printf", |shell, _, args| Ok(shell.builtin_printf(args))),
+    ("true", |_, _, _| Ok(ExitStatus::empty())),
+    (":", |_, _, _| Ok(ExitStatus::empty())),
+    ("false", |_, _, _| Ok(ExitStatus::with(1, String::new()))),
+    ("read", |shell, _, args| Ok(shell.builtin_read(args))),
+    ("mapfile", |shell, _, args| shell.builtin_mapfile(args)),
+    ("set", |shell, _, args| Ok(shell.builtin_set(args))),
+    ("shift", |shell, _, args| Ok(shell.builtin_shift(args))),
+    ("source", Interpreter::builtin_source),
+    (".", Interpreter::builtin_source),
+    ("cd", |shell, _, args| Ok(shell.builtin_cd(args))),
+    ("pwd", |shell, _, args| Ok(shell.builtin_pwd(args))),
+    ("trap", |shell, _, args| Ok(shell.builtin_trap(args))),
+    ("disown", |shell, _, args| Ok(shell.builtin_disown(args))),
+    ("export", |shell, _, args| Ok(shell.builtin_export(args))),
+    ("readonly", |shell, _, args| Ok(shell.builtin_readonly(args))),
+    ("break", |shell, _, args| Ok(shell.builtin_break(args))),
+    ("continue", |shell, _, args| Ok(shell.builtin_continue(args))),
+    ("return", |shell, _, args| Ok(shell.builtin_return(args))),
+    ("command", |shell, _, args| Ok(shell.builtin_command(args))),
+    ("type", |shell, _, args| Ok(shell.builtin_type(args))),
+];
+
+/// Builtins whose prefix assignments, as in `NAME=value export`, stay set
+/// in the shell afterwards
+const SPECIAL_BUILTINS: &[&str] = &[
+    ":", "set", "shift", "source", ".", "trap", "export", "readonly", "break", "continue", "return",
+];
+
+/// Whether `name` is run by the interpreter itself rather than spawned
+pub(crate) fn is_builtin(name: &str) -> bool {
+    BUILTINS.iter().any(|(builtin, _)| *builtin == name)
+}
+
+/// Whether `name` is a special builtin, whose prefix assignments persist
+pub(crate) fn is_special_builtin(name: &str) -> bool {
+    SPECIAL_BUILTINS.contains(&name)
+}
+
+impl Interpreter {
+    /// Run a builtin command directly, without parsing or external lookup
+    ///
+    /// `name` may be any builtin, such as `echo`, `read` or `cd`. Shell
+    /// functions are not consulted.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShexError::CommandNotFound` if `name` is not a builtin, or
+    /// any error the builtin itself reports
+    pub fn run_builtin(&mut self, name: &str, args: &[&str]) -> Result<ExitStatus, ShexError> {
+        let args: Vec<String> = args.iter().map(|arg| (*arg).to_string()).collect();
+        self.dispatch_builtin(name, &args)
+            .unwrap_or_else(|| Err(self.command_not_found_error(name.to_string(), Span::dummy())))
+    }
+
+    /// Run `name` if it is a builtin; None means it is not one
+    pub(crate) fn dispatch_builtin(
+        &mut self,
+        name: &str,
+        args: &[String],
+    ) -> Option<Result<ExitStatus, ShexError>> {
+        let (_, run) = BUILTINS.iter().find(|(builtin, _)| *builtin == name)?;
+        Some(run(self, name, args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BUILTINS, SPECIAL_BUILTINS, is_builtin};
+    use crate::Interpreter;
+
+    #[test]
+    fn test_every_builtin_dispatches() {
+        for (name, _) in BUILTINS {
+            let mut interpreter = Interpreter::new();
+            // Keep read and mapfile off the test's stdin
+            interpreter.piped_input = Some(String::new());
+            assert!(interpreter.dispatch_builtin(name, &[]).is_some(), "{name}");
+            assert!(is_builtin(name));
+        }
+        assert!(SPECIAL_BUILTINS.iter().all(|name| is_builtin(name)));
+        assert!(!is_builtin("ls"));
+        assert!(Interpreter::new().dispatch_builtin("ls", &[]).is_none());
+    }
+}
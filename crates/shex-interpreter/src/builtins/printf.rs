@@ -0,0 +1,463 @@
+//! `printf [-v NAME] FORMAT [ARGUMENT...]` - formatted output
+//!
+//! Supports the `%s`, `%b`, `%c`, `%d`, `%i`, `%u`, `%o`, `%x` and `%X`
+//! conversions with flags, width and precision. The format is reused until
+//! every argument has been consumed. With `-v` the output is assigned to
+//! `NAME` instead of being printed.
+
+use crate::{ExitStatus, Interpreter};
+use shex_parser::string_utils::is_valid_variable_name;
+
+impl Interpreter {
+    /// Run the `printf` builtin
+    pub(crate) fn builtin_printf(&mut self, args: &[String]) -> ExitStatus {
+        let (target, args) = match args {
+            [option, name, rest @ ..] if option == "-v" => (Some(name), rest),
+            [option] if option == "-v" => {
+                return ExitStatus {
+                    code: 2,
+                    stdout: String::new(),
+                    stderr: "printf: -v: option requires an argument\n".to_string(),
+                };
+            }
+            _ => (None, args),
+        };
+        if let Some(name) = target.filter(|name| !is_valid_variable_name(name)) {
+            return ExitStatus::with(2, format!("printf: `{name}': not a valid identifier\n"));
+        }
+        if let Some(name) = target.filter(|name| self.is_readonly(name)) {
+            return ExitStatus {
+                code: 1,
+                stdout: String::new(),
+                stderr: format!("printf: {name}: readonly variable\n"),
+            };
+        }
+
+        let Some((format, mut args)) = args.split_first() else {
+            return ExitStatus {
+                code: 2,
+                stdout: String::new(),
+                stderr: "printf: usage: printf format [arguments]\n".to_string(),
+            };
+        };
+
+        let mut printer = Printer::default();
+        loop {
+            let before = args.len();
+            if printer.write_format(format, &mut args) {
+                break;
+            }
+            // Reuse the format only while it consumes arguments
+            if args.is_empty() || args.len() == before {
+                break;
+            }
+        }
+
+        let mut stdout = printer.output;
+        if let Some(name) = target {
+            self.variable_context
+                .set(name.clone(), std::mem::take(&mut stdout));
+        }
+        ExitStatus {
+            code: i32::from(printer.failed),
+            stdout,
+            stderr: printer.errors,
+        }
+    }
+}
+
+/// Interpret the backslash escapes accepted by `%b` and `echo -e`
+///
+/// Returns the expanded text and whether a `\c` asked to stop all further
+/// output. Octal escapes take the `\0NNN` form.
+pub(crate) fn expand_b_escape(s: &str) -> (String, bool) {
+    expand_escapes(s, true)
+}
+
+/// Expand escapes, with octal written `\0NNN` (for `%b`) or `\NNN` (in the format)
+fn expand_escapes(s: &str, octal_needs_zero: bool) -> (String, bool) {
+    let mut output = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            output.push('\\');
+            break;
+        };
+        match escape {
+            'a' => output.push('\x07'),
+            'b' => output.push('\x08'),
+            'e' | 'E' => output.push('\x1b'),
+            'f' => output.push('\x0c'),
+            'n' => output.push('\n'),
+            'r' => output.push('\r'),
+            't' => output.push('\t'),
+            'v' => output.push('\x0b'),
+            '\\' => output.push('\\'),
+            'c' => return (output, true),
+            '0'..='7' if escape == '0' || !octal_needs_zero => {
+                let mut digits = String::new();
+                if escape != '0' || !octal_needs_zero {
+                    digits.push(escape);
+                }
+                while digits.len() < 3 {
+                    match chars.peek() {
+                        Some(d @ '0'..='7') => {
+                            digits.push(*d);
+                            chars.next();
+                        }
+                        _ => break,
+                    }
+                }
+                let value = u32::from_str_radix(&digits, 8).unwrap_or(0);
+                output.push(char::from_u32(value & 0xff).unwrap_or('\0'));
+            }
+            'x' => push_hex(&mut output, &mut chars, 2, "\\x"),
+            'u' => push_hex(&mut output, &mut chars, 4, "\\u"),
+            'U' => push_hex(&mut output, &mut chars, 8, "\\U"),
+            other => {
+                output.push('\\');
+                output.push(other);
+            }
+        }
+    }
+
+    (output, false)
+}
+
+/// Read up to `max` hex digits and push the character they encode
+fn push_hex(
+    output: &mut String,
+    chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+    max: usize,
+    prefix: &str,
+) {
+    let mut digits = String::new();
+    while digits.len() < max {
+        match chars.peek() {
+            Some(d) if d.is_ascii_hexdigit() => {
+                digits.push(*d);
+                chars.next();
+            }
+            _ => break,
+        }
+    }
+    match u32::from_str_radix(&digits, 16)
+        .ok()
+        .and_then(char::from_u32)
+    {
+        Some(c) => output.push(c),
+        // No digits: keep the escape as written
+        None => {
+            output.push_str(prefix);
+            output.push_str(&digits);
+        }
+    }
+}
+
+/// Flags, width and precision of one conversion
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+#[derive(Default)]
+struct Printer {
+    output: String,
+    errors: String,
+    failed: bool,
+}
+
+impl Printer {
+    /// Write one pass of the format; returns true when `\c` stopped output
+    fn write_format(&mut self, format: &str, args: &mut &[String]) -> bool {
+        let mut chars = format.chars().peekable();
+        let mut literal = String::new();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    literal.push('\\');
+                    if let Some(next) = chars.next() {
+                        literal.push(next);
+                    }
+                }
+                '%' if chars.peek() == Some(&'%') => {
+                    chars.next();
+                    literal.push('%');
+                }
+                '%' => {
+                    if self.write_literal(&literal) {
+                        return true;
+                    }
+                    literal.clear();
+
+                    let spec = parse_spec(&mut chars);
+                    let Some(conversion) = chars.next() else {
+                        self.error("printf: %: missing format character");
+                        return false;
+                    };
+                    if self.write_conversion(conversion, &spec, next_arg(args)) {
+                        return true;
+                    }
+                }
+                other => literal.push(other),
+            }
+        }
+
+        self.write_literal(&literal)
+    }
+
+    fn write_literal(&mut self, literal: &str) -> bool {
+        let (text, stop) = expand_escapes(literal, false);
+        self.output.push_str(&text);
+        stop
+    }
+
+    fn write_conversion(&mut self, conversion: char, spec: &Spec, arg: Option<&String>) -> bool {
+        let arg = arg.map_or("", String::as_str);
+        let mut stop = false;
+        let text = match conversion {
+            's' => pad(&truncate(arg, spec.precision), spec, false),
+            'b' => {
+                let (text, stopped) = expand_b_escape(arg);
+                stop = stopped;
+                pad(&truncate(&text, spec.precision), spec, false)
+            }
+            'c' => pad(&arg.chars().take(1).collect::<String>(), spec, false),
+            'd' | 'i' | 'u' | 'o' | 'x' | 'X' => {
+                let value = self.parse_integer(arg);
+                format_integer(conversion, value, spec)
+            }
+            other => {
+                self.error(&format!("printf: %{other}: invalid format character"));
+                String::new()
+            }
+        };
+        self.output.push_str(&text);
+        stop
+    }
+
+    /// Numeric argument: decimal, octal, hex, or `'c` for a character code
+    fn parse_integer(&mut self, arg: &str) -> i64 {
+        let trimmed = arg.trim();
+        if trimmed.is_empty() {
+            return 0;
+        }
+        if let Some(quoted) = trimmed.strip_prefix(['\'', '"']) {
+            return quoted.chars().next().map_or(0, |c| i64::from(u32::from(c)));
+        }
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let parsed = if let Some(hex) = digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"))
+        {
+            i64::from_str_radix(hex, 16)
+        } else if digits.len() > 1 && digits.starts_with('0') {
+            i64::from_str_radix(&digits[1..], 8)
+        } else {
+            digits.parse()
+        };
+        match parsed {
+            Ok(value) if negative => -value,
+            Ok(value) => value,
+            Err(_) => {
+                self.error(&format!("printf: {arg}: invalid number"));
+                0
+            }
+        }
+    }
+
+    fn error(&mut self, message: &str) {
+        self.errors.push_str(message);
+        self.errors.push('\n');
+        self.failed = true;
+    }
+}
+
+fn next_arg<'a>(args: &mut &'a [String]) -> Option<&'a String> {
+    let (first, rest) = args.split_first()?;
+    *args = rest;
+    Some(first)
+}
+
+fn parse_spec(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> Spec {
+    let mut spec = Spec::default();
+    while let Some(&flag) = chars.peek() {
+        match flag {
+            '-' => spec.left = true,
+            '0' => spec.zero = true,
+            '+' => spec.plus = true,
+            ' ' => spec.space = true,
+            '#' => spec.alternate = true,
+            _ => break,
+        }
+        chars.next();
+    }
+    spec.width = read_number(chars);
+    if chars.peek() == Some(&'.') {
+        chars.next();
+        spec.precision = Some(read_number(chars));
+    }
+    spec
+}
+
+fn read_number(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> usize {
+    let mut value = 0usize;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        value = value.saturating_mul(10).saturating_add(digit as usize);
+        chars.next();
+    }
+    value
+}
+
+fn truncate(text: &str, precision: Option<usize>) -> String {
+    match precision {
+        Some(max) => text.chars().take(max).collect(),
+        None => text.to_string(),
+    }
+}
+
+/// Pad to the field width; zero padding goes after any sign
+fn pad(text: &str, spec: &Spec, numeric: bool) -> String {
+    let len = text.chars().count();
+    if len >= spec.width {
+        return text.to_string();
+    }
+    let fill = spec.width - len;
+    if spec.left {
+        format!("{text}{}", " ".repeat(fill))
+    } else if numeric && spec.zero && spec.precision.is_none() {
+        let sign_len = usize::from(text.starts_with(['-', '+', ' ']));
+        let (sign, digits) = text.split_at(sign_len);
+        format!("{sign}{}{digits}", "0".repeat(fill))
+    } else {
+        format!("{}{text}", " ".repeat(fill))
+    }
+}
+
+fn format_integer(conversion: char, value: i64, spec: &Spec) -> String {
+    let (sign, mut digits) = match conversion {
+        'd' | 'i' => {
+            let sign = if value < 0 {
+                "-"
+            } else if spec.plus {
+                "+"
+            } else if spec.space {
+                " "
+            } else {
+                ""
+            };
+            (sign, value.unsigned_abs().to_string())
+        }
+        // Unsigned conversions print the two's complement bit pattern
+        'o' => ("", format!("{:o}", value as u64)),
+        'x' => ("", format!("{:x}", value as u64)),
+        'X' => ("", format!("{:X}", value as u64)),
+        _ => ("", (value as u64).to_string()),
+    };
+    if let Some(precision) = spec.precision
+        && digits.len() < precision
+    {
+        digits.insert_str(0, &"0".repeat(precision - digits.len()));
+    }
+    let prefix = match conversion {
+        'o' if spec.alternate && !digits.starts_with('0') => "0",
+        'x' if spec.alternate && value != 0 => "0x",
+        'X' if spec.alternate && value != 0 => "0X",
+        _ => "",
+    };
+    pad(&format!("{sign}{prefix}{digits}"), spec, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printf(values: &[&str]) -> ExitStatus {
+        let args: Vec<String> = values.iter().map(|value| (*value).to_string()).collect();
+        Interpreter::new().builtin_printf(&args)
+    }
+
+    #[test]
+    fn test_expand_b_escape() {
+        assert_eq!(
+            expand_b_escape(r"hello\tworld"),
+            ("hello\tworld".to_string(), false)
+        );
+        assert_eq!(
+            expand_b_escape(r"a\\b\0101\x42é"),
+            ("a\\bAB\u{e9}".to_string(), false)
+        );
+        assert_eq!(
+            expand_b_escape(r"keep\qthis"),
+            (r"keep\qthis".to_string(), false)
+        );
+        assert_eq!(expand_b_escape(r"stop\chere"), ("stop".to_string(), true));
+    }
+
+    #[test]
+    fn test_b_format_and_stop() {
+        assert_eq!(printf(&[r"%b\n", r"hello\tworld"]).stdout, "hello\tworld\n");
+        // \c ends all output, including the rest of the format
+        assert_eq!(printf(&[r"%b|%s\n", r"one\ctwo", "x"]).stdout, "one");
+        // %s leaves escapes alone
+        assert_eq!(printf(&[r"%s\n", r"a\tb"]).stdout, "a\\tb\n");
+    }
+
+    #[test]
+    fn test_conversions_and_reuse() {
+        assert_eq!(
+            printf(&["%5s|%-4d|%03d|%x|%c", "ab", "7", "5", "255", "zed"]).stdout,
+            "   ab|7   |005|ff|z"
+        );
+        assert_eq!(printf(&[r"%s=%d\n", "a", "1", "b"]).stdout, "a=1\nb=0\n");
+        assert_eq!(printf(&["%.2s%%", "abc"]).stdout, "ab%");
+        assert_eq!(printf(&[r"\101\n"]).stdout, "A\n");
+    }
+
+    #[test]
+    fn test_invalid_number() {
+        let result = printf(&["%d", "abc"]);
+        assert_eq!(result.code, 1);
+        assert_eq!(result.stdout, "0");
+        assert!(result.stderr.contains("invalid number"));
+        assert_eq!(printf(&[]).code, 2);
+    }
+
+    #[test]
+    fn test_assign_to_variable() {
+        let mut interpreter = Interpreter::new();
+        let args: Vec<String> = ["-v", "msg", "%s %s", "hello", "world"]
+            .iter()
+            .map(|value| (*value).to_string())
+            .collect();
+        let result = interpreter.builtin_printf(&args);
+        assert_eq!(result.code, 0);
+        assert_eq!(result.stdout, "");
+        assert_eq!(interpreter.variable_context.value("msg"), Some("hello world".to_string()));
+
+        let result = printf(&["-v", "FUNCNAME", "x"]);
+        assert_eq!(result.code, 1);
+        assert_eq!(result.stderr, "printf: FUNCNAME: readonly variable\n");
+
+        let result = printf(&["-v", "1x", "x"]);
+        assert_eq!(result.code, 2);
+        assert_eq!(result.stderr, "printf: `1x': not a valid identifier\n");
+        let result = printf(&["-v", "a-b", "x"]);
+        assert_eq!(result.code, 2);
+    }
+}
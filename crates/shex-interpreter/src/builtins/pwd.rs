@@ -0,0 +1,52 @@
+//! `pwd [-L | -P]` - print the working directory
+//!
+//! The interpreter keeps its directory with symlinks resolved, so `-L` and
+//! `-P` print the same path.
+
+use crate::{ExitStatus, Interpreter};
+
+impl Interpreter {
+    /// Run the `pwd` builtin
+    pub(crate) fn builtin_pwd(&self, args: &[String]) -> ExitStatus {
+        for arg in args {
+            match arg.as_str() {
+                "-L" | "-P" | "--" => {}
+                option if option.starts_with('-') => {
+                    return pwd_error(2, &format!("pwd: {option}: invalid option"));
+                }
+                _ => return pwd_error(1, "pwd: too many arguments"),
+            }
+        }
+        ExitStatus {
+            code: 0,
+            stdout: format!("{}\n", self.cwd.display()),
+            stderr: String::new(),
+        }
+    }
+}
+
+fn pwd_error(code: i32, message: &str) -> ExitStatus {
+    ExitStatus::with(code, format!("{message}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Interpreter;
+
+    #[test]
+    fn test_pwd_follows_cd() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(base.join("sub")).unwrap();
+        let mut interpreter = Interpreter::with_cwd(&base).unwrap();
+
+        let result = interpreter.execute_string("cd sub; pwd -P; cd - >/dev/null; pwd").unwrap();
+        let sub = base.join("sub");
+        assert_eq!(
+            result.stdout,
+            format!("{}\n{}\n", sub.display(), base.display())
+        );
+        assert_eq!(interpreter.run_builtin("pwd", &["-x"]).unwrap().code, 2);
+        assert_eq!(interpreter.run_builtin("pwd", &["extra"]).unwrap().code, 1);
+    }
+}
@@ -0,0 +1,271 @@
+//! `read [-r] [-a ARRAY] [-d DELIM] [NAME...]` - read a line from standard
+//! input into variables
+//!
+//! The line is split into fields on `$IFS`; each name takes one field and
+//! the last name takes whatever remains. With no names the whole line goes
+//! to `REPLY`, and with `-a` every field becomes an element of `ARRAY`.
+//! Without `-r`, a backslash escapes the next character and a backslash at
+//! the end of a line joins it with the next one. `-d` ends the line at the
+//! first character of `DELIM` instead of a newline, or at NUL when empty.
+//!
+//! Inside an in-process pipeline the line comes from the previous stage's
+//! output, otherwise from the shell's own stdin.
+
+use crate::{ExitStatus, Interpreter};
+use std::io::BufRead;
+
+const DEFAULT_IFS: &str = " \t\n";
+
+impl Interpreter {
+    /// Run the `read` builtin
+    pub(crate) fn builtin_read(&mut self, args: &[String]) -> ExitStatus {
+        let mut raw = false;
+        let mut array = None;
+        let mut delimiter = b'\n';
+        let mut next = 0;
+        while let Some(arg) = args.get(next) {
+            if arg == "--" {
+                next += 1;
+                break;
+            }
+            let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+                break;
+            };
+            next += 1;
+            for (index, flag) in flags.char_indices() {
+                match flag {
+                    'r' => {
+                        raw = true;
+                        continue;
+                    }
+                    'a' | 'd' => {}
+                    _ => return read_status(2, &format!("read: -{flag}: invalid option\n")),
+                }
+                // The value is the rest of this argument, or the next one
+                let attached = &flags[index + 1..];
+                let value = if attached.is_empty() {
+                    let Some(value) = args.get(next) else {
+                        let message = format!("read: -{flag}: option requires an argument\n");
+                        return read_status(2, &message);
+                    };
+                    next += 1;
+                    value.as_str()
+                } else {
+                    attached
+                };
+                if flag == 'a' {
+                    array = Some(value.to_string());
+                } else {
+                    match value.bytes().next() {
+                        Some(byte) if byte.is_ascii() => delimiter = byte,
+                        Some(_) => {
+                            return read_status(2, "read: -d: delimiter must be ASCII\n");
+                        }
+                        None => delimiter = 0,
+                    }
+                }
+                break;
+            }
+        }
+
+        let names = &args[next..];
+
+        let Some((line, complete)) = self.read_logical_line(raw, delimiter) else {
+            if let Some(array) = array {
+                self.variable_context.set_array(array, Vec::new());
+            }
+            return read_status(1, "");
+        };
+
+        let ifs = self
+            .variable_context
+            .value("IFS")
+            .unwrap_or_else(|| DEFAULT_IFS.to_string());
+        if let Some(array) = array {
+            self.variable_context
+                .set_array(array, split_fields(&line, &ifs, usize::MAX));
+            return read_status(i32::from(!complete), "");
+        }
+        let default_name = ["REPLY".to_string()];
+        let names = if names.is_empty() {
+            &default_name[..]
+        } else {
+            names
+        };
+        for (name, value) in names.iter().zip(split_into(&line, &ifs, names.len())) {
+            self.variable_context.set(name.clone(), value);
+        }
+
+        // A final line without its newline is assigned but still reports EOF
+        read_status(i32::from(!complete), "")
+    }
+
+    /// Read one line, joining backslash-continued lines unless `raw`
+    ///
+    /// Returns None at end of input; otherwise the line with escapes removed
+    /// and whether it ended with `delimiter`
+    fn read_logical_line(&mut self, raw: bool, delimiter: u8) -> Option<(String, bool)> {
+        let mut line = String::new();
+        loop {
+            let (physical, complete) = match self.read_physical_line(delimiter) {
+                Some(read) => read,
+                None if line.is_empty() => return None,
+                None => return Some((line, false)),
+            };
+            if raw {
+                return Some((physical, complete));
+            }
+            let mut chars = physical.chars();
+            let mut continued = false;
+            while let Some(c) = chars.next() {
+                if c != '\\' {
+                    line.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some(escaped) => line.push(escaped),
+                    None => continued = complete,
+                }
+            }
+            if !continued {
+                return Some((line, complete));
+            }
+        }
+    }
+
+    /// Take the next line from the pipeline input or stdin, without the
+    /// ASCII `delimiter` that ends it
+    pub(super) fn read_physical_line(&mut self, delimiter: u8) -> Option<(String, bool)> {
+        let mut line = match self.piped_input.as_mut() {
+            Some(input) => {
+                let end = input
+                    .bytes()
+                    .position(|byte| byte == delimiter)
+                    .map_or(input.len(), |position| position + 1);
+                input.drain(..end).collect()
+            }
+            None => {
+                let mut bytes = Vec::new();
+                std::io::stdin().lock().read_until(delimiter, &mut bytes).ok()?;
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+        };
+        if line.is_empty() {
+            return None;
+        }
+        let complete = line.as_bytes().last() == Some(&delimiter);
+        if complete {
+            line.pop();
+        }
+        Some((line, complete))
+    }
+}
+
+/// Split `line` into at most `count` fields on the characters of `ifs`
+///
+/// Whitespace in `ifs` is trimmed from both ends and runs of it separate
+/// fields; the last field keeps the rest of the line as it was
+fn split_into(line: &str, ifs: &str, count: usize) -> Vec<String> {
+    let mut fields = split_fields(line, ifs, count);
+    fields.resize(count, String::new());
+    fields
+}
+
+/// Split `line` on the characters of `ifs` into at most `limit` fields
+///
+/// Unlike `split_into`, missing fields are not padded with empty strings
+fn split_fields(line: &str, ifs: &str, limit: usize) -> Vec<String> {
+    let is_ifs_space = |c: char| ifs.contains(c) && c.is_whitespace();
+    let is_separator = |c: char| ifs.contains(c);
+    let mut rest = line.trim_matches(is_ifs_space);
+    let mut fields = Vec::new();
+
+    while fields.len() + 1 < limit && !rest.is_empty() {
+        let end = rest.find(is_separator).unwrap_or(rest.len());
+        fields.push(rest[..end].to_string());
+        rest = rest[end..].trim_start_matches(is_ifs_space);
+        // One non-whitespace separator ends the field as well
+        if let Some(after) = rest.strip_prefix(|c: char| is_separator(c) && !c.is_whitespace()) {
+            rest = after.trim_start_matches(is_ifs_space);
+        }
+    }
+    if !rest.is_empty() {
+        fields.push(rest.to_string());
+    }
+    fields
+}
+
+fn read_status(code: i32, stderr: &str) -> ExitStatus {
+    ExitStatus::with(code, stderr.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| (*value).to_string()).collect()
+    }
+
+    #[test]
+    fn test_split_into_fields() {
+        assert_eq!(split_into("  a b  c ", DEFAULT_IFS, 2), ["a", "b  c"]);
+        assert_eq!(split_into("a", DEFAULT_IFS, 3), ["a", "", ""]);
+        assert_eq!(split_into("x:y:z", ":", 2), ["x", "y:z"]);
+        assert_eq!(split_into("  whole line ", DEFAULT_IFS, 1), ["whole line"]);
+    }
+
+    #[test]
+    fn test_read_from_piped_input() {
+        let mut interpreter = Interpreter::new();
+        interpreter.piped_input = Some("first line\nsecond \\\n continued\nlast".to_string());
+
+        assert_eq!(interpreter.builtin_read(&args(&["a", "b"])).code, 0);
+        assert_eq!(interpreter.variable_context.value("a").unwrap(), "first");
+        assert_eq!(interpreter.variable_context.value("b").unwrap(), "line");
+
+        assert_eq!(interpreter.builtin_read(&[]).code, 0);
+        assert_eq!(
+            interpreter.variable_context.value("REPLY").unwrap(),
+            "second  continued"
+        );
+
+        // The unterminated last line is assigned but reports end of input
+        assert_eq!(interpreter.builtin_read(&args(&["-r", "x"])).code, 1);
+        assert_eq!(interpreter.variable_context.value("x").unwrap(), "last");
+        assert_eq!(interpreter.builtin_read(&args(&["x"])).code, 1);
+    }
+
+    #[test]
+    fn test_read_into_array() {
+        let mut interpreter = Interpreter::new();
+        interpreter.piped_input = Some("  one two  three \nx,y,,z\n".to_string());
+
+        assert_eq!(interpreter.builtin_read(&args(&["-a", "words"])).code, 0);
+        assert_eq!(
+            interpreter.variable_context.get_array("words").unwrap(),
+            &["one", "two", "three"]
+        );
+
+        interpreter.variable_context.set("IFS".to_string(), ",".to_string());
+        assert_eq!(interpreter.builtin_read(&args(&["-ra", "fields"])).code, 0);
+        assert_eq!(
+            interpreter.variable_context.get_array("fields").unwrap(),
+            &["x", "y", "", "z"]
+        );
+    }
+
+    #[test]
+    fn test_read_with_delimiter() {
+        let mut interpreter = Interpreter::new();
+        interpreter.piped_input = Some("a b;c\nd;".to_string());
+
+        assert_eq!(interpreter.builtin_read(&args(&["-d", ";", "x", "y"])).code, 0);
+        assert_eq!(interpreter.variable_context.value("x").unwrap(), "a");
+        assert_eq!(interpreter.variable_context.value("y").unwrap(), "b");
+
+        assert_eq!(interpreter.builtin_read(&args(&["-rd;"])).code, 0);
+        assert_eq!(interpreter.variable_context.value("REPLY").unwrap(), "c\nd");
+        assert_eq!(interpreter.builtin_read(&args(&["-d", ";"])).code, 1);
+    }
+}
@@ -0,0 +1,121 @@
+//! `readonly [-p] [NAME[=VALUE] ...]` - mark variables readonly
+//!
+//! Readonly variables can no longer be assigned. A name may be marked
+//! before it has a value. Without names, or with `-p`, every readonly
+//! variable is listed as a `readonly` command that recreates it when the
+//! output is run by the shell.
+
+use crate::{ExitStatus, Interpreter};
+use shex_parser::string_utils::{is_valid_variable_name, quote_for_shell};
+
+impl Interpreter {
+    /// Run the `readonly` builtin
+    pub(crate) fn builtin_readonly(&mut self, args: &[String]) -> ExitStatus {
+        let (print, names) = match declaration_options("readonly", args) {
+            Ok(options) => options,
+            Err(status) => return status,
+        };
+        if print || names.is_empty() {
+            return ExitStatus {
+                stdout: self.declarations("readonly", &self.variable_context.readonly_names()),
+                ..ExitStatus::empty()
+            };
+        }
+
+        let mut stderr = String::new();
+        for arg in names {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            if !is_valid_variable_name(name) {
+                stderr.push_str(&format!("readonly: `{arg}': not a valid identifier\n"));
+                continue;
+            }
+            if let Some(value) = value {
+                if self.is_readonly(name) {
+                    stderr.push_str(&format!("readonly: {name}: readonly variable\n"));
+                    continue;
+                }
+                self.variable_context
+                    .set(name.to_string(), value.to_string());
+            }
+            self.variable_context.mark_readonly(name);
+        }
+        ExitStatus::with(i32::from(!stderr.is_empty()), stderr)
+    }
+
+    /// One `command NAME='VALUE'` line per name, or `command NAME` when the
+    /// variable has no value, quoted so the shell can read it back
+    pub(super) fn declarations(&self, command: &str, names: &[String]) -> String {
+        names
+            .iter()
+            .map(|name| match self.variable_context.get(name) {
+                Some(value) => format!("{command} {name}={}\n", quote_for_shell(value)),
+                None => format!("{command} {name}\n"),
+            })
+            .collect()
+    }
+}
+
+/// Split the leading `-p` or `--` accepted by `export` and `readonly` from
+/// the names that follow, returning whether `-p` was given
+///
+/// Any other option fails with status 2
+pub(super) fn declaration_options<'a>(
+    command: &str,
+    args: &'a [String],
+) -> Result<(bool, &'a [String]), ExitStatus> {
+    match args.split_first() {
+        Some((flag, rest)) if flag == "-p" => Ok((true, rest)),
+        Some((flag, rest)) if flag == "--" => Ok((false, rest)),
+        Some((flag, _)) if flag.starts_with('-') && flag.len() > 1 => Err(ExitStatus::with(
+            2,
+            format!("{command}: {flag}: invalid option\n"),
+        )),
+        _ => Ok((false, args)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Interpreter;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| (*value).to_string()).collect()
+    }
+
+    #[test]
+    fn test_readonly_marks_and_rejects_reassignment() {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(interpreter.builtin_readonly(&args(&["A=1", "B"])).code, 0);
+        assert_eq!(interpreter.variable("A").as_deref(), Some("1"));
+        assert!(interpreter.is_readonly("A"));
+        assert!(interpreter.is_readonly("B"));
+
+        let result = interpreter.builtin_readonly(&args(&["A=2"]));
+        assert_eq!(result.code, 1);
+        assert_eq!(result.stderr, "readonly: A: readonly variable\n");
+        assert_eq!(interpreter.variable("A").as_deref(), Some("1"));
+
+        let result = interpreter.builtin_readonly(&args(&["1x=3"]));
+        assert_eq!(result.code, 1);
+        assert_eq!(result.stderr, "readonly: `1x=3': not a valid identifier\n");
+        assert_eq!(interpreter.builtin_readonly(&args(&["-x"])).code, 2);
+    }
+
+    #[test]
+    fn test_readonly_p_quotes_values() {
+        let mut interpreter = Interpreter::new();
+        interpreter.builtin_readonly(&args(&["Z=it's here", "A=", "UNSET"]));
+
+        let result = interpreter.builtin_readonly(&args(&["-p"]));
+        assert_eq!(result.code, 0);
+        assert_eq!(
+            result.stdout,
+            "readonly A=''\nreadonly UNSET\nreadonly Z='it'\"'\"'s here'\n"
+        );
+        assert_eq!(interpreter.builtin_readonly(&[]).stdout, result.stdout);
+    }
+}
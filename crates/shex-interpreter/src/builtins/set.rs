@@ -0,0 +1,98 @@
+//! `set` - change shell options
+//!
+//! Supports `-x`/`+x` style flags and `-o NAME`/`+o NAME`. The first
+//! argument that is not an option, or everything after `--`, replaces the
+//! positional parameters.
+
+use crate::{ExitStatus, Interpreter};
+
+impl Interpreter {
+    /// Run the `set` builtin
+    pub(crate) fn builtin_set(&mut self, args: &[String]) -> ExitStatus {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                self.variable_context
+                    .set_positional_params(args.cloned().collect());
+                break;
+            }
+            let enable = match arg.chars().next() {
+                Some('-') => true,
+                Some('+') => false,
+                _ => {
+                    let params = std::iter::once(arg).chain(args).cloned().collect();
+                    self.variable_context.set_positional_params(params);
+                    break;
+                }
+            };
+
+            let flags = &arg[1..];
+            if flags == "o" {
+                let Some(name) = args.next() else {
+                    return set_error("set: -o: option name required");
+                };
+                match self.options.by_name_mut(name) {
+                    Some(option) => *option = enable,
+                    None => return set_error(&format!("set: {name}: invalid option name")),
+                }
+                continue;
+            }
+
+            for flag in flags.chars() {
+                match self.options.by_flag_mut(flag) {
+                    Some(option) => *option = enable,
+                    None => return set_error(&format!("set: -{flag}: invalid option")),
+                }
+            }
+        }
+
+        ExitStatus::empty()
+    }
+}
+
+fn set_error(message: &str) -> ExitStatus {
+    ExitStatus::with(2, format!("{message}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| (*value).to_string()).collect()
+    }
+
+    #[test]
+    fn test_set_flags() {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(interpreter.builtin_set(&args(&["-x"])).code, 0);
+        assert!(interpreter.options.xtrace);
+
+        assert_eq!(interpreter.builtin_set(&args(&["+o", "xtrace"])).code, 0);
+        assert!(!interpreter.options.xtrace);
+    }
+
+    #[test]
+    fn test_set_positional_params() {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(interpreter.builtin_set(&args(&["-x", "--", "-a", "b"])).code, 0);
+        assert!(interpreter.options.xtrace);
+        assert_eq!(interpreter.variable_context.positional_params(), ["-a", "b"]);
+
+        assert_eq!(interpreter.builtin_set(&args(&["one", "-x"])).code, 0);
+        assert_eq!(interpreter.variable_context.positional_params(), ["one", "-x"]);
+
+        assert_eq!(interpreter.builtin_set(&args(&["--"])).code, 0);
+        assert!(interpreter.variable_context.positional_params().is_empty());
+    }
+
+    #[test]
+    fn test_set_invalid_option() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.builtin_set(&args(&["-o", "bogus"]));
+        assert_eq!(result.code, 2);
+        assert!(result.stderr.contains("bogus"));
+    }
+}
@@ -0,0 +1,62 @@
+//! `shift [n]` - drop the first `n` positional parameters
+//!
+//! `$n+1` becomes `$1` and `$#` goes down by `n`, which defaults to one.
+//! Shifting more parameters than there are fails and changes nothing.
+
+use crate::{ExitStatus, Interpreter};
+
+impl Interpreter {
+    /// Run the `shift` builtin
+    pub(crate) fn builtin_shift(&mut self, args: &[String]) -> ExitStatus {
+        let count = match args {
+            [] => 1,
+            [count] => match count.parse::<usize>() {
+                Ok(count) => count,
+                Err(_) => return shift_error(2, &format!("shift: {count}: numeric argument required")),
+            },
+            _ => return shift_error(2, "shift: too many arguments"),
+        };
+        let params = self.variable_context.positional_params();
+        if count > params.len() {
+            return shift_error(1, &format!("shift: {count}: shift count out of range"));
+        }
+        let remaining = params[count..].to_vec();
+        self.variable_context.set_positional_params(remaining);
+        ExitStatus::empty()
+    }
+}
+
+fn shift_error(code: i32, message: &str) -> ExitStatus {
+    ExitStatus::with(code, format!("{message}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Interpreter;
+
+    fn params(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| (*value).to_string()).collect()
+    }
+
+    #[test]
+    fn test_shift_drops_leading_parameters() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_positional_params(params(&["a", "b", "c"]));
+
+        assert_eq!(interpreter.builtin_shift(&[]).code, 0);
+        assert_eq!(interpreter.variable_context.positional_params(), params(&["b", "c"]));
+        assert_eq!(interpreter.builtin_shift(&params(&["2"])).code, 0);
+        assert!(interpreter.variable_context.positional_params().is_empty());
+    }
+
+    #[test]
+    fn test_shift_rejects_bad_counts() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_positional_params(params(&["a"]));
+
+        assert_eq!(interpreter.builtin_shift(&params(&["2"])).code, 1);
+        assert_eq!(interpreter.builtin_shift(&params(&["x"])).code, 2);
+        assert_eq!(interpreter.builtin_shift(&params(&["1", "1"])).code, 2);
+        assert_eq!(interpreter.variable_context.positional_params(), params(&["a"]));
+    }
+}
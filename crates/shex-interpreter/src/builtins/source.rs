@@ -0,0 +1,63 @@
+//! `source` / `.` - run a file in the current shell
+//!
+//! The file is parsed and executed with the caller's variables and
+//! functions. While it runs, `$LINENO` counts lines of the sourced file and
+//! `${BASH_SOURCE[0]}` names it.
+
+use crate::{CaptureMode, ExitStatus, Interpreter};
+use shex_ast::{ShexError, SourceMap};
+use shex_parser::Parser;
+use std::sync::Arc;
+
+impl Interpreter {
+    /// Run a file in the current shell, as `source path` would
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShexError` if the file fails to parse or a command in it fails
+    pub fn source_file(&mut self, path: &str) -> Result<ExitStatus, ShexError> {
+        self.builtin_source("source", &[path.to_string()])
+    }
+
+    /// Run the `source` builtin
+    pub(crate) fn builtin_source(
+        &mut self,
+        name: &str,
+        args: &[String],
+    ) -> Result<ExitStatus, ShexError> {
+        let Some(path) = args.first() else {
+            return Ok(source_error(
+                2,
+                &format!("{name}: filename argument required"),
+            ));
+        };
+        let content = match std::fs::read_to_string(self.resolve_path(path)) {
+            Ok(content) => content,
+            Err(e) => return Ok(source_error(1, &format!("{name}: {path}: {e}"))),
+        };
+
+        let parser = Parser::new_with_filename(&content, path)?;
+        let program = parser.parse()?;
+
+        let saved_map = self.source_map.replace(Arc::new(SourceMap::new(&content)));
+        self.source_files.push(path.clone());
+        self.sync_bash_source();
+
+        let result = self.execute_program(program, CaptureMode::Accumulate).map(|mut status| {
+            if let Some(code) = self.take_return() {
+                status.code = code;
+            }
+            status
+        });
+        let result = self.run_return_trap(result);
+
+        self.source_files.pop();
+        self.sync_bash_source();
+        self.source_map = saved_map;
+        result
+    }
+}
+
+fn source_error(code: i32, message: &str) -> ExitStatus {
+    ExitStatus::with(code, format!("{message}\n"))
+}
@@ -0,0 +1,98 @@
+//! `trap [ACTION CONDITION...]` - run commands when a condition occurs
+//!
+//! `ACTION` is shell code run each time one of the conditions happens. An
+//! empty action ignores the condition and `-` restores the default. Without
+//! arguments the installed traps are listed in a form that can be read back.
+//! `ERR` runs after a command exits non-zero; inside functions it only runs
+//! with `set -E`. `DEBUG` runs before each simple command, with the command
+//! in `$BASH_COMMAND`, and `RETURN` after each function call or sourced file.
+
+use crate::{ExitStatus, Interpreter};
+
+/// Conditions `trap` accepts
+const CONDITIONS: &[&str] = &["ERR", "DEBUG", "RETURN"];
+
+impl Interpreter {
+    /// Run the `trap` builtin
+    pub(crate) fn builtin_trap(&mut self, args: &[String]) -> ExitStatus {
+        let args = match args.first() {
+            Some(first) if first == "--" => &args[1..],
+            _ => args,
+        };
+        let Some((action, conditions)) = args.split_first() else {
+            return self.list_traps();
+        };
+        if conditions.is_empty() {
+            return trap_error(&format!("trap: {action}: condition required"));
+        }
+
+        for condition in conditions {
+            if !CONDITIONS.contains(&condition.as_str()) {
+                return trap_error(&format!("trap: {condition}: invalid signal specification"));
+            }
+        }
+        for condition in conditions {
+            if action == "-" {
+                self.traps.remove(condition);
+            } else {
+                self.traps.insert(condition.clone(), action.clone());
+            }
+        }
+
+        ExitStatus::empty()
+    }
+
+    fn list_traps(&self) -> ExitStatus {
+        let mut conditions: Vec<_> = self.traps.keys().collect();
+        conditions.sort();
+        let stdout = conditions
+            .into_iter()
+            .map(|condition| {
+                let action = self.traps[condition].replace('\'', "'\\''");
+                format!("trap -- '{action}' {condition}\n")
+            })
+            .collect();
+        ExitStatus {
+            code: 0,
+            stdout,
+            stderr: String::new(),
+        }
+    }
+}
+
+fn trap_error(message: &str) -> ExitStatus {
+    ExitStatus::with(2, format!("{message}\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| (*value).to_string()).collect()
+    }
+
+    #[test]
+    fn test_trap_lists_and_resets_actions() {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(interpreter.builtin_trap(&args(&["echo 'failed'", "ERR"])).code, 0);
+        assert_eq!(
+            interpreter.builtin_trap(&[]).stdout,
+            "trap -- 'echo '\\''failed'\\''' ERR\n"
+        );
+
+        assert_eq!(interpreter.builtin_trap(&args(&["-", "ERR"])).code, 0);
+        assert_eq!(interpreter.builtin_trap(&[]).stdout, "");
+    }
+
+    #[test]
+    fn test_trap_rejects_unknown_conditions() {
+        let mut interpreter = Interpreter::new();
+
+        let result = interpreter.builtin_trap(&args(&["echo hi", "NOPE"]));
+        assert_eq!(result.code, 2);
+        assert_eq!(result.stderr, "trap: NOPE: invalid signal specification\n");
+        assert!(interpreter.traps.is_empty());
+    }
+}
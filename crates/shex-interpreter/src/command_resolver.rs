@@ -0,0 +1,210 @@
+//! Pluggable execution of external commands
+//!
+//! By default the interpreter spawns real processes for external commands.
+//! Installing a `CommandResolver` with `Interpreter::set_command_resolver`
+//! routes them through it instead, so tests can check which commands a
+//! script runs without touching the system. Builtins and functions never
+//! reach the resolver; background jobs and coprocesses still spawn.
+
+use std::collections::HashMap;
+use std::process::Command as StdCommand;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Status and output of an external command
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandResult {
+    pub code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandResult {
+    /// A successful result that prints `stdout`
+    #[must_use]
+    pub fn success(stdout: &str) -> Self {
+        Self {
+            code: 0,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+        }
+    }
+
+    /// A failed result with the given exit code and error output
+    #[must_use]
+    pub fn failure(code: i32, stderr: &str) -> Self {
+        Self {
+            code,
+            stdout: String::new(),
+            stderr: stderr.to_string(),
+        }
+    }
+}
+
+/// Runs external commands on behalf of the interpreter
+pub trait CommandResolver: Send {
+    /// Run `name` with `args` in the environment `env`
+    ///
+    /// Returns None when there is no such command, which the interpreter
+    /// reports as `ERR_COMMAND_NOT_FOUND`
+    fn resolve(
+        &self,
+        name: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> Option<CommandResult>;
+}
+
+/// Spawns each command as a real process with exactly `env` as its environment
+///
+/// Unlike the interpreter's built-in spawning it applies no redirections
+/// and gives the command no stdin
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsCommandResolver;
+
+impl CommandResolver for OsCommandResolver {
+    fn resolve(
+        &self,
+        name: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> Option<CommandResult> {
+        let output = StdCommand::new(name)
+            .args(args)
+            .env_clear()
+            .envs(env)
+            .output()
+            .ok()?;
+        Some(CommandResult {
+            code: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+}
+
+/// A command as the interpreter asked a resolver to run it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedCall {
+    pub name: String,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+}
+
+/// Canned responses for known command names, recording every call
+///
+/// Clones share their responses and recorded calls, so a test can keep one
+/// clone and hand another to the interpreter. Commands without a response
+/// are not found.
+#[derive(Debug, Clone, Default)]
+pub struct MockCommandResolver {
+    responses: Arc<Mutex<HashMap<String, CommandResult>>>,
+    calls: Arc<Mutex<Vec<RecordedCall>>>,
+}
+
+impl MockCommandResolver {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer every call of `name` with `result`
+    #[must_use]
+    pub fn respond(self, name: &str, result: CommandResult) -> Self {
+        self.responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(name.to_string(), result);
+        self
+    }
+
+    /// Calls made so far, oldest first
+    #[must_use]
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl CommandResolver for MockCommandResolver {
+    fn resolve(
+        &self,
+        name: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> Option<CommandResult> {
+        self.calls
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(RecordedCall {
+                name: name.to_string(),
+                args: args.to_vec(),
+                env: env.clone(),
+            });
+        self.responses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(name)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interpreter;
+    use shex_ast::ShexError;
+
+    #[test]
+    fn test_mock_records_calls_and_answers() {
+        let mock = MockCommandResolver::new().respond("git", CommandResult::success("main\n"));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_command_resolver(Box::new(mock.clone()));
+
+        let result = interpreter
+            .execute_string("set -a; REPO=shex\ngit branch --show-current\necho finished")
+            .unwrap();
+        assert_eq!(result.stdout, "main\nfinished\n");
+
+        let calls = mock.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "git");
+        assert_eq!(calls[0].args, ["branch", "--show-current"]);
+        assert_eq!(calls[0].env.get("REPO").map(String::as_str), Some("shex"));
+    }
+
+    #[test]
+    fn test_mock_handles_pipelines_and_unknown_commands() {
+        let mock = MockCommandResolver::new()
+            .respond("ls", CommandResult::success("a\nb\n"))
+            .respond("wc", CommandResult::failure(3, "wc: broken\n"));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_command_resolver(Box::new(mock.clone()));
+
+        let result = interpreter.execute_string("ls | wc -l").unwrap();
+        assert_eq!((result.code, result.stderr.as_str()), (3, "wc: broken\n"));
+        assert_eq!(mock.calls().len(), 2);
+
+        let error = interpreter.execute_string("rm -rf /").unwrap_err();
+        assert!(matches!(error, ShexError::CommandNotFound { .. }));
+    }
+
+    #[test]
+    fn test_os_resolver_uses_given_environment() {
+        let env = HashMap::from([("GREETING".to_string(), "hi".to_string())]);
+        let result = OsCommandResolver
+            .resolve(
+                "sh",
+                &["-c".to_string(), "echo $GREETING".to_string()],
+                &env,
+            )
+            .unwrap();
+        assert_eq!(result, CommandResult::success("hi\n"));
+        assert!(
+            OsCommandResolver
+                .resolve("shex_no_such_command", &[], &env)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Dry-run descriptions of what a program would do
+//!
+//! `Interpreter::explain` walks the AST without running anything. Words are
+//! described as written, so `$var` stays `$var` rather than being expanded.
+
+use crate::Interpreter;
+use crate::builtins::is_builtin;
+use shex_ast::walk::walk;
+use shex_ast::{Command, Program, Redirection, RedirectionKind, Span};
+
+/// What one command in a program would do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandExplanation {
+    /// Human-readable summary, such as `prints to stdout: hello`
+    pub description: String,
+    /// Where the command is in the source
+    pub span: Span,
+    /// Effects the command has beyond its exit status
+    pub side_effects: Vec<SideEffect>,
+}
+
+/// An effect a command is expected to have on the shell or the system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SideEffect {
+    /// Sets the named variable
+    SetsVariable(String),
+    /// Defines the named function
+    DefinesFunction(String),
+    /// Reads the named file
+    ReadsFile(String),
+    /// Creates, truncates or appends to the named file
+    WritesFile(String),
+    /// Starts the named external program
+    SpawnsProcess(String),
+}
+
+impl Interpreter {
+    /// Describe every command in `program` without executing it
+    ///
+    /// Commands are listed depth-first, parents before the commands nested
+    /// in them. Functions already defined in this interpreter are reported
+    /// as calls rather than external programs. Comments are left out.
+    #[must_use]
+    pub fn explain(&self, program: &Program) -> Vec<CommandExplanation> {
+        let mut explanations = Vec::new();
+        walk(&program.commands, &mut |command| {
+            let mut side_effects = Vec::new();
+            let description = match &command.node {
+                Command::Simple {
+                    name,
+                    args,
+                    assignments,
+                    redirections,
+                } => {
+                    side_effects.extend(assignment_effects(assignments));
+                    side_effects.extend(redirection_effects(redirections));
+                    self.explain_simple(name, args, &mut side_effects)
+                }
+                Command::Assignment { assignments } => {
+                    side_effects.extend(assignment_effects(assignments));
+                    let assignments: Vec<String> = assignments
+                        .iter()
+                        .map(|(name, value)| format!("{name} to {value}"))
+                        .collect();
+                    format!("sets variable {}", assignments.join(", "))
+                }
+                Command::Pipeline {
+                    commands,
+                    redirections,
+                } => {
+                    side_effects.extend(redirection_effects(redirections));
+                    format!("pipes output through {} commands", commands.len())
+                }
+                Command::AndIf { .. } => "runs the second command if the first succeeds".to_string(),
+                Command::OrIf { .. } => "runs the second command if the first fails".to_string(),
+                Command::Sequence { commands } => format!("runs {} commands in order", commands.len()),
+                Command::Background { .. } => "runs a command in the background".to_string(),
+                Command::If { .. } => "runs commands depending on a condition".to_string(),
+                Command::While { .. } => "repeats commands while a condition succeeds".to_string(),
+                Command::Until { .. } => "repeats commands until a condition succeeds".to_string(),
+                Command::For { variable, words, .. } => {
+                    side_effects.push(SideEffect::SetsVariable(variable.clone()));
+                    match words {
+                        Some(words) => format!("loops over {} with {variable}", words.join(" ")),
+                        None => format!("loops over the positional parameters with {variable}"),
+                    }
+                }
+                Command::Case { word, .. } => format!("matches {word} against patterns"),
+                Command::Function { name, .. } => {
+                    side_effects.push(SideEffect::DefinesFunction(name.clone()));
+                    format!("defines function {name}")
+                }
+                Command::Subshell { .. } => "runs commands in a subshell".to_string(),
+                Command::BraceGroup { .. } => "runs a group of commands".to_string(),
+                Command::Coproc { name, .. } => {
+                    let name = name.as_deref().unwrap_or("COPROC");
+                    side_effects.push(SideEffect::SetsVariable(name.to_string()));
+                    format!("starts coprocess {name}")
+                }
+                Command::Noop => "does nothing".to_string(),
+                _ => return,
+            };
+            explanations.push(CommandExplanation {
+                description,
+                span: command.span,
+                side_effects,
+            });
+        });
+        explanations
+    }
+
+    fn explain_simple(&self, name: &str, args: &[String], side_effects: &mut Vec<SideEffect>) -> String {
+        let args = args.join(" ");
+        if self.functions.contains_key(name) {
+            return format!("calls function {name} {args}").trim_end().to_string();
+        }
+        match name {
+            "echo" | "printf" => format!("prints to stdout: {args}"),
+            "cd" => format!("changes directory to {args}"),
+            "source" | "." => {
+                side_effects.push(SideEffect::ReadsFile(args.clone()));
+                format!("runs the commands in {args}")
+            }
+            _ if is_builtin(name) => format!("runs builtin {name} {args}").trim_end().to_string(),
+            _ => {
+                side_effects.push(SideEffect::SpawnsProcess(name.to_string()));
+                format!("runs external command {name} {args}").trim_end().to_string()
+            }
+        }
+    }
+}
+
+fn assignment_effects(assignments: &[(String, String)]) -> impl Iterator<Item = SideEffect> + '_ {
+    assignments
+        .iter()
+        .map(|(name, _)| SideEffect::SetsVariable(name.clone()))
+}
+
+/// File accesses made by redirections; descriptor duplication and
+/// here-documents touch no files
+fn redirection_effects(redirections: &[Redirection]) -> impl Iterator<Item = SideEffect> + '_ {
+    redirections.iter().filter_map(|redirection| {
+        let target = redirection.target.clone();
+        match redirection.kind {
+            RedirectionKind::Input => Some(SideEffect::ReadsFile(target)),
+            RedirectionKind::Output
+            | RedirectionKind::Append
+            | RedirectionKind::Clobber
+            | RedirectionKind::InputOutput => Some(SideEffect::WritesFile(target)),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SideEffect;
+    use crate::Interpreter;
+    use shex_parser::Parser;
+
+    fn explain(script: &str) -> Vec<super::CommandExplanation> {
+        let program = Parser::new(script).unwrap().parse().unwrap();
+        Interpreter::new().explain(&program)
+    }
+
+    #[test]
+    fn test_simple_commands_are_not_expanded() {
+        let explanations = explain("X=1\necho hi $X > out.txt");
+        assert_eq!(explanations.len(), 2);
+        assert_eq!(explanations[0].description, "sets variable X to 1");
+        assert_eq!(explanations[0].side_effects, [SideEffect::SetsVariable("X".to_string())]);
+        assert_eq!(explanations[1].description, "prints to stdout: hi $X");
+        assert_eq!(explanations[1].side_effects, [SideEffect::WritesFile("out.txt".to_string())]);
+    }
+
+    #[test]
+    fn test_nested_commands_follow_their_parent() {
+        let explanations = explain("ls | wc -l");
+        let descriptions: Vec<&str> = explanations
+            .iter()
+            .map(|explanation| explanation.description.as_str())
+            .collect();
+        assert_eq!(
+            descriptions,
+            ["pipes output through 2 commands", "runs external command ls", "runs external command wc -l"]
+        );
+        assert_eq!(explanations[1].side_effects, [SideEffect::SpawnsProcess("ls".to_string())]);
+    }
+}
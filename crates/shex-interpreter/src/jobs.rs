@@ -0,0 +1,170 @@
+//! Background job table
+//!
+//! External commands run with `&` are spawned without waiting and recorded
+//! here. When the shell exits, every job still in the table that has not
+//! been marked with `disown -h` receives SIGHUP.
+//!
+//! Builtins, functions and compound commands run with `&` start a subshell
+//! on another thread instead. Having no process, they are kept apart from
+//! the job table and leave `$!` unchanged.
+
+use crate::builtins::is_builtin;
+use crate::{ExitStatus, Interpreter, LAST_BACKGROUND_PID, OutputMode};
+use shex_ast::{Command, ShexError, Spanned};
+use std::process::{Child, Command as StdCommand, Stdio};
+
+/// A background job started with `&`
+pub(crate) struct Job {
+    /// Number used in `%N` job specs, starting at 1
+    pub id: usize,
+    pub child: Child,
+    /// Set by `disown -h`: keep the job but do not send it SIGHUP
+    pub no_hup: bool,
+}
+
+impl Interpreter {
+    /// Start an external command in the background and add it to the job table
+    ///
+    /// Returns None for builtins, functions and compound commands, which
+    /// need `spawn_background_subshell`
+    pub(crate) fn spawn_background_job(
+        &mut self,
+        command: &Spanned<Command>,
+    ) -> Option<Result<ExitStatus, ShexError>> {
+        let Command::Simple {
+            name,
+            args,
+            assignments,
+            redirections,
+        } = &command.node
+        else {
+            return None;
+        };
+        if is_builtin(name) || self.functions.contains_key(name) {
+            return None;
+        }
+        Some(self.spawn_job(name, args, assignments, redirections, command))
+    }
+
+    fn spawn_job(
+        &mut self,
+        name: &str,
+        args: &[String],
+        assignments: &[(String, String)],
+        redirections: &[shex_ast::Redirection],
+        command: &Spanned<Command>,
+    ) -> Result<ExitStatus, ShexError> {
+        let child = self.with_prefix_assignments(assignments, command.span, |interpreter| {
+            let name = &interpreter.expand_command_name(name, command.span)?;
+            let expanded_args = interpreter.expand_arguments(args, command.span)?;
+
+            let mut cmd = StdCommand::new(name);
+            cmd.args(&expanded_args).stdin(Stdio::null());
+            interpreter.apply_environment(&mut cmd);
+            interpreter.apply_redirections(&mut cmd, redirections, command.span)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::CommandExt;
+                // Keep terminal signals such as Ctrl-C away from the job
+                cmd.process_group(0);
+            }
+
+            cmd.spawn()
+                .map_err(|_| interpreter.command_not_found_error(name.to_string(), command.span))
+        })?;
+
+        self.variable_context
+            .set(LAST_BACKGROUND_PID.to_string(), child.id().to_string());
+        let id = self.jobs.last().map_or(1, |job| job.id + 1);
+        self.jobs.push(Job {
+            id,
+            child,
+            no_hup: false,
+        });
+
+        Ok(ExitStatus::empty())
+    }
+
+    /// Run a builtin, function or compound command in a background subshell
+    ///
+    /// The subshell writes to the terminal when the shell does; otherwise
+    /// its output is kept until the handle is waited for
+    pub(crate) fn spawn_background_subshell(&mut self, command: &Spanned<Command>) {
+        let output_mode = match self.output_mode {
+            OutputMode::Terminal => OutputMode::Terminal,
+            _ => OutputMode::Captured,
+        };
+        let handle = self.spawn_subshell_with_output(vec![command.clone()], output_mode);
+        self.background_subshells.push(handle);
+    }
+
+    /// Send SIGHUP to every job that was not disowned
+    ///
+    /// Called when the shell exits
+    pub fn hangup_jobs(&mut self) {
+        #[cfg(unix)]
+        for job in self.jobs.iter().filter(|job| !job.no_hup) {
+            use nix::sys::signal::{Signal, kill};
+            use nix::unistd::Pid;
+            let _ = kill(Pid::from_raw(job.child.id() as i32), Signal::SIGHUP);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use shex_ast::Span;
+    use std::os::unix::process::ExitStatusExt;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    fn sleep_in_background() -> Spanned<Command> {
+        Spanned::new(
+            Command::Simple {
+                name: "sleep".to_string(),
+                args: vec!["30".to_string()],
+                assignments: vec![],
+                redirections: vec![],
+            },
+            Span::dummy(),
+        )
+    }
+
+    #[test]
+    fn test_hangup_spares_disowned_h_jobs() {
+        let mut interpreter = Interpreter::new();
+        for _ in 0..2 {
+            let result = interpreter
+                .spawn_background_job(&sleep_in_background())
+                .unwrap()
+                .unwrap();
+            assert_eq!(result.code, 0);
+        }
+        assert_eq!(interpreter.jobs[1].id, 2);
+        interpreter.jobs[1].no_hup = true;
+
+        interpreter.hangup_jobs();
+
+        let status = interpreter.jobs[0].child.wait().unwrap();
+        assert_eq!(status.signal(), Some(nix::libc::SIGHUP));
+        assert!(interpreter.jobs[1].child.try_wait().unwrap().is_none());
+        let _ = interpreter.jobs[1].child.kill();
+        let _ = interpreter.jobs[1].child.wait();
+    }
+
+    #[test]
+    fn test_compound_commands_run_in_a_background_subshell() {
+        let mut interpreter = Interpreter::new();
+        let start = Instant::now();
+        interpreter
+            .execute_string("{ sleep 0.5; cd /; echo finished; } &")
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(interpreter.jobs.is_empty());
+
+        let handle = interpreter.background_subshells.pop().unwrap();
+        assert_eq!(handle.wait().unwrap().stdout, "finished\n");
+        assert_ne!(interpreter.cwd(), Path::new("/"));
+    }
+}
@@ -3,14 +3,142 @@
 //! Simple command execution for basic shell functionality.
 
 use shex_ast::{Command, Program, ShexError, SourceMap, Spanned, Redirection, RedirectionKind, CaseArm};
-use shex_parser::string_utils::{parse_parameter_expansion, parse_simple_parameter_expansion};
-use shex_parser::variable_resolver::{ResolutionResult, VariableContext, resolve_expansion};
+use shex_ast::pretty::pretty_print_command;
+use shex_ast::walk::walk;
+use shex_parser::Parser;
+use shex_parser::variable_resolver::{
+    ExpansionRequest, ResolutionResult, VariableContext, resolve_expansion, split_subscript,
+};
+use builtins::{is_builtin, is_special_builtin};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::process::{Command as StdCommand, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command as StdCommand, Stdio};
+use std::sync::{Arc, LazyLock};
+
+mod arithmetic;
+mod builtins;
+mod command_resolver;
+mod explain;
+mod jobs;
+mod options;
+mod pipeline;
+mod statistics;
+mod subshell;
+mod validator;
+mod word_expansion;
+
+pub use command_resolver::{
+    CommandResolver, CommandResult, MockCommandResolver, OsCommandResolver, RecordedCall,
+};
+pub use explain::{CommandExplanation, SideEffect};
+pub use options::ShellOptions;
+pub use statistics::InterpreterStats;
+pub use subshell::SubshellHandle;
+pub use validator::{Severity, ValidationError, Validator};
+pub use word_expansion::{WordExpansionOptions, expand_word};
+
+/// Function call stack exposed to scripts, innermost function first
+const FUNCNAME: &str = "FUNCNAME";
+
+/// Files of the code currently executing, parallel to `FUNCNAME`
+const BASH_SOURCE: &str = "BASH_SOURCE";
+
+/// Command about to run, set for the `DEBUG` trap
+const BASH_COMMAND: &str = "BASH_COMMAND";
+
+/// Exit code of the most recent command
+const LAST_STATUS: &str = "?";
+
+/// Name of the running script, or of the shell
+const SCRIPT_NAME: &str = "0";
+
+/// Process ID of the shell, unchanged in subshells
+const SHELL_PID: &str = "$";
+
+/// Process ID of the most recent background job
+const LAST_BACKGROUND_PID: &str = "!";
+
+/// Single-letter flags of the options that are on
+const OPTION_FLAGS: &str = "-";
+
+/// Exit codes of each stage of the most recent pipeline
+const PIPESTATUS: &str = "PIPESTATUS";
+
+/// Variables a new shell has room for before it reallocates, enough for an
+/// interactive session
+const DEFAULT_VARIABLE_CAPACITY: usize = 32;
+
+/// Variables maintained by the interpreter that scripts may not assign
+pub(crate) const READONLY_SPECIALS: &[&str] = &[
+    FUNCNAME,
+    BASH_SOURCE,
+    LAST_STATUS,
+    SHELL_PID,
+    LAST_BACKGROUND_PID,
+    OPTION_FLAGS,
+];
+
+/// A shell function together with where it was defined
+#[derive(Clone)]
+struct Function {
+    body: Arc<Spanned<Command>>,
+    /// File the definition came from, for `BASH_SOURCE`
+    source_file: String,
+    /// Source of that file, for `$LINENO` inside the body
+    source_map: Option<Arc<SourceMap>>,
+}
 
 pub struct Interpreter {
     variable_context: VariableContext,
     exit_code: i32,
+    /// Exit code of the most recent command, exposed as `$?`
+    last_status: i32,
+    /// Running coprocesses by name
+    #[cfg_attr(not(unix), allow(dead_code))]
+    coprocs: HashMap<String, Child>,
+    /// Background jobs started with `&`, oldest first
+    jobs: Vec<jobs::Job>,
+    /// Builtins, functions and compound commands started with `&`, each
+    /// running in a subshell on its own thread
+    background_subshells: Vec<SubshellHandle>,
+    /// Options set with the `set` builtin
+    options: ShellOptions,
+    /// Defined shell functions by name
+    functions: HashMap<String, Function>,
+    /// Names of the functions currently executing, innermost last
+    call_stack: Vec<String>,
+    /// Source of the running program, used to compute `$LINENO`
+    source_map: Option<Arc<SourceMap>>,
+    /// File name reported in errors from the running program
+    source_file: String,
+    /// Files whose code is executing, innermost last; the first is the script
+    source_files: Vec<String>,
+    /// Where `execute` sends command output
+    capture_mode: CaptureMode,
+    /// Where the running command's output goes
+    output_mode: OutputMode,
+    /// Output of the previous stage of an in-process pipeline, read by
+    /// `read` and fed to external commands as their stdin
+    piped_input: Option<String>,
+    /// Runs external commands instead of spawning processes when set
+    command_resolver: Option<Box<dyn CommandResolver>>,
+    /// Actions installed with `trap`, by condition name
+    traps: HashMap<String, String>,
+    /// Whether a trap action is running, so traps do not fire recursively
+    in_trap: bool,
+    /// Number of `for`, `while` and `until` loops currently running
+    loop_depth: usize,
+    /// A `break` or `continue` still unwinding to its loop
+    loop_control: Option<builtins::LoopControl>,
+    /// Most recent error returned by `execute` or one of its wrappers
+    last_error: Option<ShexError>,
+    statistics: InterpreterStats,
+    /// Working directory of the script, changed by `cd`
+    ///
+    /// Relative paths resolve against it and spawned commands start in it;
+    /// the process working directory is never changed
+    cwd: PathBuf,
 }
 
 #[derive(Debug)]
@@ -20,47 +148,467 @@ pub struct ExitStatus {
     pub stderr: String,
 }
 
+impl ExitStatus {
+    /// Successful status with no output
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self {
+            code: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+        }
+    }
+
+    /// Status with the given code and error output but no stdout, as
+    /// returned by builtins that fail
+    #[must_use]
+    pub const fn with(code: i32, stderr: String) -> Self {
+        Self {
+            code,
+            stdout: String::new(),
+            stderr,
+        }
+    }
+
+    /// This status followed by that of a later command, as for pipeline
+    /// stages whose output is collected in order
+    ///
+    /// Stdout and stderr are concatenated and the later exit code wins
+    #[must_use]
+    pub fn combine_stdout(mut self, other: Self) -> Self {
+        self.append(other);
+        self
+    }
+
+    /// Follow this status with that of a later command
+    ///
+    /// Output is appended and the later exit code wins
+    fn append(&mut self, next: Self) {
+        self.append_output(&next);
+        self.code = next.code;
+    }
+
+    /// Append a later command's output but keep this exit code
+    fn append_output(&mut self, next: &Self) {
+        self.stdout.push_str(&next.stdout);
+        self.stderr.push_str(&next.stderr);
+    }
+}
+
+/// How `Interpreter::execute` delivers the output of the commands it runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaptureMode {
+    /// Collect the output of every command into the returned `ExitStatus`
+    #[default]
+    Accumulate,
+    /// Write each command's output to the shell's own stdout and stderr as
+    /// soon as it finishes, returning only the exit code
+    Passthrough,
+}
+
+/// Where the output of the command being run goes
+///
+/// Each simple command and pipeline hands its output on as soon as it
+/// finishes. `execute` starts in `Terminal` or `Captured` according to the
+/// `CaptureMode`; command substitutions capture, and a pipeline stage
+/// feeding an external command writes straight into its stdin.
+#[derive(Debug, Default)]
+pub enum OutputMode {
+    /// Write to the shell's own stdout and stderr
+    Terminal,
+    /// Keep the output in the returned `ExitStatus`
+    #[default]
+    Captured,
+    /// Write stdout into the next pipeline stage; stderr is kept
+    PipedTo(ChildStdin),
+}
+
 impl Interpreter {
     #[must_use]
     pub fn new() -> Self {
-        Self {
-            variable_context: VariableContext::new(),
+        let mut interpreter = Self {
+            variable_context: VariableContext::with_capacity(DEFAULT_VARIABLE_CAPACITY),
             exit_code: 0,
+            last_status: 0,
+            coprocs: HashMap::new(),
+            jobs: Vec::new(),
+            background_subshells: Vec::new(),
+            options: ShellOptions::default(),
+            functions: HashMap::new(),
+            call_stack: Vec::new(),
+            source_map: None,
+            source_file: "<interpreter>".to_string(),
+            source_files: vec![String::new()],
+            capture_mode: CaptureMode::default(),
+            output_mode: OutputMode::default(),
+            piped_input: None,
+            command_resolver: None,
+            traps: HashMap::new(),
+            in_trap: false,
+            loop_depth: 0,
+            loop_control: None,
+            last_error: None,
+            statistics: InterpreterStats::default(),
+            cwd: std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+        };
+        interpreter.sync_funcname();
+        interpreter.sync_bash_source();
+        interpreter.set_last_status(0);
+        interpreter
+            .variable_context
+            .set_static(SCRIPT_NAME.to_string(), "shex");
+        interpreter
+            .variable_context
+            .set(SHELL_PID.to_string(), std::process::id().to_string());
+        interpreter
+    }
+
+    /// Create an interpreter holding the process environment, as a shell
+    /// started from it would
+    ///
+    /// See `import_env`
+    #[must_use]
+    pub fn with_env() -> Self {
+        let mut interpreter = Self::new();
+        interpreter.import_env();
+        interpreter
+    }
+
+    /// Copy every variable of the process environment into the shell and
+    /// export it, so `$HOME` and `$PATH` expand and reach spawned commands
+    ///
+    /// Variables the shell maintains itself, such as `$?`, are not
+    /// overwritten, and `$PWD` is set to the working directory
+    pub fn import_env(&mut self) {
+        for (name, value) in std::env::vars() {
+            if self.is_readonly(&name) {
+                continue;
+            }
+            self.variable_context.set_from_env(name.clone(), value);
+            self.variable_context.export(&name);
+        }
+        self.variable_context
+            .set("PWD".to_string(), self.cwd.display().to_string());
+        self.variable_context.export("PWD");
+    }
+
+    /// Create an interpreter with the given shell options
+    ///
+    /// An `interactive` interpreter enables job control as `set_interactive` does
+    #[must_use]
+    pub fn with_options(options: ShellOptions) -> Self {
+        let mut interpreter = Self::new();
+        let interactive = options.interactive;
+        interpreter.options = ShellOptions {
+            interactive: false,
+            ..options
+        };
+        interpreter.set_interactive(interactive);
+        interpreter
+    }
+
+    /// Create an interpreter for a program parsed from `source_map`
+    ///
+    /// Errors point into that source and name `filename`
+    #[must_use]
+    pub fn with_source(source_map: Arc<SourceMap>, filename: String) -> Self {
+        let mut interpreter = Self::new();
+        interpreter.source_map = Some(source_map);
+        interpreter.source_file = filename;
+        interpreter
+    }
+
+    /// Create an interpreter whose scripts run in the directory `path`
+    ///
+    /// Like `cd`, this sets `$PWD` and makes relative paths in redirections
+    /// and external commands resolve against `path`. The working directory
+    /// of the process is left alone, so interpreters on several threads can
+    /// each have their own.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShexError` if the directory cannot be entered
+    pub fn with_cwd(path: &Path) -> Result<Self, ShexError> {
+        let mut interpreter = Self::new();
+        if let Err(e) = interpreter.change_dir(path) {
+            let message = format!("cd: {}: {e}", path.display());
+            return Err(interpreter.syntax_error(message, shex_ast::Span::dummy()));
+        }
+        Ok(interpreter)
+    }
+
+    /// The directory scripts run in
+    #[must_use]
+    pub fn cwd(&self) -> &Path {
+        &self.cwd
+    }
+
+    /// `path` made absolute against the script's working directory
+    pub(crate) fn resolve_path(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.cwd.join(path)
+    }
+
+    /// Make `path` the working directory and update `$PWD` and `$OLDPWD`
+    pub(crate) fn change_dir(&mut self, path: &Path) -> std::io::Result<()> {
+        let target = self.resolve_path(path).canonicalize()?;
+        if !target.is_dir() {
+            return Err(std::io::Error::new(std::io::ErrorKind::NotADirectory, "Not a directory"));
+        }
+        let previous = std::mem::replace(&mut self.cwd, target);
+        self.variable_context
+            .set("OLDPWD".to_string(), previous.display().to_string());
+        self.variable_context
+            .set("PWD".to_string(), self.cwd.display().to_string());
+        Ok(())
+    }
+
+    /// Return to the state of a new interpreter, ready for an unrelated script
+    ///
+    /// Variables, functions, jobs, traps and shell options are all dropped;
+    /// running jobs and coprocesses are forgotten but not signalled. The
+    /// process environment is imported again, as by `with_env`, and the
+    /// command resolver and capture mode are kept.
+    pub fn reset(&mut self) {
+        let command_resolver = self.command_resolver.take();
+        let capture_mode = self.capture_mode;
+        *self = Self::with_env();
+        self.command_resolver = command_resolver;
+        self.capture_mode = capture_mode;
+    }
+
+    /// Like `reset`, but keep exported variables and their values
+    pub fn reset_preserve_env(&mut self) {
+        let exported: Vec<(String, String)> = self
+            .variable_context
+            .iter_exported()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        self.reset();
+        for (name, value) in exported {
+            self.variable_context.set(name.clone(), value);
+            self.variable_context.export(&name);
         }
     }
 
     /// Execute a Shex program
     ///
+    /// Room for every variable the program assigns is made up front.
+    ///
     /// # Errors
     ///
     /// Returns `ShexError` if command execution fails, command not found, or syntax errors occur
     pub fn execute(&mut self, program: Program) -> Result<ExitStatus, ShexError> {
-        let mut last_stdout = String::new();
-        let mut last_stderr = String::new();
-        let mut last_code = 0;
+        self.variable_context.reserve(assigned_variable_count(&program.commands));
+        let mode = self.capture_mode;
+        let output = match mode {
+            CaptureMode::Accumulate => OutputMode::Captured,
+            CaptureMode::Passthrough => OutputMode::Terminal,
+        };
+        let result =
+            self.with_output_mode(output, |interpreter| interpreter.execute_program(program, mode));
+        self.record_error(result)
+    }
+
+    /// The most recent error returned by `execute`, `execute_string` or
+    /// `execute_file`, kept until `clear_error`
+    ///
+    /// A script whose last command failed is not an error: `execute`
+    /// succeeds with a non-zero exit code and this stays unchanged.
+    #[must_use]
+    pub const fn last_error(&self) -> Option<&ShexError> {
+        self.last_error.as_ref()
+    }
+
+    /// Forget the error reported by `last_error`
+    pub fn clear_error(&mut self) {
+        self.last_error = None;
+    }
+
+    /// Remember the error in `result`, if any, for `last_error`
+    fn record_error<T>(&mut self, result: Result<T, ShexError>) -> Result<T, ShexError> {
+        if let Err(error) = &result {
+            self.last_error = Some(error.clone());
+        }
+        result
+    }
+
+    /// Run `f` with output sent as `mode` says, then restore the previous mode
+    ///
+    /// Restoring drops `mode`, closing the pipe of a `PipedTo`
+    pub(crate) fn with_output_mode<T>(&mut self, mode: OutputMode, f: impl FnOnce(&mut Self) -> T) -> T {
+        let saved = std::mem::replace(&mut self.output_mode, mode);
+        let result = f(self);
+        self.output_mode = saved;
+        result
+    }
+
+    /// Hand a finished command's output on according to the output mode
+    ///
+    /// Whatever is written out is removed from `status`; the rest stays for
+    /// the caller to accumulate
+    fn emit_output(&mut self, status: &mut ExitStatus) {
+        match &mut self.output_mode {
+            OutputMode::Captured => {}
+            OutputMode::Terminal => {
+                print_output(status);
+                status.stdout.clear();
+                status.stderr.clear();
+            }
+            OutputMode::PipedTo(stdin) => {
+                use std::io::Write;
+                // The next stage may exit without reading everything
+                let _ = stdin.write_all(status.stdout.as_bytes());
+                status.stdout.clear();
+            }
+        }
+    }
+
+    /// Parse and execute a script given as a string
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShexError` if the script fails to parse or a command in it fails
+    pub fn execute_string(&mut self, script: &str) -> Result<ExitStatus, ShexError> {
+        self.execute_source(script, Parser::new(script))
+    }
+
+    /// Run `then_script` if `condition_script` succeeds, else `else_script`
+    ///
+    /// Equivalent to `if condition; then then_script; else else_script; fi`.
+    /// The output of the condition and the branch is returned together, with
+    /// the branch's exit code.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShexError` if a script fails to parse or a command in it fails
+    pub fn execute_conditional(
+        &mut self,
+        condition_script: &str,
+        then_script: &str,
+        else_script: &str,
+    ) -> Result<ExitStatus, ShexError> {
+        let mut status = self.execute_string(condition_script)?;
+        let branch = if status.code == 0 { then_script } else { else_script };
+        status.append(self.execute_string(branch)?);
+        Ok(status)
+    }
+
+    /// Run `body` for as long as `condition` succeeds
+    ///
+    /// Equivalent to `while condition; do body; done`, but stops after
+    /// `max_iterations` runs of the body when a limit is given. The exit
+    /// code is that of the last body run, or 0 if it never ran.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShexError` if a script fails to parse or a command in it fails
+    pub fn execute_while(
+        &mut self,
+        condition: &str,
+        body: &str,
+        max_iterations: Option<usize>,
+    ) -> Result<ExitStatus, ShexError> {
+        let mut status = ExitStatus::empty();
+        let mut iterations = 0;
+        while max_iterations.is_none_or(|max| iterations < max) {
+            let condition_status = self.execute_string(condition)?;
+            status.append_output(&condition_status);
+            if condition_status.code != 0 {
+                break;
+            }
+            status.append(self.execute_string(body)?);
+            iterations += 1;
+        }
+        Ok(status)
+    }
+
+    /// Read, parse and execute a script file
+    ///
+    /// Errors and `$LINENO` refer to the file and its lines
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShexError` if the file cannot be read, fails to parse, or a
+    /// command in it fails
+    pub fn execute_file(&mut self, path: &str) -> Result<ExitStatus, ShexError> {
+        let script = std::fs::read_to_string(self.resolve_path(path))
+            .map_err(|e| self.syntax_error(format!("{path}: {e}"), shex_ast::Span::dummy()));
+        let script = self.record_error(script)?;
+        self.execute_source(&script, Parser::new_with_filename(&script, path))
+    }
+
+    /// Execute the program `parser` reads from `script`, pointing errors at it
+    fn execute_source(
+        &mut self,
+        script: &str,
+        parser: Result<Parser, ShexError>,
+    ) -> Result<ExitStatus, ShexError> {
+        let parsed = parser.and_then(|parser| Ok((parser.parse()?, parser.filename().to_string())));
+        let (program, filename) = self.record_error(parsed)?;
+        self.source_map = Some(Arc::new(SourceMap::new(script)));
+        self.source_file = filename;
+        self.execute(program)
+    }
 
-        for command in program.commands {
+    /// Execute a program, delivering its output as `mode` says
+    ///
+    /// Code run from inside the shell, such as command substitutions and
+    /// sourced files, always accumulates so its output reaches the caller
+    fn execute_program(&mut self, program: Program, mode: CaptureMode) -> Result<ExitStatus, ShexError> {
+        let mut status = ExitStatus::empty();
+
+        for command in program {
+            // Comments must not hide the previous command's status
+            if matches!(command.node, Command::Comment { .. }) {
+                continue;
+            }
             let result = self.execute_command(&command)?;
-            last_stdout = result.stdout;
-            last_stderr = result.stderr;
-            last_code = result.code;
+            let code = result.code;
+            match mode {
+                CaptureMode::Accumulate => status.append(result),
+                CaptureMode::Passthrough => {
+                    print_output(&result);
+                    status.code = code;
+                }
+            }
 
-            // For now, stop on first error (errexit behavior)
-            if result.code != 0 {
+            if (code != 0 && self.options.errexit) || self.loop_control.is_some() {
                 break;
             }
         }
 
-        self.exit_code = last_code;
-        Ok(ExitStatus {
-            code: last_code,
-            stdout: last_stdout,
-            stderr: last_stderr,
-        })
+        self.exit_code = status.code;
+        Ok(status)
     }
 
     fn execute_command(&mut self, command: &Spanned<Command>) -> Result<ExitStatus, ShexError> {
-        match &command.node {
+        let mut result = self.execute_command_without_traps(command)?;
+        if result.code != 0 && matches!(command.node, Command::Simple { .. } | Command::Pipeline { .. }) {
+            let trap = self.run_err_trap()?;
+            result.append_output(&trap);
+        }
+        Ok(result)
+    }
+
+    /// Execute a command without running the `ERR` trap if it fails
+    ///
+    /// Pipeline stages run this way so only the whole pipeline's status
+    /// triggers the trap. The `DEBUG` trap still runs before simple commands.
+    fn execute_command_without_traps(&mut self, command: &Spanned<Command>) -> Result<ExitStatus, ShexError> {
+        self.count(|stats| &mut stats.commands_executed);
+        if let Some(source_map) = self.source_map.as_ref().filter(|_| !command.span.is_dummy()) {
+            let line = source_map.position(command.span.start).line;
+            self.variable_context
+                .set("LINENO".to_string(), line.to_string());
+        }
+
+        let debug = if matches!(command.node, Command::Simple { .. }) {
+            self.run_debug_trap(command)?
+        } else {
+            None
+        };
+
+        let result = match &command.node {
             Command::Simple {
                 name,
                 args,
@@ -69,12 +617,8 @@ impl Interpreter {
             } => self.execute_simple_command(name, args, assignments, redirections, command.span),
             Command::Pipeline { commands, redirections } => self.execute_pipeline(commands, redirections, command.span),
             Command::Assignment { assignments } => {
-                self.execute_assignments(assignments);
-                Ok(ExitStatus {
-                    code: 0,
-                    stdout: String::new(),
-                    stderr: String::new(),
-                })
+                self.execute_assignments(assignments, command.span)?;
+                Ok(ExitStatus::empty())
             }
             Command::AndIf { left, right } => self.execute_and_if(left, right, command.span),
             Command::OrIf { left, right } => self.execute_or_if(left, right, command.span),
@@ -84,7 +628,7 @@ impl Interpreter {
                 self.execute_if(condition, then_body, elif_clauses, else_body, command.span)
             }
             Command::While { condition, body } => {
-                self.execute_while(condition, body, command.span)
+                self.execute_while_loop(condition, body, command.span)
             }
             Command::Until { condition, body } => {
                 self.execute_until(condition, body, command.span)
@@ -104,7 +648,96 @@ impl Interpreter {
             Command::BraceGroup { commands } => {
                 self.execute_brace_group(commands, command.span)
             }
+            Command::Coproc { name, command: body } => {
+                self.require_extension("coproc", command.span)?;
+                self.execute_coproc(name.as_deref(), body, command.span)
+            }
+            Command::Comment { .. } | Command::Noop => Ok(ExitStatus::empty()),
+            _ => Err(self.syntax_error("unsupported command".to_string(), command.span)),
+        };
+
+        if let Ok(status) = &result {
+            self.set_last_status(status.code);
+        }
+        let mut status = match debug {
+            Some(mut debug) => {
+                debug.append(result?);
+                debug
+            }
+            None => result?,
+        };
+        if matches!(command.node, Command::Simple { .. } | Command::Pipeline { .. }) {
+            self.emit_output(&mut status);
+        }
+        Ok(status)
+    }
+
+    /// Run the `DEBUG` trap before a simple command, if one is set
+    ///
+    /// `$BASH_COMMAND` holds the command about to run, as written rather
+    /// than expanded
+    fn run_debug_trap(&mut self, command: &Spanned<Command>) -> Result<Option<ExitStatus>, ShexError> {
+        if self.in_trap || !self.traps.contains_key("DEBUG") {
+            return Ok(None);
         }
+        self.variable_context
+            .set(BASH_COMMAND.to_string(), pretty_print_command(command));
+        self.run_trap("DEBUG").map(Some)
+    }
+
+    /// Run the `ERR` trap after a failed command, if one is set
+    ///
+    /// Inside functions the trap only runs with `errtrace`. `$LINENO` keeps
+    /// the failing command's line and `$?` its status.
+    fn run_err_trap(&mut self) -> Result<ExitStatus, ShexError> {
+        if !self.call_stack.is_empty() && !self.options.errtrace {
+            return Ok(ExitStatus::empty());
+        }
+        self.run_trap("ERR")
+    }
+
+    /// Run the `RETURN` trap as a function or sourced file finishes
+    ///
+    /// The trap's output follows the body's, whose exit code is kept
+    pub(crate) fn run_return_trap(
+        &mut self,
+        result: Result<ExitStatus, ShexError>,
+    ) -> Result<ExitStatus, ShexError> {
+        let mut status = result?;
+        let trap = self.run_trap("RETURN")?;
+        status.append_output(&trap);
+        Ok(status)
+    }
+
+    /// Run the action installed for `condition`, if any
+    ///
+    /// Actions run in the current scope but leave `$?`, `$LINENO` and every
+    /// variable as they were, and traps do not fire while another trap is
+    /// running
+    fn run_trap(&mut self, condition: &str) -> Result<ExitStatus, ShexError> {
+        let action = match self.traps.get(condition) {
+            Some(action) if !self.in_trap => action.clone(),
+            _ => return Ok(ExitStatus::empty()),
+        };
+
+        let program = Parser::new(&action)?.parse()?;
+        let status = self.last_status;
+        let source_map = self.source_map.take();
+        let variables = self.variable_context.snapshot();
+        self.in_trap = true;
+        let result = self.execute_program(program, CaptureMode::Accumulate);
+        self.in_trap = false;
+        self.variable_context.restore(variables);
+        self.source_map = source_map;
+        self.set_last_status(status);
+        result
+    }
+
+    /// Record a command's exit code as `$?`
+    fn set_last_status(&mut self, code: i32) {
+        self.last_status = code;
+        self.variable_context
+            .set(LAST_STATUS.to_string(), code.to_string());
     }
 
     fn execute_simple_command(
@@ -115,64 +748,413 @@ impl Interpreter {
         redirections: &[Redirection],
         span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        // First, process prefix assignments
-        self.execute_assignments(assignments);
+        // Assignments before a special builtin stay in the shell; before
+        // anything else they only reach that one command
+        if assignments.is_empty() || is_special_builtin(name) {
+            self.execute_assignments(assignments, span)?;
+            return self.execute_expanded_command(name, args, redirections, span);
+        }
+        self.with_prefix_assignments(assignments, span, |interpreter| {
+            interpreter.execute_expanded_command(name, args, redirections, span)
+        })
+    }
+
+    /// Expand a simple command's name and arguments, then run it
+    fn execute_expanded_command(
+        &mut self,
+        name: &str,
+        args: &[String],
+        redirections: &[Redirection],
+        span: shex_ast::Span,
+    ) -> Result<ExitStatus, ShexError> {
+        // The name may itself be an expansion, as in `$cmd hello`
+        let name = &self.expand_command_name(name, span)?;
 
         // Then expand parameter expansions in arguments
         let expanded_args = self.expand_arguments(args, span)?;
-        // Handle built-in commands
-        match name {
-            "echo" => {
-                let output = expanded_args.join(" ");
-                Ok(ExitStatus {
-                    code: 0,
-                    stdout: output + "\n",
-                    stderr: String::new(),
-                })
+
+        let trace = if self.options.xtrace {
+            let trace = self.trace_line(name, &expanded_args, span)?;
+            self.emit_trace(trace)
+        } else {
+            String::new()
+        };
+        let mut result = self.dispatch_simple_command(name, expanded_args, redirections, span)?;
+        self.set_pipestatus(&[result.code]);
+        if !trace.is_empty() {
+            result.stderr.insert_str(0, &trace);
+        }
+        Ok(result)
+    }
+
+    /// Write a `set -x` trace to stderr now if output goes to the terminal,
+    /// so it appears before the command's own output
+    ///
+    /// Returns the part of the trace to put in front of the command's stderr
+    pub(crate) fn emit_trace(&self, trace: String) -> String {
+        if matches!(self.output_mode, OutputMode::Terminal) {
+            use std::io::Write;
+            let _ = std::io::stderr().lock().write_all(trace.as_bytes());
+            return String::new();
+        }
+        trace
+    }
+
+    /// Format the `set -x` trace for a command, prefixed by expanded `$PS4`
+    fn trace_line(
+        &mut self,
+        name: &str,
+        args: &[String],
+        span: shex_ast::Span,
+    ) -> Result<String, ShexError> {
+        let ps4 = self
+            .variable_context
+            .get("PS4")
+            .unwrap_or("+ ")
+            .to_string();
+        let mut line = self.expand_single_argument(&ps4, span)?;
+        line.push_str(name);
+        for arg in args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        line.push('\n');
+        Ok(line)
+    }
+
+    /// Run a simple command after expansion, with a here-document as its
+    /// stdin if it has one
+    fn dispatch_simple_command(
+        &mut self,
+        name: &str,
+        expanded_args: Vec<String>,
+        redirections: &[Redirection],
+        span: shex_ast::Span,
+    ) -> Result<ExitStatus, ShexError> {
+        let Some(input) = self.here_doc_input(redirections, span)? else {
+            return self.run_simple_command(name, expanded_args, redirections, span);
+        };
+        let outer_input = self.piped_input.replace(input);
+        let result = self.run_simple_command(name, expanded_args, redirections, span);
+        self.piped_input = outer_input;
+        result
+    }
+
+    /// Body of the last here-document or here-string among `redirections`
+    ///
+    /// The body is expanded like a double-quoted string unless the delimiter
+    /// was quoted, as in `<<'EOF'`. A here-string's word is expanded without
+    /// field splitting and ends with a newline.
+    fn here_doc_input(
+        &mut self,
+        redirections: &[Redirection],
+        span: shex_ast::Span,
+    ) -> Result<Option<String>, ShexError> {
+        let Some(redirection) = redirections.iter().rev().find(|r| is_here_doc(&r.kind)) else {
+            return Ok(None);
+        };
+        let (text, delimiter) = match &redirection.kind {
+            RedirectionKind::HereDoc { text, .. } | RedirectionKind::HereDocDash { text, .. } => {
+                (text, &redirection.target)
             }
-            "true" => Ok(ExitStatus {
-                code: 0,
-                stdout: String::new(),
-                stderr: String::new(),
-            }),
-            "false" => Ok(ExitStatus {
-                code: 1,
-                stdout: String::new(),
-                stderr: String::new(),
-            }),
             _ => {
-                // Try to execute external command
-                let mut cmd = StdCommand::new(name);
-                cmd.args(&expanded_args);
-                
-                // Apply redirections
-                self.apply_redirections(&mut cmd, redirections)?;
-
-                // Default to piped if no redirections specified
-                if redirections.is_empty() || !redirections.iter().any(|r| matches!(r.kind, RedirectionKind::Output | RedirectionKind::Append | RedirectionKind::Clobber)) {
-                    cmd.stdout(Stdio::piped());
-                }
-                if redirections.is_empty() || !redirections.iter().any(|r| matches!(r.kind, RedirectionKind::OutputDup) && r.fd == Some(2)) {
-                    cmd.stderr(Stdio::piped());
-                }
-
-                if let Ok(output) = cmd.output() {
-                    Ok(ExitStatus {
-                        code: output.status.code().unwrap_or(-1),
-                        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-                        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-                    })
-                } else {
-                    let source_map = SourceMap::new(""); // Dummy for now
-                    Err(ShexError::command_not_found(
-                        name.to_string(),
-                        span,
-                        &source_map,
-                        "<interpreter>",
-                    ))
-                }
+                let word = self.expand_single_argument(&redirection.target, span)?;
+                return Ok(Some(word + "\n"));
             }
+        };
+        if delimiter.contains(['\'', '"', '\\']) {
+            return Ok(Some(text.clone()));
+        }
+        let quoted = format!("\"{}\"", text.replace('"', "\\\""));
+        self.expand_single_argument(&quoted, span).map(Some)
+    }
+
+    /// Run a simple command after expansion: functions, builtins, then externals
+    fn run_simple_command(
+        &mut self,
+        name: &str,
+        expanded_args: Vec<String>,
+        redirections: &[Redirection],
+        span: shex_ast::Span,
+    ) -> Result<ExitStatus, ShexError> {
+        if let Some(function) = self.functions.get(name).cloned() {
+            return self.call_function(name, &function, expanded_args);
+        }
+
+        // `command name args` skips functions and runs `name` directly
+        if name == "command"
+            && let Some((target, args)) = Self::command_bypass(&expanded_args)
+        {
+            let target = target.to_string();
+            let args = args.to_vec();
+            return self.run_builtin_or_external(&target, args, redirections, span);
+        }
+
+        self.run_builtin_or_external(name, expanded_args, redirections, span)
+    }
+
+    /// Run a builtin, or failing that an external command, ignoring functions
+    fn run_builtin_or_external(
+        &mut self,
+        name: &str,
+        expanded_args: Vec<String>,
+        redirections: &[Redirection],
+        span: shex_ast::Span,
+    ) -> Result<ExitStatus, ShexError> {
+        // A builtin reads a `<` file the way it reads pipeline input
+        let input = if is_builtin(name) {
+            self.redirected_input(redirections, span)?
+        } else {
+            None
+        };
+        let outer_input = input.map(|input| self.piped_input.replace(input));
+        let dispatched = self.dispatch_builtin(name, &expanded_args);
+        if let Some(outer_input) = outer_input {
+            self.piped_input = outer_input;
+        }
+        if let Some(result) = dispatched {
+            self.count(|stats| &mut stats.builtin_commands);
+            let mut result = result?;
+            self.write_redirected_output(&mut result, redirections, span)?;
+            return Ok(result);
+        }
+
+        if self.command_resolver.is_some() {
+            self.count(|stats| &mut stats.external_commands);
+        }
+        if let Some(resolver) = &self.command_resolver {
+            let env = self.child_environment();
+            let Some(resolved) = resolver.resolve(name, &expanded_args, &env) else {
+                return Err(self.command_not_found_error(name.to_string(), span));
+            };
+            let mut result = ExitStatus {
+                code: resolved.code,
+                stdout: resolved.stdout,
+                stderr: resolved.stderr,
+            };
+            self.write_redirected_output(&mut result, redirections, span)?;
+            return Ok(result);
+        }
+
+        // Try to execute external command
+        let mut cmd = self.external_command(name, &expanded_args, redirections, span)?;
+
+        // Inside an in-process pipeline the command consumes the rest of the
+        // previous stage's output
+        let input = if has_input_redirection(redirections) {
+            None
+        } else {
+            self.piped_input.as_mut().map(std::mem::take)
+        };
+        if input.is_some() {
+            cmd.stdin(Stdio::piped());
+        }
+
+        if let Ok(output) = run_with_input(&mut cmd, input) {
+            Ok(ExitStatus {
+                code: output.status.code().unwrap_or(-1),
+                stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            })
+        } else {
+            Err(self.command_not_found_error(name.to_string(), span))
+        }
+    }
+
+    /// Prepare an external command with its environment and redirections
+    ///
+    /// Whatever is not redirected to a file is captured
+    pub(crate) fn external_command(
+        &mut self,
+        name: &str,
+        args: &[String],
+        redirections: &[Redirection],
+        span: shex_ast::Span,
+    ) -> Result<StdCommand, ShexError> {
+        self.count(|stats| &mut stats.external_commands);
+        let mut cmd = StdCommand::new(name);
+        cmd.args(args);
+        self.apply_environment(&mut cmd);
+        self.apply_redirections(&mut cmd, redirections, span)?;
+        if !redirects_to_file(redirections, 1) {
+            cmd.stdout(Stdio::piped());
+        }
+        if !redirects_to_file(redirections, 2) {
+            cmd.stderr(Stdio::piped());
+        }
+        Ok(cmd)
+    }
+
+    /// Give a spawned command the environment from `child_environment`,
+    /// and the script's working directory
+    pub(crate) fn apply_environment(&self, cmd: &mut StdCommand) {
+        cmd.current_dir(&self.cwd);
+        cmd.env_clear();
+        cmd.envs(self.child_environment());
+    }
+
+    /// The environment commands are started with
+    ///
+    /// Exported variables, plus the process environment when the variable
+    /// context inherits it. A shell variable, exported or not, hides the
+    /// inherited variable of the same name.
+    pub(crate) fn child_environment(&self) -> HashMap<String, String> {
+        let mut env: HashMap<String, String> = if self.variable_context.inherits_env() {
+            std::env::vars()
+                .filter(|(name, _)| !self.variable_context.contains(name))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        env.extend(
+            self.variable_context
+                .iter_exported()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+        env
+    }
+
+    /// Enable job control: pipelines get the terminal while they run
+    ///
+    /// On Unix this also makes the shell ignore SIGTTOU, SIGTTIN and SIGTSTP
+    pub fn set_interactive(&mut self, interactive: bool) {
+        #[cfg(unix)]
+        if interactive && !self.options.interactive {
+            pipeline::process_group::ignore_job_control_signals();
         }
+        self.options.interactive = interactive;
+    }
+
+    /// Choose whether `execute` returns command output or writes it out
+    pub const fn set_capture_mode(&mut self, mode: CaptureMode) {
+        self.capture_mode = mode;
+    }
+
+    /// Where the output of the command now running goes
+    #[must_use]
+    pub const fn output_mode(&self) -> &OutputMode {
+        &self.output_mode
+    }
+
+    /// Provide the program source so `$LINENO` reflects each command's line
+    pub fn set_source_map(&mut self, source_map: Arc<SourceMap>) {
+        self.source_map = Some(source_map);
+    }
+
+    /// Name the running script, reported as `$0` and the outermost
+    /// `BASH_SOURCE` entry
+    pub fn set_script_name(&mut self, name: &str) {
+        self.source_files[0] = name.to_string();
+        self.sync_bash_source();
+        self.variable_context
+            .set(SCRIPT_NAME.to_string(), name.to_string());
+    }
+
+    /// Set the positional parameters `$1`, `$2`, ..., such as a script's
+    /// command-line arguments
+    pub fn set_positional_params(&mut self, params: Vec<String>) {
+        self.variable_context.set_positional_params(params);
+    }
+
+    /// Current shell options
+    #[must_use]
+    pub const fn options(&self) -> &ShellOptions {
+        &self.options
+    }
+
+    /// Change shell options, as `set -o` does
+    pub const fn options_mut(&mut self) -> &mut ShellOptions {
+        &mut self.options
+    }
+
+    /// Run external commands through `resolver` instead of spawning processes
+    ///
+    /// Pipelines then run their stages one after another in the shell
+    pub fn set_command_resolver(&mut self, resolver: Box<dyn CommandResolver>) {
+        self.command_resolver = Some(resolver);
+    }
+
+    /// Value of a shell variable, as `$name` would expand it
+    #[must_use]
+    pub fn variable(&self, name: &str) -> Option<String> {
+        self.variable_context.value(name)
+    }
+
+    /// Assign a shell variable, as `name=value` would
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        self.count(|stats| &mut stats.variable_sets);
+        self.variable_context
+            .set(name.to_string(), value.to_string());
+    }
+
+    /// Names of the defined shell functions, sorted
+    #[must_use]
+    pub fn get_function_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.functions.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Whether a shell function called `name` is defined
+    #[must_use]
+    pub fn has_function(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// Body of the shell function `name`, for printing with the AST
+    /// pretty-printer
+    #[must_use]
+    pub fn get_function_body(&self, name: &str) -> Option<&Spanned<Command>> {
+        self.functions.get(name).map(|function| &*function.body)
+    }
+
+    /// Remove the shell function `name`, as `unset -f` does
+    ///
+    /// Returns whether it was defined
+    pub fn remove_function(&mut self, name: &str) -> bool {
+        self.functions.remove(name).is_some()
+    }
+
+    /// Whether assigning `name` must fail, because the interpreter maintains
+    /// it or a script marked it with `readonly`
+    pub(crate) fn is_readonly(&self, name: &str) -> bool {
+        READONLY_SPECIALS.contains(&name) || self.variable_context.is_readonly(name)
+    }
+
+    /// Source map of the running code, for error locations
+    fn error_source_map(&self) -> &SourceMap {
+        static EMPTY: LazyLock<SourceMap> = LazyLock::new(|| SourceMap::new(""));
+        self.source_map.as_deref().unwrap_or(&EMPTY)
+    }
+
+    /// File name of the running code, for error locations
+    ///
+    /// Sourced files and functions defined in them report their own file
+    fn error_filename(&self) -> &str {
+        match self.current_source_file() {
+            "" => &self.source_file,
+            file => file,
+        }
+    }
+
+    fn syntax_error(&self, message: String, span: shex_ast::Span) -> ShexError {
+        ShexError::syntax(message, span, self.error_source_map(), self.error_filename())
+    }
+
+    fn command_not_found_error(&self, command: String, span: shex_ast::Span) -> ShexError {
+        ShexError::command_not_found(command, span, self.error_source_map(), self.error_filename())
+    }
+
+    fn undefined_variable_error(&self, var: String, span: shex_ast::Span) -> ShexError {
+        ShexError::undefined_variable(var, span, self.error_source_map(), self.error_filename())
+    }
+
+    /// Fail when a non-POSIX feature is used in POSIX mode
+    fn require_extension(&self, feature: &str, span: shex_ast::Span) -> Result<(), ShexError> {
+        if !self.options.posix_mode {
+            return Ok(());
+        }
+        Err(self.syntax_error(format!("{feature}: feature not available in POSIX mode"), span))
     }
 
     #[must_use]
@@ -180,15 +1162,82 @@ impl Interpreter {
         self.exit_code
     }
 
-    fn execute_assignments(&mut self, assignments: &[(String, String)]) {
+    /// Exit code of the most recent command, including ones inside
+    /// functions and compound commands
+    #[must_use]
+    pub const fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    /// Set each variable to its value after expansion and quote removal
+    ///
+    /// `name=(word...)` sets an array to the words after full expansion
+    fn execute_assignments(
+        &mut self,
+        assignments: &[(String, String)],
+        span: shex_ast::Span,
+    ) -> Result<(), ShexError> {
         for (name, value) in assignments {
-            self.variable_context.set(name.clone(), value.clone());
+            if self.is_readonly(name) {
+                return Err(self.syntax_error(format!("{name}: readonly variable"), span));
+            }
+            self.count(|stats| &mut stats.variable_sets);
+            // Only an array assignment can leave an unquoted `(` in a value
+            if let Some(list) = value.strip_prefix('(').and_then(|list| list.strip_suffix(')')) {
+                let words = shex_parser::split_words(list)?;
+                let values = self.expand_arguments(&words, span)?;
+                self.variable_context.set_array(name.clone(), values);
+            } else {
+                let value = self.expand_single_argument(value, span)?;
+                self.variable_context.set(name.clone(), value);
+            }
+            if self.options.allexport {
+                self.variable_context.export(name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `run` with `assignments` set and exported, then restore the
+    /// variables they replaced
+    ///
+    /// This is how `NAME=value command` passes `NAME` to one command only
+    pub(crate) fn with_prefix_assignments<T>(
+        &mut self,
+        assignments: &[(String, String)],
+        span: shex_ast::Span,
+        run: impl FnOnce(&mut Self) -> Result<T, ShexError>,
+    ) -> Result<T, ShexError> {
+        let saved: Vec<(&str, Option<String>, bool)> = assignments
+            .iter()
+            .map(|(name, _)| {
+                let value = self.variable_context.get(name).map(str::to_string);
+                (name.as_str(), value, self.variable_context.is_exported(name))
+            })
+            .collect();
+        let result = self.execute_assignments(assignments, span).and_then(|()| {
+            for (name, _) in assignments {
+                self.variable_context.export(name);
+            }
+            run(self)
+        });
+        // In reverse, so a name assigned twice gets its original value back
+        for (name, value, exported) in saved.into_iter().rev() {
+            match value {
+                Some(value) => self.variable_context.set(name.to_string(), value),
+                None => self.variable_context.unset(name),
+            }
+            if !exported {
+                self.variable_context.unexport(name);
+            }
         }
+        result
     }
 
-    /// Expand parameter expansions in command arguments
+    /// Expand command arguments into fields
     ///
-    /// Processes arguments containing $var and ${var} expansions
+    /// Each argument goes through every expansion step, so one argument may
+    /// become several fields or none
     fn expand_arguments(
         &mut self,
         args: &[String],
@@ -197,89 +1246,274 @@ impl Interpreter {
         let mut expanded_args = Vec::new();
 
         for arg in args {
-            let expanded_arg = self.expand_single_argument(arg, span)?;
-            expanded_args.push(expanded_arg);
+            expanded_args.extend(self.expand_word(arg, span, &WordExpansionOptions::default())?);
         }
 
         Ok(expanded_args)
     }
 
-    /// Expand parameter expansions in a single argument
+    /// Expand a word that must stay a single field
     ///
-    /// Handles both simple ($var) and braced (${var}) parameter expansions
+    /// Expansions run but the result is neither split nor globbed
     fn expand_single_argument(
         &mut self,
         arg: &str,
         span: shex_ast::Span,
     ) -> Result<String, ShexError> {
-        // Check if this argument is a parameter expansion
-        if let Some(request) = parse_simple_parameter_expansion(arg) {
-            // Simple parameter expansion: $var
-            match resolve_expansion(&mut self.variable_context, &request) {
-                ResolutionResult::Resolved(value) => Ok(value),
-                ResolutionResult::Unset => {
-                    // POSIX behavior: unset variables expand to empty string by default
-                    // But with nounset option (implied by Shex safety), this should error
-                    let source_map = SourceMap::new(""); // Dummy for now
-                    Err(ShexError::undefined_variable(
-                        request.variable_name,
-                        span,
-                        &source_map,
-                        "<interpreter>",
-                    ))
-                }
-                ResolutionResult::Error(msg) => {
-                    let source_map = SourceMap::new(""); // Dummy for now
-                    Err(ShexError::syntax(msg, span, &source_map, "<interpreter>"))
-                }
+        let fields = self.expand_word(arg, span, &WordExpansionOptions::single_word())?;
+        Ok(fields.concat())
+    }
+
+    /// Expand a command name, enforcing the `restricted` option
+    fn expand_command_name(
+        &mut self,
+        name: &str,
+        span: shex_ast::Span,
+    ) -> Result<String, ShexError> {
+        let name = self.expand_single_argument(name, span)?;
+        if self.options.restricted && name.contains('/') {
+            return Err(self.syntax_error(
+                format!("{name}: restricted: cannot specify `/' in command names"),
+                span,
+            ));
+        }
+        Ok(name)
+    }
+
+    /// Resolve a single parameter expansion request
+    fn expand_parameter(
+        &mut self,
+        request: &ExpansionRequest,
+        span: shex_ast::Span,
+    ) -> Result<String, ShexError> {
+        if split_subscript(&request.variable_name).is_some() {
+            self.require_extension("arrays", span)?;
+        }
+        // Options change through `options_mut` too, so `$-` is read fresh
+        if request.variable_name == OPTION_FLAGS {
+            self.variable_context
+                .set(OPTION_FLAGS.to_string(), self.options.flags());
+        }
+        self.count(|stats| &mut stats.variable_lookups);
+        match resolve_expansion(&mut self.variable_context, request) {
+            ResolutionResult::Resolved(value) => Ok(value),
+            // Forms such as ${var:-default} handle unset variables themselves
+            ResolutionResult::Unset if self.options.nounset && !request.nounset_safe => {
+                Err(self.undefined_variable_error(request.variable_name.clone(), span))
             }
-        } else if let Some(request) = parse_parameter_expansion(arg) {
-            // Braced parameter expansion: ${var}, ${var:-default}, etc.
-            match resolve_expansion(&mut self.variable_context, &request) {
-                ResolutionResult::Resolved(value) => Ok(value),
-                ResolutionResult::Unset => {
-                    // For braced expansions without default, this is an error with nounset
-                    let source_map = SourceMap::new(""); // Dummy for now
-                    Err(ShexError::undefined_variable(
-                        request.variable_name,
-                        span,
-                        &source_map,
-                        "<interpreter>",
-                    ))
-                }
-                ResolutionResult::Error(msg) => {
-                    let source_map = SourceMap::new(""); // Dummy for now
-                    Err(ShexError::syntax(msg, span, &source_map, "<interpreter>"))
-                }
+            // POSIX behavior: unset variables expand to empty string by default
+            ResolutionResult::Unset => Ok(String::new()),
+            ResolutionResult::Error(msg) => {
+                Err(self.syntax_error(msg, span))
             }
-        } else {
-            // Not a parameter expansion, return as-is
-            Ok(arg.to_string())
         }
     }
 
+    /// Run `$(command)` and return its output without trailing newlines
+    ///
+    /// The command runs in a copy of the current variables, like a subshell
+    fn expand_command_substitution(&mut self, command: &str) -> Result<String, ShexError> {
+        let program = Parser::new(command)?.parse()?;
+
+        let saved_context = self.variable_context.clone();
+        let saved_exit_code = self.exit_code;
+        let result = self.with_output_mode(OutputMode::Captured, |interpreter| {
+            match program.commands.as_slice() {
+                [Spanned {
+                    node: Command::Pipeline { commands, .. },
+                    ..
+                }] => interpreter.execute_pipeline_with_capture(commands),
+                _ => interpreter
+                    .execute_program(program, CaptureMode::Accumulate)
+                    .map(|status| strip_trailing_newlines(status.stdout)),
+            }
+        });
+        self.variable_context = saved_context;
+        self.exit_code = saved_exit_code;
+        result
+    }
+
+    /// Run a pipeline and return the last stage's stdout without trailing newlines
+    ///
+    /// External stages are connected with OS pipes and all of them are waited
+    /// for before returning
+    pub(crate) fn execute_pipeline_with_capture(
+        &mut self,
+        commands: &[Spanned<Command>],
+    ) -> Result<String, ShexError> {
+        let span = match (commands.first(), commands.last()) {
+            (Some(first), Some(last)) => shex_ast::Span::new(first.span.start, last.span.end),
+            _ => shex_ast::Span::dummy(),
+        };
+        let status = self.execute_pipeline(commands, &[], span)?;
+        Ok(strip_trailing_newlines(status.stdout))
+    }
+
+    /// Evaluate `$((expression))` after expanding any `$` parameters inside it
+    fn expand_arithmetic(
+        &mut self,
+        expression: &str,
+        span: shex_ast::Span,
+    ) -> Result<String, ShexError> {
+        let expression = self.expand_single_argument(expression, span)?;
+        arithmetic::evaluate(&expression, &mut self.variable_context)
+            .map(|value| value.to_string())
+            .map_err(|msg| self.syntax_error(msg, span))
+    }
+
     /// Execute a pipeline: cmd1 | cmd2 | cmd3
     fn execute_pipeline(
         &mut self,
         commands: &[Spanned<Command>],
-        _redirections: &[Redirection],
-        _span: shex_ast::Span,
+        redirections: &[Redirection],
+        span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        // For now, just execute commands sequentially without actual piping
-        // TODO: Implement proper pipeline with stdio chaining
-        let mut last_result = ExitStatus {
-            code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
+        if !redirections.is_empty() {
+            return self.execute_redirected(redirections, span, |interpreter| {
+                interpreter.execute_pipeline(commands, &[], span)
+            });
+        }
+        self.count_by(|stats| &mut stats.pipeline_stages, commands.len());
+        if let Some(result) = self.execute_external_pipeline(commands, span) {
+            return result;
+        }
+
+        // Pipelines with builtins or compound commands run one stage after
+        // another, each stage reading the previous stage's captured stdout
+        let stages = self.execute_sequential_pipeline(commands)?;
+        Ok(self.finish_pipeline(stages))
+    }
+
+    /// Run `run` with the redirections that follow a compound command, as in
+    /// `while read line; do ...; done < file`
+    ///
+    /// The redirected file or here-document becomes the input `read` and
+    /// external commands see. Output redirected to a file is captured while
+    /// `run` runs and written out when it finishes.
+    fn execute_redirected(
+        &mut self,
+        redirections: &[Redirection],
+        span: shex_ast::Span,
+        run: impl FnOnce(&mut Self) -> Result<ExitStatus, ShexError>,
+    ) -> Result<ExitStatus, ShexError> {
+        let input = match self.here_doc_input(redirections, span)? {
+            Some(input) => Some(input),
+            None => self.redirected_input(redirections, span)?,
         };
+        let outer_input = input.map(|input| self.piped_input.replace(input));
+        let writes_file = redirections.iter().any(|redirection| {
+            matches!(
+                redirection.kind,
+                RedirectionKind::Output | RedirectionKind::Clobber | RedirectionKind::Append
+            )
+        });
+        let result = if writes_file {
+            self.with_output_mode(OutputMode::Captured, run)
+        } else {
+            run(self)
+        };
+        if let Some(outer_input) = outer_input {
+            self.piped_input = outer_input;
+        }
+        let mut result = result?;
+        self.write_redirected_output(&mut result, redirections, span)?;
+        Ok(result)
+    }
 
-        for command in commands {
-            last_result = self.execute_command(command)?;
-            // In a real pipeline, each command's stdout becomes the next command's stdin
-            // For now, we'll just continue with the last command's result
+    /// Contents of the file the last `<` among `redirections` reads from
+    fn redirected_input(
+        &mut self,
+        redirections: &[Redirection],
+        span: shex_ast::Span,
+    ) -> Result<Option<String>, ShexError> {
+        let Some(redirection) = redirections
+            .iter()
+            .rev()
+            .find(|redirection| matches!(redirection.kind, RedirectionKind::Input))
+        else {
+            return Ok(None);
+        };
+        let target = self.expand_single_argument(&redirection.target, span)?;
+        std::fs::read_to_string(self.resolve_path(&target))
+            .map(Some)
+            .map_err(|_| self.syntax_error(format!("Cannot open {target} for input"), span))
+    }
+
+    /// Run pipeline stages in turn, piping each one's stdout into the next
+    ///
+    /// Returns the status of every stage; all but the last have had their
+    /// stdout handed on
+    fn execute_sequential_pipeline(
+        &mut self,
+        commands: &[Spanned<Command>],
+    ) -> Result<Vec<ExitStatus>, ShexError> {
+        let outer_input = self.piped_input.clone();
+        let result = self.run_sequential_stages(commands);
+        self.piped_input = outer_input;
+        result
+    }
+
+    fn run_sequential_stages(
+        &mut self,
+        commands: &[Spanned<Command>],
+    ) -> Result<Vec<ExitStatus>, ShexError> {
+        let mut statuses: Vec<ExitStatus> = Vec::with_capacity(commands.len());
+        let mut stages = commands.iter().peekable();
+        while let Some(command) = stages.next() {
+            if let Some(previous) = statuses.last_mut() {
+                self.piped_input = Some(std::mem::take(&mut previous.stdout));
+            }
+            // An external next stage is started first so this one can stream
+            // into it rather than have its output buffered
+            if let Some(next) = stages.next_if(|next| {
+                self.command_resolver.is_none() && self.is_external_stage(next)
+            }) {
+                let (stage, next) = self.execute_stage_into_external(command, next)?;
+                statuses.extend([stage, next]);
+                continue;
+            }
+            let is_last = stages.peek().is_none();
+            statuses.push(if is_last {
+                self.execute_command_without_traps(command)?
+            } else {
+                self.with_output_mode(OutputMode::Captured, |interpreter| {
+                    interpreter.execute_command_without_traps(command)
+                })?
+            });
         }
+        Ok(statuses)
+    }
 
-        Ok(last_result)
+    /// Combine the statuses of a pipeline's stages into the pipeline's own,
+    /// recording each stage's code in `PIPESTATUS`
+    fn finish_pipeline(&mut self, stages: Vec<ExitStatus>) -> ExitStatus {
+        let codes: Vec<i32> = stages.iter().map(|stage| stage.code).collect();
+        self.set_pipestatus(&codes);
+        let code = self.pipeline_status(&codes);
+        let output = stages
+            .into_iter()
+            .fold(ExitStatus::empty(), ExitStatus::combine_stdout);
+        ExitStatus { code, ..output }
+    }
+
+    /// Record per-stage exit codes in `PIPESTATUS`
+    ///
+    /// A lone command counts as a one-stage pipeline
+    fn set_pipestatus(&mut self, codes: &[i32]) {
+        let codes = codes.iter().map(ToString::to_string).collect();
+        self.variable_context
+            .set_array(PIPESTATUS.to_string(), codes);
+    }
+
+    /// Exit status of a pipeline from its per-stage codes
+    ///
+    /// With `pipefail` this is the last non-zero code, otherwise the last stage's
+    fn pipeline_status(&self, codes: &[i32]) -> i32 {
+        let last = codes.last().copied().unwrap_or(0);
+        if !self.options.pipefail {
+            return last;
+        }
+        codes.iter().rev().copied().find(|code| *code != 0).unwrap_or(last)
     }
 
     /// Execute logical AND: cmd1 && cmd2
@@ -289,15 +1523,14 @@ impl Interpreter {
         right: &Spanned<Command>,
         _span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        let left_result = self.execute_command(left)?;
+        let mut result = self.execute_command(left)?;
 
-        if left_result.code == 0 {
+        if result.code == 0 && self.loop_control.is_none() {
             // Left succeeded, execute right
-            self.execute_command(right)
-        } else {
-            // Left failed, return its result without executing right
-            Ok(left_result)
+            result.append(self.execute_command(right)?);
         }
+        // Otherwise left failed; return its result without executing right
+        Ok(result)
     }
 
     /// Execute logical OR: cmd1 || cmd2
@@ -307,15 +1540,14 @@ impl Interpreter {
         right: &Spanned<Command>,
         _span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        let left_result = self.execute_command(left)?;
+        let mut result = self.execute_command(left)?;
 
-        if left_result.code == 0 {
-            // Left succeeded, return its result without executing right
-            Ok(left_result)
-        } else {
+        if result.code != 0 && self.loop_control.is_none() {
             // Left failed, execute right
-            self.execute_command(right)
+            result.append(self.execute_command(right)?);
         }
+        // Otherwise left succeeded; return its result without executing right
+        Ok(result)
     }
 
     /// Execute sequence: cmd1; cmd2; cmd3
@@ -324,15 +1556,15 @@ impl Interpreter {
         commands: &[Spanned<Command>],
         _span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        let mut last_result = ExitStatus {
-            code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-        };
+        let mut last_result = ExitStatus::empty();
 
         for command in commands {
-            last_result = self.execute_command(command)?;
-            // Continue executing regardless of exit status
+            last_result.append(self.execute_command(command)?);
+            // Continue executing regardless of exit status, unless a
+            // `break` or `continue` is on its way out
+            if self.loop_control.is_some() {
+                break;
+            }
         }
 
         Ok(last_result)
@@ -344,80 +1576,178 @@ impl Interpreter {
         command: &Spanned<Command>,
         _span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        // For now, just execute the command normally
-        // TODO: Implement proper background execution with job control
-        let _result = self.execute_command(command)?;
+        if let Some(result) = self.spawn_background_job(command) {
+            result?;
+        } else {
+            self.spawn_background_subshell(command);
+        }
 
         // Background commands return immediately with success
-        Ok(ExitStatus {
-            code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-        })
+        Ok(ExitStatus::empty())
     }
 
     /// Apply I/O redirections to a command
-    fn apply_redirections(&self, cmd: &mut StdCommand, redirections: &[Redirection]) -> Result<(), ShexError> {
+    ///
+    /// Targets are expanded first, so `>$outfile` and `>&$fd` both work.
+    /// Redirections of fd 0, 1 and 2 replace the command's stdio; higher
+    /// descriptors and `N>&M` duplication are applied in the child on Unix
+    fn apply_redirections(
+        &mut self,
+        cmd: &mut StdCommand,
+        redirections: &[Redirection],
+        span: shex_ast::Span,
+    ) -> Result<(), ShexError> {
         for redirection in redirections {
-            match &redirection.kind {
+            let fd = redirection.target_fd();
+            let target = self.expand_single_argument(&redirection.target, span)?;
+            self.check_output_target(&redirection.kind, &target, span)?;
+            let path = self.resolve_path(&target);
+            let file = match &redirection.kind {
                 RedirectionKind::Input => {
                     // < file - redirect stdin from file
-                    match File::open(&redirection.target) {
-                        Ok(file) => {
-                            cmd.stdin(Stdio::from(file));
-                        }
-                        Err(_) => {
-                            let source_map = SourceMap::new("");
-                            return Err(ShexError::syntax(
-                                format!("Cannot open {} for input", redirection.target),
-                                shex_ast::Span::dummy(),
-                                &source_map,
-                                "<interpreter>",
-                            ));
-                        }
-                    }
+                    File::open(&path)
+                        .map_err(|_| self.syntax_error(format!("Cannot open {target} for input"), span))?
                 }
-                RedirectionKind::Output => {
+                RedirectionKind::Output | RedirectionKind::Clobber => {
                     // > file - redirect stdout to file (truncate)
-                    match File::create(&redirection.target) {
-                        Ok(file) => {
-                            cmd.stdout(Stdio::from(file));
-                        }
-                        Err(_) => {
-                            let source_map = SourceMap::new("");
-                            return Err(ShexError::syntax(
-                                format!("Cannot create {}", redirection.target),
-                                shex_ast::Span::dummy(),
-                                &source_map,
-                                "<interpreter>",
-                            ));
-                        }
-                    }
+                    File::create(&path)
+                        .map_err(|_| self.syntax_error(format!("Cannot create {target}"), span))?
                 }
                 RedirectionKind::Append => {
                     // >> file - redirect stdout to file (append)
-                    match std::fs::OpenOptions::new()
+                    std::fs::OpenOptions::new()
                         .create(true)
                         .append(true)
-                        .open(&redirection.target)
-                    {
-                        Ok(file) => {
-                            cmd.stdout(Stdio::from(file));
-                        }
-                        Err(_) => {
-                            let source_map = SourceMap::new("");
-                            return Err(ShexError::syntax(
-                                format!("Cannot open {} for append", redirection.target),
-                                shex_ast::Span::dummy(),
-                                &source_map,
-                                "<interpreter>",
-                            ));
-                        }
+                        .open(&path)
+                        .map_err(|_| self.syntax_error(format!("Cannot open {target} for append"), span))?
+                }
+                RedirectionKind::InputOutput => {
+                    // <> file - open for reading and writing
+                    std::fs::OpenOptions::new()
+                        .read(true)
+                        .write(true)
+                        .create(true)
+                        .truncate(false)
+                        .open(&path)
+                        .map_err(|_| self.syntax_error(format!("Cannot open {target}"), span))?
+                }
+                RedirectionKind::InputDup | RedirectionKind::OutputDup => {
+                    // N>&M - make fd N a copy of fd M
+                    let source: i32 = target.parse().map_err(|_| {
+                        self.syntax_error(format!("{target}: ambiguous redirect"), span)
+                    })?;
+                    duplicate_fd(cmd, source, fd);
+                    continue;
+                }
+                // The body is passed as input by `dispatch_simple_command`
+                RedirectionKind::HereDoc { .. }
+                | RedirectionKind::HereDocDash { .. }
+                | RedirectionKind::HereString => continue,
+                _ => continue,
+            };
+
+            match fd {
+                0 => {
+                    cmd.stdin(Stdio::from(file));
+                }
+                1 => {
+                    cmd.stdout(Stdio::from(file));
+                }
+                2 => {
+                    cmd.stderr(Stdio::from(file));
+                }
+                _ => attach_fd(cmd, file, fd),
+            }
+        }
+        Ok(())
+    }
+
+    /// Refuse output redirections ruled out by `restricted` or `noclobber`
+    fn check_output_target(
+        &self,
+        kind: &RedirectionKind,
+        target: &str,
+        span: shex_ast::Span,
+    ) -> Result<(), ShexError> {
+        let writes = matches!(
+            kind,
+            RedirectionKind::Output
+                | RedirectionKind::Clobber
+                | RedirectionKind::Append
+                | RedirectionKind::InputOutput
+        );
+        if writes && self.options.restricted {
+            return Err(self.syntax_error(format!("{target}: restricted: cannot redirect output"), span));
+        }
+        if matches!(kind, RedirectionKind::Output)
+            && self.options.noclobber
+            && self.resolve_path(target).is_file()
+        {
+            return Err(self.syntax_error(format!("{target}: cannot overwrite existing file"), span));
+        }
+        Ok(())
+    }
+
+    /// Send a builtin's output where its redirections point: to files, or
+    /// to the other stream for `2>&1` and `>&2`
+    ///
+    /// Builtins run in-process, so their captured output is written out
+    /// after they finish instead of going through the command's stdio
+    fn write_redirected_output(
+        &mut self,
+        result: &mut ExitStatus,
+        redirections: &[Redirection],
+        span: shex_ast::Span,
+    ) -> Result<(), ShexError> {
+        use std::io::Write;
+        // Where fds 1 and 2 point, applied in order so `>file 2>&1` sends
+        // both to the file and `2>&1 >file` only stdout
+        let mut sinks = [OutputSink::Stdout, OutputSink::Stderr];
+        let mut files: Vec<(String, File)> = Vec::new();
+        for redirection in redirections {
+            let fd = redirection.target_fd();
+            if !matches!(fd, 1 | 2) {
+                continue;
+            }
+            let append = match redirection.kind {
+                RedirectionKind::Output | RedirectionKind::Clobber => false,
+                RedirectionKind::Append => true,
+                RedirectionKind::OutputDup | RedirectionKind::InputDup => {
+                    let target = self.expand_single_argument(&redirection.target, span)?;
+                    let source: i32 = target.parse().map_err(|_| {
+                        self.syntax_error(format!("{target}: ambiguous redirect"), span)
+                    })?;
+                    if matches!(source, 1 | 2) {
+                        sinks[fd as usize - 1] = sinks[source as usize - 1];
                     }
+                    continue;
                 }
-                // TODO: Implement other redirection types
-                _ => {
-                    // For now, ignore unsupported redirection types
+                _ => continue,
+            };
+            let target = self.expand_single_argument(&redirection.target, span)?;
+            self.check_output_target(&redirection.kind, &target, span)?;
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(append)
+                .truncate(!append)
+                .open(self.resolve_path(&target))
+                .map_err(|_| self.syntax_error(format!("Cannot create {target}"), span))?;
+            sinks[fd as usize - 1] = OutputSink::File(files.len());
+            files.push((target, file));
+        }
+
+        let output = [std::mem::take(&mut result.stdout), std::mem::take(&mut result.stderr)];
+        for (text, sink) in output.iter().zip(sinks) {
+            match sink {
+                OutputSink::Stdout => result.stdout.push_str(text),
+                OutputSink::Stderr => result.stderr.push_str(text),
+                OutputSink::File(index) => {
+                    let (target, file) = &mut files[index];
+                    if file.write_all(text.as_bytes()).is_err() {
+                        let message = format!("Cannot create {target}");
+                        return Err(self.syntax_error(message, span));
+                    }
                 }
             }
         }
@@ -434,59 +1764,60 @@ impl Interpreter {
         _span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
         // Execute condition
-        let condition_result = self.execute_command(condition)?;
-        
-        if condition_result.code == 0 {
+        let mut result = self.execute_command(condition)?;
+
+        if result.code == 0 {
             // Condition succeeded, execute then body
-            self.execute_command_list(then_body)
-        } else {
-            // Check elif clauses
-            for (elif_condition, elif_body) in elif_clauses {
-                let elif_result = self.execute_command(elif_condition)?;
-                if elif_result.code == 0 {
-                    return self.execute_command_list(elif_body);
-                }
-            }
-            
-            // Execute else body if present
-            if let Some(else_commands) = else_body {
-                self.execute_command_list(else_commands)
-            } else {
-                // No else clause, return success
-                Ok(ExitStatus {
-                    code: 0,
-                    stdout: String::new(),
-                    stderr: String::new(),
-                })
+            result.append(self.execute_command_list(then_body)?);
+            return Ok(result);
+        }
+
+        // Check elif clauses
+        for (elif_condition, elif_body) in elif_clauses {
+            result.append(self.execute_command(elif_condition)?);
+            if result.code == 0 {
+                result.append(self.execute_command_list(elif_body)?);
+                return Ok(result);
             }
         }
+
+        // Execute else body if present
+        if let Some(else_commands) = else_body {
+            result.append(self.execute_command_list(else_commands)?);
+        } else {
+            // No else clause, return success
+            result.code = 0;
+        }
+        Ok(result)
     }
 
     /// Execute while/do/done loop
-    fn execute_while(
+    fn execute_while_loop(
         &mut self,
         condition: &Spanned<Command>,
         body: &[Spanned<Command>],
         _span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        let mut last_result = ExitStatus {
-            code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-        };
+        self.run_loop(|this| {
+            let mut last_result = ExitStatus::empty();
+
+            loop {
+                // Check condition
+                let condition_result = this.execute_command(condition)?;
+                last_result.append_output(&condition_result);
+                if this.leave_loop() || condition_result.code != 0 {
+                    break; // Condition failed, exit loop
+                }
 
-        loop {
-            // Check condition
-            let condition_result = self.execute_command(condition)?;
-            if condition_result.code != 0 {
-                break; // Condition failed, exit loop
+                // Execute body
+                last_result.append(this.execute_command_list(body)?);
+                if this.leave_loop() {
+                    break;
+                }
             }
 
-            // Execute body
-            last_result = self.execute_command_list(body)?;
-        }
-
-        Ok(last_result)
+            Ok(last_result)
+        })
     }
 
     /// Execute until/do/done loop
@@ -496,24 +1827,26 @@ impl Interpreter {
         body: &[Spanned<Command>],
         _span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        let mut last_result = ExitStatus {
-            code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-        };
+        self.run_loop(|this| {
+            let mut last_result = ExitStatus::empty();
+
+            loop {
+                // Check condition (until loops when condition fails)
+                let condition_result = this.execute_command(condition)?;
+                last_result.append_output(&condition_result);
+                if this.leave_loop() || condition_result.code == 0 {
+                    break; // Condition succeeded, exit loop
+                }
 
-        loop {
-            // Check condition (until loops when condition fails)
-            let condition_result = self.execute_command(condition)?;
-            if condition_result.code == 0 {
-                break; // Condition succeeded, exit loop
+                // Execute body
+                last_result.append(this.execute_command_list(body)?);
+                if this.leave_loop() {
+                    break;
+                }
             }
 
-            // Execute body
-            last_result = self.execute_command_list(body)?;
-        }
-
-        Ok(last_result)
+            Ok(last_result)
+        })
     }
 
     /// Execute for/in/do/done loop
@@ -522,32 +1855,34 @@ impl Interpreter {
         variable: &str,
         words: &Option<Vec<String>>,
         body: &[Spanned<Command>],
-        _span: shex_ast::Span,
+        span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        let mut last_result = ExitStatus {
-            code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-        };
+        let mut last_result = ExitStatus::empty();
 
-        // Get words to iterate over
+        // Get words to iterate over; without `in`, the positional parameters
+        // as they stand when the loop starts
         let word_list = if let Some(words) = words {
-            words.clone()
+            self.expand_arguments(words, span)?
         } else {
-            // Default to $@ (positional parameters) - for now use empty list
-            vec![]
+            self.variable_context.positional_params().to_vec()
         };
 
         // Execute body for each word
-        for word in word_list {
-            // Set loop variable
-            self.variable_context.set(variable.to_string(), word);
-            
-            // Execute body
-            last_result = self.execute_command_list(body)?;
-        }
+        self.run_loop(|this| {
+            for word in word_list {
+                // Set loop variable
+                this.count(|stats| &mut stats.variable_sets);
+                this.variable_context.set(variable.to_string(), word);
+
+                // Execute body
+                last_result.append(this.execute_command_list(body)?);
+                if this.leave_loop() {
+                    break;
+                }
+            }
 
-        Ok(last_result)
+            Ok(last_result)
+        })
     }
 
     /// Execute case/esac pattern matching
@@ -555,42 +1890,117 @@ impl Interpreter {
         &mut self,
         word: &str,
         arms: &[CaseArm],
-        _span: shex_ast::Span,
+        span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        // Expand the word
-        let expanded_word = self.expand_single_argument(word, shex_ast::Span::dummy())?;
-        
-        // Try each case arm
+        // Expand the word through the full pipeline, as one field
+        let expanded_word = self
+            .expand_word(word, span, &WordExpansionOptions::single_word())?
+            .concat();
+
+        // Try each case arm; arms of plain text need only equality
         for arm in arms {
-            for pattern in &arm.patterns {
-                if self.pattern_matches(pattern, &expanded_word) {
-                    return self.execute_command_list(&arm.commands);
+            let matched = if arm.patterns.iter().all(|pattern| is_literal_pattern(pattern)) {
+                arm.patterns.contains(&expanded_word)
+            } else {
+                let mut matched = false;
+                for pattern in &arm.patterns {
+                    if self.case_pattern_matches(pattern, &expanded_word, span)? {
+                        matched = true;
+                        break;
+                    }
                 }
+                matched
+            };
+            if matched {
+                return self.execute_command_list(&arm.commands);
             }
         }
 
         // No pattern matched
-        Ok(ExitStatus {
-            code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-        })
+        Ok(ExitStatus::empty())
     }
 
     /// Execute function definition
     fn execute_function_definition(
         &mut self,
-        _name: &str,
-        _body: &Spanned<Command>,
+        name: &str,
+        body: &Arc<Spanned<Command>>,
         _redirections: &[Redirection],
         _span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        // TODO: Implement function storage and calling
-        Ok(ExitStatus {
-            code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-        })
+        let function = Function {
+            body: Arc::clone(body),
+            source_file: self.current_source_file().to_string(),
+            source_map: self.source_map.clone(),
+        };
+        self.functions.insert(name.to_string(), function);
+        Ok(ExitStatus::empty())
+    }
+
+    /// Invoke a defined function, tracking it on the call stack
+    ///
+    /// While the body runs, `args` are the positional parameters, and
+    /// `BASH_SOURCE` and `$LINENO` refer to the file the function was
+    /// defined in. A `return` in the body sets the call's status.
+    fn call_function(
+        &mut self,
+        name: &str,
+        function: &Function,
+        args: Vec<String>,
+    ) -> Result<ExitStatus, ShexError> {
+        self.count(|stats| &mut stats.function_calls);
+        let caller_params = self.variable_context.positional_params().to_vec();
+        self.variable_context.set_positional_params(args);
+        self.call_stack.push(name.to_string());
+        self.sync_funcname();
+        self.source_files.push(function.source_file.clone());
+        self.sync_bash_source();
+        let saved_map = std::mem::replace(&mut self.source_map, function.source_map.clone());
+
+        let result = self.execute_command(&function.body).map(|mut status| {
+            if let Some(code) = self.take_return() {
+                status.code = code;
+            }
+            status
+        });
+        let result = self.run_return_trap(result);
+
+        self.variable_context.set_positional_params(caller_params);
+        self.source_map = saved_map;
+        self.source_files.pop();
+        self.sync_bash_source();
+        self.call_stack.pop();
+        self.sync_funcname();
+        // Release what temporaries the function unset on the way
+        self.variable_context.shrink_to_fit();
+        result
+    }
+
+    /// Mirror the call stack into the `FUNCNAME` array, innermost first
+    ///
+    /// The bottom frame is always `main`, the top level of the script
+    fn sync_funcname(&mut self) {
+        let frames = self
+            .call_stack
+            .iter()
+            .rev()
+            .cloned()
+            .chain(std::iter::once("main".to_string()))
+            .collect();
+        self.variable_context
+            .set_array(FUNCNAME.to_string(), frames);
+    }
+
+    /// File of the code currently executing
+    fn current_source_file(&self) -> &str {
+        self.source_files.last().map_or("", String::as_str)
+    }
+
+    /// Mirror the source file stack into the `BASH_SOURCE` array, innermost first
+    fn sync_bash_source(&mut self) {
+        let files = self.source_files.iter().rev().cloned().collect();
+        self.variable_context
+            .set_array(BASH_SOURCE.to_string(), files);
     }
 
     /// Execute subshell
@@ -599,9 +2009,16 @@ impl Interpreter {
         commands: &[Spanned<Command>],
         _span: shex_ast::Span,
     ) -> Result<ExitStatus, ShexError> {
-        // TODO: Implement proper subshell with separate environment
-        // For now, just execute commands in current context
-        self.execute_command_list(commands)
+        // The body changes a copy of the shell; only its status comes back.
+        // Output and the command resolver are lent to it while it runs.
+        let mut subshell = self.clone_for_subshell();
+        subshell.capture_mode = self.capture_mode;
+        subshell.output_mode = std::mem::take(&mut self.output_mode);
+        subshell.command_resolver = self.command_resolver.take();
+        let result = subshell.execute_command_list(commands);
+        self.output_mode = std::mem::take(&mut subshell.output_mode);
+        self.command_resolver = subshell.command_resolver.take();
+        result
     }
 
     /// Execute brace group
@@ -616,25 +2033,184 @@ impl Interpreter {
 
     /// Helper: Execute a list of commands
     fn execute_command_list(&mut self, commands: &[Spanned<Command>]) -> Result<ExitStatus, ShexError> {
-        let mut last_result = ExitStatus {
-            code: 0,
-            stdout: String::new(),
-            stderr: String::new(),
-        };
+        let mut last_result = ExitStatus::empty();
 
         for command in commands {
-            last_result = self.execute_command(command)?;
+            last_result.append(self.execute_command(command)?);
+            if self.loop_control.is_some() {
+                break;
+            }
         }
 
         Ok(last_result)
     }
+}
+
+/// Whether a `case` pattern is plain text that can only match itself
+///
+/// Patterns with glob characters, escapes, quotes or expansions go through
+/// `case_pattern_matches`
+fn is_literal_pattern(pattern: &str) -> bool {
+    !pattern.contains(['*', '?', '[', '\\', '$', '`', '\'', '"', '~'])
+}
+
+/// Write a command's output to the shell's own stdout and stderr
+fn print_output(status: &ExitStatus) {
+    use std::io::Write;
+    if !status.stdout.is_empty() {
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(status.stdout.as_bytes());
+        let _ = stdout.flush();
+    }
+    if !status.stderr.is_empty() {
+        let _ = std::io::stderr().lock().write_all(status.stderr.as_bytes());
+    }
+}
+
+/// Drop the trailing newlines that command substitution removes
+fn strip_trailing_newlines(mut output: String) -> String {
+    let trimmed_len = output.trim_end_matches('\n').len();
+    output.truncate(trimmed_len);
+    output
+}
+
+/// Whether some redirection replaces the command's stdin
+pub(crate) fn has_input_redirection(redirections: &[Redirection]) -> bool {
+    redirections.iter().any(|r| {
+        r.target_fd() == 0
+            && matches!(
+                r.kind,
+                RedirectionKind::Input | RedirectionKind::InputOutput | RedirectionKind::InputDup
+            )
+    })
+}
+
+/// Number of distinct variable names assigned anywhere in `commands`
+///
+/// Counts assignments, including those prefixed to commands, and `for` loop
+/// variables
+fn assigned_variable_count(commands: &[Spanned<Command>]) -> usize {
+    let mut names = HashSet::new();
+    walk(commands, &mut |command| match &command.node {
+        Command::Simple { assignments, .. } | Command::Assignment { assignments } => {
+            names.extend(assignments.iter().map(|(name, _)| name.as_str()));
+        }
+        Command::For { variable, .. } => {
+            names.insert(variable.as_str());
+        }
+        _ => {}
+    });
+    names.len()
+}
+
+/// Whether some redirection feeds the command a here-document or
+/// here-string
+pub(crate) fn has_here_doc(redirections: &[Redirection]) -> bool {
+    redirections.iter().any(|r| is_here_doc(&r.kind))
+}
+
+const fn is_here_doc(kind: &RedirectionKind) -> bool {
+    matches!(
+        kind,
+        RedirectionKind::HereDoc { .. }
+            | RedirectionKind::HereDocDash { .. }
+            | RedirectionKind::HereString
+    )
+}
+
+/// Spawn a command and wait for its output, writing `input` to its stdin
+///
+/// The input is written from another thread so a command that fills its
+/// stdout pipe before reading everything cannot deadlock the shell
+fn run_with_input(
+    cmd: &mut StdCommand,
+    input: Option<String>,
+) -> std::io::Result<std::process::Output> {
+    let Some(input) = input else {
+        return cmd.output();
+    };
+    let mut child = cmd.spawn()?;
+    let writer = child.stdin.take().map(|mut stdin| {
+        std::thread::spawn(move || {
+            use std::io::Write;
+            // The command may exit without reading all of its input
+            let _ = stdin.write_all(input.as_bytes());
+        })
+    });
+    let output = child.wait_with_output();
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    output
+}
+
+/// Whether some redirection sends file descriptor `fd` to a file
+pub(crate) fn redirects_to_file(redirections: &[Redirection], fd: i32) -> bool {
+    redirections.iter().any(|r| {
+        r.target_fd() == fd
+            && matches!(
+                r.kind,
+                RedirectionKind::Output
+                    | RedirectionKind::Append
+                    | RedirectionKind::Clobber
+                    | RedirectionKind::InputOutput
+            )
+    })
+}
+
+/// Where a builtin's stdout or stderr ends up after its redirections
+#[derive(Debug, Clone, Copy)]
+enum OutputSink {
+    Stdout,
+    Stderr,
+    /// Index of a file opened by one of the redirections
+    File(usize),
+}
+
+/// Make `target` a copy of `source` in the child process
+fn duplicate_fd(cmd: &mut StdCommand, source: i32, target: i32) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: dup2 is async-signal-safe and the closure allocates nothing
+        unsafe {
+            cmd.pre_exec(move || dup2(source, target));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (cmd, source, target);
+}
 
-    /// Helper: Simple pattern matching for case statements
-    fn pattern_matches(&self, pattern: &str, word: &str) -> bool {
-        // Very basic pattern matching - just exact match for now
-        // TODO: Implement proper shell pattern matching with * and ?
-        pattern == word
+/// Open `file` as descriptor `target` (above 2) in the child process
+fn attach_fd(cmd: &mut StdCommand, file: File, target: i32) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        use std::os::unix::process::CommandExt;
+        // The closure owns the file so it stays open until the child spawns
+        // SAFETY: dup2 is async-signal-safe and the closure allocates nothing
+        unsafe {
+            cmd.pre_exec(move || dup2(file.as_raw_fd(), target));
+        }
     }
+    #[cfg(not(unix))]
+    let _ = (cmd, file, target);
+}
+
+#[cfg(unix)]
+fn dup2(source: i32, target: i32) -> std::io::Result<()> {
+    use nix::libc;
+    // dup2 onto itself is a no-op that would leave close-on-exec set
+    // SAFETY: dup2 and fcntl only operate on descriptor numbers
+    let result = if source == target {
+        unsafe { libc::fcntl(target, libc::F_SETFD, 0) }
+    } else {
+        unsafe { libc::dup2(source, target) }
+    };
+    if result < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
 }
 
 impl Default for Interpreter {
@@ -663,6 +2239,36 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_only_exported_variables_reach_children() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .variable_context
+            .set("SHEX_SHOWN".to_string(), "yes".to_string());
+        interpreter.variable_context.export("SHEX_SHOWN");
+        interpreter
+            .variable_context
+            .set("SHEX_HIDDEN".to_string(), "no".to_string());
+
+        let program = Program {
+            commands: vec![make_simple_command("env", vec![])],
+        };
+        let result = interpreter.execute(program).unwrap();
+        assert!(result.stdout.lines().any(|line| line == "SHEX_SHOWN=yes"));
+        assert!(!result.stdout.contains("SHEX_HIDDEN"));
+    }
+
+    #[test]
+    fn test_pipeline_with_capture() {
+        let mut interpreter = Interpreter::new();
+        let commands = vec![
+            make_simple_command("sh", vec!["-c", "echo b; echo a; echo; echo"]),
+            make_simple_command("sort", vec!["-r"]),
+        ];
+        let output = interpreter.execute_pipeline_with_capture(&commands).unwrap();
+        assert_eq!(output, "b\na");
+    }
+
     #[test]
     fn test_echo_command() {
         let mut interpreter = Interpreter::new();
@@ -700,6 +2306,33 @@ mod tests {
         assert_eq!(result.stdout, "");
     }
 
+    #[test]
+    fn test_output_mode_is_restored() {
+        let mut interpreter = Interpreter::new();
+        assert!(matches!(interpreter.output_mode(), OutputMode::Captured));
+
+        let inner = interpreter.with_output_mode(OutputMode::Terminal, |interpreter| {
+            matches!(interpreter.output_mode(), OutputMode::Terminal)
+        });
+        assert!(inner);
+        assert!(matches!(interpreter.output_mode(), OutputMode::Captured));
+    }
+
+    #[test]
+    fn test_builtin_stage_streams_into_external_command() {
+        let mut interpreter = Interpreter::new();
+        // Far more than a pipe buffer holds; head exits after three bytes
+        let result = interpreter
+            .execute_string("printf '%01000000d' 0 | head -c 3; echo \" ${PIPESTATUS[@]}\"")
+            .unwrap();
+        assert_eq!(result.stdout, "000 0 0\n");
+
+        let result = interpreter
+            .execute_string("{ echo b; echo a; } | sort | tr a-z A-Z")
+            .unwrap();
+        assert_eq!(result.stdout, "A\nB\n");
+    }
+
     #[test]
     fn test_command_not_found() {
         let mut interpreter = Interpreter::new();
@@ -722,14 +2355,31 @@ mod tests {
         let mut interpreter = Interpreter::new();
         let program = Program {
             commands: vec![
+                make_simple_command("echo", vec!["foo"]),
                 make_simple_command("true", vec![]),
-                make_simple_command("echo", vec!["test"]),
+                make_simple_command("echo", vec!["bar"]),
             ],
         };
 
         let result = interpreter.execute(program).unwrap();
         assert_eq!(result.code, 0);
-        assert_eq!(result.stdout, "test\n");
+        assert_eq!(result.stdout, "foo\nbar\n");
+    }
+
+    #[test]
+    fn test_passthrough_returns_only_status() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_capture_mode(CaptureMode::Passthrough);
+        let program = Program {
+            commands: vec![
+                make_simple_command("echo", vec!["shown"]),
+                make_simple_command("false", vec![]),
+            ],
+        };
+
+        let result = interpreter.execute(program).unwrap();
+        assert_eq!(result.code, 1);
+        assert_eq!(result.stdout, "");
     }
 
     #[test]
@@ -751,7 +2401,7 @@ mod tests {
         // Check that variable was stored
         assert_eq!(
             interpreter.variable_context.get("var"),
-            Some(&"hello".to_string())
+            Some("hello")
         );
     }
 
@@ -881,7 +2531,7 @@ mod tests {
         // Check that variable was assigned
         assert_eq!(
             interpreter.variable_context.get("new_var"),
-            Some(&"assigned_value".to_string())
+            Some("assigned_value")
         );
     }
 
@@ -906,11 +2556,40 @@ mod tests {
         assert_eq!(result.code, 0);
         assert_eq!(result.stdout, "hello world\n");
 
-        // Check that variable was assigned
-        assert_eq!(
-            interpreter.variable_context.get("name"),
-            Some(&"world".to_string())
-        );
+        // The assignment only lasted for the command
+        assert_eq!(interpreter.variable_context.get("name"), None);
+    }
+
+    #[test]
+    fn test_prefix_assignments_reach_only_their_command() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter
+            .execute_string(
+                "x=outer\nx=inner y=1 sh -c 'echo $x$y'\nsh -c 'echo [$x]'\necho $x\nz=kept :\necho $z",
+            )
+            .unwrap();
+        assert_eq!(result.stdout, "inner1\n[]\nouter\nkept\n");
+        assert!(!interpreter.variable_context.is_exported("x"));
+        assert!(!interpreter.variable_context.contains("y"));
+    }
+
+    #[test]
+    fn test_child_environment_inherits_process_env() {
+        let mut interpreter = Interpreter::new();
+        interpreter.variable_context.set("LOCAL".to_string(), "1".to_string());
+        assert!(!interpreter.child_environment().contains_key("CARGO_PKG_NAME"));
+
+        interpreter.variable_context.set_inherit_env(true);
+        interpreter.variable_context.export("LOCAL");
+        let env = interpreter.child_environment();
+        assert_eq!(env.get("CARGO_PKG_NAME").map(String::as_str), Some("shex-interpreter"));
+        assert_eq!(env.get("LOCAL").map(String::as_str), Some("1"));
+
+        // A shell variable that is not exported hides the inherited one
+        interpreter
+            .variable_context
+            .set("CARGO_PKG_NAME".to_string(), "other".to_string());
+        assert!(!interpreter.child_environment().contains_key("CARGO_PKG_NAME"));
     }
 
     #[test]
@@ -1033,7 +2712,7 @@ mod tests {
         // Check that X was assigned
         assert_eq!(
             interpreter.variable_context.get("X"),
-            Some(&"abc".to_string())
+            Some("abc")
         );
 
         // Run again - should use existing value
@@ -1154,8 +2833,8 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::AndIf {
-                    left: Box::new(make_simple_command("true", vec![])),
-                    right: Box::new(make_simple_command("echo", vec!["success"])),
+                    left: Arc::new(make_simple_command("true", vec![])),
+                    right: Arc::new(make_simple_command("echo", vec!["success"])),
                 },
                 Span::dummy(),
             )],
@@ -1172,8 +2851,8 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::AndIf {
-                    left: Box::new(make_simple_command("false", vec![])),
-                    right: Box::new(make_simple_command("echo", vec!["should_not_run"])),
+                    left: Arc::new(make_simple_command("false", vec![])),
+                    right: Arc::new(make_simple_command("echo", vec!["should_not_run"])),
                 },
                 Span::dummy(),
             )],
@@ -1190,8 +2869,8 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::OrIf {
-                    left: Box::new(make_simple_command("true", vec![])),
-                    right: Box::new(make_simple_command("echo", vec!["should_not_run"])),
+                    left: Arc::new(make_simple_command("true", vec![])),
+                    right: Arc::new(make_simple_command("echo", vec!["should_not_run"])),
                 },
                 Span::dummy(),
             )],
@@ -1208,8 +2887,8 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::OrIf {
-                    left: Box::new(make_simple_command("false", vec![])),
-                    right: Box::new(make_simple_command("echo", vec!["fallback"])),
+                    left: Arc::new(make_simple_command("false", vec![])),
+                    right: Arc::new(make_simple_command("echo", vec!["fallback"])),
                 },
                 Span::dummy(),
             )],
@@ -1238,8 +2917,7 @@ mod tests {
 
         let result = interpreter.execute(program).unwrap();
         assert_eq!(result.code, 0);
-        // Returns result of last command
-        assert_eq!(result.stdout, "third\n");
+        assert_eq!(result.stdout, "first\nsecond\nthird\n");
     }
 
     #[test]
@@ -1260,7 +2938,7 @@ mod tests {
 
         let result = interpreter.execute(program).unwrap();
         assert_eq!(result.code, 0); // Last command (echo) succeeds
-        assert_eq!(result.stdout, "third\n");
+        assert_eq!(result.stdout, "first\nthird\n");
     }
 
     #[test]
@@ -1269,7 +2947,7 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::Background {
-                    command: Box::new(make_simple_command("echo", vec!["background"])),
+                    command: Arc::new(make_simple_command("echo", vec!["background"])),
                 },
                 Span::dummy(),
             )],
@@ -1288,14 +2966,14 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::OrIf {
-                    left: Box::new(Spanned::new(
+                    left: Arc::new(Spanned::new(
                         Command::AndIf {
-                            left: Box::new(make_simple_command("true", vec![])),
-                            right: Box::new(make_simple_command("echo", vec!["success"])),
+                            left: Arc::new(make_simple_command("true", vec![])),
+                            right: Arc::new(make_simple_command("echo", vec!["success"])),
                         },
                         Span::dummy(),
                     )),
-                    right: Box::new(make_simple_command("echo", vec!["fallback"])),
+                    right: Arc::new(make_simple_command("echo", vec!["fallback"])),
                 },
                 Span::dummy(),
             )],
@@ -1314,7 +2992,7 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::AndIf {
-                    left: Box::new(Spanned::new(
+                    left: Arc::new(Spanned::new(
                         Command::Simple {
                             name: "echo".to_string(),
                             args: vec!["$var".to_string()],
@@ -1323,7 +3001,7 @@ mod tests {
                         },
                         Span::dummy(),
                     )),
-                    right: Box::new(make_simple_command("echo", vec!["world"])),
+                    right: Arc::new(make_simple_command("echo", vec!["world"])),
                 },
                 Span::dummy(),
             )],
@@ -1331,7 +3009,7 @@ mod tests {
 
         let result = interpreter.execute(program).unwrap();
         assert_eq!(result.code, 0);
-        assert_eq!(result.stdout, "world\n");
+        assert_eq!(result.stdout, "hello\nworld\n");
     }
 
     #[test]
@@ -1342,7 +3020,7 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::If {
-                    condition: Box::new(make_simple_command("true", vec![])),
+                    condition: Arc::new(make_simple_command("true", vec![])),
                     then_body: vec![make_simple_command("echo", vec!["success"])],
                     elif_clauses: vec![],
                     else_body: None,
@@ -1364,7 +3042,7 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::If {
-                    condition: Box::new(make_simple_command("false", vec![])),
+                    condition: Arc::new(make_simple_command("false", vec![])),
                     then_body: vec![make_simple_command("echo", vec!["fail"])],
                     elif_clauses: vec![],
                     else_body: None,
@@ -1386,7 +3064,7 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::If {
-                    condition: Box::new(make_simple_command("false", vec![])),
+                    condition: Arc::new(make_simple_command("false", vec![])),
                     then_body: vec![make_simple_command("echo", vec!["fail"])],
                     elif_clauses: vec![],
                     else_body: Some(vec![make_simple_command("echo", vec!["success"])]),
@@ -1412,7 +3090,7 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::While {
-                    condition: Box::new(make_simple_command("false", vec![])),
+                    condition: Arc::new(make_simple_command("false", vec![])),
                     body: vec![make_simple_command("echo", vec!["never"])],
                 },
                 Span::dummy(),
@@ -1443,7 +3121,50 @@ mod tests {
         let result = interpreter.execute(program).unwrap();
         assert_eq!(result.code, 0);
         // Should execute echo for each item: apple, banana, cherry
-        assert_eq!(result.stdout, "cherry\n"); // Last iteration result
+        assert_eq!(result.stdout, "apple\nbanana\ncherry\n");
+    }
+
+    #[test]
+    fn test_for_loop_without_in_uses_positional_params() {
+        let mut interpreter = Interpreter::new();
+        interpreter.variable_context.set_positional_params(vec![
+            "apple".to_string(),
+            "banana".to_string(),
+            "cherry".to_string(),
+        ]);
+
+        let program = Parser::new("for f; do echo $f; done").unwrap().parse().unwrap();
+        let result = interpreter.execute(program).unwrap();
+        assert_eq!(result.stdout, "apple\nbanana\ncherry\n");
+    }
+
+    #[test]
+    fn test_execute_string() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute_string("x=5\necho $x").unwrap();
+        assert_eq!(result.stdout, "5\n");
+
+        let error = interpreter.execute_string("echo ok\necho $missing").unwrap_err();
+        assert!(matches!(
+            error,
+            ShexError::UndefinedVariable { line: 2, .. }
+        ));
+        assert!(interpreter.execute_string("if true").is_err());
+    }
+
+    #[test]
+    fn test_execute_file() {
+        use std::io::Write;
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "echo from file\necho $LINENO").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute_file(path).unwrap();
+        assert_eq!(result.stdout, "from file\n2\n");
+
+        let error = interpreter.execute_file("/nonexistent/script.sh").unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/script.sh"));
     }
 
     #[test]
@@ -1592,7 +3313,7 @@ mod tests {
         let program = Program {
             commands: vec![Spanned::new(
                 Command::If {
-                    condition: Box::new(make_simple_command("true", vec![])),
+                    condition: Arc::new(make_simple_command("true", vec![])),
                     then_body: vec![Spanned::new(
                         Command::BraceGroup {
                             commands: vec![make_simple_command("echo", vec!["nested"])],
@@ -1610,4 +3331,253 @@ mod tests {
         assert_eq!(result.code, 0);
         assert_eq!(result.stdout, "nested\n");
     }
+
+    #[test]
+    fn test_function_introspection() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .execute_string("zeta() { echo z; }\nalpha() { echo a; }")
+            .unwrap();
+
+        assert_eq!(interpreter.get_function_names(), vec!["alpha", "zeta"]);
+        assert!(interpreter.has_function("alpha"));
+        assert!(!interpreter.has_function("echo"));
+        let body = interpreter.get_function_body("zeta").unwrap();
+        assert_eq!(pretty_print_command(body), "{\n  echo z\n}");
+        assert!(interpreter.get_function_body("missing").is_none());
+
+        assert!(interpreter.remove_function("zeta"));
+        assert!(!interpreter.remove_function("zeta"));
+        assert_eq!(interpreter.get_function_names(), vec!["alpha"]);
+    }
+
+    #[test]
+    fn test_assigned_variables_are_counted() {
+        let source = "a=1 b=2; LC_ALL=C ls\nfor n in 1 2; do a=3; f() { c=$n; }; done";
+        let program = Parser::new(source).unwrap().parse().unwrap();
+        assert_eq!(assigned_variable_count(&program.commands), 5);
+
+        let assignments: String = (0..100).map(|n| format!("v{n}=x\n")).collect();
+        let program = Parser::new(&assignments).unwrap().parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.execute(program).unwrap();
+        assert!(interpreter.variable_context.capacity() >= 100);
+    }
+
+    #[test]
+    fn test_combine_stdout() {
+        let first = ExitStatus {
+            code: 1,
+            stdout: "one\n".to_string(),
+            stderr: "warn\n".to_string(),
+        };
+        let second = ExitStatus {
+            code: 0,
+            stdout: "two\n".to_string(),
+            stderr: String::new(),
+        };
+        let combined = ExitStatus::empty().combine_stdout(first).combine_stdout(second);
+        assert_eq!(combined.code, 0);
+        assert_eq!(combined.stdout, "one\ntwo\n");
+        assert_eq!(combined.stderr, "warn\n");
+    }
+
+    #[test]
+    fn test_colon_and_noop() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute_string("if :; then echo yes; fi").unwrap();
+        assert_eq!(result.stdout, "yes\n");
+
+        let result = interpreter
+            .execute_string("while :; do echo once; break; echo never; done")
+            .unwrap();
+        assert_eq!(result.code, 0);
+        assert_eq!(result.stdout, "once\n");
+
+        let result = interpreter.execute_string("false; { }").unwrap();
+        assert_eq!(result.code, 0);
+    }
+
+    #[test]
+    fn test_break_and_continue_levels() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter
+            .execute_string(
+                "for a in 1 2; do for b in x y; do case $b in y) continue 2;; esac; echo $a$b; done; done",
+            )
+            .unwrap();
+        assert_eq!(result.stdout, "1x\n2x\n");
+
+        let result = interpreter
+            .execute_string("for a in 1 2; do until false; do echo $a; break 2; done; done")
+            .unwrap();
+        assert_eq!(result.stdout, "1\n");
+    }
+
+    #[test]
+    fn test_function_arguments_and_return() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_positional_params(vec!["outer".to_string()]);
+        let result = interpreter
+            .execute_string(
+                "greet() { echo $1 $#; return 3; echo never; }\ngreet world again || echo $?\necho $1 $#",
+            )
+            .unwrap();
+        assert_eq!(result.stdout, "world 2\n3\nouter 1\n");
+
+        let result = interpreter
+            .execute_string("find() { for x in $@; do case $x in b) return;; esac; done; false; }\nfind a b c && echo found")
+            .unwrap();
+        assert_eq!(result.stdout, "found\n");
+
+        let result = interpreter.execute_string("return 4").unwrap();
+        assert_eq!(result.code, 1);
+        assert!(result.stderr.contains("can only `return' from a function"));
+    }
+
+    #[test]
+    fn test_command_skips_functions() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter
+            .execute_string("true() { echo shadowed; }\ncommand true --bogus-flag")
+            .unwrap();
+        assert_eq!(result.code, 0);
+        assert_eq!(result.stdout, "");
+
+        let result = interpreter.execute_string("command false").unwrap();
+        assert_eq!(result.code, 1);
+    }
+
+    #[test]
+    fn test_reset_forgets_state() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .execute_string("x=1\nexport HOME_DIR=/home\ngreet() { echo hi; }\nset -x\nHOME=/elsewhere")
+            .unwrap();
+        interpreter.reset();
+        assert_eq!(interpreter.variable("x"), None);
+        assert_eq!(interpreter.variable("HOME_DIR"), None);
+        // The process environment is imported again
+        assert_eq!(interpreter.variable("HOME"), std::env::var("HOME").ok());
+        assert_eq!(interpreter.variable("CARGO_PKG_NAME").as_deref(), Some("shex-interpreter"));
+        assert!(interpreter.variable_context.is_exported("CARGO_PKG_NAME"));
+        assert!(!interpreter.has_function("greet"));
+        assert!(!interpreter.options.xtrace);
+        assert_eq!(interpreter.last_status(), 0);
+    }
+
+    #[test]
+    fn test_reset_preserve_env_keeps_exports() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .execute_string("x=1\nexport HOME_DIR=/home")
+            .unwrap();
+        interpreter.reset_preserve_env();
+        assert_eq!(interpreter.variable("x"), None);
+        assert_eq!(interpreter.variable("HOME_DIR").as_deref(), Some("/home"));
+        assert!(interpreter.variable_context.is_exported("HOME_DIR"));
+    }
+
+    #[test]
+    fn test_with_env_imports_and_exports_environment() {
+        let mut interpreter = Interpreter::with_env();
+        let result = interpreter
+            .execute_string("echo $CARGO_PKG_NAME; sh -c 'echo $CARGO_PKG_NAME'")
+            .unwrap();
+        assert_eq!(result.stdout, "shex-interpreter\nshex-interpreter\n");
+        assert!(interpreter.variable_context.is_exported("CARGO_PKG_NAME"));
+        assert_eq!(
+            interpreter.variable("PWD"),
+            Some(interpreter.cwd().display().to_string())
+        );
+    }
+
+    #[test]
+    fn test_execute_conditional() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter
+            .execute_conditional("true", "echo yes", "echo no")
+            .unwrap();
+        assert_eq!(result.stdout, "yes\n");
+
+        let result = interpreter
+            .execute_conditional("echo checking; false", "echo yes", "echo no")
+            .unwrap();
+        assert_eq!(result.stdout, "checking\nno\n");
+        assert_eq!(result.code, 0);
+    }
+
+    #[test]
+    fn test_execute_while_limit() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute_while(":", "echo tick", Some(3)).unwrap();
+        assert_eq!(result.stdout, "tick\ntick\ntick\n");
+
+        let result = interpreter.execute_while("false", "echo tick", None).unwrap();
+        assert_eq!(result.code, 0);
+        assert_eq!(result.stdout, "");
+    }
+
+    #[test]
+    fn test_backtick_substitution() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute_string("echo `echo hello`").unwrap();
+        assert_eq!(result.stdout, "hello\n");
+
+        let result = interpreter
+            .execute_string("name=`echo world`; echo \"hi `echo $name`\"")
+            .unwrap();
+        assert_eq!(result.stdout, "hi world\n");
+    }
+
+    #[test]
+    fn test_dollar_paren_substitution_in_words() {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter
+            .execute_string("x=$(echo a  b)\nfor w in pre$(echo $x)post; do echo \"[$w]\"; done\necho \"$x\" $((1 + 1))$(echo z)")
+            .unwrap();
+        assert_eq!(result.stdout, "[prea]\n[bpost]\na b 2z\n");
+
+        let result = interpreter.execute_string("HOME=/home/me\necho ~/bin a~b").unwrap();
+        assert_eq!(result.stdout, "/home/me/bin a~b\n");
+    }
+
+    #[test]
+    fn test_case_glob_patterns() {
+        let mut interpreter = Interpreter::new();
+        let script = "for file in a.txt b.log c.rs; do case $file in *.txt|*.log) echo $file;; *) echo other;; esac; done";
+        let result = interpreter.execute_string(script).unwrap();
+        assert_eq!(result.stdout, "a.txt\nb.log\nother\n");
+
+        let result = interpreter
+            .execute_string("case x in \"*\") echo star;; [a-z]) echo letter;; esac")
+            .unwrap();
+        assert_eq!(result.stdout, "letter\n");
+
+        let result = interpreter
+            .execute_string("want=ab; case \"$(echo a)b\" in $want) echo expanded;; esac")
+            .unwrap();
+        assert_eq!(result.stdout, "expanded\n");
+    }
+
+    #[test]
+    fn test_last_error() {
+        let mut interpreter = Interpreter::new();
+        assert!(interpreter.last_error().is_none());
+
+        assert!(interpreter.execute_string("shex_no_such_command").is_err());
+        assert!(matches!(
+            interpreter.last_error(),
+            Some(ShexError::CommandNotFound { command, .. }) if command == "shex_no_such_command"
+        ));
+
+        // A failing command is an exit code, not an error
+        assert_eq!(interpreter.execute_string("false").unwrap().code, 1);
+        assert!(interpreter.last_error().is_some());
+
+        interpreter.clear_error();
+        assert!(interpreter.last_error().is_none());
+        assert!(interpreter.execute_string("if true").is_err());
+        assert!(matches!(interpreter.last_error(), Some(ShexError::Syntax { .. })));
+    }
 }
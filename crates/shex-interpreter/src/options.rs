@@ -0,0 +1,102 @@
+//! Shell options for Shex interpreter
+//!
+//! Options toggled with `set -o NAME` / `set +o NAME` or their single-letter
+//! forms such as `set -x`. Library users can also build a `ShellOptions`
+//! directly and pass it to `Interpreter::with_options`.
+
+/// Runtime shell options
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellOptions {
+    /// Stop running a script at the first top-level command that fails; on
+    /// by default
+    pub errexit: bool,
+    /// Treat expanding an unset variable as an error; on by default
+    pub nounset: bool,
+    /// Print each command to stderr, prefixed by `$PS4`, before running it
+    pub xtrace: bool,
+    /// Give a pipeline the status of its last failing stage rather than of
+    /// its last stage
+    pub pipefail: bool,
+    /// Refuse to truncate an existing file with `>`; `>|` still overwrites
+    pub noclobber: bool,
+    /// Mark every variable assigned from now on for export
+    pub allexport: bool,
+    /// Let shell functions inherit the `ERR` trap
+    pub errtrace: bool,
+    /// Reject syntax and features beyond POSIX sh, such as `coproc` and arrays
+    pub posix_mode: bool,
+    /// Forbid command names containing `/` and redirecting output to files
+    pub restricted: bool,
+    /// Whether the shell reads commands from a terminal, enabling job control
+    pub interactive: bool,
+    /// Whether the shell was started as a login shell
+    pub login: bool,
+}
+
+impl Default for ShellOptions {
+    fn default() -> Self {
+        Self {
+            errexit: true,
+            nounset: true,
+            xtrace: false,
+            pipefail: false,
+            noclobber: false,
+            allexport: false,
+            errtrace: false,
+            posix_mode: false,
+            restricted: false,
+            interactive: false,
+            login: false,
+        }
+    }
+}
+
+impl ShellOptions {
+    /// Look up a mutable option flag by its `set -o` name
+    ///
+    /// `restricted`, `interactive` and `login` are fixed at startup and cannot
+    /// be changed this way
+    pub fn by_name_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "errexit" => Some(&mut self.errexit),
+            "xtrace" => Some(&mut self.xtrace),
+            "posix" => Some(&mut self.posix_mode),
+            "nounset" => Some(&mut self.nounset),
+            "pipefail" => Some(&mut self.pipefail),
+            "noclobber" => Some(&mut self.noclobber),
+            "allexport" => Some(&mut self.allexport),
+            "errtrace" => Some(&mut self.errtrace),
+            _ => None,
+        }
+    }
+
+    /// Single-letter flags of the options that are on, as `$-` expands
+    #[must_use]
+    pub fn flags(&self) -> String {
+        [
+            ('a', self.allexport),
+            ('e', self.errexit),
+            ('i', self.interactive),
+            ('u', self.nounset),
+            ('x', self.xtrace),
+            ('C', self.noclobber),
+            ('E', self.errtrace),
+        ]
+        .into_iter()
+        .filter_map(|(flag, on)| on.then_some(flag))
+        .collect()
+    }
+
+    /// Look up a mutable option flag by its single-letter `set` flag
+    pub fn by_flag_mut(&mut self, flag: char) -> Option<&mut bool> {
+        match flag {
+            'e' => Some(&mut self.errexit),
+            'x' => Some(&mut self.xtrace),
+            'u' => Some(&mut self.nounset),
+            'C' => Some(&mut self.noclobber),
+            'a' => Some(&mut self.allexport),
+            'E' => Some(&mut self.errtrace),
+            _ => None,
+        }
+    }
+}
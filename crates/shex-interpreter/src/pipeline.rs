@@ -0,0 +1,575 @@
+//! Pipeline execution with real OS pipes
+//!
+//! When every stage is an external command, the stages are spawned together
+//! with each stdout connected to the next stdin. On Unix all stages share one
+//! process group led by the first command, so a terminal Ctrl-C reaches the
+//! whole pipeline at once.
+
+use crate::builtins::is_builtin;
+use crate::{
+    ExitStatus, Interpreter, OutputMode, PIPESTATUS, has_here_doc, has_input_redirection, redirects_to_file,
+};
+use shex_ast::{Command, Redirection, ShexError, Span, Spanned};
+use std::collections::HashMap;
+use std::io::Read;
+use std::process::{Child, Command as StdCommand, Stdio};
+use std::thread::JoinHandle;
+
+/// A pipeline stage resolved to an external program
+struct ExternalStage<'a> {
+    program: String,
+    args: Vec<String>,
+    redirections: &'a [Redirection],
+    /// Environment including the stage's prefix assignments
+    env: HashMap<String, String>,
+}
+
+impl Interpreter {
+    /// Run a pipeline whose stages are all external commands
+    ///
+    /// Returns None when some stage is a builtin, function or compound command,
+    /// or a command resolver is installed, so the caller can fall back to
+    /// in-process execution
+    pub(crate) fn execute_external_pipeline(
+        &mut self,
+        commands: &[Spanned<Command>],
+        span: Span,
+    ) -> Option<Result<ExitStatus, ShexError>> {
+        let all_external = commands
+            .iter()
+            .all(|command| self.is_external_stage(command));
+        // A command resolver handles each stage in-process
+        if !all_external || self.command_resolver.is_some() {
+            return None;
+        }
+        Some(
+            self.run_external_pipeline(commands, span)
+                .map(|stages| self.finish_pipeline(stages)),
+        )
+    }
+
+    /// Run a pipeline and return the status of every stage, first to last
+    ///
+    /// When every stage is external they run concurrently, connected by OS
+    /// pipes, and all of them are waited for before returning. Output that
+    /// feeds the next stage is not kept, so only the last stage has stdout.
+    /// Unlike running the pipeline as a command, `PIPESTATUS` and `$?` are
+    /// left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShexError::CommandNotFound` if a stage's program cannot be
+    /// started, or any error raised while running a stage
+    pub fn execute_pipeline_stages(
+        &mut self,
+        commands: &[Spanned<Command>],
+    ) -> Result<Vec<ExitStatus>, ShexError> {
+        let span = match (commands.first(), commands.last()) {
+            (Some(first), Some(last)) => Span::new(first.span.start, last.span.end),
+            _ => return Ok(Vec::new()),
+        };
+        self.count_by(|stats| &mut stats.pipeline_stages, commands.len());
+        let all_external = commands
+            .iter()
+            .all(|command| self.is_external_stage(command));
+        if all_external && self.command_resolver.is_none() {
+            return self.run_external_pipeline(commands, span);
+        }
+        // Builtins and compound commands run one stage after another, each
+        // recording its status, so the caller's values are put back after
+        let status = self.last_status();
+        let pipestatus = self.variable_context.get_array(PIPESTATUS).cloned();
+        let result = self.execute_sequential_pipeline(commands);
+        self.set_last_status(status);
+        match pipestatus {
+            Some(codes) => self.variable_context.set_array(PIPESTATUS.to_string(), codes),
+            None => self.variable_context.unset(PIPESTATUS),
+        }
+        result
+    }
+
+    /// Whether a pipeline stage is a simple command that runs a program
+    ///
+    /// A command reading a here-document runs in-process, where its body is
+    /// passed as input
+    pub(crate) fn is_external_stage(&self, command: &Spanned<Command>) -> bool {
+        matches!(&command.node, Command::Simple { name, redirections, .. }
+            if !is_builtin(name)
+                && !self.functions.contains_key(name)
+                && !has_here_doc(redirections))
+    }
+
+    /// Run an in-process pipeline stage with its stdout streaming into the
+    /// external command `next`, returning the statuses of both
+    ///
+    /// `next` is started first and its output read on other threads, so
+    /// neither side blocks on a full pipe
+    pub(crate) fn execute_stage_into_external(
+        &mut self,
+        stage: &Spanned<Command>,
+        next: &Spanned<Command>,
+    ) -> Result<(ExitStatus, ExitStatus), ShexError> {
+        let Command::Simple {
+            name,
+            args,
+            assignments,
+            redirections,
+        } = &next.node
+        else {
+            unreachable!("external stages are simple commands");
+        };
+        let (trace, mut child) = self.with_prefix_assignments(assignments, next.span, |interpreter| {
+            let program = interpreter.expand_command_name(name, next.span)?;
+            let args = interpreter.expand_arguments(args, next.span)?;
+            let trace = if interpreter.options.xtrace {
+                let trace = interpreter.trace_line(&program, &args, next.span)?;
+                interpreter.emit_trace(trace)
+            } else {
+                String::new()
+            };
+
+            let mut cmd = interpreter.external_command(&program, &args, redirections, next.span)?;
+            if !has_input_redirection(redirections) {
+                cmd.stdin(Stdio::piped());
+            }
+            let Ok(child) = cmd.spawn() else {
+                return Err(interpreter.command_not_found_error(program, next.span));
+            };
+            Ok((trace, child))
+        })?;
+        let stdout = child.stdout.take().map(read_in_background);
+        let stderr = child.stderr.take().map(read_in_background);
+
+        // Without a pipe the next stage reads elsewhere and this output is lost
+        let output = child
+            .stdin
+            .take()
+            .map_or(OutputMode::Captured, OutputMode::PipedTo);
+        let stage_status = self.with_output_mode(output, |interpreter| {
+            interpreter.execute_command_without_traps(stage)
+        });
+        let waited = child.wait();
+        let collect = |reader: Option<JoinHandle<String>>| {
+            reader
+                .map(|reader| reader.join().unwrap_or_default())
+                .unwrap_or_default()
+        };
+        let (stdout, stderr) = (collect(stdout), collect(stderr));
+
+        let stage_status = stage_status?;
+        let waited = waited.map_err(|e| {
+            self.syntax_error(format!("Failed to wait for pipeline: {e}"), next.span)
+        })?;
+        let next_status = ExitStatus {
+            code: waited.code().unwrap_or(-1),
+            stdout,
+            stderr: trace + &stderr,
+        };
+        Ok((stage_status, next_status))
+    }
+
+    /// Spawn every stage of an all-external pipeline and wait for them all
+    fn run_external_pipeline(
+        &mut self,
+        commands: &[Spanned<Command>],
+        span: Span,
+    ) -> Result<Vec<ExitStatus>, ShexError> {
+        let mut stages = Vec::with_capacity(commands.len());
+        for command in commands {
+            if let Command::Simple {
+                name,
+                args,
+                assignments,
+                redirections,
+            } = &command.node
+            {
+                let stage = self.with_prefix_assignments(assignments, command.span, |interpreter| {
+                    Ok(ExternalStage {
+                        program: interpreter.expand_command_name(name, command.span)?,
+                        args: interpreter.expand_arguments(args, command.span)?,
+                        redirections,
+                        env: interpreter.child_environment(),
+                    })
+                })?;
+                stages.push(stage);
+            }
+        }
+
+        let mut children: Vec<Child> = Vec::with_capacity(stages.len());
+        let mut group: Option<u32> = None;
+        let last_index = stages.len() - 1;
+
+        for (index, stage) in stages.iter().enumerate() {
+            self.count(|stats| &mut stats.external_commands);
+            let mut cmd = StdCommand::new(&stage.program);
+            cmd.args(&stage.args)
+                .current_dir(&self.cwd)
+                .env_clear()
+                .envs(&stage.env);
+
+            match children
+                .last_mut()
+                .and_then(|previous| previous.stdout.take())
+            {
+                Some(previous_stdout) => {
+                    cmd.stdin(Stdio::from(previous_stdout));
+                }
+                None if index > 0 => {
+                    // The previous stage redirected its stdout elsewhere
+                    cmd.stdin(Stdio::null());
+                }
+                None => {}
+            }
+
+            if let Err(error) = self.apply_redirections(&mut cmd, stage.redirections, span) {
+                Self::kill_all(children);
+                return Err(error);
+            }
+
+            if !redirects_to_file(stage.redirections, 1) {
+                cmd.stdout(Stdio::piped());
+            }
+            if index == last_index && !redirects_to_file(stage.redirections, 2) {
+                cmd.stderr(Stdio::piped());
+            }
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::CommandExt;
+                // 0 makes the first stage the leader of a new group
+                cmd.process_group(group.map_or(0, |pgid| pgid as i32));
+            }
+
+            let child = match cmd.spawn() {
+                Ok(child) => child,
+                Err(_) => {
+                    Self::kill_all(children);
+                    return Err(self.command_not_found_error(stage.program.clone(), span));
+                }
+            };
+
+            let pgid = *group.get_or_insert(child.id());
+            #[cfg(unix)]
+            process_group::join(child.id(), pgid);
+            #[cfg(not(unix))]
+            let _ = pgid;
+            children.push(child);
+        }
+
+        #[cfg(unix)]
+        let terminal = group.and_then(|pgid| self.give_terminal_to(pgid));
+
+        let last = children.pop().expect("pipeline has at least one stage");
+        let output = last.wait_with_output();
+        let codes = Self::reap(children);
+
+        #[cfg(unix)]
+        if let Some(shell_group) = terminal {
+            process_group::set_foreground(shell_group);
+        }
+
+        let output = output
+            .map_err(|e| self.syntax_error(format!("Failed to wait for pipeline: {e}"), span))?;
+
+        let mut statuses: Vec<ExitStatus> = codes
+            .into_iter()
+            .map(|code| ExitStatus {
+                code,
+                stdout: String::new(),
+                stderr: String::new(),
+            })
+            .collect();
+        statuses.push(ExitStatus {
+            code: output.status.code().unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
+        Ok(statuses)
+    }
+
+    /// Wait for the remaining pipeline stages, returning their exit codes
+    fn reap(children: Vec<Child>) -> Vec<i32> {
+        children
+            .into_iter()
+            .map(|mut child| {
+                child
+                    .wait()
+                    .ok()
+                    .and_then(|status| status.code())
+                    .unwrap_or(-1)
+            })
+            .collect()
+    }
+
+    /// Stop the stages already started when the pipeline cannot be completed
+    fn kill_all(children: Vec<Child>) {
+        for mut child in children {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    /// Hand the terminal to the pipeline's process group when interactive
+    ///
+    /// Returns the shell's own process group so it can be restored later
+    #[cfg(unix)]
+    fn give_terminal_to(&self, pgid: u32) -> Option<u32> {
+        if !self.options.interactive {
+            return None;
+        }
+        let shell_group = process_group::foreground()?;
+        process_group::set_foreground(pgid);
+        Some(shell_group)
+    }
+}
+
+/// Read a child's output pipe to the end on another thread
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        String::from_utf8_lossy(&bytes).into_owned()
+    })
+}
+
+/// Thin wrappers over the Unix process group and terminal APIs
+#[cfg(unix)]
+pub(crate) mod process_group {
+    use nix::unistd::{Pid, setpgid, tcgetpgrp, tcsetpgrp};
+    use std::io::IsTerminal;
+
+    fn pid(id: u32) -> Pid {
+        Pid::from_raw(id as i32)
+    }
+
+    /// Move a child into a process group
+    ///
+    /// The child does this itself via `process_group`; repeating it here
+    /// closes the race where the next stage is spawned before that happens.
+    /// Failures are ignored because the child may already have exited.
+    pub fn join(child: u32, pgid: u32) {
+        let _ = setpgid(pid(child), pid(pgid));
+    }
+
+    /// Process group currently in the terminal's foreground, if stdin is one
+    pub fn foreground() -> Option<u32> {
+        let stdin = std::io::stdin();
+        if !stdin.is_terminal() {
+            return None;
+        }
+        tcgetpgrp(stdin).ok().map(|group| group.as_raw() as u32)
+    }
+
+    /// Make a process group the terminal's foreground group
+    pub fn set_foreground(pgid: u32) {
+        let _ = tcsetpgrp(std::io::stdin(), pid(pgid));
+    }
+
+    /// Ignore the signals job-control shells must not be stopped by
+    ///
+    /// Without this, reclaiming the terminal with `tcsetpgrp` from a
+    /// background group would stop the shell with SIGTTOU.
+    pub fn ignore_job_control_signals() {
+        use nix::sys::signal::{SigHandler, Signal, signal};
+        for sig in [Signal::SIGTTOU, Signal::SIGTTIN, Signal::SIGTSTP] {
+            // SAFETY: SIG_IGN installs no Rust handler code
+            let _ = unsafe { signal(sig, SigHandler::SigIgn) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shex_ast::RedirectionKind;
+
+    fn external(name: &str, args: &[&str]) -> Spanned<Command> {
+        Spanned::new(
+            Command::Simple {
+                name: name.to_string(),
+                args: args.iter().map(|arg| (*arg).to_string()).collect(),
+                assignments: vec![],
+                redirections: vec![],
+            },
+            Span::dummy(),
+        )
+    }
+
+    #[test]
+    fn test_stdout_flows_between_stages() {
+        let mut interpreter = Interpreter::new();
+        let commands = vec![
+            external("sh", &["-c", "echo a; echo b; echo c"]),
+            external("grep", &["-v", "b"]),
+            external("wc", &["-l"]),
+        ];
+
+        let result = interpreter
+            .execute_external_pipeline(&commands, Span::dummy())
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.code, 0);
+        assert_eq!(result.stdout.trim(), "2");
+    }
+
+    #[test]
+    fn test_pipestatus_records_every_stage() {
+        let mut interpreter = Interpreter::new();
+        let commands = vec![
+            external("sh", &["-c", "exit 3"]),
+            external("sh", &["-c", "cat; exit 0"]),
+            external("sh", &["-c", "cat; exit 5"]),
+        ];
+
+        let result = interpreter
+            .execute_external_pipeline(&commands, Span::dummy())
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.code, 5);
+        assert_eq!(
+            interpreter.variable_context.value("PIPESTATUS[@]"),
+            Some("3 0 5".to_string())
+        );
+    }
+
+    #[test]
+    fn test_builtin_stage_falls_back() {
+        let mut interpreter = Interpreter::new();
+        let commands = vec![external("echo", &["hi"]), external("cat", &[])];
+        assert!(
+            interpreter
+                .execute_external_pipeline(&commands, Span::dummy())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_missing_command_is_reported() {
+        let mut interpreter = Interpreter::new();
+        let commands = vec![
+            external("sh", &["-c", "echo a"]),
+            external("shex_no_such_command", &[]),
+        ];
+        let result = interpreter
+            .execute_external_pipeline(&commands, Span::dummy())
+            .unwrap();
+        assert!(matches!(result, Err(ShexError::CommandNotFound { .. })));
+    }
+
+    #[test]
+    fn test_pipeline_stages_report_each_status() {
+        let mut interpreter = Interpreter::new();
+        let commands = vec![
+            external("true", &[]),
+            external("false", &[]),
+            external("true", &[]),
+        ];
+        let codes: Vec<i32> = interpreter
+            .execute_pipeline_stages(&commands)
+            .unwrap()
+            .iter()
+            .map(|stage| stage.code)
+            .collect();
+        assert_eq!(codes, vec![0, 1, 0]);
+
+        let commands = vec![
+            external("echo", &["a"]),
+            external("sh", &["-c", "cat; exit 4"]),
+        ];
+        let stages = interpreter.execute_pipeline_stages(&commands).unwrap();
+        assert_eq!(stages.len(), 2);
+        assert_eq!((stages[0].code, stages[0].stdout.as_str()), (0, ""));
+        assert_eq!((stages[1].code, stages[1].stdout.as_str()), (4, "a\n"));
+        assert_eq!(interpreter.variable_context.value("PIPESTATUS[@]"), None);
+        assert_eq!(interpreter.last_status(), 0);
+    }
+
+    #[test]
+    fn test_sequential_pipeline_stages_keep_statuses() {
+        let mut interpreter = Interpreter::new();
+        interpreter.execute_string("false | true || true").unwrap();
+        let commands = vec![
+            external("echo", &["a"]),
+            external("sh", &["-c", "cat; exit 4"]),
+            external("false", &[]),
+        ];
+        let codes: Vec<i32> = interpreter
+            .execute_pipeline_stages(&commands)
+            .unwrap()
+            .iter()
+            .map(|stage| stage.code)
+            .collect();
+        assert_eq!(codes, vec![0, 4, 1]);
+        assert_eq!(interpreter.last_status(), 0);
+        assert_eq!(
+            interpreter.variable_context.value("PIPESTATUS[@]"),
+            Some("1 0".to_string())
+        );
+    }
+
+    /// Read a numeric field such as `NSpgid:` from /proc status text
+    #[cfg(target_os = "linux")]
+    fn status_fields(status: &str, field: &str) -> Vec<u32> {
+        status
+            .lines()
+            .filter_map(|line| line.strip_prefix(field))
+            .filter_map(|value| value.split_whitespace().next()?.parse().ok())
+            .collect()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_stages_share_a_process_group() {
+        let mut interpreter = Interpreter::new();
+        // Each stage reports its own /proc status; later stages pass earlier
+        // output through with cat
+        let commands = vec![
+            external("grep", &["-E", "^(Pid|NSpgid):", "/proc/self/status"]),
+            external("sh", &["-c", "cat; grep -E '^NSpgid:' /proc/self/status"]),
+        ];
+
+        let result = interpreter
+            .execute_external_pipeline(&commands, Span::dummy())
+            .unwrap()
+            .unwrap();
+
+        let leader = status_fields(&result.stdout, "Pid:");
+        let groups = status_fields(&result.stdout, "NSpgid:");
+        assert_eq!(leader.len(), 1);
+        assert_eq!(groups.len(), 2);
+        assert!(groups.iter().all(|group| *group == leader[0]));
+
+        let own_status = std::fs::read_to_string("/proc/self/status").unwrap();
+        assert_ne!(status_fields(&own_status, "NSpgid:"), vec![leader[0]]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_failed_redirection_stops_started_stages() {
+        let mut interpreter = Interpreter::new();
+        let marker = "30.0017";
+        let mut reader = external("cat", &[]);
+        if let Command::Simple { redirections, .. } = &mut reader.node {
+            redirections.push(Redirection {
+                fd: None,
+                kind: RedirectionKind::Input,
+                target: "/nonexistent/shex-input".to_string(),
+            });
+        }
+        let commands = vec![external("sleep", &[marker]), reader];
+
+        assert!(
+            interpreter
+                .execute_external_pipeline(&commands, Span::dummy())
+                .unwrap()
+                .is_err()
+        );
+
+        // The first stage was killed and reaped before the error returned
+        let running = std::fs::read_dir("/proc")
+            .unwrap()
+            .filter_map(|entry| std::fs::read(entry.ok()?.path().join("cmdline")).ok())
+            .any(|cmdline| cmdline == format!("sleep\0{marker}\0").as_bytes());
+        assert!(!running);
+    }
+}
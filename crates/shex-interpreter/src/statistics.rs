@@ -0,0 +1,74 @@
+//! Execution counters for profiling scripts
+//!
+//! Counting is compiled in only with the `statistics` feature. Without it
+//! `count` does nothing and every counter stays zero.
+
+use crate::Interpreter;
+
+/// How often an interpreter has done each kind of work
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterpreterStats {
+    /// Commands run, counting compound commands and the commands in them
+    pub commands_executed: u64,
+    /// Programs started, or handed to the command resolver
+    pub external_commands: u64,
+    /// Builtins run
+    pub builtin_commands: u64,
+    /// Parameter expansions such as `$name` and `${name:-default}`
+    pub variable_lookups: u64,
+    /// Variable assignments and `for` loop iterations
+    pub variable_sets: u64,
+    /// Shell function calls
+    pub function_calls: u64,
+    /// Stages of all pipelines run
+    pub pipeline_stages: u64,
+}
+
+impl Interpreter {
+    /// Counters for the work done since the interpreter was created or reset
+    ///
+    /// All zero unless built with the `statistics` feature
+    #[must_use]
+    pub const fn statistics(&self) -> InterpreterStats {
+        self.statistics
+    }
+
+    /// Add one to the counter `counter` selects
+    #[inline]
+    pub(crate) fn count(&mut self, counter: fn(&mut InterpreterStats) -> &mut u64) {
+        self.count_by(counter, 1);
+    }
+
+    /// Add `amount` to the counter `counter` selects
+    #[inline]
+    #[cfg_attr(not(feature = "statistics"), allow(unused_variables, clippy::unused_self))]
+    pub(crate) fn count_by(&mut self, counter: fn(&mut InterpreterStats) -> &mut u64, amount: usize) {
+        #[cfg(feature = "statistics")]
+        {
+            *counter(&mut self.statistics) += amount as u64;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "statistics"))]
+mod tests {
+    use crate::Interpreter;
+
+    #[test]
+    fn test_statistics_count_work() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .execute_string("greet() { echo hi; }\nfor x in a b; do greet; done\necho $x | cat")
+            .unwrap();
+        let stats = interpreter.statistics();
+        assert_eq!(stats.function_calls, 2);
+        assert_eq!(stats.builtin_commands, 3);
+        assert_eq!(stats.external_commands, 1);
+        assert_eq!(stats.pipeline_stages, 2);
+        assert_eq!(stats.variable_lookups, 1);
+        assert_eq!(stats.variable_sets, 2);
+
+        interpreter.reset();
+        assert_eq!(interpreter.statistics(), super::InterpreterStats::default());
+    }
+}
@@ -0,0 +1,138 @@
+//! Subshells that run alongside the shell
+//!
+//! A subshell starts from a copy of the shell's variables, functions,
+//! options and traps, so nothing it changes is seen by the shell. It runs
+//! on its own thread with its output captured; the handle collects the
+//! result once the commands finish.
+
+use crate::{ExitStatus, Interpreter, OutputMode};
+use shex_ast::{Command, ShexError, Spanned};
+use shex_parser::variable_resolver::VariableContext;
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+
+/// A subshell started with `Interpreter::spawn_subshell`
+pub struct SubshellHandle {
+    pub thread: JoinHandle<Result<ExitStatus, ShexError>>,
+    /// Process ID for `$!` when the subshell is a separate process; None
+    /// for thread-backed subshells
+    pub pid: Option<u32>,
+    /// Exit code, set when the commands finish
+    code: Arc<OnceLock<i32>>,
+}
+
+impl SubshellHandle {
+    /// Wait for the subshell to finish and return its result
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that stopped the subshell's commands
+    ///
+    /// # Panics
+    ///
+    /// Panics if the subshell thread panicked
+    pub fn wait(self) -> Result<ExitStatus, ShexError> {
+        self.thread.join().expect("subshell thread panicked")
+    }
+
+    /// Exit code of the subshell if it has finished, without blocking
+    ///
+    /// A subshell stopped by an error reports 1
+    #[must_use]
+    pub fn try_wait(&self) -> Option<i32> {
+        self.code.get().copied()
+    }
+}
+
+impl Interpreter {
+    /// Copy of this shell's state for running a subshell
+    ///
+    /// Jobs, coprocesses and the command resolver belong to the shell and are
+    /// not copied; external commands in the subshell are spawned directly.
+    pub(crate) fn clone_for_subshell(&self) -> Self {
+        let mut subshell = Self::new();
+        subshell.variable_context = VariableContext::merge(&self.variable_context);
+        subshell.exit_code = self.exit_code;
+        subshell.last_status = self.last_status;
+        subshell.options = self.options.clone();
+        subshell.functions = self.functions.clone();
+        subshell.call_stack = self.call_stack.clone();
+        subshell.source_map = self.source_map.clone();
+        subshell.source_file = self.source_file.clone();
+        subshell.source_files = self.source_files.clone();
+        subshell.piped_input = self.piped_input.clone();
+        subshell.traps = self.traps.clone();
+        subshell.cwd = self.cwd.clone();
+        subshell
+    }
+
+    /// Run `commands` in a subshell on another thread
+    ///
+    /// The subshell's output is captured in the `ExitStatus` returned by
+    /// `SubshellHandle::wait`.
+    pub fn spawn_subshell(&self, commands: Vec<Spanned<Command>>) -> SubshellHandle {
+        self.spawn_subshell_with_output(commands, OutputMode::Captured)
+    }
+
+    /// Like `spawn_subshell`, but with the subshell writing to `output_mode`
+    pub(crate) fn spawn_subshell_with_output(
+        &self,
+        commands: Vec<Spanned<Command>>,
+        output_mode: OutputMode,
+    ) -> SubshellHandle {
+        let mut subshell = self.clone_for_subshell();
+        subshell.output_mode = output_mode;
+        let code = Arc::new(OnceLock::new());
+        let finished = Arc::clone(&code);
+        let thread = thread::spawn(move || {
+            let result = subshell.execute_command_list(&commands);
+            let _ = finished.set(result.as_ref().map_or(1, |status| status.code));
+            result
+        });
+        SubshellHandle {
+            thread,
+            pid: None,
+            code,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Interpreter;
+    use shex_parser::Parser;
+    use std::time::{Duration, Instant};
+
+    fn commands(source: &str) -> Vec<shex_ast::Spanned<shex_ast::Command>> {
+        Parser::new(source).unwrap().parse().unwrap().commands
+    }
+
+    #[test]
+    fn test_subshell_sees_state_but_does_not_change_it() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .execute_string("x=outer; greet() { echo hi $x; }")
+            .unwrap();
+
+        let handle = interpreter.spawn_subshell(commands("x=inner; greet; exit_with() { false; }"));
+        assert_eq!(handle.pid, None);
+        let status = handle.wait().unwrap();
+        assert_eq!(status.stdout, "hi inner\n");
+        assert_eq!(interpreter.variable("x").as_deref(), Some("outer"));
+        assert!(!interpreter.functions.contains_key("exit_with"));
+    }
+
+    #[test]
+    fn test_try_wait_reports_code_once_finished() {
+        let interpreter = Interpreter::new();
+        let handle = interpreter.spawn_subshell(commands("sleep 0.2; false"));
+        assert_eq!(handle.try_wait(), None);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while handle.try_wait().is_none() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(handle.try_wait(), Some(1));
+        assert_eq!(handle.wait().unwrap().code, 1);
+    }
+}
@@ -0,0 +1,291 @@
+//! Static checks between parsing and execution
+//!
+//! `Validator` looks for mistakes that can be seen in the source alone:
+//! loop control outside a loop, `return` outside a function, functions
+//! defined twice, `shift` past the last positional parameter and
+//! assignments to variables already made readonly. Nothing is expanded or
+//! executed, so only literal words are checked.
+
+use shex_ast::{Command, Program, SourceMap, Span, Spanned};
+use std::collections::HashSet;
+
+/// Whether a validation problem stops the program from running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Suspicious, but the program can still run
+    Warning,
+    /// The program should not be run
+    Error,
+}
+
+impl Severity {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// A problem found by `Validator`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub span: Span,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl ValidationError {
+    /// Format as `Shex:file:line:column: severity: message`
+    #[must_use]
+    pub fn render(&self, source_map: &SourceMap, filename: &str) -> String {
+        let pos = source_map.position(self.span.start);
+        format!(
+            "Shex:{filename}:{}:{}: {}: {}",
+            pos.line,
+            pos.column,
+            self.severity.as_str(),
+            self.message
+        )
+    }
+}
+
+/// Semantic checks over a parsed program
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Validator {
+    /// Number of positional parameters the program starts with, if known
+    positional_count: Option<usize>,
+}
+
+impl Validator {
+    /// Validator that also checks top-level `shift` counts against the
+    /// `count` positional parameters the program will be given
+    #[must_use]
+    pub const fn with_positional_count(count: usize) -> Self {
+        Self {
+            positional_count: Some(count),
+        }
+    }
+
+    /// Check `program` with no knowledge of its positional parameters
+    #[must_use]
+    pub fn validate(program: &Program) -> Vec<ValidationError> {
+        Self::default().check(program)
+    }
+
+    /// Report every problem found in `program`, in source order
+    #[must_use]
+    pub fn check(&self, program: &Program) -> Vec<ValidationError> {
+        let mut visitor = Visitor {
+            positional_count: self.positional_count,
+            loop_depth: 0,
+            in_function: false,
+            functions: HashSet::new(),
+            readonly: HashSet::new(),
+            errors: Vec::new(),
+        };
+        visitor.visit_all(&program.commands);
+        visitor.errors
+    }
+}
+
+struct Visitor {
+    positional_count: Option<usize>,
+    loop_depth: usize,
+    in_function: bool,
+    functions: HashSet<String>,
+    /// Variables made readonly earlier in the source
+    readonly: HashSet<String>,
+    errors: Vec<ValidationError>,
+}
+
+impl Visitor {
+    fn visit_all(&mut self, commands: &[Spanned<Command>]) {
+        for command in commands {
+            self.visit(command);
+        }
+    }
+
+    fn visit(&mut self, command: &Spanned<Command>) {
+        match &command.node {
+            Command::Simple {
+                name,
+                args,
+                assignments,
+                ..
+            } => {
+                self.check_assignments(assignments.iter().map(|(name, _)| name.as_str()), command.span);
+                self.check_simple(name, args, command.span);
+            }
+            Command::Assignment { assignments } => {
+                self.check_assignments(assignments.iter().map(|(name, _)| name.as_str()), command.span);
+            }
+            Command::Pipeline { commands, .. }
+            | Command::Sequence { commands }
+            | Command::Subshell { commands }
+            | Command::BraceGroup { commands } => self.visit_all(commands),
+            Command::AndIf { left, right } | Command::OrIf { left, right } => {
+                self.visit(left);
+                self.visit(right);
+            }
+            Command::Background { command } | Command::Coproc { command, .. } => self.visit(command),
+            Command::If {
+                condition,
+                then_body,
+                elif_clauses,
+                else_body,
+            } => {
+                self.visit(condition);
+                self.visit_all(then_body);
+                for (condition, body) in elif_clauses {
+                    self.visit(condition);
+                    self.visit_all(body);
+                }
+                if let Some(body) = else_body {
+                    self.visit_all(body);
+                }
+            }
+            Command::While { condition, body } | Command::Until { condition, body } => {
+                self.visit(condition);
+                self.visit_loop_body(body);
+            }
+            Command::For { variable, body, .. } => {
+                self.check_assignments([variable.as_str()], command.span);
+                self.visit_loop_body(body);
+            }
+            Command::Case { arms, .. } => {
+                for arm in arms {
+                    self.visit_all(&arm.commands);
+                }
+            }
+            Command::Function { name, body, .. } => {
+                if !self.functions.insert(name.clone()) {
+                    self.report(command.span, Severity::Warning, format!("function {name} is defined more than once"));
+                }
+                // Loop control in a function body applies to the caller's loops
+                let outer = (self.loop_depth, self.in_function);
+                self.loop_depth = 0;
+                self.in_function = true;
+                self.visit(body);
+                (self.loop_depth, self.in_function) = outer;
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_loop_body(&mut self, body: &[Spanned<Command>]) {
+        self.loop_depth += 1;
+        self.visit_all(body);
+        self.loop_depth -= 1;
+    }
+
+    fn check_simple(&mut self, name: &str, args: &[String], span: Span) {
+        match name {
+            "break" | "continue" if self.loop_depth == 0 && !self.in_function => {
+                self.report(span, Severity::Error, format!("{name} is only meaningful in a loop"));
+            }
+            "return" if !self.in_function => {
+                self.report(span, Severity::Error, "return is only allowed in a function".to_string());
+            }
+            "shift" if !self.in_function => self.check_shift(args, span),
+            "readonly" | "export" => {
+                let names: Vec<&str> = args
+                    .iter()
+                    .filter(|arg| !arg.starts_with('-'))
+                    .map(|arg| arg.split_once('=').map_or(arg.as_str(), |(name, _)| name))
+                    .collect();
+                let assigned = args
+                    .iter()
+                    .filter_map(|arg| arg.split_once('=').map(|(name, _)| name));
+                self.check_assignments(assigned, span);
+                if name == "readonly" {
+                    self.readonly.extend(names.into_iter().map(str::to_string));
+                }
+            }
+            "read" => {
+                let names = args.iter().map(String::as_str).filter(|arg| !arg.starts_with('-'));
+                self.check_assignments(names, span);
+            }
+            _ => {}
+        }
+    }
+
+    fn check_shift(&mut self, args: &[String], span: Span) {
+        let (Some(available), Ok(count)) = (
+            self.positional_count,
+            args.first().map_or(Ok(1), |count| count.parse::<usize>()),
+        ) else {
+            return;
+        };
+        if count > available {
+            self.report(
+                span,
+                Severity::Warning,
+                format!("shift {count} is more than the {available} positional parameters"),
+            );
+        }
+    }
+
+    fn check_assignments<'a>(&mut self, names: impl IntoIterator<Item = &'a str>, span: Span) {
+        for name in names {
+            if self.readonly.contains(name) {
+                self.report(span, Severity::Error, format!("{name} is readonly"));
+            }
+        }
+    }
+
+    fn report(&mut self, span: Span, severity: Severity, message: String) {
+        self.errors.push(ValidationError { span, message, severity });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Severity, Validator};
+    use shex_parser::Parser;
+
+    fn messages(validator: Validator, script: &str) -> Vec<(Severity, String)> {
+        let program = Parser::new(script).unwrap().parse().unwrap();
+        validator
+            .check(&program)
+            .into_iter()
+            .map(|error| (error.severity, error.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_loop_control_and_return_placement() {
+        let script = "break\nwhile true; do break; done\nf() { continue; return; }\nreturn";
+        assert_eq!(
+            messages(Validator::default(), script),
+            [
+                (Severity::Error, "break is only meaningful in a loop".to_string()),
+                (Severity::Error, "return is only allowed in a function".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_functions_and_readonly_assignments() {
+        let script = "f() { :; }\nreadonly X=1\nf() { X=2; }\nX=3";
+        assert_eq!(
+            messages(Validator::default(), script),
+            [
+                (Severity::Warning, "function f is defined more than once".to_string()),
+                (Severity::Error, "X is readonly".to_string()),
+                (Severity::Error, "X is readonly".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shift_past_positional_parameters() {
+        let script = "shift\nshift 3\nshift $n";
+        assert!(messages(Validator::default(), script).is_empty());
+        assert_eq!(
+            messages(Validator::with_positional_count(2), script),
+            [(Severity::Warning, "shift 3 is more than the 2 positional parameters".to_string())]
+        );
+    }
+}
@@ -0,0 +1,733 @@
+//! POSIX word expansion
+//!
+//! A word is expanded in the order POSIX lays down: tilde expansion;
+//! parameter expansion, command substitution and arithmetic expansion,
+//! which happen left to right in one pass; field splitting; pathname
+//! expansion; and quote removal. `parse_word` already removes the quotes,
+//! so quoting survives only as a flag on each piece of text, which is what
+//! decides whether it is split and globbed.
+
+use crate::Interpreter;
+use shex_ast::{ShexError, Span};
+use shex_parser::variable_resolver::{ExpansionMode, VariableContext};
+use shex_parser::word::{QuoteKind, WordPart, parse_word};
+use std::path::Path;
+
+/// A character of an expanded field and whether it may act as a pattern
+/// character; quoted characters only match themselves
+type PatternChar = (char, bool);
+
+/// Field separators used when `IFS` is unset
+const DEFAULT_IFS: &str = " \t\n";
+
+/// Which of the optional expansion steps `expand_word` performs
+///
+/// Parameter expansion, command substitution, arithmetic expansion and
+/// quote removal always happen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WordExpansionOptions {
+    /// Replace a leading `~` with `$HOME`, `~+` with `$PWD` and `~-` with `$OLDPWD`
+    pub tilde: bool,
+    /// Split the results of unquoted expansions on the characters of `$IFS`
+    pub field_splitting: bool,
+    /// Replace unquoted patterns such as `*.rs` with the matching paths
+    pub pathname: bool,
+}
+
+impl Default for WordExpansionOptions {
+    fn default() -> Self {
+        Self {
+            tilde: true,
+            field_splitting: true,
+            pathname: true,
+        }
+    }
+}
+
+impl WordExpansionOptions {
+    /// Expansion that always yields exactly one field
+    ///
+    /// Used where the shell expects a single word: assignment values,
+    /// redirection targets, case subjects and `[[ ]]` operands
+    #[must_use]
+    pub const fn single_word() -> Self {
+        Self {
+            tilde: true,
+            field_splitting: false,
+            pathname: false,
+        }
+    }
+}
+
+/// Expand `word` against `context`, returning the resulting fields
+///
+/// Command substitutions run in a fresh interpreter that sees only the
+/// variables in `context`, not the functions of any running shell.
+///
+/// # Errors
+///
+/// Returns `ShexError` if an expansion fails, for example when a variable
+/// is unset or a command substitution does not parse
+pub fn expand_word(
+    word: &str,
+    context: &mut VariableContext,
+    opts: &WordExpansionOptions,
+) -> Result<Vec<String>, ShexError> {
+    let mut interpreter = Interpreter::new();
+    std::mem::swap(&mut interpreter.variable_context, context);
+    let result = interpreter.expand_word(word, Span::dummy(), opts);
+    std::mem::swap(&mut interpreter.variable_context, context);
+    result
+}
+
+/// Text produced by one part of a word, before field splitting
+struct Piece {
+    text: String,
+    /// Came from quoted text, so it is neither split nor globbed
+    quoted: bool,
+    /// Came from an unquoted expansion, so it is subject to field splitting
+    splittable: bool,
+    /// Ends the field it is part of, as each parameter of `"$@"` does
+    ends_field: bool,
+}
+
+/// A field being built
+#[derive(Default)]
+struct Field {
+    chars: Vec<PatternChar>,
+    /// Contains quoted text, so it survives even when empty
+    quoted: bool,
+}
+
+impl Field {
+    fn push_str(&mut self, text: &str, active: bool) {
+        self.chars.extend(text.chars().map(|c| (c, active)));
+    }
+
+    fn is_started(&self) -> bool {
+        self.quoted || !self.chars.is_empty()
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().map(|(c, _)| c).collect()
+    }
+}
+
+impl Interpreter {
+    /// Run every expansion step on `word`, returning the resulting fields
+    pub(crate) fn expand_word(
+        &mut self,
+        word: &str,
+        span: Span,
+        opts: &WordExpansionOptions,
+    ) -> Result<Vec<String>, ShexError> {
+        let pieces = self.expand_pieces(word, span, opts)?;
+
+        let fields = if opts.field_splitting {
+            let ifs = self
+                .variable_context
+                .get("IFS")
+                .map_or_else(|| DEFAULT_IFS.to_string(), str::to_string);
+            split_fields(pieces, &ifs)
+        } else {
+            vec![join_pieces(&pieces)]
+        };
+
+        let mut expanded = Vec::with_capacity(fields.len());
+        for field in fields {
+            let matches = if opts.pathname && has_pattern(&field.chars) {
+                expand_pathname(&field.chars, &self.cwd)
+            } else {
+                Vec::new()
+            };
+            if matches.is_empty() {
+                expanded.push(field.text());
+            } else {
+                expanded.extend(matches);
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Whether `text` matches the `case` pattern `pattern`
+    ///
+    /// The pattern is expanded as a single word first. `*`, `?` and `[...]`
+    /// act as pattern characters only where they were not quoted, so
+    /// `"*"` matches just a `*`.
+    pub(crate) fn case_pattern_matches(
+        &mut self,
+        pattern: &str,
+        text: &str,
+        span: Span,
+    ) -> Result<bool, ShexError> {
+        let pieces = self.expand_pieces(pattern, span, &WordExpansionOptions::single_word())?;
+        let text: Vec<char> = text.chars().collect();
+        Ok(pattern_matches(&join_pieces(&pieces).chars, &text))
+    }
+
+    /// Run tilde, parameter, command and arithmetic expansion on `word`
+    fn expand_pieces(
+        &mut self,
+        word: &str,
+        span: Span,
+        opts: &WordExpansionOptions,
+    ) -> Result<Vec<Piece>, ShexError> {
+        let mut parts = parse_word(word);
+        let mut pieces = Vec::with_capacity(parts.len() + 1);
+
+        if opts.tilde {
+            pieces.extend(self.expand_tilde(&mut parts));
+        }
+        for part in parts {
+            if is_quoted_at(&part) {
+                pieces.extend(self.expand_quoted_at());
+            } else {
+                pieces.push(self.expand_part(part, span)?);
+            }
+        }
+        Ok(pieces)
+    }
+
+    /// Replace a tilde prefix at the start of the word
+    ///
+    /// The prefix runs up to the first `/` of the leading unquoted literal.
+    /// Unknown forms such as `~user` are left alone.
+    fn expand_tilde(&self, parts: &mut [WordPart]) -> Option<Piece> {
+        let Some(WordPart::Literal {
+            text,
+            quote: QuoteKind::Unquoted,
+        }) = parts.first_mut()
+        else {
+            return None;
+        };
+        let prefix_len = text.find('/').unwrap_or(text.len());
+        let variable = match text.get(..prefix_len)? {
+            "~" => "HOME",
+            "~+" => "PWD",
+            "~-" => "OLDPWD",
+            _ => return None,
+        };
+        let value = self.variable_context.get(variable)?.to_string();
+        text.replace_range(..prefix_len, "");
+        Some(Piece {
+            text: value,
+            quoted: true,
+            splittable: false,
+            ends_field: false,
+        })
+    }
+
+    /// Expand `"$@"` to one quoted field per positional parameter
+    ///
+    /// Text before and after it joins the first and last fields; with no
+    /// parameters it produces nothing at all
+    fn expand_quoted_at(&self) -> Vec<Piece> {
+        let params = self.variable_context.positional_params();
+        params
+            .iter()
+            .enumerate()
+            .map(|(index, param)| Piece {
+                text: param.clone(),
+                quoted: true,
+                splittable: false,
+                ends_field: index + 1 < params.len(),
+            })
+            .collect()
+    }
+
+    /// Expand one part of a word to its text
+    fn expand_part(&mut self, part: WordPart, span: Span) -> Result<Piece, ShexError> {
+        let quoted = part.is_quoted();
+        let (text, expansion) = match part {
+            WordPart::Literal { text, .. } => (text, false),
+            WordPart::Parameter { request, .. } => (self.expand_parameter(&request, span)?, true),
+            WordPart::CommandSubstitution { command, .. } => {
+                (self.expand_command_substitution(&command)?, true)
+            }
+            WordPart::Arithmetic { expression, .. } => {
+                (self.expand_arithmetic(&expression, span)?, true)
+            }
+        };
+        Ok(Piece {
+            text,
+            quoted,
+            splittable: expansion && !quoted,
+            ends_field: false,
+        })
+    }
+}
+
+/// Whether a word part is `"$@"`, the one expansion that makes several
+/// fields even inside double quotes
+fn is_quoted_at(part: &WordPart) -> bool {
+    matches!(part, WordPart::Parameter { request, quote: QuoteKind::Double }
+        if request.variable_name == "@" && request.mode == ExpansionMode::Normal)
+}
+
+/// Join the pieces of a word into one field without splitting it
+///
+/// Each parameter of `"$@"` is followed by a space, as when the word is
+/// used as a single string
+fn join_pieces(pieces: &[Piece]) -> Field {
+    let mut field = Field {
+        quoted: true,
+        ..Field::default()
+    };
+    for piece in pieces {
+        field.push_str(&piece.text, !piece.quoted);
+        if piece.ends_field {
+            field.push_str(" ", false);
+        }
+    }
+    field
+}
+
+/// Split the pieces of a word into fields on the characters of `ifs`
+///
+/// Only unquoted expansion results are split. Runs of IFS whitespace
+/// separate fields; each other IFS character ends a field, so `a::b` with
+/// `IFS=:` gives an empty middle field. Fields left empty by an unquoted
+/// expansion are removed.
+fn split_fields(pieces: Vec<Piece>, ifs: &str) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let mut current = Field::default();
+    // Whether the current field was just ended by IFS whitespace
+    let mut after_whitespace = false;
+
+    for piece in pieces {
+        if !piece.splittable || ifs.is_empty() {
+            current.push_str(&piece.text, !piece.quoted);
+            current.quoted |= piece.quoted;
+            after_whitespace = false;
+            if piece.ends_field {
+                fields.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        for c in piece.text.chars() {
+            if !ifs.contains(c) {
+                current.chars.push((c, true));
+                after_whitespace = false;
+            } else if c.is_whitespace() {
+                if current.is_started() {
+                    fields.push(std::mem::take(&mut current));
+                    after_whitespace = true;
+                }
+            } else {
+                if !after_whitespace {
+                    fields.push(std::mem::take(&mut current));
+                }
+                after_whitespace = false;
+            }
+        }
+    }
+    if current.is_started() {
+        fields.push(current);
+    }
+    fields
+}
+
+/// Whether a field contains an unquoted `*`, `?` or `[`
+fn has_pattern(chars: &[PatternChar]) -> bool {
+    chars
+        .iter()
+        .any(|(c, active)| *active && matches!(c, '*' | '?' | '['))
+}
+
+/// Paths matching a pattern, sorted; empty when nothing matches
+///
+/// Each `/`-separated component is matched against the entries of the
+/// directories found so far. Names starting with `.` only match a pattern
+/// component that starts with a literal `.`. Relative patterns are looked
+/// up under `cwd` and stay relative.
+fn expand_pathname(pattern: &[PatternChar], cwd: &Path) -> Vec<String> {
+    let absolute = matches!(pattern.first(), Some(('/', _)));
+    let directories_only = matches!(pattern.last(), Some(('/', _)));
+    let components: Vec<&[PatternChar]> = pattern
+        .split(|(c, _)| *c == '/')
+        .filter(|component| !component.is_empty())
+        .collect();
+
+    let mut paths = vec![if absolute {
+        "/".to_string()
+    } else {
+        String::new()
+    }];
+    for component in components {
+        let mut next = Vec::new();
+        for base in &paths {
+            if !has_pattern(component) {
+                let name: String = component.iter().map(|(c, _)| c).collect();
+                next.push(join_path(base, &name));
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(cwd.join(base)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if name.starts_with('.') && !matches!(component.first(), Some(('.', _))) {
+                    continue;
+                }
+                let name_chars: Vec<char> = name.chars().collect();
+                if pattern_matches(component, &name_chars) {
+                    next.push(join_path(base, &name));
+                }
+            }
+        }
+        paths = next;
+    }
+
+    paths.retain(|path| {
+        let path = cwd.join(path);
+        if directories_only {
+            path.is_dir()
+        } else {
+            path.symlink_metadata().is_ok()
+        }
+    });
+    if directories_only {
+        for path in &mut paths {
+            path.push('/');
+        }
+    }
+    paths.sort();
+    paths
+}
+
+fn join_path(base: &str, name: &str) -> String {
+    if base.is_empty() {
+        name.to_string()
+    } else if base.ends_with('/') {
+        format!("{base}{name}")
+    } else {
+        format!("{base}/{name}")
+    }
+}
+
+/// Match `text` against a pattern of `*`, `?` and `[...]` expressions
+///
+/// Pattern characters flagged inactive, because they were quoted, only
+/// match themselves. Only the most recent `*` is retried on a mismatch,
+/// which is enough since each later `*` can absorb whatever an earlier
+/// one would have, so matching takes O(n·m) time.
+fn pattern_matches(pattern: &[PatternChar], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Pattern position after the last `*` and the text position it resumes at
+    let mut star = None;
+    while t < text.len() {
+        if pattern.get(p) == Some(&('*', true)) {
+            p += 1;
+            star = Some((p, t));
+        } else if let Some(next) = pattern_step(pattern, p, text[t]) {
+            p = next;
+            t += 1;
+        } else if let Some((after_star, resume)) = star {
+            // Let the `*` take one more character and try again
+            p = after_star;
+            t = resume + 1;
+            star = Some((after_star, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&element| element == ('*', true))
+}
+
+/// Match `c` against the single-character pattern element at `p`,
+/// returning the position of the next element
+fn pattern_step(pattern: &[PatternChar], p: usize, c: char) -> Option<usize> {
+    let &(expected, active) = pattern.get(p)?;
+    match expected {
+        '?' if active => Some(p + 1),
+        '[' if active => match bracket_expression(&pattern[p + 1..]) {
+            Some((set, after)) => bracket_matches(set, c).then(|| pattern.len() - after.len()),
+            // An unterminated bracket is an ordinary character
+            None => (c == '[').then_some(p + 1),
+        },
+        _ => (c == expected).then_some(p + 1),
+    }
+}
+
+/// Split a bracket expression off the pattern following its `[`
+///
+/// Returns the characters between the brackets and the rest of the
+/// pattern, or None if there is no closing `]`. A `]` right after the
+/// opening bracket or its negation is part of the set, as is any `]`
+/// inside a class such as `[:alpha:]`.
+fn bracket_expression(pattern: &[PatternChar]) -> Option<(&[PatternChar], &[PatternChar])> {
+    let start = usize::from(matches!(pattern.first(), Some(('!' | '^', true))));
+    let mut i = start;
+    while i < pattern.len() {
+        match pattern[i].0 {
+            '[' => i += class_name(&pattern[i..]).map_or(1, |(_, length)| length),
+            ']' if i > start => return Some((&pattern[..i], &pattern[i + 1..])),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// The name and length of a character class such as `[:alpha:]` at the
+/// start of `set`
+fn class_name(set: &[PatternChar]) -> Option<(String, usize)> {
+    if !matches!(set, [('[', _), (':', _), ..]) {
+        return None;
+    }
+    let end = set[2..]
+        .windows(2)
+        .position(|pair| pair[0].0 == ':' && pair[1].0 == ']')?;
+    let name = set[2..2 + end].iter().map(|(c, _)| c).collect();
+    Some((name, end + 4))
+}
+
+/// Whether `c` belongs to the POSIX character class `name`
+fn class_matches(name: &str, c: char) -> bool {
+    match name {
+        "alnum" => c.is_alphanumeric(),
+        "alpha" => c.is_alphabetic(),
+        "blank" => c == ' ' || c == '\t',
+        "cntrl" => c.is_control(),
+        "digit" => c.is_ascii_digit(),
+        "graph" => !c.is_control() && !c.is_whitespace(),
+        "lower" => c.is_lowercase(),
+        "print" => !c.is_control(),
+        "punct" => c.is_ascii_punctuation(),
+        "space" => c.is_whitespace(),
+        "upper" => c.is_uppercase(),
+        "xdigit" => c.is_ascii_hexdigit(),
+        _ => false,
+    }
+}
+
+/// Whether `c` is in the set of a bracket expression such as `!a-z_`
+fn bracket_matches(set: &[PatternChar], c: char) -> bool {
+    let (negated, set) = match set.split_first() {
+        Some((('!' | '^', true), rest)) => (true, rest),
+        _ => (false, set),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < set.len() {
+        let low = set[i].0;
+        if let Some((name, length)) = class_name(&set[i..]) {
+            found |= class_matches(&name, c);
+            i += length;
+        } else if let (Some(('-', true)), Some(&(high, _))) = (set.get(i + 1), set.get(i + 2)) {
+            found |= (low..=high).contains(&c);
+            i += 3;
+        } else {
+            found |= low == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(interpreter: &mut Interpreter, word: &str) -> Vec<String> {
+        interpreter
+            .expand_word(word, Span::dummy(), &WordExpansionOptions::default())
+            .unwrap()
+    }
+
+    fn active(pattern: &str) -> Vec<PatternChar> {
+        pattern.chars().map(|c| (c, true)).collect()
+    }
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        pattern_matches(&active(pattern), &text.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_tilde_expansion() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .variable_context
+            .set("HOME".to_string(), "/home/me".to_string());
+
+        assert_eq!(expand(&mut interpreter, "~"), ["/home/me"]);
+        assert_eq!(expand(&mut interpreter, "~/src"), ["/home/me/src"]);
+        assert_eq!(expand(&mut interpreter, "'~'/src"), ["~/src"]);
+        assert_eq!(expand(&mut interpreter, "a~"), ["a~"]);
+        assert_eq!(expand(&mut interpreter, "~nobody_here"), ["~nobody_here"]);
+    }
+
+    #[test]
+    fn test_parameter_command_and_arithmetic() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .variable_context
+            .set("n".to_string(), "4".to_string());
+
+        assert_eq!(
+            expand(&mut interpreter, "\"$n-$(echo x)-$((n*2))\""),
+            ["4-x-8"]
+        );
+    }
+
+    #[test]
+    fn test_positional_parameter_fields() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .variable_context
+            .set_positional_params(vec!["hello world".to_string(), "foo".to_string()]);
+
+        assert_eq!(expand(&mut interpreter, "\"$@\""), ["hello world", "foo"]);
+        assert_eq!(expand(&mut interpreter, "$@"), ["hello", "world", "foo"]);
+        assert_eq!(expand(&mut interpreter, "\"<$@>\""), ["<hello world", "foo>"]);
+        assert_eq!(expand(&mut interpreter, "\"$*\""), ["hello world foo"]);
+        let single = WordExpansionOptions::single_word();
+        assert_eq!(
+            interpreter.expand_word("\"$@\"", Span::dummy(), &single).unwrap(),
+            ["hello world foo"]
+        );
+
+        interpreter.variable_context.set_positional_params(vec![]);
+        assert!(expand(&mut interpreter, "\"$@\"").is_empty());
+    }
+
+    #[test]
+    fn test_field_splitting() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .variable_context
+            .set("x".to_string(), "  a b\tc ".to_string());
+        interpreter
+            .variable_context
+            .set("empty".to_string(), String::new());
+
+        assert_eq!(expand(&mut interpreter, "$x"), ["a", "b", "c"]);
+        assert_eq!(
+            expand(&mut interpreter, "pre${x}post"),
+            ["pre", "a", "b", "c", "post"]
+        );
+        assert_eq!(expand(&mut interpreter, "\"$x\""), ["  a b\tc "]);
+        assert!(expand(&mut interpreter, "$empty").is_empty());
+        assert_eq!(expand(&mut interpreter, "\"$empty\""), [""]);
+
+        interpreter
+            .variable_context
+            .set("IFS".to_string(), ":".to_string());
+        interpreter
+            .variable_context
+            .set("path".to_string(), "a::b c".to_string());
+        assert_eq!(expand(&mut interpreter, "$path"), ["a", "", "b c"]);
+    }
+
+    #[test]
+    fn test_single_word_does_not_split() {
+        let mut interpreter = Interpreter::new();
+        interpreter
+            .variable_context
+            .set("x".to_string(), "a b *".to_string());
+
+        let fields = interpreter
+            .expand_word("$x", Span::dummy(), &WordExpansionOptions::single_word())
+            .unwrap();
+        assert_eq!(fields, ["a b *"]);
+    }
+
+    #[test]
+    fn test_pattern_matching() {
+        assert!(matches("*.rs", "lib.rs"));
+        assert!(!matches("*.rs", "lib.rsx"));
+        assert!(matches("?at", "cat"));
+        assert!(!matches("?at", "at"));
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[!a-c]x", "bx"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[x", "[x"));
+        assert!(!pattern_matches(&[('*', false)], &['a']));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(matches("*", ""));
+        assert!(matches("**x*", "abx"));
+        assert!(!matches("a*", ""));
+    }
+
+    #[test]
+    fn test_many_stars_fail_quickly() {
+        let text = format!("{}c", "a".repeat(200));
+        let start = std::time::Instant::now();
+        assert!(!matches(&format!("{}b", "*a".repeat(20)), &text));
+        assert!(matches(&format!("{}*c", "*a".repeat(20)), &text));
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_posix_bracket_expressions() {
+        assert!(matches("[[:alpha:]]*", "abc"));
+        assert!(!matches("[[:alpha:]]*", "1bc"));
+        assert!(matches("[[:digit:][:upper:]]", "7"));
+        assert!(matches("[[:digit:][:upper:]]", "Q"));
+        assert!(!matches("[![:space:]]", " "));
+        assert!(matches("[a-]", "-"));
+        assert!(matches("[]a]", "a"));
+        assert!(matches("[!]]", "x"));
+        assert!(!matches("[[:bogus:]]", "b"));
+    }
+
+    #[test]
+    fn test_case_patterns_respect_quoting() {
+        let mut interpreter = Interpreter::new();
+        let mut case_matches = |pattern: &str, text: &str| {
+            interpreter
+                .case_pattern_matches(pattern, text, Span::dummy())
+                .unwrap()
+        };
+        assert!(case_matches(r"\*", "*"));
+        assert!(!case_matches(r"\*", "x"));
+        assert!(case_matches(r"a\?*", "a?b"));
+        assert!(!case_matches(r"a\?*", "ab"));
+        assert!(case_matches("'[ab]'", "[ab]"));
+        assert!(case_matches("\"*\"[[:digit:]]", "*5"));
+    }
+
+    #[test]
+    fn test_pathname_expansion() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.txt", "a.txt", "c.rs", ".hidden.txt"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let base = dir.path().display().to_string();
+
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            expand(&mut interpreter, &format!("{base}/*.txt")),
+            [format!("{base}/a.txt"), format!("{base}/b.txt")]
+        );
+        assert_eq!(
+            expand(&mut interpreter, &format!("{base}/*/")),
+            [format!("{base}/sub/")]
+        );
+        assert_eq!(
+            expand(&mut interpreter, &format!("{base}/.*.txt")),
+            [format!("{base}/.hidden.txt")]
+        );
+        // Quoted patterns and patterns with no match stay as they are
+        assert_eq!(
+            expand(&mut interpreter, &format!("\"{base}/*.txt\"")),
+            [format!("{base}/*.txt")]
+        );
+        assert_eq!(
+            expand(&mut interpreter, &format!("{base}/*.md")),
+            [format!("{base}/*.md")]
+        );
+    }
+
+    #[test]
+    fn test_expand_word_with_context() {
+        let mut context = VariableContext::new();
+        context.set("words".to_string(), "one two".to_string());
+
+        let fields = expand_word("$words", &mut context, &WordExpansionOptions::default()).unwrap();
+        assert_eq!(fields, ["one", "two"]);
+        assert_eq!(context.get("words"), Some("one two"));
+    }
+}
@@ -1,20 +1,56 @@
 //! Lexical analysis for Shex shell
 //!
 //! Implements POSIX shell tokenization plus Shex extensions using logos.
+//!
+//! `Token` is `#[non_exhaustive]`, so matches on it from other crates need
+//! a wildcard arm.
 
 use logos::Logos;
 use shex_ast::Span;
+use std::collections::VecDeque;
+
+/// What the lexer is reading, which decides how the next characters lex
+///
+/// Quoted strings and arithmetic expansions are scanned by their token
+/// callbacks, so those modes only last while one token is read. A
+/// here-document body spans the lines after the command, which the `Lexer`
+/// returns one `Token::HereDocLine` at a time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum LexerMode {
+    /// Ordinary shell syntax
+    #[default]
+    Normal,
+    /// Inside `"..."`, where only `\`, `$` and `` ` `` are special
+    DoubleQuote,
+    /// Lines of a here-document up to the line holding only `delimiter`
+    HereDoc {
+        delimiter: String,
+        /// `<<-` strips leading tabs, including before the delimiter
+        strip_tabs: bool,
+    },
+    /// Inside `$(( ))`, where `*` and `(` are arithmetic, not redirections
+    /// or subshells
+    Arithmetic,
+}
 
 /// Shell tokens - Complete POSIX token set
 #[derive(Logos, Debug, PartialEq, Eq, Clone)]
+#[logos(extras = LexerMode)]
+#[non_exhaustive]
 pub enum Token {
     // POSIX Basic Tokens
-    /// Assignment word (var=value) - must come before Word to take precedence  
-    #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*=[^\s]*", priority = 2)]
+    /// Assignment word (var=value) - must come before Word to take precedence
+    /// The value stops at whitespace, quotes, backticks, `$` and shell
+    /// operators so `x=1; cmd` splits and a quoted value or an expansion
+    /// such as `$(cmd)` lexes as a separate, adjacent token. An array
+    /// assignment such as `arr=(a b c)` is one token up to its `)`
+    #[regex(r#"[a-zA-Z_][a-zA-Z0-9_]*=([^\s;&|<>()'"`$]*|\([^)]*\))"#, priority = 2)]
     AssignmentWord,
 
     /// A word token (shell words, can contain various characters including paths)
-    #[regex(r"[a-zA-Z_/][a-zA-Z0-9_./-]*")]
+    /// `:` may appear anywhere, so the `:` builtin and `a:b` are words, and
+    /// so may the pattern characters `*` and `?`, as in `*.txt`
+    #[regex(r"([a-zA-Z_/:*?~]|\\[^\n])([a-zA-Z0-9_./:*?~-]|\\[^\n])*")]
     Word,
 
     /// Special single character tokens
@@ -34,8 +70,11 @@ pub enum Token {
     #[regex(r"[0-9]+")]
     Number,
 
+    /// File descriptor number directly before a redirection operator (`2>`)
+    IoNumber,
+
     /// String literal with quotes
-    #[regex(r#""([^"\\]|\\.)*""#)]
+    #[token("\"", lex_double_quote)]
     #[regex(r#"'([^'\\]|\\.)*'"#)]
     String,
 
@@ -43,6 +82,9 @@ pub enum Token {
     #[token("\n")]
     Newline,
 
+    /// One line of a here-document body, without its newline
+    HereDocLine,
+
     // POSIX Multi-character Operators
     /// Logical AND operator (&&)
     #[token("&&")]
@@ -80,6 +122,10 @@ pub enum Token {
     #[token("<<-")]
     Dlessdash,
 
+    /// Here-string (<<<)
+    #[token("<<<")]
+    Tless,
+
     /// Force redirection override (>|)
     #[token(">|")]
     Clobber,
@@ -137,6 +183,14 @@ pub enum Token {
     #[token("in")]
     In,
 
+    /// coproc keyword (bash extension)
+    #[token("coproc")]
+    Coproc,
+
+    /// function keyword (bash extension)
+    #[token("function")]
+    Function,
+
     /// Left brace ({)
     #[token("{")]
     Lbrace,
@@ -184,11 +238,42 @@ pub enum Token {
     #[regex(r"\$\{[^}]+\}", priority = 3)]
     ParameterExpansion,
 
-    /// Simple parameter expansion: $var
+    /// Simple parameter expansion: $var, or one of $@, $* and $#
     /// Must come after `ParameterExpansion` to avoid conflicts
-    #[regex(r"\$[a-zA-Z_][a-zA-Z0-9_]*", priority = 2)]
+    #[regex(r"\$([a-zA-Z_][a-zA-Z0-9_]*|[@*#])", priority = 2)]
     SimpleParameterExpansion,
 
+    /// Single-digit positional parameter: $0 to $9
+    #[regex(r"\$[0-9]", priority = 4)]
+    PositionalParam,
+
+    /// Special parameter maintained by the shell: $! (last background PID),
+    /// $? (last exit status), $$ (shell PID) or $- (option flags)
+    #[regex(r"\$[!?$-]", priority = 4)]
+    SpecialParam,
+
+    /// A `$` that starts no expansion and stands for itself
+    #[token("$")]
+    Dollar,
+
+    /// Arithmetic expansion: $(( expr )), including any nested parentheses
+    #[token("$((", lex_arithmetic)]
+    ArithmeticExpansion,
+
+    /// Backtick command substitution: `cmd`, where a backslash escapes the
+    /// next character, including a backtick; `$(cmd)` is preferred
+    #[regex(r"`([^`\\]|\\.)*`", priority = 3)]
+    BacktickExpansion,
+
+    /// Command substitution: $(cmd), including nested parentheses and
+    /// quoted text
+    #[token("$(", lex_command_substitution)]
+    CommandSubstitution,
+
+    /// Comment running to the end of the line, including the leading `#`
+    #[regex(r"#[^\n]*")]
+    Comment,
+
     /// Whitespace (ignored)
     #[regex(r"[ \t\f]+", logos::skip)]
     Whitespace,
@@ -200,6 +285,245 @@ pub enum Token {
     Error,
 }
 
+impl Token {
+    /// Control and redirection operators such as `&&`, `|`, `;` and `>>`
+    #[must_use]
+    pub const fn is_operator(&self) -> bool {
+        matches!(
+            self,
+            Self::AndIf | Self::OrIf | Self::Dsemi | Self::Pipe | Self::Semicolon | Self::Ampersand
+        ) || self.is_redirection()
+    }
+
+    /// Redirection operators, which may be prefixed by an IO_NUMBER
+    #[must_use]
+    pub const fn is_redirection(&self) -> bool {
+        matches!(
+            self,
+            Self::Less
+                | Self::Great
+                | Self::Dless
+                | Self::Dgreat
+                | Self::Lessand
+                | Self::Greatand
+                | Self::Lessgreat
+                | Self::Dlessdash
+                | Self::Tless
+                | Self::Clobber
+        )
+    }
+
+    /// Reserved words, including `{`, `}` and `!`
+    #[must_use]
+    pub const fn is_keyword(&self) -> bool {
+        matches!(
+            self,
+            Self::If
+                | Self::Then
+                | Self::Else
+                | Self::Elif
+                | Self::Fi
+                | Self::Do
+                | Self::Done
+                | Self::Case
+                | Self::Esac
+                | Self::While
+                | Self::Until
+                | Self::For
+                | Self::In
+                | Self::Coproc
+                | Self::Function
+                | Self::Lbrace
+                | Self::Rbrace
+                | Self::Bang
+        )
+    }
+
+    /// Human-readable name for error messages
+    ///
+    /// Operators and reserved words give their source text; other tokens
+    /// give a description of what they stand for.
+    ///
+    /// ```
+    /// use shex_lexer::Token;
+    ///
+    /// let message = format!("expected '{}', found '{}'", Token::Then.lexeme(), Token::AndIf.lexeme());
+    /// assert_eq!(message, "expected 'then', found '&&'");
+    /// ```
+    #[must_use]
+    pub const fn lexeme(&self) -> &'static str {
+        match self {
+            Self::AssignmentWord => "assignment",
+            Self::Word => "word",
+            Self::LeftBracket => "[",
+            Self::RightBracket => "]",
+            Self::Dash => "-",
+            Self::Dot => ".",
+            Self::Number => "number",
+            Self::IoNumber => "file descriptor",
+            Self::String => "string",
+            Self::Newline => "newline",
+            Self::HereDocLine => "here-document line",
+            Self::AndIf => "&&",
+            Self::OrIf => "||",
+            Self::Dsemi => ";;",
+            Self::Dless => "<<",
+            Self::Dgreat => ">>",
+            Self::Lessand => "<&",
+            Self::Greatand => ">&",
+            Self::Lessgreat => "<>",
+            Self::Dlessdash => "<<-",
+            Self::Tless => "<<<",
+            Self::Clobber => ">|",
+            Self::If => "if",
+            Self::Then => "then",
+            Self::Else => "else",
+            Self::Elif => "elif",
+            Self::Fi => "fi",
+            Self::Do => "do",
+            Self::Done => "done",
+            Self::Case => "case",
+            Self::Esac => "esac",
+            Self::While => "while",
+            Self::Until => "until",
+            Self::For => "for",
+            Self::In => "in",
+            Self::Coproc => "coproc",
+            Self::Function => "function",
+            Self::Lbrace => "{",
+            Self::Rbrace => "}",
+            Self::Bang => "!",
+            Self::Pipe => "|",
+            Self::Semicolon => ";",
+            Self::Ampersand => "&",
+            Self::Less => "<",
+            Self::Great => ">",
+            Self::Lparen => "(",
+            Self::Rparen => ")",
+            Self::ParameterExpansion
+            | Self::SimpleParameterExpansion
+            | Self::PositionalParam
+            | Self::SpecialParam => "parameter expansion",
+            Self::Dollar => "$",
+            Self::ArithmeticExpansion => "arithmetic expansion",
+            Self::BacktickExpansion | Self::CommandSubstitution => "command substitution",
+            Self::Comment => "comment",
+            Self::Whitespace => "whitespace",
+            Self::Eof => "end of input",
+            Self::Error => "invalid character",
+        }
+    }
+
+    /// Tokens that stand for a shell word or part of one
+    #[must_use]
+    pub const fn is_word_like(&self) -> bool {
+        matches!(
+            self,
+            Self::Word
+                | Self::AssignmentWord
+                | Self::String
+                | Self::SimpleParameterExpansion
+                | Self::PositionalParam
+                | Self::SpecialParam
+                | Self::Dollar
+                | Self::ParameterExpansion
+                | Self::ArithmeticExpansion
+                | Self::BacktickExpansion
+                | Self::CommandSubstitution
+                | Self::Number
+        )
+    }
+}
+
+/// Extend a `$((` match to the `))` that closes it
+///
+/// Parentheses are counted so grouping inside the expression, as in
+/// `$(( (2+3) * 4 ))`, does not end the token early. Returns false for an
+/// unterminated expansion, which logos reports as an error token.
+fn lex_arithmetic(lex: &mut logos::Lexer<Token>) -> bool {
+    lex.extras = LexerMode::Arithmetic;
+    // `$((` opens two levels
+    let mut depth = 2usize;
+    let mut end = None;
+    for (index, byte) in lex.remainder().bytes().enumerate() {
+        match byte {
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(index + 1);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    lex.extras = LexerMode::Normal;
+    end.map(|end| lex.bump(end)).is_some()
+}
+
+/// Extend a `$(` match to the `)` that closes it
+fn lex_command_substitution(lex: &mut logos::Lexer<Token>) -> bool {
+    command_substitution_end(lex.remainder().as_bytes())
+        .map(|end| lex.bump(end))
+        .is_some()
+}
+
+/// Extend an opening `"` to the quote that closes it
+///
+/// A backslash escapes the next character, and a `$( )` inside the string
+/// is skipped as a whole so quotes within the command, as in
+/// `"$(echo ")")"`, do not end the string. Returns false for an
+/// unterminated string.
+fn lex_double_quote(lex: &mut logos::Lexer<Token>) -> bool {
+    lex.extras = LexerMode::DoubleQuote;
+    let end = double_quote_end(lex.remainder().as_bytes());
+    lex.extras = LexerMode::Normal;
+    end.map(|end| lex.bump(end)).is_some()
+}
+
+/// Length of a double-quoted string body including the closing quote
+fn double_quote_end(bytes: &[u8]) -> Option<usize> {
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 1,
+            b'"' => return Some(index + 1),
+            b'$' if bytes.get(index + 1) == Some(&b'(') => {
+                index += command_substitution_end(&bytes[index + 2..])? + 1;
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    None
+}
+
+/// Length of a `$( )` body including the closing parenthesis
+fn command_substitution_end(bytes: &[u8]) -> Option<usize> {
+    let mut depth = 1usize;
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 1,
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index + 1);
+                }
+            }
+            b'"' => index += double_quote_end(&bytes[index + 1..])?,
+            b'\'' => {
+                index += bytes[index + 1..].iter().position(|&byte| byte == b'\'')? + 1;
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    None
+}
+
 /// Token with location information
 #[derive(Debug, Clone)]
 pub struct SpannedToken {
@@ -208,10 +532,55 @@ pub struct SpannedToken {
     pub text: String,
 }
 
+impl SpannedToken {
+    /// Location of the token in the source
+    ///
+    /// ```
+    /// use shex_lexer::Lexer;
+    ///
+    /// let tokens = Lexer::new("echo && ls").tokenize();
+    /// let op = &tokens[1];
+    /// let message = format!("unexpected '{}' at {}..{}", op.token.lexeme(), op.start(), op.end());
+    /// assert_eq!(message, "unexpected '&&' at 5..7");
+    /// assert_eq!(op.span(), shex_ast::Span::new(5, 7));
+    /// ```
+    #[must_use]
+    pub const fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Byte offset where the token starts
+    #[must_use]
+    pub const fn start(&self) -> usize {
+        self.span.start
+    }
+
+    /// Byte offset just past the end of the token
+    #[must_use]
+    pub const fn end(&self) -> usize {
+        self.span.end
+    }
+}
+
 /// Lexer that produces tokens with spans
+///
+/// A here-document body is lexed after the newline that ends its command,
+/// as `HereDocLine` tokens up to the delimiter line. `tokenize` moves those
+/// lines to just after the delimiter word, where the parser expects them.
 pub struct Lexer<'input> {
     lexer: logos::Lexer<'input, Token>,
     input: &'input str,
+    /// Token read ahead by `peek`, returned by the next `next_token`
+    peeked: Option<SpannedToken>,
+    /// Set after `<<` or `<<-`, until the delimiter word is read; true for
+    /// `<<-`
+    awaiting_delimiter: Option<bool>,
+    /// Here-documents whose bodies start after the next newline
+    pending_here_docs: VecDeque<LexerMode>,
+    /// Delimiter words seen so far
+    here_docs_started: usize,
+    /// Here-documents whose delimiter line has been read
+    here_docs_finished: usize,
 }
 
 impl<'input> Lexer<'input> {
@@ -220,11 +589,121 @@ impl<'input> Lexer<'input> {
         Self {
             lexer: Token::lexer(input),
             input,
+            peeked: None,
+            awaiting_delimiter: None,
+            pending_here_docs: VecDeque::new(),
+            here_docs_started: 0,
+            here_docs_finished: 0,
         }
     }
 
+    /// What the lexer is reading at its current position
+    ///
+    /// ```
+    /// use shex_lexer::{Lexer, LexerMode, Token};
+    ///
+    /// let mut lexer = Lexer::new("cat <<EOF\nhello\n");
+    /// while lexer.next_token().token != Token::Newline {}
+    /// assert_eq!(
+    ///     lexer.mode(),
+    ///     &LexerMode::HereDoc { delimiter: "EOF".to_string(), strip_tabs: false }
+    /// );
+    /// ```
+    #[must_use]
+    pub const fn mode(&self) -> &LexerMode {
+        &self.lexer.extras
+    }
+
     /// Get the next token with span information
     pub fn next_token(&mut self) -> SpannedToken {
+        self.peeked.take().unwrap_or_else(|| self.lex_token())
+    }
+
+    /// Look at the next token without consuming it
+    pub fn peek(&mut self) -> &SpannedToken {
+        let token = match self.peeked.take() {
+            Some(token) => token,
+            None => self.lex_token(),
+        };
+        self.peeked.insert(token)
+    }
+
+    fn lex_token(&mut self) -> SpannedToken {
+        if let LexerMode::HereDoc { .. } = self.lexer.extras {
+            return self.lex_here_doc_line();
+        }
+        let token = self.lex_shell_token();
+        self.track_here_docs(&token);
+        token
+    }
+
+    /// Queue a here-document once its delimiter is read, and start reading
+    /// queued bodies at the end of the line
+    fn track_here_docs(&mut self, token: &SpannedToken) {
+        match token.token {
+            Token::Dless => self.awaiting_delimiter = Some(false),
+            Token::Dlessdash => self.awaiting_delimiter = Some(true),
+            Token::Newline => {
+                if let Some(mode) = self.pending_here_docs.pop_front() {
+                    self.lexer.extras = mode;
+                }
+            }
+            _ => {
+                if let Some(strip_tabs) = self.awaiting_delimiter.take()
+                    && token.token.is_word_like()
+                {
+                    self.here_docs_started += 1;
+                    self.pending_here_docs.push_back(LexerMode::HereDoc {
+                        delimiter: remove_quotes(&token.text),
+                        strip_tabs,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Read the next line of a here-document body
+    ///
+    /// The delimiter line ends the body and is not returned. An unterminated
+    /// body stays in `HereDoc` mode at the end of input.
+    fn lex_here_doc_line(&mut self) -> SpannedToken {
+        let LexerMode::HereDoc {
+            delimiter,
+            strip_tabs,
+        } = &self.lexer.extras
+        else {
+            unreachable!("only called in here-document mode");
+        };
+        let start = self.lexer.span().end;
+        let remainder = self.lexer.remainder();
+        if remainder.is_empty() {
+            return SpannedToken {
+                token: Token::Eof,
+                span: Span::new(start, start),
+                text: String::new(),
+            };
+        }
+        let length = remainder.find('\n').unwrap_or(remainder.len());
+        let line = &remainder[..length];
+        let is_delimiter = if *strip_tabs {
+            line.trim_start_matches('\t') == delimiter
+        } else {
+            line == delimiter
+        };
+        self.lexer.bump((length + 1).min(remainder.len()));
+        if is_delimiter {
+            self.here_docs_finished += 1;
+            self.lexer.extras = self.pending_here_docs.pop_front().unwrap_or_default();
+            return self.lex_token();
+        }
+        SpannedToken {
+            token: Token::HereDocLine,
+            span: Span::new(start, start + length),
+            text: line.to_string(),
+        }
+    }
+
+    fn lex_shell_token(&mut self) -> SpannedToken {
         match self.lexer.next() {
             Some(Ok(token)) => {
                 let span = self.lexer.span();
@@ -253,24 +732,70 @@ impl<'input> Lexer<'input> {
     }
 
     /// Tokenize the entire input
+    ///
+    /// Here-document lines follow the delimiter word of their redirection.
     pub fn tokenize(&mut self) -> Vec<SpannedToken> {
-        let mut tokens = Vec::new();
+        let mut tokens: Vec<SpannedToken> = Vec::new();
+        // Index of each delimiter word, and the lines of its body
+        let mut delimiters = Vec::new();
+        let mut bodies: Vec<Vec<SpannedToken>> = Vec::new();
         loop {
+            let started = self.here_docs_started;
             let token = self.next_token();
+            if token.token == Token::HereDocLine {
+                let here_doc = self.here_docs_finished;
+                bodies.resize_with(bodies.len().max(here_doc + 1), Vec::new);
+                bodies[here_doc].push(token);
+                continue;
+            }
+            if self.here_docs_started > started {
+                delimiters.push(tokens.len());
+            }
             let is_eof = token.token == Token::Eof;
+            // A number touching a redirection operator names a file descriptor
+            if let Some(previous) = tokens.last_mut()
+                && previous.token == Token::Number
+                && previous.span.end == token.span.start
+                && token.token.is_redirection()
+            {
+                previous.token = Token::IoNumber;
+            }
             tokens.push(token);
             if is_eof {
                 break;
             }
         }
+        bodies.resize_with(delimiters.len(), Vec::new);
+        for (index, body) in delimiters.into_iter().zip(bodies).rev() {
+            tokens.splice(index + 1..index + 1, body);
+        }
         tokens
     }
 }
 
+/// Delimiter text with its quotes removed, as in `'EOF'` or `"EOF"`
+fn remove_quotes(word: &str) -> String {
+    word.chars().filter(|&ch| ch != '\'' && ch != '"').collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_comment() {
+        let mut lexer = Lexer::new("echo hi # note\n# whole line");
+        let tokens: Vec<(Token, String)> = lexer
+            .tokenize()
+            .into_iter()
+            .map(|token| (token.token, token.text))
+            .collect();
+
+        assert_eq!(tokens[2], (Token::Comment, "# note".to_string()));
+        assert_eq!(tokens[3].0, Token::Newline);
+        assert_eq!(tokens[4], (Token::Comment, "# whole line".to_string()));
+    }
+
     #[test]
     fn test_simple_command() {
         let mut lexer = Lexer::new("echo hello");
@@ -284,6 +809,17 @@ mod tests {
         assert_eq!(tokens[2].token, Token::Eof);
     }
 
+    #[test]
+    fn test_glob_words() {
+        let mut lexer = Lexer::new("ls *.txt a?c");
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens[1].token, Token::Word);
+        assert_eq!(tokens[1].text, "*.txt");
+        assert_eq!(tokens[2].token, Token::Word);
+        assert_eq!(tokens[2].text, "a?c");
+    }
+
     #[test]
     fn test_pipeline() {
         let mut lexer = Lexer::new("echo hello | wc");
@@ -349,6 +885,155 @@ mod tests {
         assert_eq!(tokens[2].text, "${other:-default}");
     }
 
+    #[test]
+    fn test_positional_parameters() {
+        let mut lexer = Lexer::new("echo $1 $# $10");
+        let tokens = lexer.tokenize();
+
+        let texts: Vec<_> = tokens[1..4]
+            .iter()
+            .map(|token| token.text.as_str())
+            .collect();
+        assert_eq!(texts, ["$1", "$#", "$1"]);
+        assert_eq!(tokens[1].token, Token::PositionalParam);
+        assert_eq!(tokens[2].token, Token::SimpleParameterExpansion);
+        assert_eq!(tokens[3].token, Token::PositionalParam);
+    }
+
+    #[test]
+    fn test_special_parameters_and_lone_dollar() {
+        let mut lexer = Lexer::new("echo $$ $! $? $- $0 $9 $");
+        let tokens = lexer.tokenize();
+
+        let kinds: Vec<_> = tokens[1..8]
+            .iter()
+            .map(|token| (token.token.clone(), token.text.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (Token::SpecialParam, "$$"),
+                (Token::SpecialParam, "$!"),
+                (Token::SpecialParam, "$?"),
+                (Token::SpecialParam, "$-"),
+                (Token::PositionalParam, "$0"),
+                (Token::PositionalParam, "$9"),
+                (Token::Dollar, "$"),
+            ]
+        );
+        assert_eq!(tokens[8].token, Token::Eof);
+    }
+
+    #[test]
+    fn test_peek_does_not_consume() {
+        let mut lexer = Lexer::new("echo hi");
+
+        assert_eq!(lexer.peek().text, "echo");
+        assert_eq!(lexer.peek().text, "echo");
+        assert_eq!(lexer.next_token().text, "echo");
+        assert_eq!(lexer.next_token().text, "hi");
+        assert_eq!(lexer.peek().token, Token::Eof);
+        assert_eq!(lexer.next_token().token, Token::Eof);
+    }
+
+    #[test]
+    fn test_arithmetic_expansion_nesting() {
+        let mut lexer = Lexer::new("echo $(( (2+3) * (4-1) )) done");
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens.len(), 4); // echo, $(( ... )), done, EOF
+        assert_eq!(tokens[1].token, Token::ArithmeticExpansion);
+        assert_eq!(tokens[1].text, "$(( (2+3) * (4-1) ))");
+        assert_eq!(tokens[2].token, Token::Done);
+
+        let mut lexer = Lexer::new("echo $(( (1 + 2 )");
+        let tokens = lexer.tokenize();
+        assert_eq!(tokens[1].token, Token::Error);
+    }
+
+    #[test]
+    fn test_backtick_expansion() {
+        let mut lexer = Lexer::new(r"echo `echo \`hi\`` done");
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens[1].token, Token::BacktickExpansion);
+        assert_eq!(tokens[1].text, r"`echo \`hi\``");
+        assert_eq!(tokens[2].token, Token::Done);
+    }
+
+    #[test]
+    fn test_command_substitution() {
+        let mut lexer = Lexer::new(r#"echo $(echo "(" $(pwd)) x=$(date) ~/bin"#);
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens[1].token, Token::CommandSubstitution);
+        assert_eq!(tokens[1].text, r#"$(echo "(" $(pwd))"#);
+        assert_eq!(tokens[2].token, Token::AssignmentWord);
+        assert_eq!(tokens[2].text, "x=");
+        assert_eq!(tokens[3].token, Token::CommandSubstitution);
+        assert_eq!(tokens[4].token, Token::Word);
+        assert_eq!(tokens[4].text, "~/bin");
+    }
+
+    #[test]
+    fn test_arithmetic_operators_are_not_redirections() {
+        let mut lexer = Lexer::new("echo $(( 1 << 2 * (3) )) > out");
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens[1].token, Token::ArithmeticExpansion);
+        assert_eq!(tokens[2].token, Token::Great);
+        assert_eq!(lexer.mode(), &LexerMode::Normal);
+    }
+
+    #[test]
+    fn test_double_quote_around_command_substitution() {
+        let mut lexer = Lexer::new(r#"echo "a $(echo ")") b" "x\"y" "open"#);
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens[1].token, Token::String);
+        assert_eq!(tokens[1].text, r#""a $(echo ")") b""#);
+        assert_eq!(tokens[2].text, r#""x\"y""#);
+        assert_eq!(tokens[3].token, Token::Error);
+    }
+
+    #[test]
+    fn test_here_doc_lines_follow_delimiter() {
+        let mut lexer =
+            Lexer::new("cat <<EOF; cat <<-'END'\nhello $x\nEOF\n\tbye\n\tEND\necho done");
+        let tokens: Vec<(Token, String)> = lexer
+            .tokenize()
+            .into_iter()
+            .map(|token| (token.token, token.text))
+            .collect();
+
+        let line = |text: &str| (Token::HereDocLine, text.to_string());
+        assert_eq!(tokens[2], (Token::Word, "EOF".to_string()));
+        assert_eq!(tokens[3], line("hello $x"));
+        assert_eq!(tokens[4].0, Token::Semicolon);
+        assert_eq!(tokens[7], (Token::String, "'END'".to_string()));
+        assert_eq!(tokens[8], line("\tbye"));
+        assert_eq!(tokens[9].0, Token::Newline);
+        assert_eq!(tokens[10], (Token::Word, "echo".to_string()));
+        assert_eq!(tokens[11], (Token::Done, "done".to_string()));
+    }
+
+    #[test]
+    fn test_here_doc_mode() {
+        let mut lexer = Lexer::new("cat <<EOF\nbody\n");
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens[3].token, Token::HereDocLine);
+        assert_eq!(tokens[3].span, Span::new(10, 14));
+        // Still waiting for the delimiter at the end of input
+        assert_eq!(
+            lexer.mode(),
+            &LexerMode::HereDoc {
+                delimiter: "EOF".to_string(),
+                strip_tabs: false
+            }
+        );
+    }
+
     #[test]
     fn test_logical_operators() {
         let mut lexer = Lexer::new("cmd1 && cmd2 || cmd3");
@@ -371,6 +1056,7 @@ mod tests {
             (">&", Token::Greatand),
             ("<>", Token::Lessgreat),
             ("<<-", Token::Dlessdash),
+            ("<<<", Token::Tless),
             (">|", Token::Clobber),
             (";;", Token::Dsemi),
         ];
@@ -395,6 +1081,8 @@ mod tests {
             ("while", Token::While),
             ("do", Token::Do),
             ("done", Token::Done),
+            ("coproc", Token::Coproc),
+            ("function", Token::Function),
         ];
 
         for (input, expected_token) in test_cases {
@@ -406,6 +1094,78 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_assignment_stops_at_operators() {
+        let mut lexer = Lexer::new("name=world; echo $name");
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens[0].token, Token::AssignmentWord);
+        assert_eq!(tokens[0].text, "name=world");
+        assert_eq!(tokens[1].token, Token::Semicolon);
+    }
+
+    #[test]
+    fn test_array_assignment_is_one_token() {
+        let tokens = Lexer::new("arr=(a \"b c\" $x); echo").tokenize();
+
+        assert_eq!(tokens[0].token, Token::AssignmentWord);
+        assert_eq!(tokens[0].text, "arr=(a \"b c\" $x)");
+        assert_eq!(tokens[1].token, Token::Semicolon);
+    }
+
+    #[test]
+    fn test_io_number_before_redirection() {
+        let mut lexer = Lexer::new("cmd 2>err 2 >out 2>&1");
+        let tokens = lexer.tokenize();
+
+        assert_eq!(tokens[1].token, Token::IoNumber);
+        assert_eq!(tokens[1].text, "2");
+        assert_eq!(tokens[2].token, Token::Great);
+        // Separated by whitespace, the number is an ordinary argument
+        assert_eq!(tokens[4].token, Token::Number);
+        assert_eq!(tokens[7].token, Token::IoNumber);
+        assert_eq!(tokens[8].token, Token::Greatand);
+        assert_eq!(tokens[9].token, Token::Number);
+    }
+
+    #[test]
+    fn test_io_number_with_each_redirection_operator() {
+        let cases = [
+            ("2>err", Token::Great),
+            ("2>>log", Token::Dgreat),
+            ("0<in", Token::Less),
+            ("3<>file", Token::Lessgreat),
+            ("1>|out", Token::Clobber),
+            ("4<&0", Token::Lessand),
+            ("0<<<text", Token::Tless),
+        ];
+        for (input, operator) in cases {
+            let tokens = Lexer::new(&format!("cmd {input}")).tokenize();
+            assert_eq!(tokens[1].token, Token::IoNumber, "{input}");
+            assert_eq!(tokens[1].text, &input[..1], "{input}");
+            assert_eq!(tokens[2].token, operator, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_token_classification() {
+        assert!(Token::AndIf.is_operator());
+        assert!(Token::Semicolon.is_operator());
+        assert!(Token::Dgreat.is_operator());
+        assert!(Token::Dgreat.is_redirection());
+        assert!(!Token::Pipe.is_redirection());
+        assert!(!Token::Word.is_operator());
+
+        assert!(Token::If.is_keyword());
+        assert!(Token::Lbrace.is_keyword());
+        assert!(!Token::Word.is_keyword());
+
+        assert!(Token::ParameterExpansion.is_word_like());
+        assert!(Token::Number.is_word_like());
+        assert!(!Token::Pipe.is_word_like());
+        assert!(!Token::Fi.is_word_like());
+    }
+
     #[test]
     fn test_operator_precedence() {
         // Test that multi-character operators take precedence over single characters
@@ -0,0 +1,115 @@
+//! Lint passes for Shex scripts
+//!
+//! Each pass inspects a parsed `Program` and reports suspicious code as
+//! `LintDiagnostic`s. Linting never changes or executes the program.
+
+use shex_ast::{Program, SourceMap, Span};
+
+mod passes;
+mod walk;
+
+pub use passes::{AlwaysTrue, QuoteRequired, UnusedVariable, UselessEcho};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Style suggestion
+    Info,
+    /// Probably a mistake
+    Warning,
+}
+
+impl Severity {
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+/// A problem found by a lint pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintDiagnostic {
+    /// Name of the pass that produced it, such as `unused-variable`
+    pub lint: &'static str,
+    pub span: Span,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl LintDiagnostic {
+    /// Format as `Shex:file:line:column: severity[lint]: message`
+    #[must_use]
+    pub fn render(&self, source_map: &SourceMap, filename: &str) -> String {
+        let pos = source_map.position(self.span.start);
+        format!(
+            "Shex:{filename}:{}:{}: {}[{}]: {}",
+            pos.line,
+            pos.column,
+            self.severity.as_str(),
+            self.lint,
+            self.message
+        )
+    }
+}
+
+/// A single lint check over a whole program
+pub trait LintPass {
+    /// Short kebab-case name shown with each diagnostic
+    fn name(&self) -> &'static str;
+
+    /// Report every problem this pass finds in `program`
+    fn check(&self, program: &Program) -> Vec<LintDiagnostic>;
+}
+
+/// Every built-in lint pass
+#[must_use]
+pub fn default_passes() -> Vec<Box<dyn LintPass>> {
+    vec![
+        Box::new(UnusedVariable),
+        Box::new(UselessEcho),
+        Box::new(QuoteRequired),
+        Box::new(AlwaysTrue),
+    ]
+}
+
+/// Run all default passes, returning diagnostics in source order
+#[must_use]
+pub fn lint(program: &Program) -> Vec<LintDiagnostic> {
+    let mut diagnostics: Vec<LintDiagnostic> = default_passes()
+        .iter()
+        .flat_map(|pass| pass.check(program))
+        .collect();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shex_parser::Parser;
+
+    #[test]
+    fn test_lint_orders_by_position() {
+        let script = "if true; then echo $a; fi\nunused=1";
+        let program = Parser::new(script).unwrap().parse().unwrap();
+        let lints: Vec<&str> = lint(&program).iter().map(|d| d.lint).collect();
+        assert_eq!(
+            lints,
+            vec!["always-true", "quote-required", "unused-variable"]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let source = "echo ok\nx=1";
+        let program = Parser::new(source).unwrap().parse().unwrap();
+        let diagnostics = lint(&program);
+        assert_eq!(
+            diagnostics[0].render(&SourceMap::new(source), "t.sh"),
+            "Shex:t.sh:2:1: warning[unused-variable]: x is assigned but never used"
+        );
+    }
+}
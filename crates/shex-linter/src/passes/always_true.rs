@@ -0,0 +1,87 @@
+//! `always-true` - `if` conditions that are the literal `true` or `false`
+
+use crate::walk::walk;
+use crate::{LintDiagnostic, LintPass, Severity};
+use shex_ast::{Command, Program, Spanned};
+
+/// Warns when an `if` or `elif` condition cannot change
+pub struct AlwaysTrue;
+
+impl LintPass for AlwaysTrue {
+    fn name(&self) -> &'static str {
+        "always-true"
+    }
+
+    fn check(&self, program: &Program) -> Vec<LintDiagnostic> {
+        let mut diagnostics = Vec::new();
+        walk(&program.commands, &mut |command| {
+            if let Command::If {
+                condition,
+                elif_clauses,
+                ..
+            } = &command.node
+            {
+                let conditions = std::iter::once(condition.as_ref())
+                    .chain(elif_clauses.iter().map(|(condition, _)| condition));
+                for condition in conditions {
+                    if let Some(diagnostic) = self.constant_condition(condition) {
+                        diagnostics.push(diagnostic);
+                    }
+                }
+            }
+        });
+        diagnostics
+    }
+}
+
+impl AlwaysTrue {
+    fn constant_condition(&self, condition: &Spanned<Command>) -> Option<LintDiagnostic> {
+        let Command::Simple { name, args, .. } = &condition.node else {
+            return None;
+        };
+        let branch = match name.as_str() {
+            "true" => "always",
+            "false" => "never",
+            _ => return None,
+        };
+        if !args.is_empty() {
+            return None;
+        }
+        Some(LintDiagnostic {
+            lint: self.name(),
+            span: condition.span,
+            message: format!("condition is `{name}`, so this branch {branch} runs"),
+            severity: Severity::Warning,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shex_parser::Parser;
+
+    fn check(script: &str) -> Vec<LintDiagnostic> {
+        AlwaysTrue.check(&Parser::new(script).unwrap().parse().unwrap())
+    }
+
+    #[test]
+    fn test_literal_conditions() {
+        let diagnostics = check("if true; then echo a; fi\nif false; then echo b; fi");
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "condition is `true`, so this branch always runs"
+        );
+        assert_eq!(
+            diagnostics[1].message,
+            "condition is `false`, so this branch never runs"
+        );
+    }
+
+    #[test]
+    fn test_real_conditions_pass() {
+        assert!(check("if test -f x; then echo a; fi").is_empty());
+        assert!(check("while true; do echo a; done").is_empty());
+    }
+}
@@ -0,0 +1,13 @@
+//! The built-in lint passes
+//!
+//! Each pass lives in its own module and implements `LintPass`.
+
+mod always_true;
+mod quote_required;
+mod unused_variable;
+mod useless_echo;
+
+pub use always_true::AlwaysTrue;
+pub use quote_required::QuoteRequired;
+pub use unused_variable::UnusedVariable;
+pub use useless_echo::UselessEcho;
@@ -0,0 +1,74 @@
+//! `quote-required` - unquoted parameter expansions in command arguments
+
+use crate::walk::walk;
+use crate::{LintDiagnostic, LintPass, Severity};
+use shex_ast::{Command, Program};
+use shex_parser::string_utils::is_special_parameter;
+use shex_parser::word::{QuoteKind, WordPart, parse_word};
+
+/// Warns when `$var` in an argument is subject to word splitting
+///
+/// Assignment values and `case` words are never split, so only command
+/// arguments are checked
+pub struct QuoteRequired;
+
+impl LintPass for QuoteRequired {
+    fn name(&self) -> &'static str {
+        "quote-required"
+    }
+
+    fn check(&self, program: &Program) -> Vec<LintDiagnostic> {
+        let mut diagnostics = Vec::new();
+        walk(&program.commands, &mut |command| {
+            let Command::Simple { args, .. } = &command.node else {
+                return;
+            };
+            for part in args.iter().flat_map(|arg| parse_word(arg)) {
+                if let WordPart::Parameter {
+                    request,
+                    quote: QuoteKind::Unquoted,
+                } = part
+                    && (!is_special_parameter(&request.variable_name)
+                        || matches!(request.variable_name.as_str(), "@" | "*"))
+                {
+                    diagnostics.push(LintDiagnostic {
+                        lint: self.name(),
+                        span: command.span,
+                        message: format!(
+                            "${} is unquoted and may be split into several words; use \"${}\"",
+                            request.variable_name, request.variable_name
+                        ),
+                        severity: Severity::Info,
+                    });
+                }
+            }
+        });
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shex_parser::Parser;
+
+    fn check(script: &str) -> Vec<LintDiagnostic> {
+        QuoteRequired.check(&Parser::new(script).unwrap().parse().unwrap())
+    }
+
+    #[test]
+    fn test_unquoted_argument() {
+        let diagnostics = check("rm $file");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.starts_with("$file is unquoted"));
+        assert_eq!(check("cp $@ dest").len(), 1);
+    }
+
+    #[test]
+    fn test_quoted_and_unsplit_positions_pass() {
+        assert!(check("rm \"$file\"").is_empty());
+        assert!(check("copy=$file").is_empty());
+        assert!(check("echo $?").is_empty());
+        assert!(check("cp \"$@\" dest").is_empty());
+    }
+}
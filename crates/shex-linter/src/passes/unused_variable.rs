@@ -0,0 +1,121 @@
+//! `unused-variable` - variables that are assigned but never read
+
+use crate::walk::{own_words, walk};
+use crate::{LintDiagnostic, LintPass, Severity};
+use shex_ast::{Command, Program, Span};
+use shex_parser::Parser;
+use shex_parser::variable_resolver::split_subscript;
+use shex_parser::word::{WordPart, parse_word};
+use std::collections::HashSet;
+
+/// Variables the shell itself reads, so assigning them is never useless
+const SHELL_VARIABLES: &[&str] = &["CDPATH", "HOME", "IFS", "PATH", "PS1", "PS2", "PS4"];
+
+/// Warns when a variable is assigned but nothing in the script expands it
+///
+/// Shell variables are global, so the whole program is one scope
+pub struct UnusedVariable;
+
+impl LintPass for UnusedVariable {
+    fn name(&self) -> &'static str {
+        "unused-variable"
+    }
+
+    fn check(&self, program: &Program) -> Vec<LintDiagnostic> {
+        let mut assigned: Vec<(&str, Span)> = Vec::new();
+        walk(&program.commands, &mut |command| {
+            if let Command::Assignment { assignments } = &command.node {
+                for (name, _) in assignments {
+                    if !assigned.iter().any(|(seen, _)| seen == name) {
+                        assigned.push((name, command.span));
+                    }
+                }
+            }
+        });
+
+        let used = variables_read(program);
+        assigned
+            .into_iter()
+            .filter(|(name, _)| !used.contains(*name) && !SHELL_VARIABLES.contains(name))
+            .map(|(name, span)| LintDiagnostic {
+                lint: self.name(),
+                span,
+                message: format!("{name} is assigned but never used"),
+                severity: Severity::Warning,
+            })
+            .collect()
+    }
+}
+
+/// Names of every variable expanded anywhere in `program`
+fn variables_read(program: &Program) -> HashSet<String> {
+    let mut used = HashSet::new();
+    walk(&program.commands, &mut |command| {
+        for word in own_words(&command.node) {
+            collect_reads(word, &mut used);
+        }
+    });
+    used
+}
+
+fn collect_reads(word: &str, used: &mut HashSet<String>) {
+    for part in parse_word(word) {
+        match part {
+            WordPart::Parameter { request, .. } => {
+                let name = split_subscript(&request.variable_name)
+                    .map_or(request.variable_name.as_str(), |(base, _)| base);
+                used.insert(name.to_string());
+                if let Some(parameter) = &request.parameter {
+                    collect_reads(parameter, used);
+                }
+            }
+            WordPart::CommandSubstitution { command, .. } => {
+                if let Ok(program) = Parser::new(&command).and_then(|parser| parser.parse()) {
+                    used.extend(variables_read(&program));
+                }
+            }
+            // Arithmetic reads bare names as variables
+            WordPart::Arithmetic { expression, .. } => {
+                used.extend(
+                    expression
+                        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                        .filter(|name| {
+                            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                        })
+                        .map(str::to_string),
+                );
+            }
+            WordPart::Literal { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(script: &str) -> Vec<LintDiagnostic> {
+        UnusedVariable.check(&Parser::new(script).unwrap().parse().unwrap())
+    }
+
+    #[test]
+    fn test_unused_assignment() {
+        let diagnostics = check("greeting=hello\necho bye");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "greeting is assigned but never used"
+        );
+        assert_eq!(diagnostics[0].span.start, 0);
+    }
+
+    #[test]
+    fn test_reads_in_every_position() {
+        assert!(check("a=1; echo \"$a\"").is_empty());
+        assert!(check("a=1; b=${a}x; echo $b").is_empty());
+        assert!(check("a=1; echo \"${missing:-$a}\"").is_empty());
+        assert!(check("a=1; echo \"$((a + 1))\"").is_empty());
+        assert!(check("a=1; echo \"$(echo $a)\"").is_empty());
+        assert!(check("PS4=x; echo hi").is_empty());
+    }
+}
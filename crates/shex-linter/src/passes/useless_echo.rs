@@ -0,0 +1,65 @@
+//! `useless-echo` - `echo` whose output is thrown away
+
+use crate::walk::walk;
+use crate::{LintDiagnostic, LintPass, Severity};
+use shex_ast::{Command, Program, RedirectionKind};
+
+/// Warns when `echo` writes its standard output to `/dev/null`
+pub struct UselessEcho;
+
+impl LintPass for UselessEcho {
+    fn name(&self) -> &'static str {
+        "useless-echo"
+    }
+
+    fn check(&self, program: &Program) -> Vec<LintDiagnostic> {
+        let mut diagnostics = Vec::new();
+        walk(&program.commands, &mut |command| {
+            let Command::Simple {
+                name, redirections, ..
+            } = &command.node
+            else {
+                return;
+            };
+            let discarded = redirections.iter().any(|redirection| {
+                matches!(
+                    redirection.kind,
+                    RedirectionKind::Output | RedirectionKind::Append | RedirectionKind::Clobber
+                ) && redirection.target_fd() == 1
+                    && redirection.target == "/dev/null"
+            });
+            if name == "echo" && discarded {
+                diagnostics.push(LintDiagnostic {
+                    lint: self.name(),
+                    span: command.span,
+                    message: "echo output is redirected to /dev/null and has no effect".to_string(),
+                    severity: Severity::Warning,
+                });
+            }
+        });
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shex_parser::Parser;
+
+    fn check(script: &str) -> Vec<LintDiagnostic> {
+        UselessEcho.check(&Parser::new(script).unwrap().parse().unwrap())
+    }
+
+    #[test]
+    fn test_echo_to_dev_null() {
+        assert_eq!(check("echo hi >/dev/null").len(), 1);
+        assert_eq!(check("if true; then echo hi >> /dev/null; fi").len(), 1);
+    }
+
+    #[test]
+    fn test_other_redirections_pass() {
+        assert!(check("echo hi > out.txt").is_empty());
+        assert!(check("echo hi 2>/dev/null").is_empty());
+        assert!(check("ls >/dev/null").is_empty());
+    }
+}
@@ -0,0 +1,42 @@
+//! Depth-first traversal of command trees
+
+use shex_ast::Command;
+pub use shex_ast::walk::walk;
+
+/// Words that belong to `command` itself rather than to a nested command
+///
+/// Includes command names, arguments, assignment values, redirection
+/// targets, `for` word lists and `case` words and patterns
+pub fn own_words(command: &Command) -> Vec<&str> {
+    let mut words = Vec::new();
+    match command {
+        Command::Simple {
+            name,
+            args,
+            assignments,
+            redirections,
+        } => {
+            words.push(name.as_str());
+            words.extend(args.iter().map(String::as_str));
+            words.extend(assignments.iter().map(|(_, value)| value.as_str()));
+            words.extend(redirections.iter().map(|r| r.target.as_str()));
+        }
+        Command::Assignment { assignments } => {
+            words.extend(assignments.iter().map(|(_, value)| value.as_str()));
+        }
+        Command::Pipeline { redirections, .. } | Command::Function { redirections, .. } => {
+            words.extend(redirections.iter().map(|r| r.target.as_str()));
+        }
+        Command::For {
+            words: Some(list), ..
+        } => words.extend(list.iter().map(String::as_str)),
+        Command::Case { word, arms } => {
+            words.push(word.as_str());
+            for arm in arms {
+                words.extend(arm.patterns.iter().map(String::as_str));
+            }
+        }
+        _ => {}
+    }
+    words
+}
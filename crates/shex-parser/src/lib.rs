@@ -6,18 +6,25 @@
 #![allow(unused_variables)] // Allow unused variables in generated LALRPOP code
 #![allow(clippy::all, clippy::pedantic, clippy::nursery)]
 
-use shex_ast::{Command, Program, ShexError, SourceMap, Span};
-use shex_lexer::{Lexer, SpannedToken, Token};
+use shex_ast::{Command, Program, ShexError, SourceMap, Span, Spanned};
+use std::sync::Arc;
+use shex_lexer::{Lexer, LexerMode, SpannedToken, Token};
 
 // Include the generated LALRPOP parser
 lalrpop_util::lalrpop_mod!(pub shex);
 
+// Comment and line-continuation removal
+mod preprocess;
+
 // String processing utilities
 pub mod string_utils;
 
 // Variable resolution infrastructure
 pub mod variable_resolver;
 
+// Word parsing into literal and expansion parts
+pub mod word;
+
 // Helper functions for POSIX grammar implementation
 pub fn combine_args(prefix: Vec<SpannedToken>, suffix: Vec<SpannedToken>) -> Vec<String> {
     string_utils::combine_args(&prefix, &suffix)
@@ -31,11 +38,63 @@ pub fn token_to_string(token: SpannedToken) -> String {
     string_utils::token_to_string(&token)
 }
 
+/// Append a command to a list, flattening into a single `Sequence`
+pub fn append_command(
+    list: Spanned<Command>,
+    cmd: Spanned<Command>,
+    span: Span,
+) -> Spanned<Command> {
+    match list.node {
+        Command::Sequence { mut commands } => {
+            commands.push(cmd);
+            Spanned::new(Command::Sequence { commands }, span)
+        }
+        _ => Spanned::new(
+            Command::Sequence {
+                commands: vec![list, cmd],
+            },
+            span,
+        ),
+    }
+}
+
+/// Run the last command of a list in the background (`cmd &`)
+pub fn make_background(list: Spanned<Command>) -> Spanned<Command> {
+    let span = list.span;
+    match list.node {
+        Command::Sequence { mut commands } => {
+            if let Some(last) = commands.pop() {
+                commands.push(make_background(last));
+            }
+            Spanned::new(Command::Sequence { commands }, span)
+        }
+        node => Spanned::new(
+            Command::Background {
+                command: Arc::new(Spanned::new(node, span)),
+            },
+            span,
+        ),
+    }
+}
+
 pub struct Parser {
     input: String,
     source_map: SourceMap,
     filename: String,
     tokens: Vec<SpannedToken>,
+    /// Input arrives line by line, so lexical errors are reported by `parse`
+    interactive: bool,
+}
+
+/// Whether the input fed to a parser so far forms a whole program
+#[derive(Debug)]
+pub enum ParseState {
+    /// More lines are needed to close this many open constructs
+    Incomplete(usize),
+    /// The input parses as a complete program
+    Complete,
+    /// The input is invalid and more lines cannot fix it
+    Error(ShexError),
 }
 
 impl Parser {
@@ -60,46 +119,136 @@ impl Parser {
         let mut lexer = Lexer::new(input);
         let tokens = lexer.tokenize();
 
-        // Check for lexer errors
-        for token in &tokens {
-            if token.token == Token::Error {
-                return Err(ShexError::syntax(
-                    format!("Unexpected character: {}", token.text),
-                    token.span,
-                    &source_map,
-                    filename,
-                ));
-            }
-        }
+        check_lexer_errors(&tokens, &source_map, filename)?;
 
         Ok(Self {
             input: input.to_string(),
             source_map,
             filename: filename.to_string(),
             tokens,
+            interactive: false,
         })
     }
 
+    /// Create a parser for input typed at a prompt
+    ///
+    /// Further lines are added with `add_line`; lexical errors are only
+    /// reported once the input is parsed
+    #[must_use]
+    pub fn new_interactive(input: &str) -> Self {
+        Self {
+            input: input.to_string(),
+            source_map: SourceMap::new(input),
+            filename: "<stdin>".to_string(),
+            tokens: Lexer::new(input).tokenize(),
+            interactive: true,
+        }
+    }
+
+    /// Remove comments and line continuations from a script
+    ///
+    /// `#` comments are dropped up to the end of their line and each
+    /// backslash-newline pair is joined away, except inside single quotes and
+    /// here-document bodies, where both are literal text. The parser itself
+    /// lexes comments as tokens instead, so spans keep pointing into the
+    /// original source.
+    ///
+    /// ```
+    /// use shex_parser::Parser;
+    ///
+    /// assert_eq!(Parser::preprocess("echo a \\\n b # note"), "echo a  b ");
+    /// ```
+    #[must_use]
+    pub fn preprocess(input: &str) -> String {
+        preprocess::preprocess(input)
+    }
+
+    /// Append a line of input and report whether the program is complete
+    ///
+    /// Open compound commands, unterminated quotes and here-documents, and
+    /// trailing `&&`, `||` or `|` make the input incomplete
+    pub fn add_line(&mut self, line: &str) -> ParseState {
+        self.input.push_str(line);
+        if !self.input.ends_with('\n') {
+            self.input.push('\n');
+        }
+        self.source_map = SourceMap::new(&self.input);
+        let mut lexer = Lexer::new(&self.input);
+        self.tokens = lexer.tokenize();
+
+        let in_here_doc = matches!(lexer.mode(), LexerMode::HereDoc { .. });
+        if in_here_doc
+            || self
+                .tokens
+                .iter()
+                .any(|token| token.token == Token::Error && token.text.starts_with(['"', '\'']))
+        {
+            return ParseState::Incomplete(open_depth(&self.tokens) + 1);
+        }
+
+        let depth = open_depth(&self.tokens);
+        let continues = self
+            .tokens
+            .iter()
+            .rev()
+            .find(|token| !matches!(token.token, Token::Newline | Token::Eof))
+            .is_some_and(|token| {
+                matches!(token.token, Token::AndIf | Token::OrIf | Token::Pipe)
+            });
+        if continues {
+            return ParseState::Incomplete(depth + 1);
+        }
+        if depth > 0 {
+            return ParseState::Incomplete(depth);
+        }
+
+        match self.parse() {
+            Ok(_) => ParseState::Complete,
+            Err(err) => ParseState::Error(err),
+        }
+    }
+
     /// Parse the input into a program AST
     ///
     /// # Errors
     ///
     /// Returns `ShexError` if there are syntax errors during parsing
     pub fn parse(&self) -> Result<Program, ShexError> {
-        // Filter out newlines and empty commands, keep only meaningful tokens
-        let filtered_tokens: Vec<SpannedToken> = self
+        self.parse_program(false)
+    }
+
+    /// Parse the input, keeping top-level comments as `Command::Comment` nodes
+    ///
+    /// Comments appear in the command list in source order. Comments inside
+    /// compound commands are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShexError` if there are syntax errors during parsing
+    pub fn parse_with_comments(&self) -> Result<Program, ShexError> {
+        self.parse_program(true)
+    }
+
+    fn parse_program(&self, keep_comments: bool) -> Result<Program, ShexError> {
+        if self.interactive {
+            check_lexer_errors(&self.tokens, &self.source_map, &self.filename)?;
+        }
+
+        // Comments never reach the grammar; they are spliced back in afterwards
+        let (comments, tokens): (Vec<SpannedToken>, Vec<SpannedToken>) = self
             .tokens
             .iter()
-            .filter(|token| token.token != Token::Newline)
             .cloned()
-            .collect();
+            .partition(|token| token.token == Token::Comment);
+
+        // Newlines are significant: they separate commands like `;`
+        let filtered_tokens: Vec<SpannedToken> = merge_adjacent_words(tokens.into_iter());
 
         // Convert tokens to the format LALRPOP expects
         let lalrpop_tokens: Vec<Result<(usize, SpannedToken, usize), ()>> = filtered_tokens
             .into_iter()
             .map(|token| {
-                let start = token.span.start;
-                let end = token.span.end;
+                let (start, end) = (token.start(), token.end());
                 Ok((start, token, end))
             })
             .collect();
@@ -113,14 +262,16 @@ impl Parser {
                     Command::Simple { name, .. } => !name.is_empty(),
                     _ => true,
                 });
+                if keep_comments {
+                    insert_comments(&mut program, comments);
+                }
                 Ok(program)
             }
             Err(err) => {
-                // Convert LALRPOP error to ShexError
-                let error_msg = format!("Parse error: {err:?}");
+                let (message, span) = describe_parse_error(err, self.input.len());
                 Err(ShexError::syntax(
-                    error_msg,
-                    Span::new(0, self.input.len()),
+                    message,
+                    span,
                     &self.source_map,
                     &self.filename,
                 ))
@@ -153,10 +304,225 @@ impl Parser {
     }
 }
 
+/// Fail on the first token the lexer could not recognise
+fn check_lexer_errors(
+    tokens: &[SpannedToken],
+    source_map: &SourceMap,
+    filename: &str,
+) -> Result<(), ShexError> {
+    match tokens.iter().find(|token| token.token == Token::Error) {
+        Some(token) => Err(ShexError::syntax(
+            format!("Unexpected character: {}", token.text),
+            token.span,
+            source_map,
+            filename,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Count compound commands and groups that are opened but not yet closed
+///
+/// Inside `case` the `)` ending each pattern does not close anything
+fn open_depth(tokens: &[SpannedToken]) -> usize {
+    let mut open: Vec<&Token> = Vec::new();
+    for token in tokens {
+        match token.token {
+            Token::If | Token::Case | Token::Do | Token::Lbrace | Token::Lparen => {
+                open.push(&token.token);
+            }
+            Token::Rparen if open.last() == Some(&&Token::Case) => {}
+            Token::Fi | Token::Esac | Token::Done | Token::Rbrace | Token::Rparen => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+    open.len()
+}
+
+/// Splice top-level comments into a program's command list by position
+fn insert_comments(program: &mut Program, comments: Vec<SpannedToken>) {
+    for comment in comments {
+        let start = comment.start();
+        if program
+            .commands
+            .iter()
+            .any(|cmd| cmd.span.start < start && start < cmd.span.end)
+        {
+            continue;
+        }
+        let index = program
+            .commands
+            .partition_point(|cmd| cmd.span.start < start);
+        let text = comment.text.strip_prefix('#').unwrap_or(&comment.text);
+        program.commands.insert(
+            index,
+            Spanned::new(
+                Command::Comment {
+                    text: text.to_string(),
+                },
+                comment.span(),
+            ),
+        );
+    }
+}
+
+/// Remove every `Command::Comment` node from a program
+#[must_use]
+pub fn strip_comments(mut program: Program) -> Program {
+    program
+        .commands
+        .retain(|cmd| !matches!(cmd.node, Command::Comment { .. }));
+    program
+}
+
+/// Whether `input` parses to the same program after being pretty-printed
+///
+/// The comparison ignores spans, so only formatting may differ between the
+/// two parses. Errors from the first parse are returned; a pretty-printed
+/// program that fails to parse counts as a mismatch.
+pub fn roundtrip(input: &str) -> Result<bool, ShexError> {
+    let program = Parser::new(input)?.parse()?;
+    let printed = shex_ast::pretty::pretty_print(&program);
+    let reparsed = Parser::new(&printed).and_then(|parser| parser.parse());
+    Ok(reparsed.is_ok_and(|reparsed| reparsed == program))
+}
+
+/// Split `input` into shell words as the parser would, each keeping its
+/// quotes and expansions for the interpreter to process
+///
+/// Used for the elements of an array assignment such as `arr=(a "b c")`;
+/// anything that is not a word, such as a newline, is dropped
+///
+/// # Errors
+///
+/// Returns `ShexError` if `input` does not lex
+pub fn split_words(input: &str) -> Result<Vec<String>, ShexError> {
+    let tokens = Lexer::new(input).tokenize();
+    check_lexer_errors(&tokens, &SourceMap::new(input), "<input>")?;
+    Ok(merge_adjacent_words(tokens.into_iter())
+        .iter()
+        .filter(|token| is_word_piece(&token.token))
+        .map(string_utils::token_to_string)
+        .collect())
+}
+
+/// Closing words the grammar commonly waits for, by terminal name
+const EXPECTED_CLOSERS: &[(&str, Token)] = &[
+    ("Then", Token::Then),
+    ("Fi", Token::Fi),
+    ("Do", Token::Do),
+    ("Done", Token::Done),
+    ("In", Token::In),
+    ("Esac", Token::Esac),
+    ("Rbrace", Token::Rbrace),
+    ("Rparen", Token::Rparen),
+];
+
+/// Turn a LALRPOP error into a message and the span it points at
+fn describe_parse_error(
+    err: lalrpop_util::ParseError<usize, SpannedToken, ()>,
+    input_len: usize,
+) -> (String, Span) {
+    use lalrpop_util::ParseError;
+
+    match err {
+        ParseError::UnrecognizedToken {
+            token: (_, token, _),
+            expected,
+        } => (
+            format!("{}{}", describe_found(&token), describe_expected(&expected)),
+            token.span(),
+        ),
+        ParseError::UnrecognizedEof { location, expected } => (
+            format!("unexpected end of input{}", describe_expected(&expected)),
+            Span::new(location, location),
+        ),
+        ParseError::ExtraToken { token: (_, token, _) } => (describe_found(&token), token.span()),
+        ParseError::InvalidToken { location } => {
+            ("invalid token".to_string(), Span::new(location, location))
+        }
+        ParseError::User { .. } => ("parse error".to_string(), Span::new(0, input_len)),
+    }
+}
+
+fn describe_found(token: &SpannedToken) -> String {
+    if token.token.is_operator() || token.token.is_keyword() {
+        format!("unexpected '{}'", token.token.lexeme())
+    } else if token.text.is_empty() || token.token == Token::Newline {
+        format!("unexpected {}", token.token.lexeme())
+    } else {
+        format!("unexpected {} '{}'", token.token.lexeme(), token.text)
+    }
+}
+
+fn describe_expected(expected: &[String]) -> String {
+    let closers: Vec<String> = EXPECTED_CLOSERS
+        .iter()
+        .filter(|(name, _)| expected.iter().any(|e| e.trim_matches('"') == *name))
+        .map(|(_, token)| format!("'{}'", token.lexeme()))
+        .collect();
+    if closers.is_empty() {
+        String::new()
+    } else {
+        format!(", expected {}", closers.join(" or "))
+    }
+}
+
+/// Tokens that can form part of a single shell word
+const fn is_word_piece(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Word
+            | Token::Number
+            | Token::String
+            | Token::AssignmentWord
+            | Token::SimpleParameterExpansion
+            | Token::PositionalParam
+            | Token::SpecialParam
+            | Token::Dollar
+            | Token::ParameterExpansion
+            | Token::ArithmeticExpansion
+            | Token::BacktickExpansion
+            | Token::CommandSubstitution
+            | Token::LeftBracket
+            | Token::RightBracket
+            | Token::Dash
+            | Token::Dot
+    )
+}
+
+/// Merge word pieces with no whitespace between them into one word
+///
+/// The lexer splits `-la`, `PS4='+ '` and `pre$var` into several tokens;
+/// the shell sees each of them as a single word.
+fn merge_adjacent_words(tokens: impl Iterator<Item = SpannedToken>) -> Vec<SpannedToken> {
+    let mut merged: Vec<SpannedToken> = Vec::new();
+    for token in tokens {
+        if let Some(previous) = merged.last_mut() {
+            // A `!` continues a word, as in `[!a-z]`, but never starts one
+            if is_word_piece(&previous.token)
+                && (is_word_piece(&token.token) || token.token == Token::Bang)
+                && previous.span.end == token.span.start
+            {
+                previous.text.push_str(&token.text);
+                previous.span = Span::new(previous.span.start, token.span.end);
+                if previous.token != Token::AssignmentWord {
+                    previous.token = Token::Word;
+                }
+                continue;
+            }
+        }
+        merged.push(token);
+    }
+    merged
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use shex_ast::Command;
+    use shex_ast::{Command, RedirectionKind};
 
     #[test]
     fn test_simple_command() {
@@ -194,7 +560,11 @@ mod tests {
                 redirections: _,
             } => {
                 assert_eq!(name, "echo");
-                assert_eq!(args, &["hello", "world test", "$var", "${other:-default}"]);
+                // Double quotes are kept so the interpreter can expand their contents
+                assert_eq!(
+                    args,
+                    &["hello", "\"world test\"", "$var", "${other:-default}"]
+                );
                 assert_eq!(assignments, &[]);
             }
             _ => panic!("Expected simple command"),
@@ -222,6 +592,88 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_assignment_words_after_command_name_are_arguments() {
+        let parser = Parser::new("readonly A=1 B='x y'").unwrap();
+        let program = parser.parse().unwrap();
+
+        match &program.commands[0].node {
+            Command::Simple {
+                name,
+                args,
+                assignments,
+                redirections: _,
+            } => {
+                assert_eq!(name, "readonly");
+                assert_eq!(args, &["A=1", "B='x y'"]);
+                assert_eq!(assignments, &[]);
+            }
+            _ => panic!("Expected simple command"),
+        }
+    }
+
+    #[test]
+    fn test_comments_are_preserved_on_request() {
+        let input = "# header\necho hi # trailing\nif true; then # inner\n  echo yes\nfi";
+        let parser = Parser::new(input).unwrap();
+
+        let program = parser.parse_with_comments().unwrap();
+        let texts: Vec<Option<&str>> = program
+            .commands
+            .iter()
+            .map(|cmd| match &cmd.node {
+                Command::Comment { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, vec![Some(" header"), None, Some(" trailing"), None]);
+
+        let plain = parser.parse().unwrap();
+        let stripped = strip_comments(program);
+        assert_eq!(format!("{stripped:?}"), format!("{plain:?}"));
+    }
+
+    #[test]
+    fn test_interactive_lines() {
+        let mut parser = Parser::new_interactive("");
+        assert!(matches!(parser.add_line("if true; then"), ParseState::Incomplete(1)));
+        assert!(matches!(parser.add_line("  while true; do"), ParseState::Incomplete(2)));
+        assert!(matches!(parser.add_line("    echo yes; done"), ParseState::Incomplete(1)));
+        assert!(matches!(parser.add_line("fi"), ParseState::Complete));
+        assert_eq!(parser.parse().unwrap().commands.len(), 1);
+
+        let mut parser = Parser::new_interactive("");
+        assert!(matches!(parser.add_line("case x in a) echo a;;"), ParseState::Incomplete(1)));
+        assert!(matches!(parser.add_line("esac"), ParseState::Complete));
+
+        let mut parser = Parser::new_interactive("");
+        assert!(matches!(parser.add_line("echo a |"), ParseState::Incomplete(1)));
+        assert!(matches!(parser.add_line("cat"), ParseState::Complete));
+
+        let mut parser = Parser::new_interactive("");
+        assert!(matches!(parser.add_line("cat <<EOF"), ParseState::Incomplete(1)));
+        assert!(matches!(parser.add_line("body"), ParseState::Incomplete(1)));
+        assert!(matches!(parser.add_line("EOF"), ParseState::Complete));
+
+        let mut parser = Parser::new_interactive("");
+        assert!(matches!(parser.add_line("echo \"one"), ParseState::Incomplete(1)));
+        assert!(matches!(parser.add_line("two\""), ParseState::Complete));
+
+        let mut parser = Parser::new_interactive("");
+        assert!(matches!(parser.add_line("fi"), ParseState::Error(_)));
+    }
+
+    #[test]
+    fn test_parse_error_names_tokens() {
+        let err = Parser::new("if true; echo yes; done").unwrap().parse().unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("unexpected 'done', expected 'then'"), "{message}");
+        assert_eq!(err.span(), Span::new(19, 23));
+
+        let err = Parser::new("while true; do echo").unwrap().parse().unwrap_err();
+        assert!(err.to_string().contains("unexpected end of input, expected 'done'"));
+    }
+
     #[test]
     fn test_empty_input() {
         let parser = Parser::new("").unwrap();
@@ -231,6 +683,92 @@ mod tests {
     }
 
     // Pipeline test disabled for Phase 0.5 - will re-enable in Phase 1
+    #[test]
+    fn test_adjacent_tokens_form_one_word() {
+        let parser = Parser::new("PS4='+ x' ls -la pre$var").unwrap();
+        let program = parser.parse().unwrap();
+
+        match &program.commands[0].node {
+            Command::Simple {
+                name,
+                args,
+                assignments,
+                ..
+            } => {
+                assert_eq!(name, "ls");
+                assert_eq!(args, &["-la", "pre$var"]);
+                assert_eq!(
+                    assignments,
+                    &[("PS4".to_string(), "'+ x'".to_string())]
+                );
+            }
+            _ => panic!("Expected simple command"),
+        }
+    }
+
+    #[test]
+    fn test_redirected_compound_command() {
+        let program = Parser::new("while read l; do echo $l; done < file | cat")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let Command::Pipeline { commands, redirections } = &program.commands[0].node else {
+            panic!("Expected pipeline");
+        };
+        assert!(redirections.is_empty());
+        assert_eq!(commands.len(), 2);
+        match &commands[0].node {
+            Command::Pipeline { commands, redirections } => {
+                assert!(matches!(commands[0].node, Command::While { .. }));
+                assert_eq!(redirections[0].kind, RedirectionKind::Input);
+                assert_eq!(redirections[0].target, "file");
+            }
+            _ => panic!("Expected redirected while loop"),
+        }
+    }
+
+    #[test]
+    fn test_negated_bracket_stays_in_word() {
+        let program = Parser::new("case $x in [!a-z]*|x) echo; esac")
+            .unwrap()
+            .parse()
+            .unwrap();
+        match &program.commands[0].node {
+            Command::Case { arms, .. } => assert_eq!(arms[0].patterns, ["[!a-z]*", "x"]),
+            _ => panic!("Expected case command"),
+        }
+    }
+
+    #[test]
+    fn test_expansion_as_command_name() {
+        for (input, expected) in [("$cmd hello", "$cmd"), ("x=1 ${cmd}", "${cmd}")] {
+            let program = Parser::new(input).unwrap().parse().unwrap();
+            match &program.commands[0].node {
+                Command::Simple { name, .. } => assert_eq!(name, expected),
+                _ => panic!("Expected simple command"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_here_doc_body() {
+        let input = "cat <<-'END' > out\n\tline $x\n\n\tEND\necho after";
+        let program = Parser::new(input).unwrap().parse().unwrap();
+        assert_eq!(program.commands.len(), 2);
+        match &program.commands[0].node {
+            Command::Simple { redirections, .. } => {
+                let RedirectionKind::HereDocDash { delimiter, text } = &redirections[0].kind else {
+                    panic!("Expected <<- here-document");
+                };
+                assert_eq!(delimiter, "END");
+                assert_eq!(text, "line $x\n\n");
+                assert_eq!(redirections[0].target, "'END'");
+                assert_eq!(redirections[1].target, "out");
+            }
+            _ => panic!("Expected simple command"),
+        }
+    }
+
     #[test]
     #[ignore]
     fn test_pipeline() {
@@ -261,4 +799,11 @@ mod tests {
             _ => panic!("Expected pipeline"),
         }
     }
+
+    #[test]
+    fn test_roundtrip() {
+        assert!(roundtrip("echo hello").unwrap());
+        assert!(roundtrip("if true\nthen\n  echo   yes\nfi").unwrap());
+        assert!(roundtrip("echo 'unterminated").is_err());
+    }
 }
@@ -0,0 +1,139 @@
+//! Comment and line-continuation removal ahead of lexing
+//!
+//! A character-by-character scanner tracks quotes and pending
+//! here-documents, so a `#` or backslash inside a string or a
+//! here-document body is left alone.
+
+use std::collections::VecDeque;
+
+/// A here-document whose body starts after the current line
+struct PendingHereDoc {
+    delimiter: String,
+    strip_tabs: bool,
+}
+
+/// Remove comments and backslash-newline pairs from `input`
+///
+/// The newline ending a comment is kept. Single-quoted text and
+/// here-document bodies are copied unchanged; inside double quotes only
+/// line continuations are removed.
+pub(crate) fn preprocess(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut pending: VecDeque<PendingHereDoc> = VecDeque::new();
+    let mut in_double_quotes = false;
+    let mut rest = input;
+
+    while let Some(ch) = rest.chars().next() {
+        let mut consumed = ch.len_utf8();
+        match ch {
+            '\\' => match rest[1..].chars().next() {
+                Some('\n') => consumed = 2,
+                Some(escaped) => {
+                    output.push(ch);
+                    output.push(escaped);
+                    consumed += escaped.len_utf8();
+                }
+                None => output.push(ch),
+            },
+            '"' => {
+                in_double_quotes = !in_double_quotes;
+                output.push(ch);
+            }
+            _ if in_double_quotes => output.push(ch),
+            '\'' => {
+                consumed = rest[1..].find('\'').map_or(rest.len(), |end| end + 2);
+                output.push_str(&rest[..consumed]);
+            }
+            '#' if starts_word(&output) => consumed = rest.find('\n').unwrap_or(rest.len()),
+            '<' if rest.starts_with("<<") && !rest.starts_with("<<<") => {
+                let strip_tabs = rest.starts_with("<<-");
+                let operator = if strip_tabs { 3 } else { 2 };
+                let blanks = rest[operator..].len() - rest[operator..].trim_start_matches([' ', '\t']).len();
+                let word_start = operator + blanks;
+                let word_length = rest[word_start..].find(ends_word).unwrap_or(rest.len() - word_start);
+                let word = &rest[word_start..word_start + word_length];
+                if !word.is_empty() {
+                    pending.push_back(PendingHereDoc {
+                        delimiter: word.chars().filter(|&c| c != '\'' && c != '"').collect(),
+                        strip_tabs,
+                    });
+                }
+                consumed = word_start + word_length;
+                output.push_str(&rest[..consumed]);
+            }
+            '\n' => {
+                output.push(ch);
+                while let Some(here_doc) = pending.pop_front() {
+                    let body = here_doc_length(&rest[consumed..], &here_doc);
+                    output.push_str(&rest[consumed..consumed + body]);
+                    consumed += body;
+                }
+            }
+            _ => output.push(ch),
+        }
+        rest = &rest[consumed..];
+    }
+    output
+}
+
+/// Whether a `#` written next would start a word, and so a comment
+fn starts_word(output: &str) -> bool {
+    output
+        .chars()
+        .next_back()
+        .is_none_or(|last| last.is_whitespace() || matches!(last, ';' | '&' | '|' | '(' | ')'))
+}
+
+/// Characters that end a here-document delimiter word
+fn ends_word(ch: char) -> bool {
+    ch.is_whitespace() || matches!(ch, ';' | '&' | '|' | '(' | ')' | '<' | '>')
+}
+
+/// Length of a here-document body at the start of `rest`, including its
+/// delimiter line
+fn here_doc_length(rest: &str, here_doc: &PendingHereDoc) -> usize {
+    let mut length = 0;
+    for line in rest.split_inclusive('\n') {
+        length += line.len();
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        let text = if here_doc.strip_tabs {
+            text.trim_start_matches('\t')
+        } else {
+            text
+        };
+        if text == here_doc.delimiter {
+            break;
+        }
+    }
+    length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::preprocess;
+
+    #[test]
+    fn test_comments_are_removed() {
+        assert_eq!(preprocess("echo hello # comment\necho world"), "echo hello \necho world");
+        assert_eq!(preprocess("# whole line\necho $# a#b"), "\necho $# a#b");
+    }
+
+    #[test]
+    fn test_quotes_are_kept() {
+        assert_eq!(preprocess("echo '# x' \"# y\" \\# z"), "echo '# x' \"# y\" \\# z");
+        assert_eq!(preprocess("echo 'a\\\nb' \"c\\\nd\""), "echo 'a\\\nb' \"cd\"");
+    }
+
+    #[test]
+    fn test_line_continuations_are_joined() {
+        assert_eq!(preprocess("echo a \\\n  b"), "echo a   b");
+    }
+
+    #[test]
+    fn test_here_doc_bodies_are_kept() {
+        let script = "cat << EOF # note\n# not a comment \\\nEOF\n# gone\n";
+        assert_eq!(preprocess(script), "cat << EOF \n# not a comment \\\nEOF\n\n");
+        let script = "cat <<-'END'\n\t# kept\n\tEND\necho done";
+        assert_eq!(preprocess(script), script);
+    }
+}
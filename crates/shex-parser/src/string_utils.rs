@@ -9,6 +9,8 @@ use shex_lexer::{SpannedToken, Token};
 /// Remove quotes from a string token while preserving the content
 ///
 /// Handles both single and double quotes according to POSIX rules
+#[must_use]
+#[inline]
 pub fn remove_quotes(text: &str) -> String {
     if text.len() < 2 {
         return text.to_string();
@@ -27,12 +29,21 @@ pub fn remove_quotes(text: &str) -> String {
 
 /// Convert a token to its string representation
 ///
-/// Handles quote removal for string tokens and preserves other token text
-/// Parameter expansion tokens are returned as-is for later processing
+/// Quoted strings keep their quotes so the interpreter knows which parts
+/// to expand and performs quote removal as the last expansion step.
+/// Parameter, arithmetic and backtick expansion tokens are returned as-is
+/// for later processing
+#[must_use]
+#[inline]
 pub fn token_to_string(token: &SpannedToken) -> String {
     match token.token {
-        Token::String => remove_quotes(&token.text),
-        Token::SimpleParameterExpansion | Token::ParameterExpansion => {
+        Token::SimpleParameterExpansion
+        | Token::PositionalParam
+        | Token::SpecialParam
+        | Token::ParameterExpansion
+        | Token::ArithmeticExpansion
+        | Token::BacktickExpansion
+        | Token::CommandSubstitution => {
             // Return parameter expansion as-is for later resolution
             token.text.clone()
         }
@@ -59,11 +70,39 @@ pub fn parse_assignment(text: &str) -> Option<(String, String)> {
     }
 }
 
+/// Quote `value` so the shell reads it back as exactly that text
+///
+/// The result is single-quoted; each embedded `'` closes the quotes, appears
+/// inside double quotes and reopens them, as in `'it'"'"'s'`
+#[must_use]
+pub fn quote_for_shell(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r#"'"'"'"#))
+}
+
+/// Text of a here-document body from its lines
+///
+/// Each line ends with a newline; `strip_tabs` removes leading tabs, as
+/// `<<-` does
+pub fn here_doc_text(lines: &[SpannedToken], strip_tabs: bool) -> String {
+    let mut text = String::new();
+    for line in lines {
+        if strip_tabs {
+            text.push_str(line.text.trim_start_matches('\t'));
+        } else {
+            text.push_str(&line.text);
+        }
+        text.push('\n');
+    }
+    text
+}
+
 /// Check if a string is a valid POSIX variable name
 ///
 /// Variable names must start with letter or underscore, followed by
 /// letters, digits, or underscores
-fn is_valid_variable_name(name: &str) -> bool {
+#[must_use]
+#[inline]
+pub fn is_valid_variable_name(name: &str) -> bool {
     if name.is_empty() {
         return false;
     }
@@ -80,6 +119,22 @@ fn is_valid_variable_name(name: &str) -> bool {
     chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
+/// Whether `name` is a special parameter such as `?`, `@` or `#`, or a
+/// positional parameter such as `1` or `10`
+pub fn is_special_parameter(name: &str) -> bool {
+    matches!(name, "?" | "@" | "*" | "#" | "$" | "!" | "-")
+        || (!name.is_empty() && name.bytes().all(|byte| byte.is_ascii_digit()))
+}
+
+/// Check if a string is a variable name with an optional `[subscript]`, or
+/// a special parameter
+fn is_valid_parameter_name(name: &str) -> bool {
+    match crate::variable_resolver::split_subscript(name) {
+        Some((base, index)) => is_valid_variable_name(base) && !index.is_empty(),
+        None => is_valid_variable_name(name) || is_special_parameter(name),
+    }
+}
+
 /// Extract assignment tokens from a list and convert to (name, value) pairs
 pub fn extract_assignments(tokens: &[SpannedToken]) -> Vec<(String, String)> {
     let mut assignments = Vec::new();
@@ -109,17 +164,21 @@ pub fn extract_arguments(tokens: &[SpannedToken]) -> Vec<String> {
 /// Filters out assignment words from prefix, includes all suffix tokens
 pub fn combine_args(prefix: &[SpannedToken], suffix: &[SpannedToken]) -> Vec<String> {
     let mut args = extract_arguments(prefix);
-    args.extend(extract_arguments(suffix));
+    args.extend(suffix.iter().map(token_to_string));
     args
 }
 
 /// Parse a simple parameter expansion ($var) into an expansion request
 ///
 /// Returns None if the text doesn't match the expected format
+#[must_use]
 pub fn parse_simple_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
     if text.starts_with('$') && text.len() > 1 {
         let var_name = &text[1..];
-        if is_valid_variable_name(var_name) {
+        // Without braces only single-digit positional parameters are allowed
+        if is_valid_variable_name(var_name)
+            || (is_special_parameter(var_name) && var_name.len() == 1)
+        {
             Some(ExpansionRequest::simple(var_name.to_string()))
         } else {
             None
@@ -132,6 +191,7 @@ pub fn parse_simple_parameter_expansion(text: &str) -> Option<ExpansionRequest>
 /// Parse a braced parameter expansion (${var}, ${var:-default}, etc.) into an expansion request
 ///
 /// Supports all POSIX parameter expansion modes
+#[must_use]
 pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
     if !text.starts_with("${") || !text.ends_with('}') {
         return None;
@@ -139,12 +199,16 @@ pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
 
     let inner = &text[2..text.len() - 1];
 
+    if let Some(name) = inner.strip_prefix('#').filter(|name| !name.is_empty()) {
+        return parse_length_expansion(name);
+    }
+
     // Check for different expansion modes
     if let Some(colon_pos) = inner.find(':') {
         let var_name = &inner[..colon_pos];
         let rest = &inner[colon_pos + 1..];
 
-        if !is_valid_variable_name(var_name) {
+        if !is_valid_parameter_name(var_name) {
             return None;
         }
 
@@ -157,6 +221,7 @@ pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
                     mode: ExpansionMode::DefaultValue,
                     parameter: Some(default_value.to_string()),
                     check_unset: true,
+                    nounset_safe: true,
                 })
             }
             Some('=') => {
@@ -167,6 +232,7 @@ pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
                     mode: ExpansionMode::AssignDefault,
                     parameter: Some(default_value.to_string()),
                     check_unset: true,
+                    nounset_safe: true,
                 })
             }
             Some('?') => {
@@ -181,6 +247,7 @@ pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
                     mode: ExpansionMode::ErrorIfUnset,
                     parameter: message,
                     check_unset: true,
+                    nounset_safe: true,
                 })
             }
             Some('+') => {
@@ -191,17 +258,24 @@ pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
                     mode: ExpansionMode::AlternativeValue,
                     parameter: Some(alternative.to_string()),
                     check_unset: true,
+                    nounset_safe: true,
                 })
             }
             _ => None,
         }
-    } else if let Some(operator_pos) = inner.find_any(&['-', '=', '?', '+']) {
-        // Non-colon versions (test only for unset, not null)
+    } else if let Some(operator_pos) = inner
+        .get(1..)
+        .and_then(|rest| rest.find_any(&['-', '=', '?', '+']))
+        .map(|pos| pos + 1)
+    {
+        // Non-colon versions (test only for unset, not null). The name is
+        // never empty, so `${?}` and `${-}` name special parameters rather
+        // than starting with an operator
         let var_name = &inner[..operator_pos];
         let operator = inner.chars().nth(operator_pos).unwrap();
         let rest = &inner[operator_pos + 1..];
 
-        if !is_valid_variable_name(var_name) {
+        if !is_valid_parameter_name(var_name) {
             return None;
         }
 
@@ -213,6 +287,7 @@ pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
                     mode: ExpansionMode::DefaultValue,
                     parameter: Some(rest.to_string()),
                     check_unset: false,
+                    nounset_safe: true,
                 })
             }
             '=' => {
@@ -222,6 +297,7 @@ pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
                     mode: ExpansionMode::AssignDefault,
                     parameter: Some(rest.to_string()),
                     check_unset: false,
+                    nounset_safe: true,
                 })
             }
             '?' => {
@@ -236,6 +312,7 @@ pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
                     mode: ExpansionMode::ErrorIfUnset,
                     parameter: message,
                     check_unset: false,
+                    nounset_safe: true,
                 })
             }
             '+' => {
@@ -245,13 +322,14 @@ pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
                     mode: ExpansionMode::AlternativeValue,
                     parameter: Some(rest.to_string()),
                     check_unset: false,
+                    nounset_safe: true,
                 })
             }
             _ => None,
         }
     } else {
         // Simple ${var} expansion
-        if is_valid_variable_name(inner) {
+        if is_valid_parameter_name(inner) || is_special_parameter(inner) {
             Some(ExpansionRequest::simple(inner.to_string()))
         } else {
             None
@@ -259,6 +337,29 @@ pub fn parse_parameter_expansion(text: &str) -> Option<ExpansionRequest> {
     }
 }
 
+/// Parse the name in `${#name}`, counting elements for `arr[@]`, `arr[*]`,
+/// `@` and `*` and characters otherwise
+fn parse_length_expansion(name: &str) -> Option<ExpansionRequest> {
+    let counts_elements = match crate::variable_resolver::split_subscript(name) {
+        Some((base, "@" | "*")) => is_valid_variable_name(base),
+        _ => matches!(name, "@" | "*"),
+    };
+    let (mode, nounset_safe) = if counts_elements {
+        (ExpansionMode::Count, true)
+    } else if is_valid_parameter_name(name) {
+        (ExpansionMode::Length, false)
+    } else {
+        return None;
+    };
+    Some(ExpansionRequest {
+        variable_name: name.to_string(),
+        mode,
+        parameter: None,
+        check_unset: false,
+        nounset_safe,
+    })
+}
+
 /// Helper trait to find any of multiple characters
 trait FindAny {
     fn find_any(&self, chars: &[char]) -> Option<usize>;
@@ -296,8 +397,12 @@ mod tests {
 
     #[test]
     fn test_token_to_string() {
-        let string_token = make_token(Token::String, "\"hello world\"");
-        assert_eq!(token_to_string(&string_token), "hello world");
+        // Quotes are left for the interpreter's quote removal step
+        let string_token = make_token(Token::String, "'$x'");
+        assert_eq!(token_to_string(&string_token), "'$x'");
+
+        let string_token = make_token(Token::String, "\"hello $name\"");
+        assert_eq!(token_to_string(&string_token), "\"hello $name\"");
 
         let word_token = make_token(Token::Word, "hello");
         assert_eq!(token_to_string(&word_token), "hello");
@@ -333,6 +438,13 @@ mod tests {
         assert_eq!(parse_assignment("=value"), None);
     }
 
+    #[test]
+    fn test_quote_for_shell() {
+        assert_eq!(quote_for_shell("plain"), "'plain'");
+        assert_eq!(quote_for_shell(""), "''");
+        assert_eq!(quote_for_shell("it's $x"), r#"'it'"'"'s $x'"#);
+    }
+
     #[test]
     fn test_is_valid_variable_name() {
         assert!(is_valid_variable_name("var"));
@@ -375,7 +487,7 @@ mod tests {
         let args = extract_arguments(&tokens);
         assert_eq!(args.len(), 3);
         assert_eq!(args[0], "echo");
-        assert_eq!(args[1], "hello world");
+        assert_eq!(args[1], "\"hello world\"");
         assert_eq!(args[2], "test");
     }
 
@@ -394,7 +506,7 @@ mod tests {
         assert_eq!(combined.len(), 3);
         assert_eq!(combined[0], "arg1");
         assert_eq!(combined[1], "arg2");
-        assert_eq!(combined[2], "arg 3");
+        assert_eq!(combined[2], "\"arg 3\"");
     }
 
     #[test]
@@ -404,6 +516,15 @@ mod tests {
         assert_eq!(request.variable_name, "var");
         assert_eq!(request.mode, ExpansionMode::Normal);
 
+        let request = parse_simple_parameter_expansion("$1").unwrap();
+        assert_eq!(request.variable_name, "1");
+        assert_eq!(
+            parse_simple_parameter_expansion("$#")
+                .unwrap()
+                .variable_name,
+            "#"
+        );
+
         // Invalid cases
         assert!(parse_simple_parameter_expansion("$123").is_none());
         assert!(parse_simple_parameter_expansion("$").is_none());
@@ -450,6 +571,65 @@ mod tests {
         assert!(request.check_unset);
     }
 
+    #[test]
+    fn test_parse_parameter_expansion_subscript() {
+        let request = parse_parameter_expansion("${COPROC[1]}").unwrap();
+        assert_eq!(request.variable_name, "COPROC[1]");
+        assert_eq!(request.mode, ExpansionMode::Normal);
+
+        assert!(parse_parameter_expansion("${arr[]}").is_none());
+        assert!(parse_parameter_expansion("${1arr[0]}").is_none());
+    }
+
+    #[test]
+    fn test_parse_parameter_expansion_special_parameters() {
+        for name in ["?", "$", "!", "-", "#", "0", "10"] {
+            let request = parse_parameter_expansion(&format!("${{{name}}}")).unwrap();
+            assert_eq!(request.variable_name, name);
+            assert_eq!(request.mode, ExpansionMode::Normal);
+        }
+
+        let request = parse_parameter_expansion("${!-none}").unwrap();
+        assert_eq!(request.variable_name, "!");
+        assert_eq!(request.mode, ExpansionMode::DefaultValue);
+        assert_eq!(request.parameter, Some("none".to_string()));
+
+        let request = parse_parameter_expansion("${#?}").unwrap();
+        assert_eq!(request.variable_name, "?");
+        assert_eq!(request.mode, ExpansionMode::Length);
+    }
+
+    #[test]
+    fn test_parse_parameter_expansion_length() {
+        let request = parse_parameter_expansion("${#var}").unwrap();
+        assert_eq!(request.variable_name, "var");
+        assert_eq!(request.mode, ExpansionMode::Length);
+
+        for text in ["${#arr[@]}", "${#arr[*]}", "${#@}", "${#*}"] {
+            let request = parse_parameter_expansion(text).unwrap();
+            assert_eq!(request.mode, ExpansionMode::Count, "{text}");
+        }
+        assert_eq!(
+            parse_parameter_expansion("${#arr[1]}").unwrap().mode,
+            ExpansionMode::Length
+        );
+        assert!(parse_parameter_expansion("${#1arr}").is_none());
+    }
+
+    #[test]
+    fn test_nounset_safe_forms() {
+        assert!(!parse_simple_parameter_expansion("$x").unwrap().nounset_safe);
+        assert!(!parse_parameter_expansion("${x}").unwrap().nounset_safe);
+        for text in [
+            "${x:-d}", "${x-d}", "${x:=d}", "${x?m}", "${x:+a}", "${x+a}",
+        ] {
+            assert!(
+                parse_parameter_expansion(text).unwrap().nounset_safe,
+                "{text}"
+            );
+        }
+    }
+
     #[test]
     fn test_find_any() {
         assert_eq!("hello-world".find_any(&['-', '+']), Some(5));
@@ -3,7 +3,13 @@
 //! Provides the foundation for parameter expansion, variable scoping,
 //! and context-aware string resolution needed for POSIX shell behavior.
 
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+/// Environment values imported so far, each kept once for the rest of the
+/// process so every context holding one borrows the same copy
+static ENV_VALUES: LazyLock<Mutex<HashSet<&'static str>>> = LazyLock::new(Mutex::default);
 
 /// Variable resolution context for parameter expansion
 ///
@@ -11,10 +17,23 @@ use std::collections::HashMap;
 /// error handling, and nested contexts as we implement more POSIX features
 #[derive(Debug, Clone)]
 pub struct VariableContext {
-    /// Current variable bindings
-    variables: HashMap<String, String>,
+    /// Current variable bindings; fixed values from `set_static` and
+    /// environment values from `set_from_env` are borrowed, so copying the
+    /// context does not copy them
+    variables: HashMap<String, Cow<'static, str>>,
+    /// Indexed array bindings
+    arrays: HashMap<String, Vec<String>>,
+    /// Names passed to the environment of spawned commands
+    exported: HashSet<String>,
+    /// Names that may no longer be assigned or unset
+    readonly: HashSet<String>,
+    /// Positional parameters `$1`, `$2`, ... in order
+    positional_params: Vec<String>,
     /// Parent context for nested scopes (future use)
     parent: Option<Box<VariableContext>>,
+    /// Whether `$name` falls back to the process environment when `name`
+    /// is unset here
+    inherit_env: bool,
 }
 
 impl VariableContext {
@@ -23,38 +42,324 @@ impl VariableContext {
     pub fn new() -> Self {
         Self {
             variables: HashMap::new(),
+            arrays: HashMap::new(),
+            exported: HashSet::new(),
+            readonly: HashSet::new(),
+            positional_params: Vec::new(),
             parent: None,
+            inherit_env: false,
+        }
+    }
+
+    /// Create a context holding every variable of the process environment,
+    /// all exported
+    ///
+    /// Everything is imported up front, so the context does not also fall
+    /// back to the environment on lookup
+    #[must_use]
+    pub fn from_env() -> Self {
+        let mut context = Self::new();
+        for (name, value) in std::env::vars() {
+            context.exported.insert(name.clone());
+            context.set_from_env(name, value);
+        }
+        context
+    }
+
+    /// Value of the process environment variable `name`, if it is set and
+    /// valid Unicode
+    #[must_use]
+    pub fn env_var(name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+
+    /// Let `$name` expand to the process environment variable `name` when
+    /// no shell variable of that name is set
+    ///
+    /// Off by default, so a script only sees the environment it was given
+    pub fn set_inherit_env(&mut self, inherit: bool) {
+        self.inherit_env = inherit;
+    }
+
+    /// Whether unset variables fall back to the process environment
+    #[must_use]
+    pub const fn inherits_env(&self) -> bool {
+        self.inherit_env
+    }
+
+    /// Create an empty context with room for `n` variables before it
+    /// reallocates
+    #[must_use]
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            variables: HashMap::with_capacity(n),
+            ..Self::new()
         }
     }
 
+    /// Make room for `additional` more variables
+    pub fn reserve(&mut self, additional: usize) {
+        self.variables.reserve(additional);
+    }
+
+    /// Release memory held for variables that have since been unset
+    pub fn shrink_to_fit(&mut self) {
+        self.variables.shrink_to_fit();
+        self.arrays.shrink_to_fit();
+        self.exported.shrink_to_fit();
+        self.readonly.shrink_to_fit();
+    }
+
+    /// Number of variables this context can hold without reallocating
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.variables.capacity()
+    }
+
     /// Create a new context with a parent for nested scoping
     #[must_use]
     pub fn with_parent(parent: VariableContext) -> Self {
         Self {
             variables: HashMap::new(),
+            arrays: HashMap::new(),
+            exported: HashSet::new(),
+            readonly: HashSet::new(),
+            positional_params: parent.positional_params.clone(),
+            inherit_env: parent.inherit_env,
             parent: Some(Box::new(parent)),
         }
     }
 
+    /// Flatten `other` and all its parents into a single-level context
+    ///
+    /// Bindings in inner contexts hide those of the same name further out,
+    /// exactly as lookups through `other` would see them. A subshell starts
+    /// from such a copy, so nothing it assigns reaches the original.
+    #[must_use]
+    pub fn merge(other: &VariableContext) -> VariableContext {
+        let mut merged = other.parent.as_deref().map_or_else(Self::new, Self::merge);
+        for name in other.variables.keys().chain(other.arrays.keys()) {
+            merged.variables.remove(name);
+            merged.arrays.remove(name);
+        }
+        merged.variables.extend(other.variables.clone());
+        merged.arrays.extend(other.arrays.clone());
+        merged.exported.extend(other.exported.iter().cloned());
+        merged.readonly.extend(other.readonly.iter().cloned());
+        merged.positional_params = other.positional_params.clone();
+        merged.inherit_env = other.inherit_env;
+        merged
+    }
+
+    /// Variables whose value differs between this context and `other`, in
+    /// name order, as `(name, value here, value in other)`
+    ///
+    /// A variable only set in `other` was added and one only set here was
+    /// removed. Values are compared as `$name` would expand them.
+    pub fn diff<'a>(
+        &'a self,
+        other: &'a VariableContext,
+    ) -> impl Iterator<Item = (&'a str, Option<&'a str>, Option<&'a str>)> {
+        let mut names = self.visible_names();
+        names.extend(other.visible_names());
+        names.into_iter().filter_map(move |name| {
+            let before = self.get(name);
+            let after = other.get(name);
+            (before != after).then_some((name, before, after))
+        })
+    }
+
+    /// Names of every variable and array in this context and its parents
+    fn visible_names(&self) -> BTreeSet<&str> {
+        let mut names = BTreeSet::new();
+        let mut context = Some(self);
+        while let Some(current) = context {
+            names.extend(current.variables.keys().map(String::as_str));
+            names.extend(current.arrays.keys().map(String::as_str));
+            context = current.parent.as_deref();
+        }
+        names
+    }
+
     /// Set a variable in the current context
     pub fn set(&mut self, name: String, value: String) {
-        self.variables.insert(name, value);
+        self.variables.insert(name, Cow::Owned(value));
+    }
+
+    /// Set a variable to a value that lives for the whole program, such as
+    /// a default, without allocating a copy of it
+    pub fn set_static(&mut self, name: String, value: &'static str) {
+        self.variables.insert(name, Cow::Borrowed(value));
+    }
+
+    /// Set a variable to a value imported from the process environment
+    ///
+    /// The value is interned, so importing the environment again or copying
+    /// the context for a subshell or snapshot does not copy it
+    pub fn set_from_env(&mut self, name: String, value: String) {
+        let mut values = ENV_VALUES.lock().unwrap_or_else(PoisonError::into_inner);
+        let interned = match values.get(value.as_str()) {
+            Some(interned) => interned,
+            None => {
+                let interned: &'static str = value.leak();
+                values.insert(interned);
+                interned
+            }
+        };
+        self.variables.insert(name, Cow::Borrowed(interned));
     }
 
     /// Get a variable value, checking parent contexts if not found locally
-    pub fn get(&self, name: &str) -> Option<&String> {
+    ///
+    /// `name[index]` looks up an array element; a bare array name refers to
+    /// its first element
+    #[must_use]
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        if let Some((base, index)) = split_subscript(name) {
+            let index: usize = index.parse().ok()?;
+            return self
+                .get_array(base)
+                .and_then(|values| values.get(index))
+                .map(String::as_str);
+        }
         self.variables
             .get(name)
+            .map(AsRef::as_ref)
+            .or_else(|| {
+                self.arrays
+                    .get(name)
+                    .and_then(|values| values.first())
+                    .map(String::as_str)
+            })
             .or_else(|| self.parent.as_ref().and_then(|parent| parent.get(name)))
     }
 
-    /// Check if a variable exists in any accessible context
-    pub fn contains(&self, name: &str) -> bool {
-        self.variables.contains_key(name)
+    /// Remove a variable or array from the current context
+    pub fn unset(&mut self, name: &str) {
+        self.variables.remove(name);
+        self.arrays.remove(name);
+        self.exported.remove(name);
+    }
+
+    /// Mark a variable for export to spawned commands
+    ///
+    /// The name may be marked before it has a value
+    pub fn export(&mut self, name: &str) {
+        self.exported.insert(name.to_string());
+    }
+
+    /// Stop exporting a variable of the current context
+    ///
+    /// The value is kept; an export mark in a parent context still applies
+    pub fn unexport(&mut self, name: &str) {
+        self.exported.remove(name);
+    }
+
+    /// Check whether a variable is marked for export in any accessible context
+    pub fn is_exported(&self, name: &str) -> bool {
+        self.exported.contains(name)
+            || self
+                .parent
+                .as_ref()
+                .is_some_and(|parent| parent.is_exported(name))
+    }
+
+    /// Sorted names marked for export in any accessible context, with or
+    /// without a value
+    #[must_use]
+    pub fn exported_names(&self) -> Vec<String> {
+        self.collect_names(|context| &context.exported)
+    }
+
+    /// Mark a variable readonly; the name may be marked before it has a value
+    pub fn mark_readonly(&mut self, name: &str) {
+        self.readonly.insert(name.to_string());
+    }
+
+    /// Check whether a variable is readonly in any accessible context
+    pub fn is_readonly(&self, name: &str) -> bool {
+        self.readonly.contains(name)
             || self
                 .parent
                 .as_ref()
-                .map_or(false, |parent| parent.contains(name))
+                .is_some_and(|parent| parent.is_readonly(name))
+    }
+
+    /// Sorted names marked readonly in any accessible context
+    #[must_use]
+    pub fn readonly_names(&self) -> Vec<String> {
+        self.collect_names(|context| &context.readonly)
+    }
+
+    /// Sorted union of a name set over this context and its parents
+    fn collect_names(&self, set: impl Fn(&Self) -> &HashSet<String>) -> Vec<String> {
+        let mut names = HashSet::new();
+        let mut context = Some(self);
+        while let Some(current) = context {
+            names.extend(set(current).iter().cloned());
+            context = current.parent.as_deref();
+        }
+        let mut names: Vec<String> = names.into_iter().collect();
+        names.sort();
+        names
+    }
+
+    /// Get a variable's value as an owned string
+    ///
+    /// Unlike `get`, this also handles `name[@]` and `name[*]`, which join
+    /// all array elements with spaces, `@` and `*`, which join the
+    /// positional parameters, `#`, their count, and positional parameters
+    /// such as `1`. `0` is an ordinary variable holding the script name.
+    pub fn value(&self, name: &str) -> Option<String> {
+        match name {
+            "@" | "*" => return Some(self.positional_params.join(" ")),
+            "#" => return Some(self.positional_params.len().to_string()),
+            _ => {}
+        }
+        if let Some(index) = name.parse::<usize>().ok().filter(|index| *index > 0) {
+            return self.positional_params.get(index - 1).cloned();
+        }
+        if let Some((base, "@" | "*")) = split_subscript(name) {
+            return self
+                .get_array(base)
+                .map(|values| values.join(" "))
+                .or_else(|| self.get(base).map(str::to_string));
+        }
+        self.get(name).map(str::to_string)
+    }
+
+    /// Set an indexed array in the current context
+    pub fn set_array(&mut self, name: String, values: Vec<String>) {
+        self.variables.remove(&name);
+        self.arrays.insert(name, values);
+    }
+
+    /// Get an indexed array, checking parent contexts if not found locally
+    pub fn get_array(&self, name: &str) -> Option<&Vec<String>> {
+        self.arrays.get(name).or_else(|| {
+            self.parent
+                .as_ref()
+                .and_then(|parent| parent.get_array(name))
+        })
+    }
+
+    /// Positional parameters, the words `$@` expands to
+    #[must_use]
+    pub fn positional_params(&self) -> &[String] {
+        &self.positional_params
+    }
+
+    /// Replace the positional parameters, as `set --` does
+    pub fn set_positional_params(&mut self, params: Vec<String>) {
+        self.positional_params = params;
+    }
+
+    /// Check if a variable exists in any accessible context
+    #[must_use]
+    #[inline]
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
     }
 
     /// Get all variable names from all accessible contexts
@@ -69,6 +374,29 @@ impl VariableContext {
         names
     }
 
+    /// Name-value pairs of every variable visible from this context
+    ///
+    /// Parent contexts are included, but a name bound here, as a variable
+    /// or an array, hides the parent's binding. Arrays are not listed.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        let parent: Box<dyn Iterator<Item = (&str, &str)> + '_> = match &self.parent {
+            Some(parent) => Box::new(parent.iter()),
+            None => Box::new(std::iter::empty()),
+        };
+        self.variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_ref()))
+            .chain(parent.filter(|(name, _)| {
+                !self.variables.contains_key(*name) && !self.arrays.contains_key(*name)
+            }))
+    }
+
+    /// Like `iter`, but only variables marked for export, as passed to the
+    /// environment of spawned commands
+    pub fn iter_exported(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.iter().filter(|(name, _)| self.is_exported(name))
+    }
+
     /// Import variables from another context (shallow copy)
     pub fn import_from(&mut self, other: &VariableContext) {
         for (name, value) in &other.variables {
@@ -78,8 +406,63 @@ impl VariableContext {
 
     /// Get a copy of all variables in the current context only
     pub fn current_variables(&self) -> HashMap<String, String> {
-        self.variables.clone()
+        self.variables
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_string()))
+            .collect()
     }
+
+    /// Take a deep copy of every binding, including parent contexts
+    #[must_use]
+    pub fn snapshot(&self) -> VariableSnapshot {
+        VariableSnapshot {
+            variables: self.variables.clone(),
+            arrays: self.arrays.clone(),
+            exported: self.exported.clone(),
+            readonly: self.readonly.clone(),
+            positional_params: self.positional_params.clone(),
+            parent: self.parent.clone(),
+            inherit_env: self.inherit_env,
+        }
+    }
+
+    /// Replace the whole context with a previously taken snapshot
+    ///
+    /// Bindings created after the snapshot are discarded
+    pub fn restore(&mut self, snapshot: VariableSnapshot) {
+        self.variables = snapshot.variables;
+        self.arrays = snapshot.arrays;
+        self.exported = snapshot.exported;
+        self.readonly = snapshot.readonly;
+        self.positional_params = snapshot.positional_params;
+        self.parent = snapshot.parent;
+        self.inherit_env = snapshot.inherit_env;
+    }
+}
+
+/// Saved state of a `VariableContext`
+///
+/// Used to run code such as trap handlers without letting its assignments
+/// leak into the surrounding environment
+#[derive(Debug, Clone)]
+pub struct VariableSnapshot {
+    variables: HashMap<String, Cow<'static, str>>,
+    arrays: HashMap<String, Vec<String>>,
+    exported: HashSet<String>,
+    readonly: HashSet<String>,
+    positional_params: Vec<String>,
+    parent: Option<Box<VariableContext>>,
+    inherit_env: bool,
+}
+
+/// Split `name[index]` into its name and subscript
+///
+/// Returns None for names without a subscript
+#[must_use]
+pub fn split_subscript(name: &str) -> Option<(&str, &str)> {
+    let open = name.find('[')?;
+    let inner = name[open + 1..].strip_suffix(']')?;
+    Some((&name[..open], inner))
 }
 
 impl Default for VariableContext {
@@ -93,6 +476,7 @@ impl Default for VariableContext {
 /// This enum will be used when we implement full parameter expansion
 /// to handle different expansion behaviors
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ExpansionMode {
     /// Normal expansion: $var or ${var}
     Normal,
@@ -104,13 +488,17 @@ pub enum ExpansionMode {
     ErrorIfUnset,
     /// Alternative value: ${var:+value}
     AlternativeValue,
+    /// String length: ${#var}
+    Length,
+    /// Element count: ${#array[@]}, ${#array[*]} or ${#@} for positional parameters
+    Count,
 }
 
 /// Parameter expansion request
 ///
 /// This struct will be used when we implement parameter expansion
 /// to represent expansion requests and their context
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ExpansionRequest {
     /// Variable name to expand
     pub variable_name: String,
@@ -120,6 +508,9 @@ pub struct ExpansionRequest {
     pub parameter: Option<String>,
     /// Whether to check for unset (: prefix in expansion)
     pub check_unset: bool,
+    /// Whether the expansion copes with an unset variable itself, so
+    /// `nounset` does not apply: true for every form except plain `$var`
+    pub nounset_safe: bool,
 }
 
 impl ExpansionRequest {
@@ -131,6 +522,7 @@ impl ExpansionRequest {
             mode: ExpansionMode::Normal,
             parameter: None,
             check_unset: false,
+            nounset_safe: false,
         }
     }
 
@@ -142,6 +534,7 @@ impl ExpansionRequest {
             mode: ExpansionMode::DefaultValue,
             parameter: Some(default_value),
             check_unset: false,
+            nounset_safe: true,
         }
     }
 }
@@ -150,7 +543,7 @@ impl ExpansionRequest {
 ///
 /// Used to communicate the result of variable resolution and
 /// any side effects (like assignments) that occurred
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ResolutionResult {
     /// Variable resolved successfully
     Resolved(String),
@@ -164,18 +557,24 @@ pub enum ResolutionResult {
 ///
 /// This function will be expanded to handle all POSIX parameter expansion
 /// modes as we implement them
+#[must_use]
 pub fn resolve_expansion(
     context: &mut VariableContext,
     request: &ExpansionRequest,
 ) -> ResolutionResult {
     match request.mode {
-        ExpansionMode::Normal => match context.get(&request.variable_name) {
-            Some(value) => ResolutionResult::Resolved(value.clone()),
+        ExpansionMode::Normal => match context.value(&request.variable_name).or_else(|| {
+            context
+                .inherit_env
+                .then(|| VariableContext::env_var(&request.variable_name))
+                .flatten()
+        }) {
+            Some(value) => ResolutionResult::Resolved(value),
             None => ResolutionResult::Unset,
         },
-        ExpansionMode::DefaultValue => match context.get(&request.variable_name) {
+        ExpansionMode::DefaultValue => match context.value(&request.variable_name) {
             Some(value) if !value.is_empty() || !request.check_unset => {
-                ResolutionResult::Resolved(value.clone())
+                ResolutionResult::Resolved(value)
             }
             _ => match &request.parameter {
                 Some(default) => ResolutionResult::Resolved(default.clone()),
@@ -184,9 +583,9 @@ pub fn resolve_expansion(
                 ),
             },
         },
-        ExpansionMode::AssignDefault => match context.get(&request.variable_name) {
+        ExpansionMode::AssignDefault => match context.value(&request.variable_name) {
             Some(value) if !value.is_empty() || !request.check_unset => {
-                ResolutionResult::Resolved(value.clone())
+                ResolutionResult::Resolved(value)
             }
             _ => match &request.parameter {
                 Some(default) => {
@@ -198,9 +597,9 @@ pub fn resolve_expansion(
                 ),
             },
         },
-        ExpansionMode::ErrorIfUnset => match context.get(&request.variable_name) {
+        ExpansionMode::ErrorIfUnset => match context.value(&request.variable_name) {
             Some(value) if !value.is_empty() || !request.check_unset => {
-                ResolutionResult::Resolved(value.clone())
+                ResolutionResult::Resolved(value)
             }
             _ => {
                 let message = request.parameter.as_ref().map_or_else(
@@ -210,13 +609,28 @@ pub fn resolve_expansion(
                 ResolutionResult::Error(message)
             }
         },
-        ExpansionMode::AlternativeValue => match context.get(&request.variable_name) {
+        ExpansionMode::AlternativeValue => match context.value(&request.variable_name) {
             Some(value) if !value.is_empty() || !request.check_unset => match &request.parameter {
                 Some(alternative) => ResolutionResult::Resolved(alternative.clone()),
                 None => ResolutionResult::Resolved(String::new()),
             },
             _ => ResolutionResult::Resolved(String::new()),
         },
+        ExpansionMode::Length => match context.value(&request.variable_name) {
+            Some(value) => ResolutionResult::Resolved(value.chars().count().to_string()),
+            None => ResolutionResult::Unset,
+        },
+        ExpansionMode::Count => {
+            let count = match split_subscript(&request.variable_name) {
+                Some((base, _)) => context
+                    .get_array(base)
+                    .map(Vec::len)
+                    .or_else(|| context.get(base).map(|_| 1))
+                    .unwrap_or(0),
+                None => context.positional_params().len(),
+            };
+            ResolutionResult::Resolved(count.to_string())
+        }
     }
 }
 
@@ -233,10 +647,42 @@ mod tests {
 
         context.set("var".to_string(), "value".to_string());
 
-        assert_eq!(context.get("var"), Some(&"value".to_string()));
+        assert_eq!(context.get("var"), Some("value"));
         assert!(context.contains("var"));
     }
 
+    #[test]
+    fn test_static_values() {
+        let mut context = VariableContext::new();
+        context.set_static("IFS".to_string(), " \t\n");
+        assert_eq!(context.get("IFS"), Some(" \t\n"));
+        assert_eq!(context.current_variables()["IFS"], " \t\n");
+
+        let copy = VariableContext::merge(&context);
+        context.set("IFS".to_string(), ",".to_string());
+        assert_eq!(context.get("IFS"), Some(","));
+        assert_eq!(copy.get("IFS"), Some(" \t\n"));
+    }
+
+    #[test]
+    fn test_capacity_grows_and_shrinks() {
+        let mut context = VariableContext::with_capacity(100);
+        assert!(context.capacity() >= 100);
+
+        for n in 0..200 {
+            context.set(format!("var_{n}"), n.to_string());
+        }
+        for n in 1..200 {
+            context.unset(&format!("var_{n}"));
+        }
+        context.shrink_to_fit();
+        assert!(context.capacity() < 100);
+        assert_eq!(context.get("var_0"), Some("0"));
+
+        context.reserve(500);
+        assert!(context.capacity() >= 501);
+    }
+
     #[test]
     fn test_nested_context() {
         let mut parent = VariableContext::new();
@@ -245,13 +691,30 @@ mod tests {
         let mut child = VariableContext::with_parent(parent);
         child.set("child_var".to_string(), "child_value".to_string());
 
-        assert_eq!(child.get("child_var"), Some(&"child_value".to_string()));
-        assert_eq!(child.get("parent_var"), Some(&"parent_value".to_string()));
+        assert_eq!(child.get("child_var"), Some("child_value"));
+        assert_eq!(child.get("parent_var"), Some("parent_value"));
         assert!(child.contains("parent_var"));
 
         // Child variables shadow parent
         child.set("parent_var".to_string(), "overridden".to_string());
-        assert_eq!(child.get("parent_var"), Some(&"overridden".to_string()));
+        assert_eq!(child.get("parent_var"), Some("overridden"));
+    }
+
+    #[test]
+    fn test_array_variables() {
+        let mut context = VariableContext::new();
+        context.set_array("arr".to_string(), vec!["10".to_string(), "11".to_string()]);
+
+        assert_eq!(context.get("arr[1]"), Some("11"));
+        assert_eq!(context.get("arr"), Some("10"));
+        assert!(context.get("arr[2]").is_none());
+        assert!(context.contains("arr[0]"));
+
+        assert_eq!(context.value("arr[@]"), Some("10 11".to_string()));
+        assert_eq!(context.value("arr[*]"), Some("10 11".to_string()));
+
+        let child = VariableContext::with_parent(context);
+        assert_eq!(child.get_array("arr").map(Vec::len), Some(2));
     }
 
     #[test]
@@ -268,6 +731,24 @@ mod tests {
         assert_eq!(names, vec!["a", "b", "c"]);
     }
 
+    #[test]
+    fn test_iter_shadows_parent_bindings() {
+        let mut parent = VariableContext::new();
+        parent.set("outer".to_string(), "1".to_string());
+        parent.set("shadowed".to_string(), "parent".to_string());
+        parent.set("listed".to_string(), "parent".to_string());
+        parent.export("outer");
+
+        let mut child = VariableContext::with_parent(parent);
+        child.set("shadowed".to_string(), "child".to_string());
+        child.set_array("listed".to_string(), vec!["a".to_string()]);
+
+        let mut pairs: Vec<(&str, &str)> = child.iter().collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs, [("outer", "1"), ("shadowed", "child")]);
+        assert_eq!(child.iter_exported().collect::<Vec<_>>(), [("outer", "1")]);
+    }
+
     #[test]
     fn test_import_from() {
         let mut source = VariableContext::new();
@@ -277,8 +758,95 @@ mod tests {
         let mut target = VariableContext::new();
         target.import_from(&source);
 
-        assert_eq!(target.get("var1"), Some(&"value1".to_string()));
-        assert_eq!(target.get("var2"), Some(&"value2".to_string()));
+        assert_eq!(target.get("var1"), Some("value1"));
+        assert_eq!(target.get("var2"), Some("value2"));
+    }
+
+    #[test]
+    fn test_merge_flattens_parents() {
+        let mut parent = VariableContext::new();
+        parent.set("a".to_string(), "outer".to_string());
+        parent.set("b".to_string(), "2".to_string());
+        parent.set_array("list".to_string(), vec!["x".to_string()]);
+        parent.export("b");
+
+        let mut child = VariableContext::with_parent(parent);
+        child.set("a".to_string(), "inner".to_string());
+        child.set("list".to_string(), "scalar".to_string());
+        child.mark_readonly("a");
+
+        let merged = VariableContext::merge(&child);
+        assert!(merged.parent.is_none());
+        assert_eq!(merged.get("a"), Some("inner"));
+        assert_eq!(merged.get("b"), Some("2"));
+        assert_eq!(merged.get("list"), Some("scalar"));
+        assert!(merged.get_array("list").is_none());
+        assert!(merged.is_exported("b"));
+        assert!(merged.is_readonly("a"));
+        assert_eq!(merged.diff(&child).count(), 0);
+    }
+
+    #[test]
+    fn test_diff_reports_added_removed_and_changed() {
+        let mut before = VariableContext::new();
+        before.set("same".to_string(), "1".to_string());
+        before.set("changed".to_string(), "old".to_string());
+        before.set("removed".to_string(), "gone".to_string());
+
+        let mut after = VariableContext::merge(&before);
+        after.set("changed".to_string(), "new".to_string());
+        after.unset("removed");
+        after.set("added".to_string(), "here".to_string());
+
+        let changes: Vec<_> = before.diff(&after).collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("added", None, Some("here")),
+                ("changed", Some("old"), Some("new")),
+                ("removed", Some("gone"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut context = VariableContext::new();
+        context.set("X".to_string(), "original".to_string());
+        context.set_array("arr".to_string(), vec!["a".to_string()]);
+
+        let snapshot = context.snapshot();
+        context.set("X".to_string(), "modified".to_string());
+        context.set("NEW".to_string(), "1".to_string());
+        context.unset("arr");
+
+        context.set_inherit_env(true);
+
+        context.restore(snapshot);
+        assert_eq!(context.get("X"), Some("original"));
+        assert!(!context.contains("NEW"));
+        assert_eq!(context.value("arr[@]"), Some("a".to_string()));
+        assert!(!context.inherits_env());
+    }
+
+    #[test]
+    fn test_iter_exported_uses_local_values() {
+        let mut parent = VariableContext::new();
+        parent.set("HOME".to_string(), "/home/user".to_string());
+        parent.export("HOME");
+        parent.set("LOCAL".to_string(), "hidden".to_string());
+
+        let mut child = VariableContext::with_parent(parent);
+        child.set("HOME".to_string(), "/tmp".to_string());
+        child.export("PENDING");
+
+        let env: Vec<(&str, &str)> = child.iter_exported().collect();
+        assert_eq!(env, vec![("HOME", "/tmp")]);
+        assert!(child.is_exported("HOME"));
+        assert!(!child.is_exported("LOCAL"));
+
+        child.unset("PENDING");
+        assert!(!child.is_exported("PENDING"));
     }
 
     #[test]
@@ -315,6 +883,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_normal_expansion_falls_back_to_environment() {
+        // Cargo sets this for the test process
+        let request = ExpansionRequest::simple("CARGO_PKG_NAME".to_string());
+        let mut context = VariableContext::new();
+        assert!(!context.inherits_env());
+        assert!(matches!(resolve_expansion(&mut context, &request), ResolutionResult::Unset));
+
+        context.set_inherit_env(true);
+        match resolve_expansion(&mut context, &request) {
+            ResolutionResult::Resolved(value) => assert_eq!(value, "shex-parser"),
+            _ => panic!("Expected resolved result"),
+        }
+        context.set("CARGO_PKG_NAME".to_string(), "shadowed".to_string());
+        match resolve_expansion(&mut context, &request) {
+            ResolutionResult::Resolved(value) => assert_eq!(value, "shadowed"),
+            _ => panic!("Expected resolved result"),
+        }
+
+        let imported = VariableContext::from_env();
+        assert!(!imported.inherits_env());
+        assert_eq!(imported.get("CARGO_PKG_NAME"), Some("shex-parser"));
+        assert!(imported.is_exported("CARGO_PKG_NAME"));
+        assert_eq!(VariableContext::env_var("CARGO_PKG_NAME").as_deref(), Some("shex-parser"));
+
+        // Imported values are shared, not copied, by later imports and copies
+        let again = VariableContext::from_env();
+        let snapshot = imported.clone();
+        let value = imported.get("CARGO_PKG_NAME").unwrap();
+        assert!(std::ptr::eq(value, again.get("CARGO_PKG_NAME").unwrap()));
+        assert!(std::ptr::eq(value, snapshot.get("CARGO_PKG_NAME").unwrap()));
+    }
+
     #[test]
     fn test_default_value_expansion() {
         let mut context = VariableContext::new();
@@ -356,6 +957,68 @@ mod tests {
         }
 
         // Verify variable was set
-        assert_eq!(context.get("unset_var"), Some(&"default_value".to_string()));
+        assert_eq!(context.get("unset_var"), Some("default_value"));
+    }
+
+    #[test]
+    fn test_resolve_length_and_count() {
+        let mut context = VariableContext::new();
+        context.set("word".to_string(), "héllo".to_string());
+        context.set_array(
+            "arr".to_string(),
+            vec!["a".to_string(), "bb".to_string(), "c".to_string()],
+        );
+        context.set_positional_params(vec!["x".to_string(), "y".to_string()]);
+
+        let resolve = |context: &mut VariableContext, name: &str, mode| {
+            let request = ExpansionRequest {
+                mode,
+                ..ExpansionRequest::simple(name.to_string())
+            };
+            resolve_expansion(context, &request)
+        };
+        let resolved = |value: &str| ResolutionResult::Resolved(value.to_string());
+
+        assert_eq!(
+            resolve(&mut context, "word", ExpansionMode::Length),
+            resolved("5")
+        );
+        assert_eq!(
+            resolve(&mut context, "arr[1]", ExpansionMode::Length),
+            resolved("2")
+        );
+        assert_eq!(
+            resolve(&mut context, "none", ExpansionMode::Length),
+            ResolutionResult::Unset
+        );
+        assert_eq!(
+            resolve(&mut context, "arr[@]", ExpansionMode::Count),
+            resolved("3")
+        );
+        assert_eq!(
+            resolve(&mut context, "word[*]", ExpansionMode::Count),
+            resolved("1")
+        );
+        assert_eq!(
+            resolve(&mut context, "none[@]", ExpansionMode::Count),
+            resolved("0")
+        );
+        assert_eq!(
+            resolve(&mut context, "@", ExpansionMode::Count),
+            resolved("2")
+        );
+    }
+
+    #[test]
+    fn test_positional_parameter_values() {
+        let mut context = VariableContext::new();
+        context.set_positional_params(vec!["first".to_string(), "second".to_string()]);
+
+        assert_eq!(context.value("1"), Some("first".to_string()));
+        assert_eq!(context.value("2"), Some("second".to_string()));
+        assert_eq!(context.value("3"), None);
+        assert_eq!(context.value("#"), Some("2".to_string()));
+        // $0 is not a positional parameter
+        assert_eq!(context.value("0"), None);
     }
 }
@@ -0,0 +1,486 @@
+//! Word parsing for Shex parser
+//!
+//! Splits a shell word into literal text and expansion parts so the
+//! interpreter can expand each part according to its quoting context.
+
+use crate::string_utils::{
+    is_special_parameter, parse_parameter_expansion, parse_simple_parameter_expansion,
+};
+use crate::variable_resolver::ExpansionRequest;
+
+/// Quoting context a word part appeared in
+///
+/// Any quoting protects the part from word splitting and pathname expansion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteKind {
+    /// Outside of any quotes
+    Unquoted,
+    /// Inside '...': fully literal
+    Single,
+    /// Inside "...": only `$` expansions are recognized
+    Double,
+}
+
+/// A single component of a shell word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordPart {
+    /// Literal text with quotes and escapes already removed
+    Literal { text: String, quote: QuoteKind },
+    /// Parameter expansion: $var, ${var}, ${var:-default}, etc.
+    Parameter {
+        request: ExpansionRequest,
+        quote: QuoteKind,
+    },
+    /// Command substitution: $(command) or `command`
+    CommandSubstitution { command: String, quote: QuoteKind },
+    /// Arithmetic expansion: $((expression))
+    Arithmetic {
+        expression: String,
+        quote: QuoteKind,
+    },
+}
+
+/// Characters that keep their backslash-escape meaning inside double quotes
+const DOUBLE_QUOTE_ESCAPES: [char; 5] = ['$', '`', '"', '\\', '\n'];
+
+/// Parse a shell word into its literal and expansion parts
+///
+/// Single-quoted text is kept verbatim with no expansions or escapes, and
+/// an unquoted backslash-escaped character becomes a single-quoted part.
+/// Inside double quotes only `$` expansions are recognized; `*`, `?` and
+/// spaces stay literal, and only `\$`, `` \` ``, `\"`, `\\` and
+/// backslash-newline are treated as escapes.
+pub fn parse_word(text: &str) -> Vec<WordPart> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut in_double = false;
+    let mut quote_start = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '"' => {
+                // An empty "" still produces a (quoted) empty field
+                let empty_quotes = in_double && parts.len() == quote_start;
+                if empty_quotes || !literal.is_empty() {
+                    parts.push(WordPart::Literal {
+                        text: std::mem::take(&mut literal),
+                        quote: if in_double {
+                            QuoteKind::Double
+                        } else {
+                            QuoteKind::Unquoted
+                        },
+                    });
+                }
+                in_double = !in_double;
+                quote_start = parts.len();
+                i += 1;
+            }
+            '\'' if !in_double && chars[i + 1..].contains(&'\'') => {
+                if !literal.is_empty() {
+                    parts.push(WordPart::Literal {
+                        text: std::mem::take(&mut literal),
+                        quote: QuoteKind::Unquoted,
+                    });
+                }
+                let close = i + 1 + chars[i + 1..].iter().position(|c| *c == '\'').unwrap_or(0);
+                parts.push(WordPart::Literal {
+                    text: chars[i + 1..close].iter().collect(),
+                    quote: QuoteKind::Single,
+                });
+                i = close + 1;
+            }
+            '\\' if i + 1 < chars.len() => {
+                let next = chars[i + 1];
+                if next == '\n' {
+                    // Line continuation is removed entirely
+                } else if in_double && !DOUBLE_QUOTE_ESCAPES.contains(&next) {
+                    literal.push('\\');
+                    literal.push(next);
+                } else if in_double {
+                    literal.push(next);
+                } else {
+                    // An escaped character is quoted, so `\*` is not a pattern
+                    // and `\ ` does not split the word
+                    if !literal.is_empty() {
+                        parts.push(WordPart::Literal {
+                            text: std::mem::take(&mut literal),
+                            quote: QuoteKind::Unquoted,
+                        });
+                    }
+                    parts.push(WordPart::Literal {
+                        text: next.to_string(),
+                        quote: QuoteKind::Single,
+                    });
+                }
+                i += 2;
+            }
+            c @ ('$' | '`') => match if c == '$' {
+                parse_dollar(&chars, i)
+            } else {
+                parse_backtick(&chars, i)
+            } {
+                Some((part, next)) => {
+                    if !literal.is_empty() {
+                        parts.push(WordPart::Literal {
+                            text: std::mem::take(&mut literal),
+                            quote: if in_double {
+                                QuoteKind::Double
+                            } else {
+                                QuoteKind::Unquoted
+                            },
+                        });
+                    }
+                    parts.push(part.with_quote(if in_double {
+                        QuoteKind::Double
+                    } else {
+                        QuoteKind::Unquoted
+                    }));
+                    i = next;
+                }
+                None => {
+                    literal.push(c);
+                    i += 1;
+                }
+            },
+            c => {
+                literal.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    if !literal.is_empty() {
+        parts.push(WordPart::Literal {
+            text: literal,
+            quote: if in_double {
+                QuoteKind::Double
+            } else {
+                QuoteKind::Unquoted
+            },
+        });
+    }
+
+    parts
+}
+
+impl WordPart {
+    /// Quoting context this part appeared in
+    #[must_use]
+    pub const fn quote(&self) -> QuoteKind {
+        match self {
+            Self::Literal { quote, .. }
+            | Self::Parameter { quote, .. }
+            | Self::CommandSubstitution { quote, .. }
+            | Self::Arithmetic { quote, .. } => *quote,
+        }
+    }
+
+    /// Whether this part is protected from word splitting and globbing
+    #[must_use]
+    pub fn is_quoted(&self) -> bool {
+        self.quote() != QuoteKind::Unquoted
+    }
+
+    fn with_quote(mut self, value: QuoteKind) -> Self {
+        match &mut self {
+            Self::Literal { quote, .. }
+            | Self::Parameter { quote, .. }
+            | Self::CommandSubstitution { quote, .. }
+            | Self::Arithmetic { quote, .. } => *quote = value,
+        }
+        self
+    }
+}
+
+/// Parse the expansion starting at the `$` at `start`
+///
+/// Returns the expansion part and the index just past it, or None when the
+/// `$` does not start a valid expansion and should be kept literally.
+fn parse_dollar(chars: &[char], start: usize) -> Option<(WordPart, usize)> {
+    match chars.get(start + 1)? {
+        '(' => {
+            let close = find_closing(chars, start + 1, '(', ')')?;
+            // $(( expr )) is arithmetic only when the inner parens close together
+            if chars.get(start + 2) == Some(&'(')
+                && close > start + 3
+                && chars[close - 1] == ')'
+                && find_closing(chars, start + 2, '(', ')') == Some(close - 1)
+            {
+                let expression: String = chars[start + 3..close - 1].iter().collect();
+                return Some((
+                    WordPart::Arithmetic {
+                        expression,
+                        quote: QuoteKind::Unquoted,
+                    },
+                    close + 1,
+                ));
+            }
+            let command: String = chars[start + 2..close].iter().collect();
+            Some((
+                WordPart::CommandSubstitution {
+                    command,
+                    quote: QuoteKind::Unquoted,
+                },
+                close + 1,
+            ))
+        }
+        '{' => {
+            let close = find_closing(chars, start + 1, '{', '}')?;
+            let text: String = chars[start..=close].iter().collect();
+            let request = parse_parameter_expansion(&text)?;
+            Some((
+                WordPart::Parameter {
+                    request,
+                    quote: QuoteKind::Unquoted,
+                },
+                close + 1,
+            ))
+        }
+        c if is_special_parameter(&c.to_string()) => {
+            let request = ExpansionRequest::simple(c.to_string());
+            Some((
+                WordPart::Parameter {
+                    request,
+                    quote: QuoteKind::Unquoted,
+                },
+                start + 2,
+            ))
+        }
+        c if c.is_ascii_alphabetic() || *c == '_' => {
+            let end = chars[start + 1..]
+                .iter()
+                .position(|c| !c.is_ascii_alphanumeric() && *c != '_')
+                .map_or(chars.len(), |offset| start + 1 + offset);
+            let text: String = chars[start..end].iter().collect();
+            let request = parse_simple_parameter_expansion(&text)?;
+            Some((
+                WordPart::Parameter {
+                    request,
+                    quote: QuoteKind::Unquoted,
+                },
+                end,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Parse the command substitution starting at the backtick at `start`
+///
+/// Inside backticks a backslash only escapes `$`, `` ` `` and `\`. Returns
+/// None when the backtick is never closed. `$(command)` nests without
+/// escaping and is preferred.
+fn parse_backtick(chars: &[char], start: usize) -> Option<(WordPart, usize)> {
+    let mut command = String::new();
+    let mut i = start + 1;
+    loop {
+        match *chars.get(i)? {
+            '`' => break,
+            '\\' if matches!(chars.get(i + 1), Some('$' | '`' | '\\')) => {
+                command.push(chars[i + 1]);
+                i += 2;
+            }
+            c => {
+                command.push(c);
+                i += 1;
+            }
+        }
+    }
+    Some((
+        WordPart::CommandSubstitution {
+            command,
+            quote: QuoteKind::Unquoted,
+        },
+        i + 1,
+    ))
+}
+
+/// Find the index of the delimiter closing the one at `open_index`
+///
+/// Nested pairs, quoted sections and backslash escapes are skipped.
+fn find_closing(chars: &[char], open_index: usize, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = open_index;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '\'' if open != '\'' => {
+                i += chars[i + 1..].iter().position(|c| *c == '\'')? + 1;
+            }
+            '"' => {
+                let mut j = i + 1;
+                while j < chars.len() && chars[j] != '"' {
+                    if chars[j] == '\\' {
+                        j += 1;
+                    }
+                    j += 1;
+                }
+                if j >= chars.len() {
+                    return None;
+                }
+                i = j;
+            }
+            c if c == open => depth += 1,
+            c if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variable_resolver::ExpansionMode;
+
+    fn literal(text: &str, quote: QuoteKind) -> WordPart {
+        WordPart::Literal {
+            text: text.to_string(),
+            quote,
+        }
+    }
+
+    #[test]
+    fn test_plain_word() {
+        assert_eq!(
+            parse_word("hello"),
+            vec![literal("hello", QuoteKind::Unquoted)]
+        );
+    }
+
+    #[test]
+    fn test_double_quoted_expansion() {
+        let parts = parse_word("\"hello $name\"");
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0], literal("hello ", QuoteKind::Double));
+        match &parts[1] {
+            WordPart::Parameter { request, quote } => {
+                assert_eq!(request.variable_name, "name");
+                assert_eq!(request.mode, ExpansionMode::Normal);
+                assert_eq!(*quote, QuoteKind::Double);
+            }
+            _ => panic!("Expected parameter part"),
+        }
+    }
+
+    #[test]
+    fn test_special_parameter() {
+        let parts = parse_word("$?x");
+        assert_eq!(parts.len(), 2);
+        match &parts[0] {
+            WordPart::Parameter { request, .. } => assert_eq!(request.variable_name, "?"),
+            _ => panic!("Expected parameter part"),
+        }
+        assert_eq!(parts[1], literal("x", QuoteKind::Unquoted));
+    }
+
+    #[test]
+    fn test_double_quoted_globs_are_literal() {
+        assert_eq!(
+            parse_word("\"* ? x\""),
+            vec![literal("* ? x", QuoteKind::Double)]
+        );
+    }
+
+    #[test]
+    fn test_empty_double_quotes() {
+        assert_eq!(parse_word("\"\""), vec![literal("", QuoteKind::Double)]);
+    }
+
+    #[test]
+    fn test_double_quote_escapes() {
+        assert_eq!(
+            parse_word(r#""a \$b \" \\ \n""#),
+            vec![literal(r#"a $b " \ \n"#, QuoteKind::Double)]
+        );
+    }
+
+    #[test]
+    fn test_braced_and_arithmetic_parts() {
+        let parts = parse_word("\"${var:-x} $((2*3)) $(echo hi)\"");
+        assert!(matches!(
+            &parts[0],
+            WordPart::Parameter { request, .. } if request.mode == ExpansionMode::DefaultValue
+        ));
+        assert!(matches!(
+            &parts[2],
+            WordPart::Arithmetic { expression, quote: QuoteKind::Double } if expression == "2*3"
+        ));
+        assert!(matches!(
+            &parts[4],
+            WordPart::CommandSubstitution { command, quote: QuoteKind::Double } if command == "echo hi"
+        ));
+    }
+
+    #[test]
+    fn test_nested_substitution() {
+        let parts = parse_word("$(echo $(echo inner))");
+        assert!(matches!(
+            &parts[0],
+            WordPart::CommandSubstitution { command, .. } if command == "echo $(echo inner)"
+        ));
+    }
+
+    #[test]
+    fn test_backtick_substitution() {
+        let parts = parse_word(r#""a `echo \`x\` \$y` b""#);
+        assert!(matches!(
+            &parts[1],
+            WordPart::CommandSubstitution { command, quote: QuoteKind::Double } if command == "echo `x` $y"
+        ));
+        assert_eq!(parse_word("`open"), vec![literal("`open", QuoteKind::Unquoted)]);
+    }
+
+    #[test]
+    fn test_lone_dollar_is_literal() {
+        assert_eq!(
+            parse_word("\"cost $ 5\""),
+            vec![literal("cost $ 5", QuoteKind::Double)]
+        );
+    }
+
+    #[test]
+    fn test_unquoted_backslash_quotes_next_character() {
+        assert_eq!(
+            parse_word(r"a\*b"),
+            vec![
+                literal("a", QuoteKind::Unquoted),
+                literal("*", QuoteKind::Single),
+                literal("b", QuoteKind::Unquoted),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_single_quotes_are_literal() {
+        assert_eq!(
+            parse_word(r"'$x \n $(cmd)'"),
+            vec![literal(r"$x \n $(cmd)", QuoteKind::Single)]
+        );
+    }
+
+    #[test]
+    fn test_single_quotes_inside_double_quotes() {
+        assert_eq!(
+            parse_word("\"it's\""),
+            vec![literal("it's", QuoteKind::Double)]
+        );
+    }
+
+    #[test]
+    fn test_mixed_quoting() {
+        let parts = parse_word("pre'$a'\"$b\"");
+        assert_eq!(parts[0], literal("pre", QuoteKind::Unquoted));
+        assert_eq!(parts[1], literal("$a", QuoteKind::Single));
+        assert!(parts[1].is_quoted());
+        assert_eq!(parts[2].quote(), QuoteKind::Double);
+    }
+}
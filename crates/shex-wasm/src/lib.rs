@@ -0,0 +1,119 @@
+//! WebAssembly bindings for the Shex interpreter
+//!
+//! Exposes `ShexInterpreter` to JavaScript through `wasm-bindgen`. Builtins,
+//! functions and variables work as in the native shell; WebAssembly cannot
+//! spawn processes, so external commands fail with `ERR_COMMAND_NOT_FOUND`.
+//!
+//! Build the npm package with `wasm-pack build crates/shex-wasm`.
+
+use shex_interpreter::Interpreter;
+use wasm_bindgen::prelude::*;
+
+/// A shell session whose variables persist between `execute` calls
+#[wasm_bindgen]
+pub struct ShexInterpreter {
+    interpreter: Interpreter,
+}
+
+/// Outcome of running a script, before conversion to a JavaScript object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResult {
+    pub code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[wasm_bindgen]
+impl ShexInterpreter {
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    /// Run a script, returning `{ code, stdout, stderr }`
+    ///
+    /// Parse and runtime errors are reported in `stderr` with their exit code
+    /// rather than thrown
+    pub fn execute(&mut self, script: &str) -> JsValue {
+        let result = self.run(script);
+        let object = js_sys::Object::new();
+        for (key, value) in [
+            ("code", JsValue::from(result.code)),
+            ("stdout", JsValue::from(result.stdout)),
+            ("stderr", JsValue::from(result.stderr)),
+        ] {
+            // Setting a property on a fresh plain object cannot fail
+            let _ = js_sys::Reflect::set(&object, &JsValue::from_str(key), &value);
+        }
+        object.into()
+    }
+
+    /// Assign a shell variable
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        self.interpreter.set_variable(name, value);
+    }
+
+    /// Value of a shell variable, or `undefined` if it is unset
+    #[must_use]
+    pub fn get_var(&self, name: &str) -> Option<String> {
+        self.interpreter.variable(name)
+    }
+}
+
+impl ShexInterpreter {
+    /// Run a script and collect its status and output
+    pub fn run(&mut self, script: &str) -> ExecutionResult {
+        match self.interpreter.execute_string(script) {
+            Ok(status) => ExecutionResult {
+                code: status.code,
+                stdout: status.stdout,
+                stderr: status.stderr,
+            },
+            Err(error) => ExecutionResult {
+                code: i32::try_from(error.error_code()).unwrap_or(1),
+                stdout: String::new(),
+                stderr: format!("{error}\n"),
+            },
+        }
+    }
+}
+
+impl Default for ShexInterpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_collects_output() {
+        let mut shell = ShexInterpreter::new();
+        let result = shell.run("echo hello\nfalse");
+        assert_eq!(result.code, 1);
+        assert_eq!(result.stdout, "hello\n");
+    }
+
+    #[test]
+    fn test_variables_persist_between_runs() {
+        let mut shell = ShexInterpreter::new();
+        shell.set_var("greeting", "hi");
+        shell.run("name=shex");
+        assert_eq!(shell.run("echo $greeting $name").stdout, "hi shex\n");
+        assert_eq!(shell.get_var("name"), Some("shex".to_string()));
+        assert_eq!(shell.get_var("missing"), None);
+    }
+
+    #[test]
+    fn test_errors_are_reported_not_thrown() {
+        let mut shell = ShexInterpreter::new();
+        let result = shell.run("echo $missing");
+        assert_ne!(result.code, 0);
+        assert!(result.stderr.contains("ERR_UNDEF_VAR"));
+    }
+}
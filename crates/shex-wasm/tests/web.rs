@@ -0,0 +1,37 @@
+//! Tests run inside a WebAssembly runtime with `wasm-pack test --node`
+
+#![cfg(target_arch = "wasm32")]
+
+use shex_wasm::ShexInterpreter;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn field(result: &JsValue, key: &str) -> JsValue {
+    js_sys::Reflect::get(result, &JsValue::from_str(key)).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn test_execute_returns_status_object() {
+    let mut shell = ShexInterpreter::new();
+    let result = shell.execute("x=5\necho $x");
+    assert_eq!(field(&result, "code").as_f64(), Some(0.0));
+    assert_eq!(field(&result, "stdout").as_string().as_deref(), Some("5\n"));
+    assert_eq!(field(&result, "stderr").as_string().as_deref(), Some(""));
+}
+
+#[wasm_bindgen_test]
+fn test_external_commands_are_unavailable() {
+    let mut shell = ShexInterpreter::new();
+    let result = shell.execute("ls");
+    assert_ne!(field(&result, "code").as_f64(), Some(0.0));
+    let stderr = field(&result, "stderr").as_string().unwrap();
+    assert!(stderr.contains("ERR_COMMAND_NOT_FOUND"));
+}
+
+#[wasm_bindgen_test]
+fn test_get_and_set_var() {
+    let mut shell = ShexInterpreter::new();
+    shell.set_var("name", "shex");
+    assert_eq!(shell.get_var("name").as_deref(), Some("shex"));
+    assert_eq!(shell.get_var("unset_name"), None);
+}
@@ -2,6 +2,7 @@
 //! Tests the entire application through the command-line interface
 
 use std::process::Command;
+use tempfile::NamedTempFile;
 
 const CLI_BINARY: &str = "target/debug/shex-cli";
 
@@ -28,7 +29,7 @@ fn test_basic_echo() {
 fn test_command_not_found() {
     let output = run_command_string("nonexistent_command_12345");
 
-    assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(127));
     let stderr = String::from_utf8_lossy(&output.stderr);
     assert!(stderr.contains("nonexistent_command_12345"));
     assert!(stderr.contains("ERR_COMMAND_NOT_FOUND"));
@@ -66,8 +67,7 @@ fn test_sequence_operator() {
     let output = run_command_string("echo first; echo second");
 
     assert!(output.status.success());
-    // Only last command output is returned in our current implementation
-    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "second");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "first\nsecond\n");
 }
 
 #[test]
@@ -110,3 +110,124 @@ fn test_exit_code_failure() {
     let output = run_command_string("false");
     assert!(!output.status.success());
 }
+
+#[test]
+fn test_posix_flag_rejects_extensions() {
+    let output = run_command(&["--posix", "-c", "name=sh; echo posix $name"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "posix sh");
+
+    let output = run_command(&["--posix", "-c", "coproc sleep 1"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("feature not available in POSIX mode"));
+}
+
+#[test]
+fn test_interactive_reads_continuation_lines() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(CLI_BINARY)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|_| panic!("Failed to execute {}", CLI_BINARY));
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"if true; then\n  echo yes\nfi\nx=2\necho $x\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "yes\n2\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("> "));
+}
+
+#[test]
+fn test_lint_flag_reports_before_running() {
+    let output = run_command(&[
+        "--lint",
+        "-c",
+        "unused=1; x=hi; echo $x >/dev/null; echo finished",
+    ]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "finished\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("warning[unused-variable]: unused is assigned but never used"));
+    assert!(stderr.contains("warning[useless-echo]"));
+    assert!(stderr.contains("info[quote-required]: $x is unquoted"));
+}
+
+#[test]
+fn test_explain_flag_describes_without_running() {
+    let output = run_command(&["--explain", "-c", "x=1; echo $x | wc -c"]);
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1:1: sets variable x to 1"));
+    assert!(stdout.contains("prints to stdout: $x"));
+    assert!(stdout.contains("runs external command wc -c"));
+}
+
+#[test]
+fn test_validate_flag_stops_on_errors() {
+    let output = run_command(&["--validate", "-c", "echo ran; break"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("1:11: error: break is only meaningful in a loop"));
+
+    let output = run_command(&["--validate", "-c", "f() { :; }; f() { :; }; echo ran"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ran\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("warning: function f is defined more than once"));
+}
+
+#[test]
+fn test_profile_flag_prints_statistics() {
+    let script = NamedTempFile::new().unwrap();
+    std::fs::write(script.path(), "greet() { echo hi; }\ngreet\ngreet\n").unwrap();
+    let output = run_command(&["--profile", script.path().to_str().unwrap()]);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hi\nhi\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // The counters are opt-in through the shex-cli `statistics` feature
+    if stderr.contains("statistics are not available") {
+        return;
+    }
+    assert!(stderr.contains("function calls:    2"));
+    assert!(stderr.contains("builtin commands:  2"));
+}
+
+#[test]
+fn test_output_streams_before_a_failing_command() {
+    let output = run_command_string("echo before; nonexistent_command_12345");
+
+    assert_eq!(output.status.code(), Some(127));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\n");
+}
+
+#[test]
+fn test_xtrace_lines_come_before_command_output() {
+    let log = NamedTempFile::new().unwrap();
+    let file = log.reopen().unwrap();
+    let status = Command::new(CLI_BINARY)
+        .args(["-c", "set -x; echo one; echo two"])
+        .stdout(file.try_clone().unwrap())
+        .stderr(file)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    assert_eq!(
+        std::fs::read_to_string(log.path()).unwrap(),
+        "+ echo one\none\n+ echo two\ntwo\n"
+    );
+}
@@ -120,3 +120,228 @@ fn test_empty_script() {
     assert!(output.status.success());
     assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "");
 }
+
+#[test]
+fn test_script_redirects_to_variable_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let outfile = dir.path().join("test.txt");
+    let script = NamedTempFile::new().unwrap();
+    fs::write(
+        &script,
+        format!(
+            "outfile={}\necho hi > $outfile\nls \"$outfile\" >> ${{outfile}}\ncat $outfile",
+            outfile.display()
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(CLI_BINARY)
+        .arg(script.path().to_str().unwrap())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("hi\n{}\n", outfile.display())
+    );
+}
+
+#[test]
+fn test_script_reads_piped_lines_in_while_loop() {
+    let data = NamedTempFile::new().unwrap();
+    fs::write(&data, "alpha\nbeta gamma\n").unwrap();
+    let script = NamedTempFile::new().unwrap();
+    fs::write(
+        &script,
+        format!(
+            "cat {} | while read line; do echo \"got $line\"; done\nprintf 'b\\na\\n' | sort",
+            data.path().display()
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(CLI_BINARY)
+        .arg(script.path().to_str().unwrap())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "got alpha\ngot beta gamma\na\nb\n"
+    );
+}
+
+#[test]
+fn test_script_cd_searches_cdpath() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = dir.path().canonicalize().unwrap();
+    fs::create_dir(base.join("target")).unwrap();
+    fs::create_dir(base.join("local")).unwrap();
+    let script = NamedTempFile::new().unwrap();
+    fs::write(
+        &script,
+        format!(
+            "CDPATH='/nonexistent:{}'\ncd target\npwd\ncd {}\nCDPATH=''\ncd local\npwd",
+            base.display(),
+            base.display()
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(CLI_BINARY)
+        .arg(script.path().to_str().unwrap())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let target = base.join("target");
+    let local = base.join("local");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "{}\n{}\n{}\n",
+            target.display(),
+            target.display(),
+            local.display()
+        )
+    );
+}
+
+#[test]
+fn test_script_builtins_read_redirected_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.txt");
+    fs::write(&input, "one two\nthree\n").unwrap();
+    let script = dir.path().join("script.sh");
+    fs::write(
+        &script,
+        format!(
+            "read x y < {input}\necho \"$x/$y\"\nmapfile -t lines < {input}\necho ${{#lines[@]}}",
+            input = input.display()
+        ),
+    )
+    .unwrap();
+
+    let output = Command::new(CLI_BINARY)
+        .arg(script.to_str().unwrap())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one/two\n2\n");
+}
+
+#[test]
+fn test_script_array_assignment() {
+    let script = NamedTempFile::new().unwrap();
+    fs::write(
+        &script,
+        "arr=(a b c); echo \"${arr[1]}\"\necho ${#arr[@]}\nwords=(\"x y\" z); echo \"${words[0]}\"",
+    )
+    .unwrap();
+
+    let output = Command::new(CLI_BINARY)
+        .arg(script.path().to_str().unwrap())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "b\n3\nx y\n");
+}
+
+#[test]
+fn test_script_sees_process_environment() {
+    let script = NamedTempFile::new().unwrap();
+    fs::write(&script, "echo $SHEX_GREETING\nsh -c 'echo child $SHEX_GREETING'").unwrap();
+
+    let output = Command::new(CLI_BINARY)
+        .arg(script.path().to_str().unwrap())
+        .env("SHEX_GREETING", "hello")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello\nchild hello\n");
+}
+
+#[test]
+fn test_script_cd_reports_missing_directory() {
+    let script = NamedTempFile::new().unwrap();
+    fs::write(&script, "cd /shex_no_such_directory").unwrap();
+
+    let output = Command::new(CLI_BINARY)
+        .arg(script.path().to_str().unwrap())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("cd: /shex_no_such_directory: No such file or directory")
+    );
+}
+
+#[test]
+fn test_script_arguments_are_positional_parameters() {
+    let script = NamedTempFile::new().unwrap();
+    fs::write(&script, "echo $2\necho $# \"$@\"").unwrap();
+
+    let output = Command::new(CLI_BINARY)
+        .args([script.path().to_str().unwrap(), "foo", "bar", "-x"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "bar\n3 foo bar -x\n"
+    );
+}
+
+#[test]
+fn test_script_shifts_through_arguments() {
+    let script = NamedTempFile::new().unwrap();
+    fs::write(&script, "for arg; do echo \"$# $1\"; shift; done\necho $#").unwrap();
+
+    let output = Command::new(CLI_BINARY)
+        .args([script.path().to_str().unwrap(), "a b", "c"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "2 a b\n1 c\n0\n"
+    );
+}
+
+#[test]
+fn test_script_json_errors() {
+    let temp_file = NamedTempFile::new().unwrap();
+    fs::write(&temp_file, "echo ok\necho $missing").unwrap();
+    let path = temp_file.path().to_str().unwrap();
+
+    let output = Command::new(CLI_BINARY)
+        .args(["--json-errors", path])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    let report: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    let errors = report["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0]["code"], "ERR_UNDEF_VAR");
+    assert_eq!(errors[0]["message"], "missing is not set");
+    assert_eq!(errors[0]["file"], path);
+    assert_eq!(errors[0]["line"], 2);
+
+    fs::write(&temp_file, "echo ok").unwrap();
+    let output = Command::new(CLI_BINARY)
+        .args(["--json-errors", path])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(report, serde_json::json!({ "errors": [] }));
+}
@@ -4,6 +4,7 @@
 //! Run with: cargo test --test integration
 
 mod integration {
+    pub mod builtins;
     pub mod execution;
     pub mod parsing;
 }
@@ -0,0 +1,72 @@
+//! Builtins exercised one at a time through `Interpreter::run_builtin`
+
+use shex_ast::ShexError;
+use shex_interpreter::Interpreter;
+use std::io::Write;
+
+#[test]
+fn test_echo() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter
+        .run_builtin("echo", &["hello", "world"])
+        .unwrap();
+    assert_eq!(result.stdout, "hello world\n");
+
+    let result = interpreter.run_builtin("echo", &["-n", "hi"]).unwrap();
+    assert_eq!(result.stdout, "hi");
+}
+
+#[test]
+fn test_printf() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter
+        .run_builtin("printf", &["%s-%d\\n", "a", "1"])
+        .unwrap();
+    assert_eq!(result.stdout, "a-1\n");
+}
+
+#[test]
+fn test_true_and_false() {
+    let mut interpreter = Interpreter::new();
+    assert_eq!(interpreter.run_builtin("true", &[]).unwrap().code, 0);
+    assert_eq!(interpreter.run_builtin("false", &[]).unwrap().code, 1);
+}
+
+#[test]
+fn test_set() {
+    let mut interpreter = Interpreter::new();
+    interpreter.run_builtin("set", &["-x"]).unwrap();
+    assert!(interpreter.options().xtrace);
+}
+
+#[test]
+fn test_source() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    write!(file, "echo sourced").unwrap();
+    let path = file.path().to_str().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    assert_eq!(
+        interpreter.run_builtin("source", &[path]).unwrap().stdout,
+        "sourced\n"
+    );
+    assert_eq!(
+        interpreter.run_builtin(".", &[path]).unwrap().stdout,
+        "sourced\n"
+    );
+    assert_eq!(interpreter.run_builtin("source", &[]).unwrap().code, 2);
+}
+
+#[test]
+fn test_disown_without_jobs() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.run_builtin("disown", &["%1"]).unwrap();
+    assert_ne!(result.code, 0);
+}
+
+#[test]
+fn test_unknown_builtin() {
+    let mut interpreter = Interpreter::new();
+    let err = interpreter.run_builtin("ls", &[]).unwrap_err();
+    assert!(matches!(err, ShexError::CommandNotFound { .. }));
+}
@@ -1,9 +1,32 @@
 //! Integration tests for parser + interpreter pipeline
 //! Tests AST execution and variable resolution
 
-use shex_ast::ShexError;
-use shex_interpreter::Interpreter;
+use shex_ast::{ShexError, SourceMap};
+use shex_interpreter::{ExitStatus, Interpreter, ShellOptions};
 use shex_parser::Parser;
+use std::sync::Arc;
+
+/// Parse `input` and run it in a new interpreter
+fn run(input: &str) -> Result<ExitStatus, ShexError> {
+    let program = Parser::new(input).unwrap().parse().unwrap();
+    Interpreter::new().execute(program)
+}
+
+/// Parse `input` and run it in a new interpreter with `options`
+fn run_with(options: ShellOptions, input: &str) -> Result<ExitStatus, ShexError> {
+    let program = Parser::new(input).unwrap().parse().unwrap();
+    Interpreter::with_options(options).execute(program)
+}
+
+/// Status of running `input`, which must not fail
+fn run_ok(input: &str) -> ExitStatus {
+    run(input).unwrap()
+}
+
+/// Stdout of running `input`, which must not fail
+fn stdout_of(input: &str) -> String {
+    run_ok(input).stdout
+}
 
 #[test]
 fn test_parser_interpreter_simple_execution() {
@@ -53,6 +76,53 @@ fn test_parser_interpreter_parameter_expansion() {
     assert_eq!(result.stdout, "fallback\n");
 }
 
+#[test]
+fn test_double_quoted_parameter_expansion() {
+    let parser = Parser::new(r#"name=world; echo "hello $name""#).unwrap();
+    let program = parser.parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+
+    assert_eq!(result.code, 0);
+    assert_eq!(result.stdout, "hello world\n");
+}
+
+#[test]
+fn test_double_quoted_arithmetic_expansion() {
+    let parser = Parser::new(r#"echo "price: $((2*3))""#).unwrap();
+    let program = parser.parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+
+    assert_eq!(result.code, 0);
+    assert_eq!(result.stdout, "price: 6\n");
+}
+
+#[test]
+fn test_double_quoted_command_substitution() {
+    let parser = Parser::new(r#"echo "got $(echo inner) here""#).unwrap();
+    let program = parser.parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+
+    assert_eq!(result.stdout, "got inner here\n");
+}
+
+#[test]
+fn test_single_quoted_string_is_literal() {
+    let parser = Parser::new("x=world; echo '$x'").unwrap();
+    let program = parser.parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+
+    assert_eq!(result.code, 0);
+    assert_eq!(result.stdout, "$x\n");
+}
+
 #[test]
 fn test_error_propagation_undefined_variable() {
     let parser = Parser::new("echo $undefined_variable").unwrap();
@@ -90,8 +160,7 @@ fn test_complex_command_chain() {
     let result = interpreter.execute(program).unwrap();
 
     assert_eq!(result.code, 0);
-    // Should return the last successful command's output
-    assert_eq!(result.stdout, "third\n");
+    assert_eq!(result.stdout, "first\nsecond\nthird\n");
 }
 
 #[test]
@@ -107,3 +176,705 @@ fn test_function_definition_execution() {
     // TODO: Test function invocation when function calling is implemented
     // For now, just verify the definition was successful
 }
+
+#[test]
+fn test_xtrace_uses_ps4_with_lineno() {
+    let script = "PS4='line $LINENO: '; set -x; echo hi";
+    let program = Parser::new(script).unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_source_map(Arc::new(SourceMap::new(script)));
+    let result = interpreter.execute(program).unwrap();
+
+    assert_eq!(result.stdout, "hi\n");
+    assert_eq!(result.stderr, "line 1: echo hi\n");
+}
+
+#[test]
+fn test_xtrace_prefix_reports_function_name() {
+    let script = "greet() ( echo hello ); PS4='[${FUNCNAME:-main}] '; set -x; greet";
+    let program = Parser::new(script).unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+
+    assert_eq!(result.stdout, "hello\n");
+    assert_eq!(result.stderr, "[main] greet\n[greet] echo hello\n");
+}
+
+#[test]
+fn test_funcname_tracks_call_stack() {
+    let script = "inner() ( echo ${FUNCNAME[0]} ${FUNCNAME[1]} ); outer() ( inner ); outer";
+    let program = Parser::new(script).unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+
+    assert_eq!(result.stdout, "inner outer\n");
+}
+
+#[test]
+fn test_funcname_full_stack_and_top_level() {
+    let script = "inner() ( echo ${FUNCNAME[@]} ); outer() ( inner ); outer";
+    let program = Parser::new(script).unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "inner outer main\n");
+
+    let program = Parser::new("echo ${FUNCNAME[0]}").unwrap().parse().unwrap();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "main\n");
+}
+
+#[test]
+fn test_funcname_is_readonly() {
+    let program = Parser::new("FUNCNAME=x").unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    assert!(interpreter.execute(program).is_err());
+}
+
+#[test]
+fn test_sourced_function_reports_bash_source_and_lineno() {
+    let library = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(
+        library.path(),
+        "true\nshow() {\n  echo ${BASH_SOURCE[0]} $LINENO\n}\n",
+    )
+    .unwrap();
+    let path = library.path().to_str().unwrap();
+
+    let script = format!("source {path}\nshow");
+    let program = Parser::new(&script).unwrap().parse().unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.set_source_map(Arc::new(SourceMap::new(&script)));
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, format!("{path} 3\n"));
+
+    // Back at the top level the script itself is the current source
+    let program = Parser::new(r#"echo "[${BASH_SOURCE[@]}]""#).unwrap().parse().unwrap();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "[]\n");
+}
+
+#[test]
+fn test_dot_reports_missing_file() {
+    let program = Parser::new("source /nonexistent/shex_lib").unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.code, 1);
+    assert!(result.stderr.starts_with("source: /nonexistent/shex_lib:"));
+}
+
+#[test]
+fn test_pipestatus_holds_each_stage_code() {
+    let program = Parser::new("true | false | true; echo ${PIPESTATUS[@]}").unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "0 1 0\n");
+
+    // A plain command is a one-stage pipeline
+    let program = Parser::new("true; echo ${PIPESTATUS[@]}").unwrap().parse().unwrap();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "0\n");
+}
+
+#[test]
+fn test_posix_mode_rejects_arrays() {
+    let program = Parser::new("echo ${FUNCNAME[0]}").unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    interpreter.options_mut().posix_mode = true;
+    let err = interpreter.execute(program).unwrap_err();
+    assert!(err.to_string().contains("feature not available in POSIX mode"));
+
+    interpreter.options_mut().posix_mode = false;
+    let program = Parser::new("echo ${FUNCNAME[0]}").unwrap().parse().unwrap();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "main\n");
+}
+
+#[test]
+fn test_stderr_redirection_by_fd() {
+    let dir = tempfile::tempdir().unwrap();
+    let err_path = dir.path().join("err.txt");
+    let script = format!(r#"sh -c "echo err 1>&2" 2>{}"#, err_path.display());
+    let program = Parser::new(&script).unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "");
+    assert_eq!(result.stderr, "");
+    assert_eq!(std::fs::read_to_string(&err_path).unwrap(), "err\n");
+
+    let program = Parser::new(r#"sh -c "echo err 1>&2" 2>&1"#).unwrap().parse().unwrap();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "err\n");
+    assert_eq!(result.stderr, "");
+}
+
+#[test]
+fn test_redirection_to_higher_fd() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("three.txt");
+    let script = format!(r#"sh -c "echo three 1>&3" 3>{}"#, path.display());
+    let program = Parser::new(&script).unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.code, 0);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "three\n");
+}
+
+#[test]
+fn test_printf_b_interprets_escapes() {
+    let program = Parser::new(r"printf '%b\n' 'hello\tworld'").unwrap().parse().unwrap();
+
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "hello\tworld\n");
+}
+
+#[test]
+fn test_errors_report_file_and_line() {
+    let script = "true\nshex_no_such_command_xyz";
+    let program = Parser::new(script).unwrap().parse().unwrap();
+
+    let mut interpreter =
+        Interpreter::with_source(Arc::new(SourceMap::new(script)), "script.sh".to_string());
+    let err = interpreter.execute(program).unwrap_err();
+    assert!(err.to_string().starts_with("Shex:script.sh:2:1: ERR_COMMAND_NOT_FOUND"));
+
+    let script = "true\n\ncat </nonexistent/shex_input";
+    let program = Parser::new(script).unwrap().parse().unwrap();
+    let mut interpreter =
+        Interpreter::with_source(Arc::new(SourceMap::new(script)), "script.sh".to_string());
+    let err = interpreter.execute(program).unwrap_err();
+    assert!(err.to_string().starts_with("Shex:script.sh:3:1: ERR_SYNTAX: Cannot open"));
+}
+
+#[test]
+fn test_last_status_parameter() {
+    let mut interpreter = Interpreter::new();
+    let mut run = |script: &str| {
+        let program = Parser::new(script).unwrap().parse().unwrap();
+        interpreter.execute(program).unwrap().stdout
+    };
+
+    assert_eq!(run("true; echo $?"), "0\n");
+    assert_eq!(run("false; echo $?"), "1\n");
+    assert_eq!(run("true && echo $?"), "0\n");
+    assert_eq!(run("false || echo \"status $?\""), "status 1\n");
+    // Functions and compound commands report their last command
+    assert_eq!(run("f() { true; false; }; f; echo $?"), "1\n");
+    assert_eq!(interpreter.last_status(), 0);
+}
+
+#[test]
+fn test_comments_do_not_affect_execution() {
+    let script = "# setup\nx=hi\necho $x # show it\n# trailing note";
+    let parser = Parser::new(script).unwrap();
+
+    for program in [parser.parse().unwrap(), parser.parse_with_comments().unwrap()] {
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.execute(program).unwrap();
+        assert_eq!(result.stdout, "hi\n");
+    }
+}
+
+#[test]
+fn test_command_substitution_of_pipeline() {
+    let program = Parser::new("echo \"[$(ls Cargo.toml | tr a-z A-Z)]\"")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "[CARGO.TOML]\n");
+}
+
+#[test]
+fn test_unquoted_arithmetic_expansion() {
+    let program = Parser::new("n=3\necho $(( (2+3) * (4-1) )) $((n*2))")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "15 6\n");
+}
+
+#[test]
+fn test_builtin_output_redirection() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.txt");
+    let script = format!(
+        "echo one > {0}\necho two >> {0}\necho shown",
+        path.display()
+    );
+    let program = Parser::new(&script).unwrap().parse().unwrap();
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+
+    assert_eq!(result.stdout, "shown\n");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+}
+
+#[test]
+fn test_command_name_from_variable() {
+    assert_eq!(run_ok("cmd=echo; $cmd hello").stdout, "hello\n");
+    assert_eq!(run_ok("cmd=echo; ${cmd} braced").stdout, "braced\n");
+    assert_eq!(run_ok("cmd=false; $cmd").code, 1);
+    assert_eq!(run_ok("cmd=tr; ls Cargo.toml | $cmd a-z A-Z").stdout, "CARGO.TOML\n");
+}
+
+#[test]
+fn test_arguments_are_split_into_fields() {
+    let script = "x='a  b'\nprintf '[%s]' $x \"$x\"\nfor word in $x c; do echo $word; done";
+    let program = Parser::new(script).unwrap().parse().unwrap();
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(program).unwrap();
+    assert_eq!(result.stdout, "[a][b][a  b]a\nb\nc\n");
+}
+
+#[test]
+fn test_nounset_applies_only_to_plain_expansions() {
+    assert!(matches!(
+        run("echo $u"),
+        Err(ShexError::UndefinedVariable { .. })
+    ));
+    assert!(matches!(
+        run("echo ${u}"),
+        Err(ShexError::UndefinedVariable { .. })
+    ));
+    assert_eq!(run("echo ${u:-d} ${u-e}").unwrap().stdout, "d e\n");
+    assert_eq!(run("echo ${u:=a}; echo $u").unwrap().stdout, "a\na\n");
+    assert_eq!(run("echo \"[${u:+alt}]\"").unwrap().stdout, "[]\n");
+    for script in ["echo ${u:?custom}", "set '+u'; echo ${u:?custom}"] {
+        let error = run(script).unwrap_err();
+        assert!(error.to_string().contains("custom"));
+    }
+
+    assert_eq!(run("set '+u'; echo \"[$u]\"").unwrap().stdout, "[]\n");
+    assert_eq!(
+        run("set '+o' nounset; echo \"[${u}]\"").unwrap().stdout,
+        "[]\n"
+    );
+}
+
+#[test]
+fn test_interpreter_with_options() {
+    let strict = ShellOptions {
+        nounset: true,
+        errexit: true,
+        ..Default::default()
+    };
+    let lenient = ShellOptions {
+        nounset: false,
+        errexit: false,
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        run_with(strict.clone(), "echo $undefined"),
+        Err(ShexError::UndefinedVariable { .. })
+    ));
+    assert_eq!(
+        run_with(lenient.clone(), "echo \"[$undefined]\"").unwrap().stdout,
+        "[]\n"
+    );
+
+    assert_eq!(run_with(strict, "false\necho after").unwrap().stdout, "");
+    let result = run_with(lenient, "false\necho after").unwrap();
+    assert_eq!((result.code, result.stdout.as_str()), (0, "after\n"));
+}
+
+#[test]
+fn test_pipefail_reports_failing_stage() {
+    let script = "sh -c 'exit 3' | sh -c 'exit 0'";
+    let run = |pipefail: bool| {
+        let program = Parser::new(script).unwrap().parse().unwrap();
+        Interpreter::with_options(ShellOptions {
+            pipefail,
+            ..Default::default()
+        })
+        .execute(program)
+        .unwrap()
+        .code
+    };
+
+    assert_eq!(run(false), 0);
+    assert_eq!(run(true), 3);
+}
+
+#[test]
+fn test_noclobber_and_restricted_redirections() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("out.txt");
+    let target = target.display();
+    let noclobber = ShellOptions {
+        noclobber: true,
+        ..Default::default()
+    };
+    let restricted = ShellOptions {
+        restricted: true,
+        ..Default::default()
+    };
+
+    run_with(noclobber.clone(), &format!("echo one >{target}")).unwrap();
+    let error = run_with(noclobber.clone(), &format!("echo two >{target}")).unwrap_err();
+    assert!(error.to_string().contains("cannot overwrite existing file"));
+    run_with(noclobber, &format!("echo three >|{target}")).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("out.txt")).unwrap(),
+        "three\n"
+    );
+
+    let error = run_with(restricted.clone(), &format!("echo four >>{target}")).unwrap_err();
+    assert!(error.to_string().contains("restricted"));
+    let error = run_with(restricted, "/bin/true").unwrap_err();
+    assert!(error.to_string().contains("restricted"));
+}
+
+#[test]
+fn test_case_matches_exact_pattern() {
+    assert_eq!(stdout_of("case x in x) echo yes ;; esac"), "yes\n");
+    assert_eq!(stdout_of("case x in y|x) echo second ;; esac"), "second\n");
+    assert_eq!(stdout_of("case x in y) echo no ;; esac"), "");
+}
+
+#[test]
+fn test_case_matches_shell_patterns() {
+    let classify = |word: &str| {
+        stdout_of(&format!(
+            "case {word} in \\*) echo star ;; [!a-z]*) echo other ;; [[:lower:]]?) echo pair ;; *) echo word ;; esac"
+        ))
+    };
+
+    assert_eq!(classify("'*'"), "star\n");
+    assert_eq!(classify("Abc"), "other\n");
+    assert_eq!(classify("ab"), "pair\n");
+    assert_eq!(classify("abc"), "word\n");
+}
+
+#[test]
+fn test_elif_runs_only_first_successful_branch() {
+    assert_eq!(
+        stdout_of("if false; then echo a; elif false; then echo b; elif true; then echo c; else echo d; fi"),
+        "c\n"
+    );
+    assert_eq!(
+        stdout_of("if false; then echo a; elif false; then echo b; else echo d; fi"),
+        "d\n"
+    );
+    // Conditions after the matching branch are never evaluated
+    assert_eq!(
+        stdout_of("if true; then echo a; elif echo side; then echo b; fi"),
+        "a\n"
+    );
+    assert_eq!(
+        stdout_of("if false; then echo a; elif true; then echo b; elif echo side; then echo c; else echo d; fi"),
+        "b\n"
+    );
+}
+
+#[test]
+fn test_assignment_values_are_expanded() {
+    assert_eq!(stdout_of("x=5; y=$x; echo $y"), "5\n");
+    assert_eq!(stdout_of("x=5; z=${x:-default}; echo $z"), "5\n");
+    assert_eq!(stdout_of("z=${x:-default}; echo $z"), "default\n");
+}
+
+#[test]
+fn test_length_and_count_expansions() {
+    assert_eq!(stdout_of("word=hello; echo ${#word}"), "5\n");
+    assert_eq!(stdout_of("false | true | true; echo ${#PIPESTATUS[@]}"), "3\n");
+    assert_eq!(stdout_of("echo ${#@}"), "0\n");
+}
+
+#[test]
+fn test_allexport_exports_later_assignments() {
+    assert_eq!(stdout_of("set -a; FOO=bar; env | grep FOO"), "FOO=bar\n");
+    assert_eq!(stdout_of("set -a; FOO=bar env | grep '^FOO='"), "FOO=bar\n");
+    // Earlier assignments stay unexported, and set +a stops exporting
+    assert_eq!(
+        stdout_of("EARLY=1; set -a; LATE=2; set '+a'; AFTER=3; env | grep -E '^(EARLY|LATE|AFTER)='"),
+        "LATE=2\n"
+    );
+}
+
+#[test]
+fn test_quoted_at_keeps_positional_parameters_whole() {
+    assert_eq!(
+        stdout_of("set -- \"hello world\" foo; printf '[%s]\\n' \"$@\""),
+        "[hello world]\n[foo]\n"
+    );
+    assert_eq!(
+        stdout_of("set -- \"hello world\" foo; printf '[%s]\\n' $@"),
+        "[hello]\n[world]\n[foo]\n"
+    );
+    assert_eq!(stdout_of("set --; for a in \"$@\"; do echo \"<$a>\"; done"), "");
+}
+
+#[test]
+fn test_err_trap_runs_after_failing_commands() {
+    let result = Interpreter::new()
+        .execute_string("trap 'echo error at $LINENO' ERR; false; echo after")
+        .unwrap();
+    assert_eq!(result.stdout, "error at 1\nafter\n");
+    // The trap sees the failing status, and a failing trap does not recurse
+    assert_eq!(stdout_of("trap 'echo status $?; false' ERR; sh -c 'exit 3'"), "status 3\n");
+    // A pipeline triggers the trap once, for its final status
+    assert_eq!(stdout_of("trap 'echo trapped' ERR; false | false"), "trapped\n");
+    assert_eq!(stdout_of("trap 'echo trapped' ERR; trap - ERR; false; echo after"), "after\n");
+}
+
+#[test]
+fn test_err_trap_in_functions_needs_errtrace() {
+    // Without errtrace only the failing call itself triggers the trap
+    assert_eq!(
+        stdout_of("f() { false; echo inside; }\ntrap 'echo trapped' ERR; f"),
+        "inside\n"
+    );
+    assert_eq!(
+        stdout_of("f() { false; echo inside; }\ntrap 'echo trapped' ERR; set -E; f"),
+        "trapped\ninside\n"
+    );
+}
+
+#[test]
+fn test_debug_trap_runs_before_simple_commands() {
+    assert_eq!(
+        stdout_of("trap 'echo \"running: $BASH_COMMAND\"' DEBUG; echo a; echo b"),
+        "running: echo a\na\nrunning: echo b\nb\n"
+    );
+    // The command is shown as written, before expansion
+    assert_eq!(
+        stdout_of("name=x; trap 'echo \"[$BASH_COMMAND]\"' DEBUG; echo $name"),
+        "[echo $name]\nx\n"
+    );
+}
+
+#[test]
+fn test_return_trap_runs_after_function_calls() {
+    let result = run_ok("f() { echo body; false; }\ntrap 'echo returned' RETURN; f");
+    assert_eq!(result.stdout, "body\nreturned\n");
+    assert_eq!(result.code, 1);
+}
+
+#[test]
+fn test_trap_variable_changes_are_rolled_back() {
+    assert_eq!(
+        stdout_of("X=original; f() { :; }\ntrap 'X=modified; NEW=1; echo \"in trap $X\"' RETURN\nf\necho \"$X ${NEW:-unset}\""),
+        "in trap modified\noriginal unset\n"
+    );
+}
+
+#[test]
+fn test_mapfile_reads_lines_into_array() {
+    assert_eq!(
+        stdout_of("printf 'a\\nb c\\n' | mapfile -t; echo ${#MAPFILE[@]} ${MAPFILE[@]}"),
+        "2 a b c\n"
+    );
+    assert_eq!(
+        stdout_of("seq 1 5 | mapfile -t -s 1 -n 2 nums; echo ${nums[@]}"),
+        "2 3\n"
+    );
+}
+
+#[test]
+fn test_mapfile_calls_callback_every_quantum_lines() {
+    assert_eq!(
+        stdout_of("cb() { echo called; }\nseq 0 9 | mapfile -t -c 3 -C cb lines; echo ${#lines[@]}"),
+        "called\ncalled\ncalled\ncalled\n10\n"
+    );
+    assert_eq!(
+        stdout_of("seq 0 9 | mapfile -t -c 3 -C 'echo at' lines"),
+        "at 0 0\nat 3 3\nat 6 6\nat 9 9\n"
+    );
+    assert_eq!(
+        stdout_of("cb() { echo \"[$1:$2]\"; }\nprintf 'a\\nb\\n' | mapfile -c 1 -C cb lines; echo \"${lines[1]}\""),
+        "[0:a]\n[1:b]\nb\n\n"
+    );
+}
+
+#[test]
+fn test_printf_assigns_to_variable() {
+    let program = Parser::new("printf -v msg '%s %s' hello world; echo \"$msg\"")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let result = Interpreter::new().execute(program).unwrap();
+    assert_eq!(result.stdout, "hello world\n");
+}
+
+#[test]
+fn test_read_splits_fields_into_array() {
+    let program = Parser::new("echo \"x,y,z\" | IFS=, read -ra arr; echo ${#arr[@]} ${arr[@]}")
+        .unwrap()
+        .parse()
+        .unwrap();
+    let result = Interpreter::new().execute(program).unwrap();
+    assert_eq!(result.stdout, "3 x y z\n");
+}
+
+#[test]
+fn test_readonly_p_output_recreates_variables() {
+    let script = "readonly MSG=\"it's a \\$test\" EMPTY=; readonly LATER; readonly -p";
+    let program = Parser::new(script).unwrap().parse().unwrap();
+    let listing = Interpreter::new().execute(program).unwrap().stdout;
+
+    let program = Parser::new(&listing).unwrap().parse().unwrap();
+    let mut recreated = Interpreter::new();
+    recreated.execute(program).unwrap();
+    assert_eq!(recreated.variable("MSG").as_deref(), Some("it's a $test"));
+    assert_eq!(recreated.variable("EMPTY").as_deref(), Some(""));
+
+    let program = Parser::new("readonly -p").unwrap().parse().unwrap();
+    assert_eq!(recreated.execute(program).unwrap().stdout, listing);
+
+    let program = Parser::new("MSG=changed").unwrap().parse().unwrap();
+    assert!(recreated.execute(program).is_err());
+}
+
+#[test]
+fn test_special_parameters_and_lone_dollar() {
+    assert_eq!(stdout_of("echo $$"), format!("{}\n", std::process::id()));
+    assert_eq!(stdout_of("echo $-"), "eu\n");
+    assert_eq!(stdout_of("echo costs $ 5 a$"), "costs $ 5 a$\n");
+    assert_eq!(stdout_of("false || echo ${?} ${#$} $0"), format!("1 {} shex\n", std::process::id().to_string().len()));
+    assert_eq!(stdout_of("echo ${!-none}"), "none\n");
+
+    let output = stdout_of("sleep 0 & echo $!");
+    assert!(output.trim().parse::<u32>().is_ok(), "unexpected $!: {output}");
+}
+
+#[test]
+fn test_here_documents() {
+    assert_eq!(
+        stdout_of("x=world\ncat <<EOF\nhello $x \"q\"\n\\$x $((1 << 2))\nEOF"),
+        "hello world \"q\"\n$x 4\n"
+    );
+    assert_eq!(stdout_of("x=1\ncat <<'EOF' | tr a-z A-Z\nraw $x\nEOF"), "RAW $X\n");
+    assert_eq!(
+        stdout_of("\tcat <<-EOF\n\ttabbed\n\tEOF\necho after"),
+        "tabbed\nafter\n"
+    );
+    assert_eq!(
+        stdout_of("read first <<A; cat <<B\none\nA\ntwo\nB\necho $first"),
+        "two\none\n"
+    );
+}
+
+#[test]
+fn test_builtin_output_duplication() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("out.txt");
+    let mut interpreter = Interpreter::new();
+
+    let result = interpreter.execute_string("echo err >&2; echo out 1>&2").unwrap();
+    assert_eq!((result.stdout.as_str(), result.stderr.as_str()), ("", "err\nout\n"));
+
+    let result = interpreter.execute_string("type no_such_command_here 2>&1 || true").unwrap();
+    assert!(result.stdout.contains("no_such_command_here"));
+    assert_eq!(result.stderr, "");
+
+    let script = format!(
+        "type no_such_command_here >{file} 2>&1 || echo finished",
+        file = file.display()
+    );
+    let result = interpreter.execute_string(&script).unwrap();
+    assert_eq!((result.stdout.as_str(), result.stderr.as_str()), ("finished\n", ""));
+    assert!(std::fs::read_to_string(&file).unwrap().contains("no_such_command_here"));
+
+    // Duplicating before the file redirection keeps stderr where it was
+    let script = format!("type no_such_command_here 2>&1 >{file} || true", file = file.display());
+    let result = interpreter.execute_string(&script).unwrap();
+    assert!(result.stdout.contains("no_such_command_here"));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+}
+
+#[test]
+fn test_subshell_changes_do_not_leak() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut interpreter = Interpreter::with_cwd(dir.path()).unwrap();
+    let result = interpreter
+        .execute_string("x=1; (x=2; echo inner $x; cd /; f() { :; }); echo $x; (false) || echo failed")
+        .unwrap();
+    assert_eq!(result.stdout, "inner 2\n1\nfailed\n");
+    assert_eq!(interpreter.cwd(), dir.path().canonicalize().unwrap());
+    assert!(interpreter.execute_string("f").is_err());
+}
+
+#[test]
+fn test_here_string_feeds_read_and_commands() {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter
+        .execute_string("IFS=, read -ra arr <<< \"x,y,z\"; echo ${#arr[@]}")
+        .unwrap();
+    assert_eq!(result.stdout, "3\n");
+    assert_eq!(interpreter.variable("arr[2]").as_deref(), Some("z"));
+
+    let result = interpreter.execute_string("v=world; cat <<< \"hello $v\"").unwrap();
+    assert_eq!(result.stdout, "hello world\n");
+}
+
+#[test]
+fn test_read_loop_over_redirected_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("input.txt");
+    let output = dir.path().join("output.txt");
+    std::fs::write(&input, "one two three\nfour\\\nfive\n").unwrap();
+
+    let script = format!(
+        "while read first rest; do echo \"$first:$rest\"; done < {input}\n\
+         while read -r line; do echo \"$line\"; done < {input} > {output}",
+        input = input.display(),
+        output = output.display()
+    );
+    let result = Interpreter::new().execute_string(&script).unwrap();
+    assert_eq!(result.stdout, "one:two three\nfourfive:\n");
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "one two three\nfour\\\nfive\n"
+    );
+}
+
+#[test]
+fn test_with_cwd_resolves_relative_paths() {
+    let original = std::env::current_dir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut interpreter = Interpreter::with_cwd(dir.path()).unwrap();
+    let result = interpreter
+        .execute_string("echo inside > relative.txt; echo $PWD; echo *.txt; cat relative.txt")
+        .unwrap();
+
+    // Only the interpreter moved, not the process
+    assert_eq!(std::env::current_dir().unwrap(), original);
+    let expected = dir.path().canonicalize().unwrap();
+    assert_eq!(
+        result.stdout,
+        format!("{}\nrelative.txt\ninside\n", expected.display())
+    );
+    assert_eq!(interpreter.cwd(), expected);
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("relative.txt")).unwrap(),
+        "inside\n"
+    );
+    assert!(Interpreter::with_cwd(&dir.path().join("missing")).is_err());
+}
+
+#[test]
+fn test_missing_pipeline_stage_fails_without_waiting() {
+    let start = std::time::Instant::now();
+    let error = Interpreter::new()
+        .execute_string("sleep 5 | shex_no_such_command")
+        .unwrap_err();
+
+    assert_eq!(error.error_code(), 127);
+    assert!(start.elapsed() < std::time::Duration::from_secs(2));
+}
@@ -3,5 +3,6 @@
 //! Tests component interactions and boundaries.
 //! Can be run with: cargo test --test integration
 
+mod builtins;
 mod parsing;
 mod execution;
\ No newline at end of file